@@ -1,12 +1,14 @@
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use std::str::FromStr;
 
+use penning_helper_types::{Lenient, Locale};
 use serde::{Deserialize, Serialize};
 
 pub use penning_helper_macros::Describe;
 pub use v1::conscribo::ConscriboConfig;
 pub use v1::mail::{Credentials, MailAddress, MailConfig};
-pub use v1::sepa::SEPAConfig;
+pub use v1::sepa::{LeadTimes, SEPAConfig, SchemaVersion};
 
 mod v1 {
     /// Email Config
@@ -44,18 +46,15 @@ impl Type {
 
     pub fn to_type_stuff(&self) -> Vec<TypeStuff> {
         let mut v = vec![];
-        match self {
-            Type::Struct(s) => {
-                for (k, t) in s {
-                    if t.is_struct() {
-                        v.push(TypeStuff::Header(k));
-                        v.extend(t.to_type_stuff());
-                    } else {
-                        v.push(TypeStuff::Field(k, t.clone()));
-                    }
+        if let Type::Struct(s) = self {
+            for (k, t) in s {
+                if t.is_struct() {
+                    v.push(TypeStuff::Header(k));
+                    v.extend(t.to_type_stuff());
+                } else {
+                    v.push(TypeStuff::Field(k, t.clone()));
                 }
             }
-            _ => {}
         }
         v
     }
@@ -77,6 +76,13 @@ impl Describe for String {
     }
 }
 
+/// Entered as text, see [`Lenient`]
+impl<T: FromStr> Describe for Lenient<T> {
+    fn describe_self() -> Type {
+        Type::String
+    }
+}
+
 impl Describe for u16 {
     fn describe_self() -> Type {
         Type::Integer
@@ -89,6 +95,9 @@ pub const CURRENT_VERSION: usize = 1;
 pub struct Config {
     #[serde(default = "v1::default_year_format")]
     year_format: String,
+    #[serde(default)]
+    #[describe(skip)]
+    locale: Locale,
     mail: v1::mail::MailConfig,
     sepa: v1::sepa::SEPAConfig,
     conscribo: v1::conscribo::ConscriboConfig,
@@ -129,6 +138,14 @@ impl Config {
         &mut self.year_format
     }
 
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn locale_mut(&mut self) -> &mut Locale {
+        &mut self.locale
+    }
+
     pub fn needs_upgrade(&self) -> bool {
         self.version < CURRENT_VERSION
     }
@@ -193,11 +210,15 @@ impl Config {
         if self.sepa().company_name.is_empty() {
             errors.push("SEPA company name is empty");
         }
-        if self.sepa().company_iban.is_empty() {
-            errors.push("SEPA company IBAN is empty");
+        match self.sepa().company_iban {
+            Lenient::Missing => errors.push("SEPA company IBAN is empty"),
+            Lenient::Invalid { .. } => errors.push("SEPA company IBAN is not a valid IBAN"),
+            Lenient::Valid(_) => {}
         }
-        if self.sepa().company_bic.is_empty() {
-            errors.push("SEPA company BIC is empty");
+        match self.sepa().company_bic {
+            Lenient::Missing => errors.push("SEPA company BIC is empty"),
+            Lenient::Invalid { .. } => errors.push("SEPA company BIC is not a valid BIC"),
+            Lenient::Valid(_) => {}
         }
 
         if self.conscribo().username.is_empty() {
@@ -232,6 +253,7 @@ impl Default for Config {
             sepa: v1::sepa::SEPAConfig::default(),
             conscribo: v1::conscribo::ConscriboConfig::default(),
             year_format: v1::default_year_format(),
+            locale: Locale::default(),
         }
     }
 }
@@ -10,4 +10,15 @@ pub struct ConscriboConfig {
     pub account_name: String,
     #[serde(default)]
     pub merch_winst_rekening: String,
+    #[serde(default)]
+    pub bank_rekening: String,
+    // the account asks for a code from an authenticator app on every login
+    #[serde(default)]
+    #[describe(skip)]
+    pub two_fa: bool,
+    // another server than Conscribo, like the mock server in tests; empty is
+    // the real one
+    #[serde(default)]
+    #[describe(skip)]
+    pub base_url: String,
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use penning_helper_types::{Bic, Iban, Lenient};
 use serde::{Serialize, Deserialize};
 
 use crate::{Describe, Type};
@@ -6,7 +7,66 @@ use crate::{Describe, Type};
 #[derive(Debug, Clone, Deserialize, Serialize, Default, Describe)]
 pub struct SEPAConfig {
     pub company_name: String,
-    pub company_iban: String,
-    pub company_bic: String,
+    pub company_iban: Lenient<Iban>,
+    pub company_bic: Lenient<Bic>,
     pub company_id: String,
+    #[serde(default)]
+    #[describe(skip)]
+    pub schema_version: SchemaVersion,
+    // put an RF creditor reference on invoices instead of the description,
+    // so transfers made by hand can be matched
+    #[serde(default)]
+    #[describe(skip)]
+    pub creditor_references: bool,
+    #[serde(default)]
+    #[describe(skip)]
+    pub lead_times: LeadTimes,
+}
+
+/// Business days between making a file and the date it asks the bank for.
+/// First and one-off collections have to reach the debtor's bank earlier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeadTimes {
+    pub first: u64,
+    pub recurring: u64,
+    pub one_off: u64,
+    pub last: u64,
+    pub transfer: u64,
+}
+
+impl Default for LeadTimes {
+    fn default() -> Self {
+        Self {
+            first: 5,
+            recurring: 2,
+            one_off: 5,
+            last: 2,
+            transfer: 1,
+        }
+    }
+}
+
+/// Which versions of the pain.008 (direct debit) and pain.001 (credit
+/// transfer) messages to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum SchemaVersion {
+    /// pain.008.001.02 and pain.001.001.03
+    #[default]
+    #[serde(rename = "2009")]
+    V2009,
+    /// pain.008.001.08 and pain.001.001.09
+    #[serde(rename = "2019")]
+    V2019,
+}
+
+impl SchemaVersion {
+    pub const ALL: [SchemaVersion; 2] = [SchemaVersion::V2009, SchemaVersion::V2019];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchemaVersion::V2009 => "pain.008.001.02 / pain.001.001.03",
+            SchemaVersion::V2019 => "pain.008.001.08 / pain.001.001.09",
+        }
+    }
 }
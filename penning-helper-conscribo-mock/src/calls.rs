@@ -0,0 +1,275 @@
+//! The calls the helper makes, on the fixtures instead of real books
+
+use chrono::NaiveDate;
+use penning_helper_types::Euro;
+use serde_json::{json, Map, Value};
+
+use crate::{error, Booking, Relation, Row, Side, State};
+
+/// The status and body of the reply to `method` on `path`, which starts with
+/// the account name like the urls of Conscribo do
+pub(crate) fn handle(
+    state: &mut State,
+    method: &str,
+    path: &str,
+    session: Option<&str>,
+    body: Value,
+) -> (u16, Value) {
+    let path = path.trim_matches('/');
+    let (account, call) = path.split_once('/').unwrap_or((path, ""));
+    if account != state.fixtures.account_name {
+        return (404, error(format!("There is no account {}", account)));
+    }
+    if method == "POST" && call == "sessions" {
+        return reply(login(state, &body));
+    }
+    if !session.is_some_and(|s| state.sessions.contains(s)) {
+        return expired(state, method, call, &body);
+    }
+    if method == "POST" && call == "multirequest" {
+        // the responses of a multirequest are not wrapped in a status
+        return (200, multirequest(state, &body));
+    }
+    reply(one(state, method, call, &body))
+}
+
+const EXPIRED: &str = "The session has expired";
+
+fn expired(state: &State, method: &str, call: &str, body: &Value) -> (u16, Value) {
+    if !state.expired_in_body {
+        return (401, error(EXPIRED));
+    }
+    if method != "POST" || call != "multirequest" {
+        return (200, error(EXPIRED));
+    }
+    let requests = body["requests"].as_array().cloned().unwrap_or_default();
+    let responses = requests
+        .iter()
+        .map(|r| {
+            let mut response = error(EXPIRED);
+            response["Request.sequence"] = r["Request.sequence"].clone();
+            response["Response.HTTPStatusCode"] = json!(401);
+            response
+        })
+        .collect::<Vec<_>>();
+    (200, json!({ "responses": responses }))
+}
+
+fn reply(result: Result<Value, String>) -> (u16, Value) {
+    match result {
+        Ok(mut content) => {
+            content["status"] = json!(200);
+            (200, content)
+        }
+        Err(e) => (400, error(e)),
+    }
+}
+
+fn one(state: &mut State, method: &str, call: &str, body: &Value) -> Result<Value, String> {
+    match (method, call.trim_matches('/')) {
+        ("POST", "relations/entities/filters") => Ok(entities(state, body)),
+        ("POST", "financial/transactions/filters") => Ok(transactions(state, body)),
+        ("POST", "financial/transactions") => add_transaction(state, body),
+        ("GET", "financial/accounts") => Ok(accounts(state)),
+        (method, call) => Err(format!("{} {} is not in the mock", method, call)),
+    }
+}
+
+fn login(state: &mut State, body: &Value) -> Result<Value, String> {
+    let fixtures = &state.fixtures;
+    if body["userName"] != fixtures.user_name.as_str()
+        || body["passPhrase"] != fixtures.pass_phrase.as_str()
+    {
+        return Err("Wrong username or password".to_string());
+    }
+    if let Some(code) = fixtures.two_fa_code {
+        if body["twoFaCode"].as_i64() != Some(code.into()) {
+            return Err("The two-factor code is missing or wrong".to_string());
+        }
+    }
+    state.logins += 1;
+    let session_id = format!("session-{}", state.logins);
+    state.sessions.insert(session_id.clone());
+    Ok(json!({ "sessionId": session_id }))
+}
+
+/// Every request is handled on its own, like Conscribo does
+fn multirequest(state: &mut State, body: &Value) -> Value {
+    let requests = body["requests"].as_array().cloned().unwrap_or_default();
+    let responses = requests
+        .iter()
+        .map(|r| {
+            let content = r
+                .as_object()
+                .map(|o| {
+                    o.iter()
+                        .filter(|(k, _)| !k.starts_with("Request."))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect::<Map<_, _>>()
+                })
+                .unwrap_or_default();
+            let method = r["Request.httpMethod"].as_str().unwrap_or("");
+            let call = r["Request.url"].as_str().unwrap_or("");
+            let (code, mut response) = reply(one(state, method, call, &Value::Object(content)));
+            response["Request.sequence"] = r["Request.sequence"].clone();
+            response["Response.HTTPStatusCode"] = json!(code);
+            response
+        })
+        .collect::<Vec<_>>();
+    json!({ "responses": responses })
+}
+
+fn entities(state: &State, body: &Value) -> Value {
+    let filters = body["filters"].as_array().cloned().unwrap_or_default();
+    let entities = state
+        .fixtures
+        .relations
+        .iter()
+        .map(Relation::to_json)
+        .filter(|e| filters.iter().all(|f| matches(e, f)))
+        .map(|e| (text(&e["code"]), e))
+        .collect::<Map<_, _>>();
+    json!({
+        "resultCount": entities.len().to_string(),
+        "entities": entities,
+    })
+}
+
+/// Only filters on text, every entity passes the date filters
+fn matches(entity: &Value, filter: &Value) -> bool {
+    let Some(value) = filter["value"].as_str() else {
+        return true;
+    };
+    let field = text(&entity[filter["fieldName"].as_str().unwrap_or("")]);
+    match filter["operator"].as_str() {
+        Some("=") => field == value,
+        Some("~") => field.contains(value),
+        Some("!~") => !field.contains(value),
+        _ => true,
+    }
+}
+
+fn transactions(state: &State, body: &Value) -> Value {
+    let filters = &body["filters"];
+    let date_start = date(&filters["dateStart"]);
+    let date_end = date(&filters["dateEnd"]);
+    let ids = texts(&filters["transactionIds"]);
+    let relations = texts(&filters["relations"]);
+    let accounts = texts(&filters["accounts"]);
+    let references = texts(&filters["references"]);
+    let any_row = |b: &Booking, wanted: &[String], field: fn(&Row) -> Option<&str>| {
+        wanted.is_empty()
+            || b.rows
+                .iter()
+                .any(|r| field(r).is_some_and(|f| wanted.iter().any(|w| w == f)))
+    };
+
+    let mut matching = state
+        .bookings()
+        .filter(|b| {
+            date_start.is_none_or(|d| b.date >= d)
+                && date_end.is_none_or(|d| b.date <= d)
+                && (ids.is_empty() || ids.contains(&b.id.to_string()))
+                && any_row(b, &relations, |r| r.relation_nr.as_deref())
+                && any_row(b, &accounts, |r| Some(&r.account_nr))
+                && any_row(b, &references, |r| r.reference.as_deref())
+        })
+        .collect::<Vec<_>>();
+    matching.sort_by_key(|b| b.id);
+
+    let limit = body["limit"].as_u64().unwrap_or(100) as usize;
+    let offset = body["offset"].as_u64().unwrap_or(0) as usize;
+    let page = matching
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|b| (b.id.to_string(), b.to_json()))
+        .collect::<Map<_, _>>();
+    json!({
+        "nrTransactions": matching.len().to_string(),
+        "transactions": page,
+    })
+}
+
+/// Refuses transactions on accounts that don't exist and ones that don't
+/// balance, like Conscribo does
+fn add_transaction(state: &mut State, body: &Value) -> Result<Value, String> {
+    let date = date(&body["date"]).ok_or("The transaction has no date")?;
+    let mut rows = vec![];
+    for r in body["transactionRows"].as_array().into_iter().flatten() {
+        let account_nr = r["accountNr"].as_str().unwrap_or("");
+        if !state.fixtures.accounts.iter().any(|a| a.nr == account_nr) {
+            return Err(format!("Account {} does not exist", account_nr));
+        }
+        let amount = r["amount"].as_f64().ok_or("A row has no amount")?;
+        let side = match r["side"].as_str() {
+            Some("debet") => Side::Debet,
+            Some("credit") => Side::Credit,
+            _ => return Err("A row is neither debet nor credit".to_string()),
+        };
+        rows.push(Row {
+            account_nr: account_nr.to_string(),
+            amount: Euro::from(amount),
+            side,
+            reference: r["reference"].as_str().map(str::to_string),
+            description: r["description"].as_str().map(str::to_string),
+            relation_nr: r["relationNr"].as_str().map(str::to_string),
+        });
+    }
+    if rows.is_empty() {
+        return Err("The transaction has no rows".to_string());
+    }
+    let balance: i64 = rows
+        .iter()
+        .map(|r| match r.side {
+            Side::Debet => r.amount.cents(),
+            Side::Credit => -r.amount.cents(),
+        })
+        .sum();
+    if balance != 0 {
+        return Err("Debet and credit are not equal".to_string());
+    }
+
+    let id = state.bookings().map(|b| b.id).max().unwrap_or(0) + 1;
+    let description = rows
+        .iter()
+        .find_map(|r| r.description.clone())
+        .unwrap_or_default();
+    let mut booking = Booking::new(id, date, &description);
+    booking.rows = rows;
+    if let Some(nr) = body["transactionNr"].as_str() {
+        booking.nr = nr.to_string();
+    }
+    let reply = json!({
+        "transactionId": booking.id,
+        "transactionNr": booking.nr,
+    });
+    state.booked.push(booking);
+    Ok(reply)
+}
+
+fn accounts(state: &State) -> Value {
+    let accounts = state
+        .fixtures
+        .accounts
+        .iter()
+        .map(|a| a.to_json())
+        .collect::<Vec<_>>();
+    json!({ "accounts": accounts })
+}
+
+fn text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+fn texts(v: &Value) -> Vec<String> {
+    v.as_array().into_iter().flatten().map(text).collect()
+}
+
+fn date(v: &Value) -> Option<NaiveDate> {
+    v.as_str()?.parse().ok()
+}
@@ -0,0 +1,290 @@
+use chrono::NaiveDate;
+use penning_helper_types::Euro;
+use serde_json::{json, Value};
+
+/// What the mock server knows: one account with its login, relations, ledger
+/// accounts and the transactions booked before the test
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    pub account_name: String,
+    pub user_name: String,
+    pub pass_phrase: String,
+    /// Logging in also needs this code when it is set
+    pub two_fa_code: Option<i32>,
+    pub relations: Vec<Relation>,
+    pub accounts: Vec<LedgerAccount>,
+    pub transactions: Vec<Booking>,
+}
+
+impl Default for Fixtures {
+    /// Two members with an open balance, one owing and one owed, and someone
+    /// who is only known by name
+    fn default() -> Self {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        Self {
+            account_name: "aegee-delft".to_string(),
+            user_name: "penningmeester".to_string(),
+            pass_phrase: "geheim".to_string(),
+            two_fa_code: None,
+            relations: vec![
+                Relation::member("101", "Jan Jansen", "jan@example.com")
+                    .with_bank_account("NL91ABNA0417164300", "ABNANL2A")
+                    .with_membership(NaiveDate::from_ymd_opt(2023, 9, 1).unwrap()),
+                Relation::member("102", "Erika Mustermann", "erika@example.com")
+                    .with_bank_account("DE89370400440532013000", "COBADEFFXXX")
+                    .with_membership(NaiveDate::from_ymd_opt(2022, 2, 14).unwrap()),
+                Relation::unknown("103", "Piet Pietersen", "piet@example.com"),
+            ],
+            accounts: vec![
+                LedgerAccount::new("1001", "Debiteuren"),
+                LedgerAccount::new("1002", "Crediteuren"),
+                LedgerAccount::new("1100", "Bank"),
+                LedgerAccount::new("3010", "Shotglass (2.50)"),
+                LedgerAccount::new("8010", "Turflijsten"),
+                LedgerAccount::new("8400", "Merch winst"),
+            ],
+            transactions: vec![
+                Booking::new(1, date, "Turflijst februari")
+                    .with_row(
+                        Row::debet("1001", Euro::new(25, 0))
+                            .with_relation("101")
+                            .with_reference("T0010-00"),
+                    )
+                    .with_row(Row::credit("8010", Euro::new(25, 0)).with_reference("T0010-00")),
+                Booking::new(2, date, "Declaratie")
+                    .with_row(
+                        Row::credit("1002", Euro::new(20, 0))
+                            .with_relation("102")
+                            .with_reference("D0003-00"),
+                    )
+                    .with_row(Row::debet("8010", Euro::new(20, 0)).with_reference("D0003-00")),
+            ],
+        }
+    }
+}
+
+/// A relation with the fields the helper reads
+#[derive(Debug, Clone)]
+pub struct Relation {
+    pub code: String,
+    /// `lid` or `onbekend`
+    pub entity_type: String,
+    pub name: String,
+    pub email: String,
+    pub iban: String,
+    pub bic: String,
+    pub membership_start: Option<NaiveDate>,
+    pub membership_end: Option<NaiveDate>,
+    pub no_invoice: bool,
+}
+
+impl Relation {
+    pub fn member(code: &str, name: &str, email: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            entity_type: "lid".to_string(),
+            name: name.to_string(),
+            email: email.to_string(),
+            iban: String::new(),
+            bic: String::new(),
+            membership_start: None,
+            membership_end: None,
+            no_invoice: false,
+        }
+    }
+
+    pub fn unknown(code: &str, name: &str, email: &str) -> Self {
+        Self {
+            entity_type: "onbekend".to_string(),
+            ..Self::member(code, name, email)
+        }
+    }
+
+    pub fn with_bank_account(mut self, iban: &str, bic: &str) -> Self {
+        self.iban = iban.to_string();
+        self.bic = bic.to_string();
+        self
+    }
+
+    pub fn with_membership(mut self, start: NaiveDate) -> Self {
+        self.membership_start = Some(start);
+        self
+    }
+
+    pub fn with_membership_end(mut self, end: NaiveDate) -> Self {
+        self.membership_end = Some(end);
+        self
+    }
+
+    /// As Conscribo sends it, with the fields of the AEGEE-Delft account
+    pub(crate) fn to_json(&self) -> Value {
+        let (first, last) = self.name.split_once(' ').unwrap_or((&self.name, ""));
+        let account = (!self.iban.is_empty()).then(|| {
+            json!({
+                "nr": "",
+                "country": "NL",
+                "name": self.name,
+                "city": "",
+                "iban": self.iban,
+                "bic": self.bic,
+            })
+        });
+        json!({
+            "code_sort": self.code,
+            "id": self.code,
+            "entity_type": self.entity_type,
+            "naam": self.name,
+            "code": self.code,
+            "lidmaatschap_gestart": self.membership_start,
+            "lidmaatschap_be__indigd": self.membership_end,
+            "gesproken_taal": "Nederlands",
+            "ere_lid": 0,
+            "alumni_lidmaatschap_gestart": null,
+            "alumni_lidmaatschap_be__indigd": null,
+            "alumni_contributie": "0.00",
+            "geen_invoice": i64::from(self.no_invoice),
+            "leeftijd": "",
+            "voornaam": first,
+            "achternaam": last,
+            "display_name": self.name,
+            "email": self.email,
+            "account": account,
+            "postal_address": "",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LedgerAccount {
+    pub nr: String,
+    pub name: String,
+}
+
+impl LedgerAccount {
+    pub fn new(nr: &str, name: &str) -> Self {
+        Self {
+            nr: nr.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "accountNr": self.nr,
+            "accountName": self.name,
+            "type": "balance",
+            "usage": "generic",
+            "usedForCredit": true,
+            "usedForDebit": true,
+            "parent": null,
+        })
+    }
+}
+
+/// A transaction in the books, either a fixture or one added by a test
+#[derive(Debug, Clone, PartialEq)]
+pub struct Booking {
+    pub id: i64,
+    pub nr: String,
+    pub date: NaiveDate,
+    pub description: String,
+    pub rows: Vec<Row>,
+}
+
+impl Booking {
+    pub fn new(id: i64, date: NaiveDate, description: &str) -> Self {
+        Self {
+            id,
+            nr: format!("M{:05}", id),
+            date,
+            description: description.to_string(),
+            rows: vec![],
+        }
+    }
+
+    pub fn with_row(mut self, row: Row) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let rows = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (format!("{}-{}", self.id, i + 1), r.to_json()))
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "transactionId": self.id,
+            "date": self.date,
+            "description": self.description,
+            "transactionNr": self.nr,
+            "transactionRows": rows,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Debet,
+    Credit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub account_nr: String,
+    pub amount: Euro,
+    pub side: Side,
+    pub reference: Option<String>,
+    pub description: Option<String>,
+    pub relation_nr: Option<String>,
+}
+
+impl Row {
+    pub fn debet(account_nr: &str, amount: Euro) -> Self {
+        Self {
+            account_nr: account_nr.to_string(),
+            amount,
+            side: Side::Debet,
+            reference: None,
+            description: None,
+            relation_nr: None,
+        }
+    }
+
+    pub fn credit(account_nr: &str, amount: Euro) -> Self {
+        Self {
+            side: Side::Credit,
+            ..Self::debet(account_nr, amount)
+        }
+    }
+
+    pub fn with_relation(mut self, relation_nr: &str) -> Self {
+        self.relation_nr = Some(relation_nr.to_string());
+        self
+    }
+
+    pub fn with_reference(mut self, reference: &str) -> Self {
+        self.reference = Some(reference.to_string());
+        self
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "accountNr": self.account_nr,
+            "amount": self.amount,
+            "side": match self.side {
+                Side::Debet => "debet",
+                Side::Credit => "credit",
+            },
+            "reference": self.reference,
+            "description": self.description,
+            "relationNr": self.relation_nr,
+        })
+    }
+}
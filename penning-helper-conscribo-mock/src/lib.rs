@@ -0,0 +1,154 @@
+//! An in-process stand-in for the Conscribo API, so the client and the
+//! workflows on top of it can be tested offline. Point the client at
+//! [`MockConscribo::url`] with `with_base_url`, or set it as `base_url` in the
+//! Conscribo part of the config.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+mod calls;
+
+pub mod fixtures;
+
+pub use fixtures::{Booking, Fixtures, LedgerAccount, Relation, Row, Side};
+
+/// The books as they are now, the fixtures plus what was booked since
+pub(crate) struct State {
+    pub(crate) fixtures: Fixtures,
+    pub(crate) sessions: HashSet<String>,
+    /// Unknown sessions get a 200 with the error in the body instead of a 401
+    pub(crate) expired_in_body: bool,
+    pub(crate) logins: usize,
+    pub(crate) booked: Vec<Booking>,
+}
+
+impl State {
+    pub(crate) fn bookings(&self) -> impl Iterator<Item = &Booking> {
+        self.fixtures.transactions.iter().chain(&self.booked)
+    }
+}
+
+/// A running mock server, which stops when it is dropped
+pub struct MockConscribo {
+    url: String,
+    state: Arc<Mutex<State>>,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockConscribo {
+    /// Listens on a free port of localhost
+    pub fn start(fixtures: Fixtures) -> Self {
+        let server =
+            Arc::new(Server::http("127.0.0.1:0").expect("Could not start the mock server"));
+        let port = server
+            .server_addr()
+            .to_ip()
+            .expect("The mock server is not on an ip address")
+            .port();
+        let state = Arc::new(Mutex::new(State {
+            fixtures,
+            sessions: HashSet::new(),
+            expired_in_body: false,
+            logins: 0,
+            booked: vec![],
+        }));
+        let thread = {
+            let server = server.clone();
+            let state = state.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&state, request);
+                }
+            })
+        };
+        Self {
+            url: format!("http://127.0.0.1:{}", port),
+            state,
+            server,
+            thread: Some(thread),
+        }
+    }
+
+    /// The base url, without the account name
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Times out every session, the next request with one gets a 401
+    pub fn expire_sessions(&self) {
+        self.state.lock().unwrap().sessions.clear();
+    }
+
+    /// Times out every session, but the next request with one gets a 200 with
+    /// the session error in the body, in every response of a multirequest
+    pub fn expire_sessions_in_body(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.clear();
+        state.expired_in_body = true;
+    }
+
+    /// How many times someone logged in
+    pub fn logins(&self) -> usize {
+        self.state.lock().unwrap().logins
+    }
+
+    /// The transactions added since the server started
+    pub fn booked(&self) -> Vec<Booking> {
+        self.state.lock().unwrap().booked.clone()
+    }
+}
+
+impl Drop for MockConscribo {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn respond(state: &Mutex<State>, mut request: tiny_http::Request) {
+    let mut body = String::new();
+    let _ = request.as_reader().read_to_string(&mut body);
+    let body = serde_json::from_str(&body).unwrap_or(Value::Null);
+    let session = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("X-Conscribo-SessionId"))
+        .map(|h| h.value.to_string());
+    let method = request.method().as_str().to_string();
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+
+    let (status, reply) = calls::handle(
+        &mut state.lock().unwrap(),
+        &method,
+        &path,
+        session.as_deref(),
+        body,
+    );
+    let response = Response::from_string(reply.to_string())
+        .with_status_code(status)
+        .with_header(
+            "Content-Type: application/json"
+                .parse::<Header>()
+                .expect("A valid header"),
+        );
+    let _ = request.respond(response);
+}
+
+/// The messages Conscribo sends when it refuses a call
+pub(crate) fn error(message: impl ToString) -> Value {
+    json!({
+        "status": 0,
+        "responseMessages": {
+            "error": [{ "message": message.to_string() }],
+        },
+    })
+}
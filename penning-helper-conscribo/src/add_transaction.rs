@@ -37,6 +37,13 @@ pub struct TransactionRow {
     vat_amount: Option<Euro>,
 }
 
+impl Default for AddTransaction {
+    /// An empty transaction dated today
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AddTransaction {
     pub fn new() -> Self {
         Self {
@@ -122,6 +129,78 @@ impl AddTransaction {
         )
     }
 
+    /// Money from the relation arrived on `bank_rekening`
+    pub fn settle_debet(
+        self,
+        bank_rekening: String,
+        amount: Euro,
+    ) -> Self {
+        self.with_row(
+            bank_rekening,
+            amount,
+            Side::Debet,
+        )
+        .with_row(
+            "1001".to_string(),
+            amount,
+            Side::Credit,
+        )
+    }
+
+    /// Money to the relation left `bank_rekening`
+    pub fn settle_credit(
+        self,
+        bank_rekening: String,
+        amount: Euro,
+    ) -> Self {
+        self.with_row(
+            "1002".to_string(),
+            amount,
+            Side::Debet,
+        )
+        .with_row(
+            bank_rekening,
+            amount,
+            Side::Credit,
+        )
+    }
+
+    /// Undoes `settle_debet`, the money went back to the relation
+    pub fn reverse_debet(
+        self,
+        bank_rekening: String,
+        amount: Euro,
+    ) -> Self {
+        self.with_row(
+            "1001".to_string(),
+            amount,
+            Side::Debet,
+        )
+        .with_row(
+            bank_rekening,
+            amount,
+            Side::Credit,
+        )
+    }
+
+    /// Undoes `settle_credit`, the money came back from the relation
+    pub fn reverse_credit(
+        self,
+        bank_rekening: String,
+        amount: Euro,
+    ) -> Self {
+        self.with_row(
+            bank_rekening,
+            amount,
+            Side::Debet,
+        )
+        .with_row(
+            "1002".to_string(),
+            amount,
+            Side::Credit,
+        )
+    }
+
     pub fn add_merch(
         self,
         merch_rekening: String,
@@ -0,0 +1,191 @@
+//! A blocking [`ConscriboClient`], for the interface and the command line,
+//! which runs the async client on a runtime of its own like
+//! `reqwest::blocking` does.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::runtime::Runtime;
+
+use crate::{
+    entities::Entity, response::ApiResponse, session::Credentials,
+    transactions::UnifiedTransaction, ApiCall, ConscriboError, GetTransactionResult, Progress,
+    TransactionDownload,
+};
+
+#[derive(Default)]
+enum Download {
+    #[default]
+    NotStarted,
+    Running(TransactionDownload),
+    Done(Vec<UnifiedTransaction>),
+    /// Kept until cancelled, so polling doesn't start it over every time
+    Failed(Arc<ConscriboError>),
+}
+
+#[derive(Clone)]
+pub struct ConscriboClient {
+    inner: crate::ConscriboClient,
+    runtime: Arc<Runtime>,
+    download: Arc<Mutex<Download>>,
+}
+
+impl ConscriboClient {
+    pub fn new(account_name: String) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("Could not start the runtime for Conscribo");
+        Self {
+            inner: crate::ConscriboClient::new(account_name),
+            runtime: Arc::new(runtime),
+            download: Default::default(),
+        }
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.inner = self.inner.with_credentials(credentials);
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: impl ToString) -> Self {
+        self.inner = self.inner.with_base_url(base_url);
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: String) -> Self {
+        self.inner = self.inner.with_session_id(session_id);
+        self
+    }
+
+    pub fn with_two_fa_prompt(
+        mut self,
+        prompt: impl Fn() -> Option<i32> + Send + Sync + 'static,
+    ) -> Self {
+        self.inner = self.inner.with_two_fa_prompt(prompt);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.inner = self.inner.with_concurrency(concurrency);
+        self
+    }
+
+    pub fn execute<A: ApiCall>(&self, call: A) -> Result<ApiResponse<A::Response>, ConscriboError> {
+        self.runtime.block_on(self.inner.execute(call))
+    }
+
+    pub fn login(&self) -> Result<(), ConscriboError> {
+        self.runtime.block_on(self.inner.login())
+    }
+
+    pub fn get_relations(&self) -> Result<Vec<Entity>, ConscriboError> {
+        self.runtime.block_on(self.inner.get_relations())
+    }
+
+    /// Starts downloading every transaction on the debtor and creditor
+    /// accounts the first time, after that tells how far along it is. Meant to
+    /// be polled every frame; once done, the same transactions or the same
+    /// error are returned until [`Self::cancel_transactions`].
+    pub fn transactions(&self) -> Result<GetTransactionResult, ConscriboError> {
+        let mut download = self.download.lock().unwrap();
+        let progress = match &*download {
+            Download::NotStarted => {
+                let _runtime = self.runtime.enter();
+                let d = self.inner.download_transactions();
+                let progress = d.progress();
+                *download = Download::Running(d);
+                progress
+            }
+            Download::Running(d) if d.is_finished() => {
+                let Download::Running(d) = std::mem::take(&mut *download) else {
+                    unreachable!()
+                };
+                return match self.runtime.block_on(d.finish()) {
+                    Ok(t) => {
+                        *download = Download::Done(t.clone());
+                        Ok(GetTransactionResult::Done(t))
+                    }
+                    Err(e) => {
+                        let e = Arc::new(e);
+                        *download = Download::Failed(e.clone());
+                        Err(ConscriboError::Download(e))
+                    }
+                };
+            }
+            Download::Running(d) => d.progress(),
+            Download::Done(t) => return Ok(GetTransactionResult::Done(t.clone())),
+            Download::Failed(e) => return Err(ConscriboError::Download(e.clone())),
+        };
+        Ok(GetTransactionResult::NotDone {
+            total: progress.total,
+            count: progress.count,
+            from_cache: progress.from_cache,
+        })
+    }
+
+    /// Stops a running download, or forgets the finished or failed one so the
+    /// next [`Self::transactions`] starts over
+    pub fn cancel_transactions(&self) {
+        *self.download.lock().unwrap() = Download::NotStarted;
+    }
+
+    /// Downloads every transaction on the debtor and creditor accounts,
+    /// calling `on_progress` as they come in
+    pub fn wait_for_transactions(
+        &self,
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<Vec<UnifiedTransaction>, ConscriboError> {
+        let t = self.runtime.block_on(async {
+            let d = self.inner.download_transactions();
+            let mut progress = d.subscribe();
+            let mut finished = std::pin::pin!(d.finish());
+            loop {
+                tokio::select! {
+                    t = &mut finished => break t,
+                    Ok(()) = progress.changed() => on_progress(*progress.borrow_and_update()),
+                }
+            }
+        })?;
+        *self.download.lock().unwrap() = Download::Done(t.clone());
+        Ok(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use penning_helper_conscribo_mock::{Fixtures, MockConscribo};
+
+    use super::ConscriboClient;
+    use crate::{session::Credentials, ConscriboError, GetTransactionResult};
+
+    #[test]
+    fn a_failed_download_stays_failed_until_cancelled() {
+        let mock = MockConscribo::start(Fixtures::default());
+        let client = ConscriboClient::new("aegee-delft".to_string())
+            .with_base_url(mock.url())
+            .with_credentials(Credentials::new(
+                "penningmeester".to_string(),
+                "fout".to_string(),
+            ));
+        let failed = loop {
+            match client.transactions() {
+                Ok(GetTransactionResult::NotDone { .. }) => {
+                    std::thread::sleep(std::time::Duration::from_millis(10))
+                }
+                r => break r,
+            }
+        };
+        assert!(matches!(failed, Err(ConscriboError::Download(_))));
+        assert!(matches!(
+            client.transactions(),
+            Err(ConscriboError::Download(_))
+        ));
+
+        client.cancel_transactions();
+        assert!(matches!(
+            client.transactions(),
+            Ok(GetTransactionResult::NotDone { .. })
+        ));
+    }
+}
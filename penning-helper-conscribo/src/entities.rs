@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use chrono::NaiveDate;
 use filters::Filter;
-use penning_helper_types::Euro;
+use penning_helper_types::{Bic, Euro, Iban, Lenient};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::serde_as;
@@ -11,7 +11,7 @@ use crate::ApiCall;
 
 pub mod filters;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 pub struct Entities {
     filters: Vec<Filter>,
 }
@@ -78,6 +78,9 @@ pub struct Account {
     pub country: String,
     pub name: String,
     pub city: String,
-    pub iban: String,
-    pub bic: String,
+    /// Conscribo doesn't check bank details, so they are only validated here
+    #[serde(default)]
+    pub iban: Lenient<Iban>,
+    #[serde(default)]
+    pub bic: Lenient<Bic>,
 }
@@ -1,15 +1,17 @@
 use std::{
     collections::HashSet,
     path::PathBuf,
-    sync::{atomic::AtomicUsize, Arc, Mutex, RwLock},
+    sync::{Arc, RwLock},
 };
 
 use chrono::NaiveDate;
 use entities::Entity;
-use response::ApiResponse;
+use futures::{Stream, TryStreamExt};
+use response::{ApiResponse, Message};
 use serde::{de::DeserializeOwned, Serialize};
 use session::Credentials;
-use transactions::{TransactionConvertError, Transactions, UnifiedTransaction};
+use tokio::{sync::watch, task::JoinHandle};
+use transactions::{Transaction, TransactionConvertError, Transactions, UnifiedTransaction};
 
 pub mod response;
 
@@ -31,81 +33,109 @@ pub mod add_invoice;
 
 pub mod add_transaction;
 
-const VERSION: &'static str = "1.20240610";
+pub mod blocking;
+
+mod paginate;
+
+pub use paginate::paginate;
+
+const VERSION: &str = "1.20240610";
+
+const HOST: &str = "https://api.secure.conscribo.nl";
+
+/// Conscribo hands out at most this many transactions per request
+const PAGE_SIZE: usize = 100;
+
+/// How many pages of transactions are requested at the same time by default
+const CONCURRENCY: usize = 4;
 
 pub trait ApiCall: Serialize {
     type Response: DeserializeOwned + Default;
     const PATH: &'static str;
     const METHOD: reqwest::Method;
 
-    #[deprecated = "Prefer ConscriboClient::execute"]
-    fn call(&self, client: &ConscriboClient) -> Result<ApiResponse<Self::Response>, RequestError> {
-        let url = format!(
-            "https://api.secure.conscribo.nl/{}/{}/{}",
-            client.account_name,
+    fn path_params(&self) -> Vec<&str> {
+        vec![]
+    }
+
+    fn url(&self, base_url: &str, account_name: &str) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            base_url,
+            account_name,
             Self::PATH,
             self.path_params().join("/"),
-        );
-        // let mut request = client.client.post(&url).header("X-Conscribo-API-Version", VERSION);
-        let mut request = client
-            .client
-            .request(Self::METHOD, &url)
-            .header("X-Conscribo-API-Version", VERSION);
-        if let Some(session_id) = client.session_id.read().unwrap().as_ref() {
-            request = request.header("X-Conscribo-SessionId", session_id);
-        }
-        // let response = request.json(self).send().unwrap();
-        if Self::METHOD == reqwest::Method::GET {
-            request = request.query(self);
-        } else {
-            request = request.json(self);
-        }
-        let response = request.send()?;
-        // let response = response.json::<ApiResponse<Self::Response>>()?;
-        let response_text = response.text()?;
-        // println!("{}", response_text);
-        // let now = SystemTime::now()
-        //     .duration_since(SystemTime::UNIX_EPOCH)
-        //     .unwrap()
-        //     .as_millis();
-        // std::fs::write(format!("./hidden/{}.json", now), &response_text).unwrap();
-        let response = serde_json::from_str(&response_text)?;
-
-        Ok(response)
+        )
     }
 
-    fn path_params(&self) -> Vec<&str> {
-        vec![]
+    /// Most calls are wrapped in a status and messages, the ones that are not
+    /// override this
+    fn parse(text: &str) -> Result<ApiResponse<Self::Response>, serde_json::Error> {
+        serde_json::from_str(text)
     }
 }
 
 #[derive(Debug, thiserror::Error)]
-pub enum RequestError {
-    #[error("Request error: {0}")]
-    RequestError(#[from] reqwest::Error),
-    #[error("Serde error: {0}")]
-    SerdeError(#[from] serde_json::Error),
+pub enum ConscriboError {
+    #[error("Could not log in to Conscribo: {}", join(.0))]
+    Auth(Vec<Message>),
+    #[error("There are no credentials to log in to Conscribo with")]
+    NoCredentials,
+    #[error("The Conscribo session has expired")]
+    SessionExpired,
+    #[error("Conscribo asks for a two-factor code")]
+    TwoFaRequired,
+    #[error("Conscribo: {}", join(.0))]
+    Api(Vec<Message>),
+    #[error("Conscribo sent an empty response")]
+    EmptyResponse,
+    #[error("Could not reach Conscribo: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Could not read the response of Conscribo: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("Could not read transaction: {0}")]
+    Convert(#[from] TransactionConvertError),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Downloading the transactions failed: {0}")]
+    Download(Arc<ConscriboError>),
 }
 
+fn join(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(Message::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Asked for the code of the authenticator app before every login, gives
+/// `None` when there is no code (yet)
+pub type TwoFaPrompt = Arc<dyn Fn() -> Option<i32> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct ConscriboClient {
+    base_url: String,
     account_name: String,
     credentials: Option<Credentials>,
+    two_fa: Option<TwoFaPrompt>,
     session_id: Arc<RwLock<Option<String>>>,
-    client: reqwest::blocking::Client,
-    t_get: Arc<RwLock<Option<TransactionGet>>>,
-    t_get_fast: Arc<RwLock<Option<TransactionGetFast>>>,
+    login: Arc<tokio::sync::Mutex<()>>,
+    client: reqwest::Client,
+    concurrency: usize,
 }
 
 impl ConscriboClient {
     pub fn new(account_name: String) -> Self {
         Self {
+            base_url: HOST.to_string(),
             account_name,
             credentials: None,
+            two_fa: None,
             session_id: Arc::new(RwLock::new(Option::None)),
-            client: reqwest::blocking::Client::new(),
-            t_get: Default::default(),
-            t_get_fast: Default::default(),
+            login: Default::default(),
+            client: reqwest::Client::new(),
+            concurrency: CONCURRENCY,
         }
     }
 
@@ -114,364 +144,316 @@ impl ConscriboClient {
         self
     }
 
+    /// Talks to another server than Conscribo, like the mock server in tests
+    pub fn with_base_url(mut self, base_url: impl ToString) -> Self {
+        self.base_url = base_url.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
     pub fn with_session_id(self, session_id: String) -> Self {
         *self.session_id.write().unwrap() = Some(session_id);
         self
     }
 
-    fn check_session_id(&self) {
-        if self.session_id.read().unwrap().is_none() {
-            if let Some(creds) = self.credentials.as_ref() {
-                #[allow(deprecated)]
-                let response = creds.call(self).unwrap();
-                if let Some(session_id) = response.response() {
-                    *self.session_id.write().unwrap() = Some(session_id.session_id.clone());
-                } else {
-                    let msgs = response.get_messages().unwrap();
-                    for msg in msgs.errors() {
-                        eprintln!("{:?}", msg);
-                    }
-
-                    for msg in msgs.warnings() {
-                        eprintln!("{:?}", msg);
-                    }
-
-                    for msg in msgs.infos() {
-                        eprintln!("{:?}", msg);
-                    }
-
-                    panic!("No session id returned");
-                }
-            } else {
-                panic!("No session id set");
-            }
+    /// For accounts with two-factor authentication
+    pub fn with_two_fa_prompt(
+        mut self,
+        prompt: impl Fn() -> Option<i32> + Send + Sync + 'static,
+    ) -> Self {
+        self.two_fa = Some(Arc::new(prompt));
+        self
+    }
+
+    /// How many pages of transactions are requested at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Logs in with the credentials, unless there is a session already
+    pub async fn login(&self) -> Result<(), ConscriboError> {
+        self.session().await.map(|_| ())
+    }
+
+    /// The current session id, logging in first when there is none
+    async fn session(&self) -> Result<String, ConscriboError> {
+        if let Some(session_id) = self.session_id.read().unwrap().clone() {
+            return Ok(session_id);
+        }
+        // one login at a time, the requests waiting for it use its session
+        let _login = self.login.lock().await;
+        if let Some(session_id) = self.session_id.read().unwrap().clone() {
+            return Ok(session_id);
         }
+        let session_id = self.new_session().await?;
+        *self.session_id.write().unwrap() = Some(session_id.clone());
+        Ok(session_id)
     }
 
-    pub fn execute<A: ApiCall>(&self, call: A) -> Result<ApiResponse<A::Response>, RequestError> {
-        self.check_session_id();
-        #[allow(deprecated)]
-        call.call(self)
+    /// Logs in again after `expired` timed out, unless another request did
+    /// so already
+    async fn renew(&self, expired: &str) -> Result<String, ConscriboError> {
+        let _login = self.login.lock().await;
+        let current = self.session_id.read().unwrap().clone();
+        if let Some(session_id) = current.filter(|s| s != expired) {
+            return Ok(session_id);
+        }
+        let session_id = self.new_session().await?;
+        *self.session_id.write().unwrap() = Some(session_id.clone());
+        Ok(session_id)
     }
 
-    pub fn get_relations(&self) -> Vec<Entity> {
-        let leden = self
-            .execute(
+    async fn new_session(&self) -> Result<String, ConscriboError> {
+        let mut creds = self
+            .credentials
+            .clone()
+            .ok_or(ConscriboError::NoCredentials)?;
+        if let Some(prompt) = self.two_fa.clone() {
+            // the prompt may wait for someone to type the code
+            let code = tokio::task::spawn_blocking(move || prompt())
+                .await
+                .ok()
+                .flatten()
+                .ok_or(ConscriboError::TwoFaRequired)?;
+            creds = creds.with_two_fa_code(code);
+        }
+        match self.send(&creds, None).await {
+            Ok(r) => Ok(r.into_result()?.session_id),
+            Err(ConscriboError::Api(m)) => Err(ConscriboError::Auth(m)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends `call` as is, with errors in the response turned into
+    /// [`ConscriboError::Api`]
+    async fn send<A: ApiCall>(
+        &self,
+        call: &A,
+        session_id: Option<&str>,
+    ) -> Result<ApiResponse<A::Response>, ConscriboError> {
+        let mut request = self
+            .client
+            .request(A::METHOD, call.url(&self.base_url, &self.account_name))
+            .header("X-Conscribo-API-Version", VERSION);
+        if let Some(session_id) = session_id {
+            request = request.header("X-Conscribo-SessionId", session_id);
+        }
+        if A::METHOD == reqwest::Method::GET {
+            request = request.query(call);
+        } else {
+            request = request.json(call);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(ConscriboError::SessionExpired);
+        }
+        // other client errors come with messages that say more than the status
+        let response = if response.status().is_server_error() {
+            response.error_for_status()?
+        } else {
+            response
+        };
+        let response_text = response.text().await?;
+        // Conscribo doesn't always answer an expired session with a 401
+        if session_id.is_some() && response::session_expired(&response_text) {
+            return Err(ConscriboError::SessionExpired);
+        }
+        let errors = response::errors_in(&response_text);
+        if !errors.is_empty() {
+            return Err(ConscriboError::Api(errors));
+        }
+        Ok(A::parse(&response_text)?)
+    }
+
+    /// Logs in when there is no session yet, and again when the session
+    /// timed out, after which `call` is sent once more
+    pub async fn execute<A: ApiCall>(
+        &self,
+        call: A,
+    ) -> Result<ApiResponse<A::Response>, ConscriboError> {
+        let session_id = self.session().await?;
+        match self.send(&call, Some(&session_id)).await {
+            Err(ConscriboError::SessionExpired) if self.credentials.is_some() => {
+                let session_id = self.renew(&session_id).await?;
+                self.send(&call, Some(&session_id)).await
+            }
+            r => r,
+        }
+    }
+
+    pub async fn get_relations(&self) -> Result<Vec<Entity>, ConscriboError> {
+        let (leden, onbekend) = futures::join!(
+            self.execute(
                 entities::Entities::new().filter(entities::filters::Filter::entity_type("lid")),
-            )
-            .unwrap();
-        let onbekend = self
-            .execute(
+            ),
+            self.execute(
                 entities::Entities::new()
                     .filter(entities::filters::Filter::entity_type("onbekend")),
-            )
-            .unwrap();
+            ),
+        );
         let mut entities = vec![];
-        if let Some(leden) = leden.response_owned() {
+        if let Some(leden) = leden?.response_owned() {
             entities.extend(leden.entities.into_values());
         }
-        if let Some(onbekend) = onbekend.response_owned() {
+        if let Some(onbekend) = onbekend?.response_owned() {
             entities.extend(onbekend.entities.into_values());
         }
 
-        entities
-    }
-
-    pub fn get_transactions(&self) -> Result<GetTransactionResult, TransactionConvertError> {
-        let r = { self.t_get.write().unwrap().take() };
-        if let Some(mut t_get) = r {
-            let r = self.execute(
-                Transactions::new(1000, t_get.offset + 100)
-                    .relations(t_get.relations.iter().map(String::as_str).collect())
-                    .accounts(vec!["1001", "1002"]),
-            );
-            let r = r.unwrap();
-            if let Some(m) = r.get_messages() {
-                for message in m.errors() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.warnings() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.infos() {
-                    eprintln!("{:?}", message);
-                }
-                return Err(TransactionConvertError::Other("oof".to_string()));
-            } else if let Some(res) = r.response_owned() {
-                let t = res.transactions();
-                let t = t
-                    .into_values()
-                    .map(|t| t.unify())
-                    .collect::<Result<Vec<_>, _>>()
-                    .unwrap();
-                let t: Vec<UnifiedTransaction> = t.into_iter().flatten().collect();
-                // println!("Got {} transactions", t.len());
-                // println!("Total transactions: {}", t_get.total);
-                t_get.unifieds.extend(t);
-                t_get.offset += 100;
-                if t_get.unifieds.len() >= res.nr_transactions as usize {
-                    return Ok(GetTransactionResult::Done(t_get.unifieds));
-                } else {
-                    let res = GetTransactionResult::NotDone {
-                        total: t_get.total,
-                        count: t_get.unifieds.len(),
-                        from_cache: 0,
-                    };
-                    self.t_get.write().unwrap().replace(t_get);
-                    return Ok(res);
-                }
-            } else {
-                return Err(TransactionConvertError::Other("oof".to_string()));
-            }
-        } else {
-            let all_relations: Vec<String> =
-                self.get_relations().into_iter().map(|e| e.code).collect();
-            let r = self.execute(
-                Transactions::new(1000, 0)
-                    .relations(all_relations.iter().map(String::as_str).collect())
-                    .accounts(vec!["1001", "1002"]),
-            );
-            let r = r.unwrap();
-            if let Some(m) = r.get_messages() {
-                for message in m.errors() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.warnings() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.infos() {
-                    eprintln!("{:?}", message);
-                }
-                return Err(TransactionConvertError::Other("oof".to_string()));
-            } else if let Some(res) = r.response_owned() {
-                let t = res.transactions();
-                let t = t
-                    .into_values()
-                    .map(|t| t.unify())
-                    .collect::<Result<Vec<_>, _>>()
-                    .unwrap();
-                let t: Vec<UnifiedTransaction> = t.into_iter().flatten().collect();
-                if t.len() >= res.nr_transactions as usize {
-                    return Ok(GetTransactionResult::Done(t));
-                } else {
-                    let r = GetTransactionResult::NotDone {
-                        total: res.nr_transactions,
-                        count: t.len(),
-                        from_cache: 0,
-                    };
-                    self.t_get.write().unwrap().replace(TransactionGet {
-                        total: res.nr_transactions,
-                        offset: 0,
-                        relations: all_relations,
-                        unifieds: t,
-                    });
-                    return Ok(r);
-                }
-            } else {
-                return Err(TransactionConvertError::Other("oof".to_string()));
-            }
-        }
+        Ok(entities)
     }
 
-    pub fn get_transactions_faster(&self) -> Result<GetTransactionResult, TransactionConvertError> {
-        let t_get_fast = self.t_get_fast.read().unwrap();
-        if let Some(tgf) = t_get_fast.as_ref() {
-            if tgf.count.load(std::sync::atomic::Ordering::SeqCst) >= tgf.total {
-                let res1 = tgf.unifieds.lock().unwrap().clone();
-                let mut cache: HashSet<_> =
-                    tgf.cache.clone().unifieds.clone().into_iter().collect();
-                cache.extend(res1.clone());
-                let res: Vec<UnifiedTransaction> = cache.into_iter().collect();
-                // find difference between res1 and res
-                let diff: Vec<_> = res1.into_iter().filter(|e| !res.contains(e)).collect();
-                println!("Found {} new transactions", diff.len());
-
-                ClientCache::new(tgf.total, res.clone()).save();
-                return Ok(GetTransactionResult::Done(res));
-            } else {
-                return Ok(GetTransactionResult::NotDone {
-                    total: tgf.total as i64,
-                    count: tgf.count.load(std::sync::atomic::Ordering::SeqCst),
-                    from_cache: tgf.cache.unifieds.len(),
-                });
-            }
-        } else {
-            drop(t_get_fast);
-            let cache = ClientCache::load().unwrap_or_else(|| ClientCache::empty());
-
-            let all_relations: Vec<String> =
-                self.get_relations().into_iter().map(|e| e.code).collect();
-            let relations = Arc::new(all_relations);
-            let r = self.execute(
+    /// How many transactions there are on the debtor and creditor accounts of
+    /// `relations` since `since`
+    pub async fn count_transactions(
+        &self,
+        relations: &[String],
+        since: NaiveDate,
+    ) -> Result<usize, ConscriboError> {
+        let r = self
+            .execute(
                 Transactions::new(0, 0)
                     .relations(relations.iter().map(String::as_str).collect())
                     .accounts(vec!["1001", "1002"])
-                    .date_start(cache.date),
-            );
-
-            let r = r.unwrap();
-            if let Some(m) = r.get_messages() {
-                for message in m.errors() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.warnings() {
-                    eprintln!("{:?}", message);
-                }
-
-                for message in m.infos() {
-                    eprintln!("{:?}", message);
-                }
-                return Err(TransactionConvertError::Other("oof".to_string()));
-            } else if let Some(res) = r.response_owned() {
-                let r = GetTransactionResult::NotDone {
-                    total: res.nr_transactions,
-                    count: 0,
-                    from_cache: cache.unifieds.len(),
-                };
-                let tgf = TransactionGetFast {
-                    total: res.nr_transactions as usize,
-                    offset: Arc::new(AtomicUsize::new(0)),
-                    count: Arc::new(AtomicUsize::new(0)),
-                    relations: relations.clone(),
-                    unifieds: Arc::new(Mutex::new(vec![])),
-                    cache: Arc::new(cache),
-                };
-                self.t_get_fast.write().unwrap().replace(tgf);
-                let c = self.clone();
-                std::thread::spawn(move || {
-                    c.get_transactions_faster_worker();
-                });
-                return Ok(r);
-            } else {
-                return Err(TransactionConvertError::Other("niks?".to_string()));
-            }
-        }
+                    .date_start(since),
+            )
+            .await?;
+        Ok(r.into_result()?.nr_transactions.max(0) as usize)
     }
 
-    fn get_transactions_faster_worker(&self) {
-        let relations = self.get_tgf_relations();
-        let cache = self.get_tgf_cache();
-        loop {
-            let offset = self.get_tgf_offset();
-            let t = self.tgf_transactions(offset as i64, &relations, cache.date);
-            {
-                let tgf = self.t_get_fast.read().unwrap();
-                if let Some(tgf) = tgf.as_ref() {
-                    tgf.count
-                        .fetch_add(t.len(), std::sync::atomic::Ordering::SeqCst);
-                    tgf.offset
-                        .fetch_add(100, std::sync::atomic::Ordering::SeqCst);
-                    tgf.unifieds.lock().unwrap().extend(t);
-                    if tgf.count.load(std::sync::atomic::Ordering::SeqCst) >= tgf.total {
-                        break;
-                    }
-                    println!("memes");
-                } else {
-                    break;
-                }
+    /// The first `total` transactions on the debtor and creditor accounts of
+    /// `relations` since `since`, a page at a time with the pages of
+    /// [`Self::with_concurrency`] in flight
+    pub fn transactions(
+        &self,
+        relations: Arc<[String]>,
+        since: NaiveDate,
+        total: usize,
+    ) -> impl Stream<Item = Result<Transaction, ConscriboError>> + '_ {
+        paginate(total, PAGE_SIZE, self.concurrency, move |offset| {
+            let relations = relations.clone();
+            async move {
+                let r = self
+                    .execute(
+                        Transactions::new(PAGE_SIZE as i64, offset as i64)
+                            .relations(relations.iter().map(String::as_str).collect())
+                            .accounts(vec!["1001", "1002"])
+                            .date_start(since),
+                    )
+                    .await?;
+                Ok(r.into_result()?.transactions().into_values().collect())
             }
-        }
+        })
     }
 
-    fn get_tgf_offset(&self) -> usize {
-        let tgf = self.t_get_fast.read().unwrap();
-        if let Some(tgf) = tgf.as_ref() {
-            tgf.offset.load(std::sync::atomic::Ordering::SeqCst)
-        } else {
-            0
+    /// Downloads every transaction on the debtor and creditor accounts in the
+    /// background, on top of the ones cached by the last download. Has to be
+    /// called from within a tokio runtime.
+    pub fn download_transactions(&self) -> TransactionDownload {
+        let (progress, receiver) = watch::channel(Progress::default());
+        let client = self.clone();
+        let task = tokio::spawn(async move { client.download(progress).await });
+        TransactionDownload {
+            progress: receiver,
+            task: Some(task),
         }
     }
 
-    fn get_tgf_relations(&self) -> Arc<Vec<String>> {
-        let tgf = self.t_get_fast.read().unwrap();
-        if let Some(tgf) = tgf.as_ref() {
-            tgf.relations.clone()
-        } else {
-            Arc::new(vec![])
+    async fn download(
+        &self,
+        progress: watch::Sender<Progress>,
+    ) -> Result<Vec<UnifiedTransaction>, ConscriboError> {
+        let cache = ClientCache::load().unwrap_or_else(ClientCache::empty);
+        let relations: Arc<[String]> = self
+            .get_relations()
+            .await?
+            .into_iter()
+            .map(|e| e.code)
+            .collect();
+        let total = self.count_transactions(&relations, cache.date).await?;
+        progress.send_replace(Progress {
+            total,
+            count: 0,
+            from_cache: cache.unifieds.len(),
+        });
+
+        let since = cache.date;
+        let mut unifieds: HashSet<UnifiedTransaction> = cache.unifieds.into_iter().collect();
+        let mut transactions = std::pin::pin!(self.transactions(relations, since, total));
+        while let Some(t) = transactions.try_next().await? {
+            unifieds.extend(t.unify()?);
+            progress.send_modify(|p| p.count += 1);
         }
-    }
 
-    fn get_tgf_cache(&self) -> Arc<ClientCache> {
-        let tgf = self.t_get_fast.read().unwrap();
-        if let Some(tgf) = tgf.as_ref() {
-            tgf.cache.clone()
-        } else {
-            Arc::new(ClientCache::empty())
+        let unifieds: Vec<UnifiedTransaction> = unifieds.into_iter().collect();
+        if let Err(e) = ClientCache::new(total, unifieds.clone()).save() {
+            eprintln!("Could not save the transaction cache: {}", e);
         }
+        Ok(unifieds)
     }
+}
 
-    fn tgf_transactions(
-        &self,
-        offset: i64,
-        relations: &[String],
-        start_date: NaiveDate,
-    ) -> Vec<UnifiedTransaction> {
-        let r = self.execute(
-            Transactions::new(100, offset)
-                .relations(relations.iter().map(String::as_str).collect())
-                .accounts(vec!["1001", "1002"])
-                .date_start(start_date),
-        );
-        let r = r.unwrap();
-        if let Some(m) = r.get_messages() {
-            for message in m.errors() {
-                eprintln!("{:?}", message);
-            }
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Transactions since the last download
+    pub total: usize,
+    pub count: usize,
+    pub from_cache: usize,
+}
 
-            for message in m.warnings() {
-                eprintln!("{:?}", message);
-            }
+/// A transaction download running in the background, which is cancelled when
+/// it is dropped
+pub struct TransactionDownload {
+    progress: watch::Receiver<Progress>,
+    task: Option<JoinHandle<Result<Vec<UnifiedTransaction>, ConscriboError>>>,
+}
 
-            for message in m.infos() {
-                eprintln!("{:?}", message);
-            }
-            return vec![];
-        } else if let Some(res) = r.response_owned() {
-            let t = res.transactions();
-            let t = t
-                .into_values()
-                .map(|t| t.unify())
-                .collect::<Result<Vec<_>, _>>()
-                .unwrap();
-            let t: Vec<UnifiedTransaction> = t.into_iter().flatten().collect();
-            t
-        } else {
-            vec![]
+impl TransactionDownload {
+    pub fn progress(&self) -> Progress {
+        *self.progress.borrow()
+    }
+
+    /// A receiver that is notified every time a transaction comes in
+    pub fn subscribe(&self) -> watch::Receiver<Progress> {
+        self.progress.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    pub fn cancel(&self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+
+    pub async fn finish(mut self) -> Result<Vec<UnifiedTransaction>, ConscriboError> {
+        let Some(task) = self.task.take() else {
+            return Err(ConscriboError::Cancelled);
+        };
+        match task.await {
+            Ok(r) => r,
+            Err(e) if e.is_cancelled() => Err(ConscriboError::Cancelled),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
         }
     }
 }
 
-struct TransactionGet {
-    total: i64,
-    offset: i64,
-    relations: Vec<String>,
-    unifieds: Vec<UnifiedTransaction>,
+impl Drop for TransactionDownload {
+    fn drop(&mut self) {
+        self.cancel();
+    }
 }
 
 pub enum GetTransactionResult {
     Done(Vec<UnifiedTransaction>),
     NotDone {
-        total: i64,
+        total: usize,
         count: usize,
         from_cache: usize,
     },
 }
 
-#[derive(Clone)]
-struct TransactionGetFast {
-    total: usize,
-    offset: Arc<AtomicUsize>,
-    count: Arc<AtomicUsize>,
-    relations: Arc<Vec<String>>,
-    unifieds: Arc<Mutex<Vec<UnifiedTransaction>>>,
-    cache: Arc<ClientCache>,
-}
-
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 struct ClientCache {
     total: usize,
@@ -507,14 +489,122 @@ impl ClientCache {
         Some(res)
     }
 
-    pub fn save(&self) {
+    pub fn save(&self) -> std::io::Result<()> {
         let dir = dirs::data_local_dir()
             .unwrap_or(PathBuf::from("."))
             .join("penning-helper");
-        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(&dir)?;
         let dir = dir.join("clientcache.bin");
-        let file = std::fs::File::create(dir).unwrap();
+        let file = std::fs::File::create(dir)?;
         let writer = std::io::BufWriter::new(file);
-        bincode::serialize_into(writer, self).unwrap();
+        bincode::serialize_into(writer, self).map_err(std::io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use futures::TryStreamExt;
+    use penning_helper_conscribo_mock::{Booking, Fixtures, MockConscribo, Row};
+    use penning_helper_types::Euro;
+
+    use crate::{
+        entities::{filters::Filter, Entities},
+        multirequest::{MultiRequest, MultiRequestElementResponse},
+        session::Credentials,
+        ConscriboClient, ConscriboError,
+    };
+
+    fn client(mock: &MockConscribo, pass_phrase: &str) -> ConscriboClient {
+        ConscriboClient::new("aegee-delft".to_string())
+            .with_base_url(mock.url())
+            .with_credentials(Credentials::new(
+                "penningmeester".to_string(),
+                pass_phrase.to_string(),
+            ))
+    }
+
+    #[tokio::test]
+    async fn expired_sessions_are_renewed() {
+        let mock = MockConscribo::start(Fixtures::default());
+        let client = client(&mock, "geheim");
+        assert_eq!(client.get_relations().await.unwrap().len(), 3);
+        assert_eq!(mock.logins(), 1);
+
+        mock.expire_sessions();
+        assert_eq!(client.get_relations().await.unwrap().len(), 3);
+        assert_eq!(mock.logins(), 2);
+    }
+
+    #[tokio::test]
+    async fn session_errors_in_a_body_renew_the_session() {
+        let mock = MockConscribo::start(Fixtures::default());
+        let client = client(&mock, "geheim");
+        client.login().await.unwrap();
+
+        mock.expire_sessions_in_body();
+        let responses = client
+            .execute(
+                MultiRequest::new().push("lid", Entities::new().filter(Filter::entity_type("lid"))),
+            )
+            .await
+            .and_then(|r| r.responses_owned())
+            .unwrap();
+        assert!(matches!(
+            responses["lid"].content(),
+            Some(MultiRequestElementResponse::EntityRequest(_))
+        ));
+        assert_eq!(mock.logins(), 2);
+
+        mock.expire_sessions_in_body();
+        assert_eq!(client.get_relations().await.unwrap().len(), 3);
+        assert_eq!(mock.logins(), 3);
+    }
+
+    #[tokio::test]
+    async fn refused_logins_are_auth_errors() {
+        let mock = MockConscribo::start(Fixtures::default());
+        let err = client(&mock, "fout").login().await.unwrap_err();
+        assert!(matches!(err, ConscriboError::Auth(_)), "{}", err);
+
+        let mock = MockConscribo::start(Fixtures {
+            two_fa_code: Some(123456),
+            ..Default::default()
+        });
+        let err = client(&mock, "geheim").login().await.unwrap_err();
+        assert!(matches!(err, ConscriboError::Auth(_)), "{}", err);
+        let client = client(&mock, "geheim").with_two_fa_prompt(|| Some(123456));
+        client.login().await.unwrap();
+        assert_eq!(mock.logins(), 1);
+    }
+
+    #[tokio::test]
+    async fn transactions_come_a_page_at_a_time() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mock = MockConscribo::start(Fixtures {
+            transactions: (1..=250)
+                .map(|id| {
+                    Booking::new(id, date, "Turflijst")
+                        .with_row(Row::debet("1001", Euro::new(1, 0)).with_relation("101"))
+                        .with_row(Row::credit("8010", Euro::new(1, 0)))
+                })
+                .collect(),
+            ..Default::default()
+        });
+        let client = client(&mock, "geheim").with_concurrency(2);
+        let since = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let relations: std::sync::Arc<[String]> = ["101".to_string()].into();
+        let total = client.count_transactions(&relations, since).await.unwrap();
+        assert_eq!(total, 250);
+
+        let transactions: Vec<_> = client
+            .transactions(relations, since, total)
+            .try_collect()
+            .await
+            .unwrap();
+        // a page comes as a map, so only the pages are in order
+        let mut ids: Vec<i64> = transactions.iter().map(|t| t.transaction_id).collect();
+        ids.sort();
+        assert_eq!(ids, (1..=250).collect::<Vec<_>>());
     }
 }
@@ -3,11 +3,11 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    response::{ApiResponse, ResponseMessages},
-    ApiCall,
+    response::{ApiResponse, Message, ResponseMessages},
+    ApiCall, ConscriboError,
 };
 
-#[derive(Serialize)]
+#[derive(Default, Serialize)]
 pub struct MultiRequest {
     requests: Vec<MultiElement>,
 }
@@ -53,36 +53,13 @@ impl ApiCall for MultiRequest {
 
     const METHOD: reqwest::Method = reqwest::Method::POST;
 
-    fn call(
-        &self,
-        client: &crate::ConscriboClient,
-    ) -> Result<crate::response::ApiResponse<Self::Response>, crate::RequestError> {
-        let url = format!(
-            "https://api.secure.conscribo.nl/{}/{}",
-            client.account_name,
-            Self::PATH
-        );
-        // let mut request = client.client.post(&url).header("X-Conscribo-API-Version", VERSION);
-        let mut request = client
-            .client
-            .request(Self::METHOD, &url)
-            .header("X-Conscribo-API-Version", super::VERSION);
-        if let Some(session_id) = client.session_id.read().unwrap().as_ref() {
-            request = request.header("X-Conscribo-SessionId", session_id);
-        }
-        // let response = request.json(self).send().unwrap();
-        if Self::METHOD == reqwest::Method::GET {
-            request = request.query(self);
-        } else {
-            request = request.json(self);
-        }
-        let response = request.send()?;
-        // let response = response.json::<ApiResponse<Self::Response>>()?;
-        let response_text = response.text()?;
-        // println!("{}", response_text);
-        let response = serde_json::from_str::<MultiRequestResponse>(&response_text)?;
+    fn url(&self, base_url: &str, account_name: &str) -> String {
+        format!("{}/{}/{}", base_url, account_name, Self::PATH)
+    }
 
-        Ok(ApiResponse::new(response))
+    /// The responses of a multirequest are not wrapped in a status
+    fn parse(text: &str) -> Result<ApiResponse<Self::Response>, serde_json::Error> {
+        Ok(ApiResponse::new(serde_json::from_str(text)?))
     }
 }
 
@@ -165,25 +142,25 @@ multi_request_elements!(
 
 #[derive(Deserialize, Default)]
 pub struct MultiRequestResponse {
-    responses: MRT,
+    responses: Mrt,
 }
 
 #[derive(Deserialize)]
 #[serde(untagged)]
-enum MRT {
+enum Mrt {
     Vec(Vec<Element>),
     HashMap(HashMap<String, Element>),
 }
 
-impl Default for MRT {
+impl Default for Mrt {
     fn default() -> Self {
         Self::Vec(Vec::new())
     }
 }
 
 impl MultiRequestResponse {
-    pub fn from_json(s: &str) -> ApiResponse<Self> {
-        ApiResponse::new(serde_json::from_str(s).unwrap())
+    pub fn from_json(s: &str) -> Result<ApiResponse<Self>, ConscriboError> {
+        Ok(ApiResponse::new(serde_json::from_str(s)?))
     }
 }
 
@@ -196,7 +173,7 @@ pub struct Element {
     code: i32,
     #[serde(flatten, default)]
     content: Option<MultiRequestElementResponse>,
-    #[serde(default)]
+    #[serde(default, rename = "responseMessages", alias = "response_messages")]
     response_messages: Option<ResponseMessages>,
 }
 
@@ -221,30 +198,60 @@ impl Element {
         self.content
     }
 
-    pub fn content_unsafe(&self) -> &MultiRequestElementResponse {
-        self.content().unwrap()
+    pub fn get_messages(&self) -> Option<&ResponseMessages> {
+        self.response_messages.as_ref()
     }
 
-    pub fn content_unsafe_owned(self) -> MultiRequestElementResponse {
-        self.content_owned().unwrap()
+    /// Why Conscribo refused this request, `None` when it was accepted
+    pub fn error(&self) -> Option<&Message> {
+        self.get_messages()?.errors().first()
     }
+}
 
-    pub fn get_messages(&self) -> Option<&ResponseMessages> {
-        self.response_messages.as_ref()
+/// Why Conscribo refused the request pushed as `seq`, `None` when it was
+/// accepted. A request it didn't answer counts as refused.
+pub fn refusal(responses: &HashMap<String, Element>, seq: impl ToString) -> Option<String> {
+    match responses.get(&seq.to_string()) {
+        Some(e) => e.error().map(|m| m.message().to_string()),
+        None => Some("no answer".to_string()),
     }
 }
 
 impl ApiResponse<MultiRequestResponse> {
     pub fn responses(&self) -> Option<HashMap<String, &Element>> {
         self.response().as_ref().map(|r| match &r.responses {
-            MRT::Vec(v) => v.iter().map(|e| (e.seq.clone(), e)).collect(),
-            MRT::HashMap(m) => m.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            Mrt::Vec(v) => v.iter().map(|e| (e.seq.clone(), e)).collect(),
+            Mrt::HashMap(m) => m.iter().map(|(k, v)| (k.clone(), v)).collect(),
         })
     }
-    pub fn responses_owned_unsafe(self) -> HashMap<String, Element> {
-        match self.response_unsafe_owned().responses {
-            MRT::Vec(v) => v.into_iter().map(|e| (e.seq.clone(), e)).collect(),
-            MRT::HashMap(m) => m,
-        }
+
+    /// The responses by their sequence, or [`ConscriboError::EmptyResponse`]
+    /// when there are none
+    pub fn responses_owned(self) -> Result<HashMap<String, Element>, ConscriboError> {
+        Ok(match self.into_result()?.responses {
+            Mrt::Vec(v) => v.into_iter().map(|e| (e.seq.clone(), e)).collect(),
+            Mrt::HashMap(m) => m,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{refusal, MultiRequestResponse};
+
+    #[test]
+    fn refused_and_unanswered_requests_are_refusals() {
+        let responses = MultiRequestResponse::from_json(
+            r#"{"responses": [
+                {"status": 1, "Request.sequence": "0", "Response.HTTPStatusCode": 200},
+                {"status": 0, "Request.sequence": "1", "Response.HTTPStatusCode": 400,
+                 "responseMessages": {"error": [{"message": "Unknown account"}]}}
+            ]}"#,
+        )
+        .and_then(|r| r.responses_owned())
+        .unwrap();
+        assert_eq!(refusal(&responses, 0), None);
+        assert_eq!(refusal(&responses, 1), Some("Unknown account".to_string()));
+        assert_eq!(refusal(&responses, 2), Some("no answer".to_string()));
     }
 }
@@ -0,0 +1,68 @@
+use std::future::Future;
+
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+
+/// Requests `total` items `page_size` at a time, with at most `concurrency`
+/// pages in flight. `page` gets the offset of the page, the items come out in
+/// order and a page that failed comes out as its error.
+pub fn paginate<T, E, F, Fut>(
+    total: usize,
+    page_size: usize,
+    concurrency: usize,
+    page: F,
+) -> impl Stream<Item = Result<T, E>>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, E>>,
+{
+    stream::iter((0..total).step_by(page_size.max(1)))
+        .map(page)
+        .buffered(concurrency.max(1))
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::TryStreamExt;
+
+    use super::paginate;
+
+    #[tokio::test]
+    async fn pages_come_out_in_order_with_bounded_concurrency() {
+        let in_flight = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let items: Vec<usize> = paginate(250, 100, 2, |offset| {
+            let (in_flight, most) = (&in_flight, &most);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                // the later pages finish first
+                tokio::time::sleep(std::time::Duration::from_millis(30 - offset as u64 / 10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, ()>((offset..(offset + 100).min(250)).collect())
+            }
+        })
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(items, (0..250).collect::<Vec<_>>());
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn a_failed_page_is_an_error() {
+        let r: Result<Vec<usize>, &str> = paginate(300, 100, 3, |offset| async move {
+            if offset == 100 {
+                Err("page 2")
+            } else {
+                Ok(vec![offset])
+            }
+        })
+        .try_collect()
+        .await;
+        assert_eq!(r, Err("page 2"));
+    }
+}
@@ -1,4 +1,9 @@
+use std::fmt::Display;
+
 use serde::Deserialize;
+use serde_json::Value;
+
+use crate::ConscriboError;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -35,12 +40,10 @@ impl<T> ApiResponse<T> {
         self.response
     }
 
-    pub fn response_unsafe(&self) -> &T {
-        self.response().unwrap()
-    }
-
-    pub fn response_unsafe_owned(self) -> T {
-        self.response_owned().unwrap()
+    /// The response, errors Conscribo sends instead are already turned into
+    /// [`ConscriboError::Api`] by the client
+    pub fn into_result(self) -> Result<T, ConscriboError> {
+        self.response.ok_or(ConscriboError::EmptyResponse)
     }
 }
 
@@ -89,4 +92,99 @@ impl Message {
     pub fn hint(&self) -> Option<&str> {
         self.hint.as_deref()
     }
+
+    /// Conscribo doesn't know the session id (anymore), logging in again
+    /// fixes that
+    pub fn is_session_error(&self) -> bool {
+        let about_session = |s: &str| s.to_lowercase().contains("session");
+        about_session(&self.message) || self.code.as_deref().is_some_and(about_session)
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " ({})", code)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, ", {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Only the messages of a response, for any call
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Messages {
+    #[serde(default)]
+    response_messages: Option<ResponseMessages>,
+}
+
+/// The errors Conscribo put in `text`, if it is json at all
+pub(crate) fn errors_in(text: &str) -> Vec<Message> {
+    serde_json::from_str::<Messages>(text)
+        .ok()
+        .and_then(|m| m.response_messages)
+        .map(|m| m.error)
+        .unwrap_or_default()
+}
+
+/// Whether Conscribo says the session expired in `text`, at the top or in
+/// any of the responses of a multirequest, which come with a 200 either way
+pub(crate) fn session_expired(text: &str) -> bool {
+    let Ok(value) = serde_json::from_str::<Value>(text) else {
+        return false;
+    };
+    let responses: Vec<&Value> = match &value["responses"] {
+        Value::Array(v) => v.iter().collect(),
+        Value::Object(m) => m.values().collect(),
+        _ => vec![],
+    };
+    std::iter::once(&value).chain(responses).any(|r| {
+        ResponseMessages::deserialize(&r["responseMessages"])
+            .is_ok_and(|m| m.error.iter().any(Message::is_session_error))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{errors_in, session_expired};
+
+    #[test]
+    fn errors_are_read_from_any_response() {
+        let text = r#"{
+            "status": 0,
+            "responseMessages": {
+                "error": [{"message": "Wrong password", "code": "auth", "hint": "Check your settings"}],
+                "warning": [{"message": "Slow down"}]
+            }
+        }"#;
+        let errors = errors_in(text);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), Some("auth"));
+        assert_eq!(
+            errors[0].to_string(),
+            "Wrong password (auth), Check your settings"
+        );
+
+        assert!(errors_in(r#"{"status": 200, "sessionId": "abc"}"#).is_empty());
+        assert!(errors_in("<html>Bad Gateway</html>").is_empty());
+    }
+
+    #[test]
+    fn session_errors_are_found_in_multirequests() {
+        let expired =
+            r#"{"status": 0, "responseMessages": {"error": [{"message": "Invalid session"}]}}"#;
+        assert!(session_expired(expired));
+        let multi = format!(
+            r#"{{"responses": {{"1": {{"status": 200}}, "2": {}}}}}"#,
+            expired
+        );
+        assert!(session_expired(&multi));
+        let refused = r#"{"responses": [{"responseMessages": {"error": [{"message": "Account 9999 does not exist"}]}}]}"#;
+        assert!(!session_expired(refused));
+        assert!(!session_expired("<html>Bad Gateway</html>"));
+    }
 }
@@ -6,7 +6,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::serde_as;
 use thiserror::Error;
 
-use crate::ApiCall;
+use crate::{ApiCall, ConscriboError};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -110,15 +110,15 @@ pub enum Pain {
 }
 
 impl TransactionsResponse {
-    pub fn from_json(s: &str) -> TransactionsResponse {
-        serde_json::from_str(s).unwrap()
+    pub fn from_json(s: &str) -> Result<TransactionsResponse, ConscriboError> {
+        Ok(serde_json::from_str(s)?)
     }
 
     pub fn transactions(&self) -> HashMap<String, Transaction> {
         match &self.transactions {
             Pain::Map(m) => m.clone(),
             Pain::Vec(v) => v
-                .into_iter()
+                .iter()
                 .cloned()
                 .map(|t| (t.transaction_id.to_string(), t))
                 .collect(),
@@ -220,12 +220,10 @@ impl TryFrom<Transaction> for Vec<UnifiedTransaction> {
                 let urow = rows.entry(r).or_insert_with(|| UnifiedTransaction {
                     unique_id: format!(
                         "{}-{}-{}-{}-{}",
-                        row.reference.as_ref().map(String::as_str).unwrap_or("????"),
+                        row.reference.as_deref().unwrap_or("????"),
                         r,
                         row.amount,
-                        row.description
-                            .as_ref()
-                            .map(String::as_str)
+                        row.description.as_deref()
                             .unwrap_or("????"),
                         row.account_nr,
                     ),
@@ -245,7 +243,7 @@ impl TryFrom<Transaction> for Vec<UnifiedTransaction> {
                 }
             }
         }
-        Ok(rows.into_iter().map(|(_, v)| v).collect())
+        Ok(rows.into_values().collect())
     }
 }
 
@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+
+use egui::{RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use penning_helper_conscribo::{
+    add_transaction::AddTransaction,
+    multirequest::{refusal, MultiRequest},
+    GetTransactionResult,
+};
+use penning_helper_sepa::{
+    parse_mt940, parse_statements, reconcile, suggest, Counterparty, Direction, IdRegistry,
+    LedgerItem, MatchedOn, Reconciliation, Statement, SuggestedOn, Suggestion, UnmatchedReason,
+};
+
+use crate::{
+    file_receiver::{FileReceiverResult, FileReceiverSource},
+    sepa_stuff::id_registry_file,
+    FooBar, Relations, ERROR_STUFF,
+};
+
+/// Reads a camt.053/camt.054 file, or an MT940/MT942 file for the accounts
+/// that can't export camt
+pub(crate) fn read_statements(file: &Path) -> Result<Vec<Statement>, String> {
+    let f = std::fs::File::open(file).map_err(|e| e.to_string())?;
+    let xml = file
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("xml"));
+    if xml {
+        parse_statements(f).map_err(|e| e.to_string())
+    } else {
+        parse_mt940(f).map_err(|e| e.to_string())
+    }
+}
+
+/// Reads bank statements and settles the direct debits and transfers they
+/// contain
+#[derive(Clone, Debug, Default)]
+pub struct BankImport {
+    /// The file the current result is for
+    file: Option<PathBuf>,
+    ids: Option<IdRegistry>,
+    result: Option<Reconciliation>,
+    booked: bool,
+    /// Getting the bookings from Conscribo to suggest who sent the
+    /// unmatched lines
+    suggesting: bool,
+    /// For every unmatched line
+    suggestions: Option<Vec<Option<Suggestion>>>,
+    items: Vec<LedgerItem>,
+}
+
+impl BankImport {
+    pub fn ui(&mut self, ui: &mut Ui, foobar: &mut FooBar, members: &Relations) {
+        ui.horizontal(|ui| {
+            if ui.button("Open bank statement").clicked() {
+                self.file = None;
+                self.result = None;
+                self.suggestions = None;
+                foobar.files.new_receiver(FileReceiverSource::BankStatement);
+            }
+            if let Some(r) = foobar.files.get_receiver(FileReceiverSource::BankStatement) {
+                match r.get_file() {
+                    FileReceiverResult::File(f) => {
+                        ui.label(format!("File: {:?}", f));
+                        if self.file.as_deref() != Some(f) {
+                            self.file = Some(f.to_path_buf());
+                            self.load();
+                        }
+                    }
+                    FileReceiverResult::NoFile => {
+                        ui.label("No file selected.");
+                    }
+                    FileReceiverResult::Waiting => {
+                        ui.label("Waiting for file");
+                    }
+                }
+            }
+        });
+        let Some(result) = &self.result else {
+            return;
+        };
+        ui.label(format!(
+            "{} settled, {} to check by hand",
+            result.settled.len(),
+            result.unmatched.len()
+        ));
+        let mut book = false;
+        ui.horizontal(|ui| {
+            let bank = &foobar.cfg.conscribo().bank_rekening;
+            let can_book = !self.booked && !result.settled.is_empty() && !bank.is_empty();
+            book = ui
+                .add_enabled(can_book, egui::Button::new("Book in Conscribo"))
+                .on_disabled_hover_text("Needs the bank account in the settings")
+                .clicked();
+            if ui.button("Copy report").clicked() {
+                let report = result.report();
+                ui.output_mut(|o| o.copied_text = report);
+            }
+        });
+        if book {
+            self.book(foobar);
+        }
+        if self.booked {
+            ui.label("Booked in Conscribo");
+        }
+        let Some(result) = &self.result else {
+            return;
+        };
+        ui.heading("Settled");
+        TableBuilder::new(ui)
+            .striped(true)
+            .auto_shrink([true, true])
+            .columns(Column::auto(), 6)
+            .header(20.0, |mut h| {
+                for title in ["Date", "Relation", "Amount", "Kind", "Id", "Matched on"] {
+                    h.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|b| {
+                b.rows(20.0, result.settled.len(), |mut r| {
+                    let s = &result.settled[r.index()];
+                    r.col(|ui| {
+                        ui.label(s.date.to_string());
+                    });
+                    r.col(|ui| {
+                        ui.label(format!("{} ({})", s.payment.name, s.payment.code));
+                    });
+                    r.col(|ui| {
+                        ui.label(s.payment.amount.to_string());
+                    });
+                    r.col(|ui| {
+                        ui.label(match s.payment.direction {
+                            Direction::Collection => "Direct debit",
+                            Direction::Transfer => "Transfer",
+                        });
+                    });
+                    r.col(|ui| {
+                        ui.label(s.payment.end_to_end_id.as_str());
+                    });
+                    r.col(|ui| {
+                        let label = match s.matched_on {
+                            MatchedOn::EndToEndId => RichText::new("Id"),
+                            MatchedOn::IbanAndAmount => {
+                                RichText::new("IBAN and amount").color(ui.visuals().warn_fg_color)
+                            }
+                        };
+                        ui.label(label);
+                    });
+                });
+            });
+        let open = result
+            .unmatched
+            .iter()
+            .any(|u| u.reason == UnmatchedReason::NoMatch);
+        ui.horizontal(|ui| {
+            ui.heading("To check by hand");
+            if ui
+                .add_enabled(
+                    open && !self.suggesting && self.suggestions.is_none(),
+                    egui::Button::new("Suggest relations"),
+                )
+                .on_hover_text("Match the lines on reference, IBAN and name with Conscribo")
+                .clicked()
+            {
+                self.suggesting = true;
+            }
+        });
+        if self.suggesting {
+            self.fetch_items(ui, foobar, members);
+        }
+        let Some(result) = &self.result else {
+            return;
+        };
+        for (n, u) in result.unmatched.iter().enumerate() {
+            ui.label(u.to_string());
+            let suggestion = self
+                .suggestions
+                .as_ref()
+                .and_then(|s| s.get(n))
+                .and_then(Option::as_ref);
+            if let Some(s) = suggestion {
+                ui.label(
+                    RichText::new(format!("    {}", self.describe(s, members)))
+                        .color(ui.visuals().warn_fg_color),
+                );
+            }
+        }
+    }
+
+    /// Polls Conscribo for the bookings on the debtors and creditors
+    /// accounts, and suggests a relation for every unmatched line once they
+    /// are in
+    fn fetch_items(&mut self, ui: &mut Ui, foobar: &mut FooBar, members: &Relations) {
+        let r = foobar.conscribo.run(|c| c.transactions());
+        match r {
+            Some(Ok(GetTransactionResult::Done(unifieds))) => {
+                self.suggesting = false;
+                self.items = unifieds
+                    .into_iter()
+                    .map(|t| LedgerItem {
+                        code: t.code,
+                        reference: t.reference,
+                        amount: t.cost,
+                        date: t.date,
+                    })
+                    .collect();
+                let relations = members
+                    .iter()
+                    .map(|m| Counterparty {
+                        code: m.code.clone(),
+                        name: m.display_name.clone(),
+                        iban: m.account.as_ref().and_then(|a| a.iban.valid().cloned()),
+                    })
+                    .collect::<Vec<_>>();
+                let Some(result) = &self.result else {
+                    return;
+                };
+                self.suggestions = Some(
+                    result
+                        .unmatched
+                        .iter()
+                        .map(|u| {
+                            (u.reason == UnmatchedReason::NoMatch)
+                                .then(|| suggest(&u.line(), &relations, &self.items))
+                                .flatten()
+                        })
+                        .collect(),
+                );
+            }
+            Some(Ok(GetTransactionResult::NotDone { total, count, .. })) => {
+                ui.label(format!("Got {} out of {} transactions", count, total));
+                ui.ctx().request_repaint();
+            }
+            Some(Err(e)) => {
+                self.suggesting = false;
+                // so suggesting again starts the download over
+                foobar.conscribo.run(|c| c.cancel_transactions());
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send(format!("Error: {}", e)).unwrap();
+                }
+            }
+            None => {
+                self.suggesting = false;
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send("Not connected to Conscribo".to_string()).unwrap();
+                }
+            }
+        }
+    }
+
+    fn describe(&self, s: &Suggestion, members: &Relations) -> String {
+        let name = members
+            .find_member(&s.code)
+            .map(|m| m.display_name.as_str())
+            .unwrap_or("unknown");
+        let on = match s.on {
+            SuggestedOn::CreditorReference => "the payment reference",
+            SuggestedOn::Reference => "the reference",
+            SuggestedOn::Iban => "the IBAN",
+            SuggestedOn::Name => "the name",
+        };
+        let mut text = format!("Probably {} ({}), on {}", name, s.code, on);
+        if !s.items.is_empty() {
+            let references = s
+                .items
+                .iter()
+                .filter_map(|i| self.items.get(*i))
+                .map(|i| i.reference.as_str())
+                .collect::<Vec<_>>();
+            text.push_str(&format!(", pays {}", references.join(", ")));
+        }
+        text
+    }
+
+    fn load(&mut self) {
+        self.result = None;
+        self.booked = false;
+        self.suggestions = None;
+        let Some(file) = &self.file else {
+            return;
+        };
+        let statements = read_statements(file);
+        let ids = IdRegistry::load(&id_registry_file()).map_err(|e| e.to_string());
+        match statements.and_then(|s| ids.map(|i| (s, i))) {
+            Ok((statements, ids)) => {
+                self.result = Some(reconcile(&statements, ids.payments()));
+                self.ids = Some(ids);
+            }
+            Err(e) => {
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send(e).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Books the settlements in Conscribo and marks the ones it accepted as
+    /// settled. Refused ones stay in the list, to book again.
+    fn book(&mut self, foobar: &mut FooBar) {
+        let (Some(result), Some(ids)) = (&mut self.result, &mut self.ids) else {
+            return;
+        };
+        let bank = foobar.cfg.conscribo().bank_rekening.clone();
+        let transactions = result
+            .settled
+            .iter()
+            .map(|s| {
+                let reference = s
+                    .bank_reference
+                    .clone()
+                    .unwrap_or_else(|| s.payment.end_to_end_id.clone());
+                let t = AddTransaction::new()
+                    .with_date(*s.date)
+                    .with_description(format!("SEPA {}", s.payment.end_to_end_id))
+                    .with_reference(reference)
+                    .with_relation_nr(s.payment.code.clone());
+                match s.payment.direction {
+                    Direction::Collection => t.settle_debet(bank.clone(), s.payment.amount),
+                    Direction::Transfer => t.settle_credit(bank.clone(), s.payment.amount),
+                }
+            })
+            .collect::<Vec<_>>();
+        let res = foobar.conscribo.run(|c| {
+            c.execute(MultiRequest::new().push_all(transactions.into_iter().enumerate().collect()))
+                .and_then(|res| res.responses_owned())
+        });
+        match res {
+            Some(Ok(responses)) => {
+                let total = result.settled.len();
+                let mut refused = vec![];
+                for (i, s) in std::mem::take(&mut result.settled).into_iter().enumerate() {
+                    match refusal(&responses, i) {
+                        None => {
+                            ids.settle(&s);
+                        }
+                        Some(e) => {
+                            refused.push(format!("{}: {}", s.payment.end_to_end_id, e));
+                            result.settled.push(s);
+                        }
+                    }
+                }
+                self.booked = refused.is_empty();
+                if !refused.is_empty() {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(format!(
+                            "{} of {} transactions were refused: {}",
+                            refused.len(),
+                            total,
+                            refused.join(", ")
+                        ))
+                        .unwrap();
+                    }
+                }
+                if let Err(e) = ids.save(&id_registry_file()) {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(e.to_string()).unwrap();
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send(format!("Error: {}", e)).unwrap();
+                }
+            }
+            None => {
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send("Not connected to Conscribo".to_string()).unwrap();
+                }
+            }
+        }
+    }
+}
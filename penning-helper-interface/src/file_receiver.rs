@@ -9,6 +9,9 @@ use std::{
 pub enum FileReceiverSource {
     TurfList,
     SepaSaveLoc,
+    BankStatement,
+    StatusReport,
+    ReturnFile,
 }
 
 impl FileReceiverSource {
@@ -16,6 +19,15 @@ impl FileReceiverSource {
         match self {
             FileReceiverSource::TurfList => &[("Turflist", &["xlsx", "xls", "csv"])],
             FileReceiverSource::SepaSaveLoc => &[("SEPA", &["xml"])],
+            FileReceiverSource::BankStatement => &[
+                ("camt.053/camt.054", &["xml"]),
+                ("MT940/MT942", &["sta", "940", "942", "swi", "txt"]),
+            ],
+            FileReceiverSource::StatusReport => &[("pain.002", &["xml"])],
+            FileReceiverSource::ReturnFile => &[
+                ("camt.053/camt.054", &["xml"]),
+                ("MT940/MT942", &["sta", "940", "942", "swi", "txt"]),
+            ],
         }
     }
 
@@ -23,6 +35,9 @@ impl FileReceiverSource {
         match self {
             FileReceiverSource::TurfList => false,
             FileReceiverSource::SepaSaveLoc => true,
+            FileReceiverSource::BankStatement => false,
+            FileReceiverSource::StatusReport => false,
+            FileReceiverSource::ReturnFile => false,
         }
     }
 }
@@ -101,7 +116,7 @@ impl FileReceiver {
         }
     }
 
-    pub fn get_file(&self) -> FileReceiverResult {
+    pub fn get_file(&self) -> FileReceiverResult<'_> {
         if self.has_received {
             if let Some(f) = &self.file {
                 FileReceiverResult::File(f)
@@ -113,7 +128,7 @@ impl FileReceiver {
         }
     }
 
-    pub fn try_recv(&mut self) -> FileReceiverResult {
+    pub fn try_recv(&mut self) -> FileReceiverResult<'_> {
         if self.has_received {
             return if let Some(f) = &self.file {
                 FileReceiverResult::File(f)
@@ -3,11 +3,11 @@
 
 use std::{
     collections::HashMap,
-    ops::Index,
     path::PathBuf,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{channel, Receiver, Sender},
-        OnceLock,
+        Arc, Mutex, OnceLock,
     },
     time::SystemTime,
 };
@@ -16,30 +16,36 @@ use eframe::egui::{self, Ui};
 use egui::{Color32, Visuals};
 use egui_dock::{DockState, NodeIndex, Style, SurfaceIndex};
 
+use bank_import::BankImport;
 use file_receiver::{FileReceievers, FileReceiverResult, FileReceiverSource};
 use member_info::MemberInfo;
 use merch_sales::MerchSales;
 use penning_helper_config::{Config, ConscriboConfig};
+use penning_helper_invoices::Relations;
+use penning_helper_types::BicDirectory;
 use penning_helper_conscribo::{
     accounts::{AccountRequest, AccountResponse},
-    entities::{filters::Filter, Entities, Entity},
+    blocking::ConscriboClient,
+    entities::{filters::Filter, Entities},
     field_definitions::FieldDefs,
     multirequest::{MultiRequest, MultiRequestElementResponse},
     session::Credentials,
-    ConscriboClient,
 };
 
 use popup::{ErrorThing, Popup};
 
+use returns::Returns;
 use sepa_stuff::SepaGen;
 use settings::SettingsWindow;
 use turflist::TurflistImport;
 
+mod bank_import;
 mod file_receiver;
 mod member_info;
 mod merch_sales;
 mod popup;
 mod rekening_selector;
+mod returns;
 mod sepa_stuff;
 mod settings;
 mod turflist;
@@ -68,75 +74,18 @@ struct PenningHelperApp {
     conscribo_client: ConscriboConnector,
     r: Option<Receiver<String>>,
     members: Relations,
-    sepa_stuff: penning_helper_sepa::SEPAConfig,
+    sepa_stuff: Option<penning_helper_sepa::SEPAConfig>,
+    bic_directory: Option<Arc<BicDirectory>>,
     rekeningen: AccountResponse,
 }
 
-#[derive(Debug, Clone, Default)]
-struct Relations {
-    remapper: HashMap<String, String>,
-    members: Vec<Entity>,
-}
-
-impl Relations {
-    pub fn new(member_lists: &[Vec<Entity>]) -> Self {
-        let mut remapper = HashMap::new();
-        let mut members: Vec<Entity> = vec![];
-        for l in member_lists {
-            for m in l {
-                if let Some(r) = members.iter().find(|&mem| {
-                    mem.display_name == m.display_name
-                        && mem.email.to_lowercase() == m.email.to_lowercase()
-                }) {
-                    remapper.insert(m.code.clone(), r.code.clone());
-                } else {
-                    remapper.insert(m.code.clone(), m.code.clone());
-                    members.push(m.clone());
-                }
-            }
-        }
-        members.sort_by(|a, b| a.display_name.cmp(&b.display_name));
-
-        Self { remapper, members }
-    }
-
-    pub fn find_member(&self, code: &str) -> Option<&Entity> {
-        let actual_code = self.remapper.get(code)?.as_str();
-        self.members.iter().find(|m| m.code == actual_code)
-    }
-
-    pub fn find_member_by_name(&self, name: &str) -> Option<&Entity> {
-        self.find_member(&self.members.iter().find(|m| m.display_name == name)?.code)
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.members.is_empty()
-    }
-
-    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
-        self.members.iter()
-    }
-
-    pub fn len(&self) -> usize {
-        self.members.len()
-    }
-}
-
-impl Index<usize> for Relations {
-    type Output = Entity;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.members[index]
-    }
-}
-
 struct FooBar<'t> {
     popups: &'t mut HashMap<String, Popup>,
     conscribo: &'t ConscriboConnector,
     files: &'t mut FileReceievers,
     cfg: &'t Config,
     members: &'t Relations,
-    sepa: &'t penning_helper_sepa::SEPAConfig,
+    sepa: &'t Option<penning_helper_sepa::SEPAConfig>,
     accounts: &'t AccountResponse,
 }
 
@@ -160,6 +109,24 @@ struct ConscriboConnector {
     username: String,
     password: String,
     n: u32,
+    two_fa: Arc<TwoFaCode>,
+}
+
+/// The code from the authenticator app, used by the next login
+#[derive(Default)]
+struct TwoFaCode {
+    code: Mutex<Option<i32>>,
+    asked: AtomicBool,
+}
+
+impl TwoFaCode {
+    fn take(&self) -> Option<i32> {
+        let code = self.code.lock().unwrap().take();
+        if code.is_none() {
+            self.asked.store(true, Ordering::SeqCst);
+        }
+        code
+    }
 }
 
 impl ConscriboConnector {
@@ -199,25 +166,52 @@ impl ConscriboConnector {
             //     }
             // }
 
-            Some(
-                ConscriboClient::new(cfg.account_name.clone())
-                    .with_credentials(Credentials::new(cfg.username.clone(), cfg.password.clone())),
-            )
+            let mut client = ConscriboClient::new(cfg.account_name.clone())
+                .with_credentials(Credentials::new(cfg.username.clone(), cfg.password.clone()));
+            if !cfg.base_url.is_empty() {
+                client = client.with_base_url(&cfg.base_url);
+            }
+            if cfg.two_fa {
+                let two_fa = self.two_fa.clone();
+                Some(client.with_two_fa_prompt(move || two_fa.take()))
+            } else {
+                Some(client)
+            }
         };
         if let Some(c) = &self.client {
-            println!("Connected to Conscribo");
             let fields = c.execute(FieldDefs::new("lid".to_string()));
-            if let Ok(_fields) = fields {
-                // println!("Fields: {:?}", fields);
-            } else {
+            if let Err(e) = fields {
+                eprintln!("Error logging in: {}", e);
+                if let Some(s) = ERROR_STUFF.get() {
+                    s.send(format!("Error logging in: {}", e)).unwrap();
+                }
+                self.client = None;
+                self.n += 1;
                 return false;
             }
+            println!("Connected to Conscribo");
             return true;
         }
         self.n += 1;
         false
     }
 
+    /// A login needed a two-factor code that was not typed in yet
+    fn wants_two_fa_code(&self) -> bool {
+        self.two_fa.asked.load(Ordering::SeqCst)
+    }
+
+    fn set_two_fa_code(&mut self, code: Option<i32>) {
+        self.two_fa.asked.store(false, Ordering::SeqCst);
+        if let Some(code) = code {
+            *self.two_fa.code.lock().unwrap() = Some(code);
+            // the first login waited on the code, so try it again
+            if self.client.is_none() {
+                self.username.clear();
+            }
+        }
+    }
+
     pub fn run<F: FnOnce(&ConscriboClient) -> R, R>(&self, f: F) -> Option<R> {
         self.client.as_ref().map(f)
     }
@@ -232,6 +226,14 @@ impl PenningHelperApp {
 
         let mut s = Self::with_config(Config::load_from_file());
         s.r = Some(r);
+        let bic_file = dirs::data_local_dir()
+            .unwrap_or(PathBuf::from("."))
+            .join("penning-helper")
+            .join("bic_directory.csv");
+        match BicDirectory::bundled_with_overrides(&bic_file) {
+            Ok(d) => s.bic_directory = Some(Arc::new(d)),
+            Err(e) => eprintln!("Error loading BIC directory: {}", e),
+        }
         s
     }
 
@@ -257,44 +259,57 @@ impl PenningHelperApp {
 impl eframe::App for PenningHelperApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.settings_window.show(ctx);
-        for (_, p) in &mut self.popups {
+        for p in self.popups.values_mut() {
             p.show(ctx);
         }
         self.file_channels.receive_all();
+        if self.conscribo_client.wants_two_fa_code() {
+            let res = self.popups.entry("TwoFa".to_string()).or_insert(
+                Popup::new_default::<(String, i32)>("Conscribo two-factor code"),
+            );
+            if let Some(v) = res.value::<String>() {
+                self.conscribo_client.set_two_fa_code(v.trim().parse().ok());
+                self.popups.remove("TwoFa");
+            }
+        }
         if self.login_conscribo() {
             self.sepa_stuff =
-                penning_helper_sepa::SEPAConfig::from_config(self.settings_window.config.sepa());
+                penning_helper_sepa::SEPAConfig::from_config(self.settings_window.config.sepa())
+                    .ok()
+                    .map(|s| match &self.bic_directory {
+                        Some(d) => s.with_bic_directory(d.clone()),
+                        None => s,
+                    });
             if self.members.is_empty() {
                 let relations = self.conscribo_client.run(|c| {
-                    let res = c.execute(
-                        MultiRequest::new()
-                            .push("lid", Entities::new().filter(Filter::entity_type("lid")))
-                            .push(
-                                "onbekend",
-                                Entities::new().filter(Filter::entity_type("onbekend")),
-                            ),
-                    );
-
-                    let r = if let Ok(res) = res {
-                        let resses = res.responses_owned_unsafe();
-                        let lid = resses.get("lid").unwrap();
-                        let onbekend = resses.get("onbekend").unwrap();
-                        let mut relations = vec![];
-                        if let MultiRequestElementResponse::EntityRequest(leden) =
-                            lid.content_unsafe()
-                        {
-                            relations.push(leden.entities.values().cloned().collect());
+                    let res = c
+                        .execute(
+                            MultiRequest::new()
+                                .push("lid", Entities::new().filter(Filter::entity_type("lid")))
+                                .push(
+                                    "onbekend",
+                                    Entities::new().filter(Filter::entity_type("onbekend")),
+                                ),
+                        )
+                        .and_then(|res| res.responses_owned());
+
+                    match res {
+                        Ok(resses) => {
+                            let mut relations = vec![];
+                            for seq in ["lid", "onbekend"] {
+                                if let Some(MultiRequestElementResponse::EntityRequest(e)) =
+                                    resses.get(seq).and_then(|r| r.content())
+                                {
+                                    relations.push(e.entities.values().cloned().collect());
+                                }
+                            }
+                            relations
                         }
-                        if let MultiRequestElementResponse::EntityRequest(onbekenden) =
-                            onbekend.content_unsafe()
-                        {
-                            relations.push(onbekenden.entities.values().cloned().collect());
+                        Err(e) => {
+                            eprintln!("Error getting relations: {}", e);
+                            vec![]
                         }
-                        relations
-                    } else {
-                        vec![]
-                    };
-                    r
+                    }
                 });
                 if let Some(relations) = relations {
                     self.members = Relations::new(&relations);
@@ -303,12 +318,12 @@ impl eframe::App for PenningHelperApp {
 
             if self.rekeningen.accounts().is_empty() {
                 if let Some(res) = self.conscribo_client.run(|c| {
-                    let res = c.execute(AccountRequest::today());
-                    res
+                    
+                    c.execute(AccountRequest::today())
                 }) {
-                    match res {
+                    match res.and_then(|r| r.into_result()) {
                         Ok(res) => {
-                            self.rekeningen = res.response_unsafe_owned();
+                            self.rekeningen = res;
                         }
                         Err(e) => {
                             eprintln!("Error getting accounts: {}", e);
@@ -446,7 +461,7 @@ impl MyTabs {
                 ui,
                 &mut TabViewer {
                     added_nodes: &mut nodes,
-                    members: &foobar.members,
+                    members: foobar.members,
                     foobar,
                 },
             );
@@ -457,12 +472,15 @@ impl MyTabs {
 }
 
 #[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
 enum ContentThing {
     Info,
     MemberInfo(MemberInfo),
     TurflistImport(TurflistImport),
     SepaGen(SepaGen),
     MerchSales(MerchSales),
+    BankImport(BankImport),
+    Returns(Returns),
 }
 
 impl ContentThing {
@@ -473,6 +491,8 @@ impl ContentThing {
             ContentThing::TurflistImport(_) => "Turflist Import",
             ContentThing::SepaGen(_) => "Invoice Generator",
             ContentThing::MerchSales(_) => "Merch Sales",
+            ContentThing::BankImport(_) => "Bank Import",
+            ContentThing::Returns(_) => "Returns",
         }
     }
 
@@ -483,6 +503,8 @@ impl ContentThing {
             ContentThing::TurflistImport(_) => false,
             ContentThing::SepaGen(_) => false,
             ContentThing::MerchSales(_) => false,
+            ContentThing::BankImport(_) => false,
+            ContentThing::Returns(_) => false,
         }
     }
 
@@ -493,12 +515,16 @@ impl ContentThing {
             ContentThing::TurflistImport(tli) => tli.ui(ui, cfg, members),
             ContentThing::SepaGen(sg) => sg.ui(ui, cfg, members),
             ContentThing::MerchSales(ms) => ms.ui(ui, cfg, members),
+            ContentThing::BankImport(bi) => bi.ui(ui, cfg, members),
+            ContentThing::Returns(r) => r.ui(ui, cfg, members),
         }
     }
 
     pub fn file_handle(&self) -> Option<FileReceiverSource> {
         match self {
             ContentThing::TurflistImport(_) => Some(FileReceiverSource::TurfList),
+            ContentThing::BankImport(_) => Some(FileReceiverSource::BankStatement),
+            ContentThing::Returns(_) => Some(FileReceiverSource::ReturnFile),
             _ => None,
         }
     }
@@ -515,7 +541,7 @@ impl InfoTab {
         ui.label("It can also be used to generate SEPA files for the bank,");
         ui.label("and can send automated emails to the members that have an open balance to inform them that they have to pay.");
         let errors = foobar.cfg.config_errors();
-        if errors.len() > 0 {
+        if !errors.is_empty() {
             ui.heading("Config Errors:");
             for error in errors {
                 ui.label(error);
@@ -560,6 +586,14 @@ impl<'a> egui_dock::TabViewer for TabViewer<'a> {
                 self.added_nodes
                     .push((node, ContentThing::MerchSales(Default::default())));
             }
+            if ui.button("Bank Import").clicked() {
+                self.added_nodes
+                    .push((node, ContentThing::BankImport(Default::default())));
+            }
+            if ui.button("Returns").clicked() {
+                self.added_nodes
+                    .push((node, ContentThing::Returns(Default::default())));
+            }
         });
     }
 
@@ -1,4 +1,4 @@
-use egui::Ui;
+use egui::{RichText, Ui};
 use egui_extras::{Column, TableBuilder};
 
 use crate::{FooBar, Relations};
@@ -46,7 +46,7 @@ impl MemberInfo {
                 }) {
                     b.row(20.0, |mut r| {
                         r.col(|ui| {
-                            ui.label(&member.code.to_string());
+                            ui.label(member.code.to_string());
                         });
                         r.col(|ui| {
                             ui.label(&member.display_name);
@@ -55,13 +55,21 @@ impl MemberInfo {
                             ui.label(&member.email);
                         });
                         r.col(|ui| {
-                            ui.label(
-                                &member
-                                    .account
-                                    .as_ref()
-                                    .map(|a| a.iban.clone())
-                                    .unwrap_or("".to_string()),
-                            );
+                            if let Some(account) = &member.account {
+                                match account.iban.get() {
+                                    Some(Ok(iban)) => {
+                                        ui.label(iban.pretty());
+                                    }
+                                    None => {}
+                                    Some(Err(e)) => {
+                                        ui.label(
+                                            RichText::new(account.iban.to_string())
+                                                .color(ui.visuals().error_fg_color),
+                                        )
+                                        .on_hover_text(e.to_string());
+                                    }
+                                }
+                            }
                         });
                         
                     });
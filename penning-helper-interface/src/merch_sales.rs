@@ -155,13 +155,11 @@ impl MerchSales {
                             MultiRequest::new()
                                 .push_all(transactions.into_iter().enumerate().collect()),
                         )
+                        .and_then(|res| res.responses_owned())
                     }) {
                         match res {
                             Ok(v) => {
-                                let s = format!(
-                                    "Added {} transactions",
-                                    v.responses_owned_unsafe().len()
-                                );
+                                let s = format!("Added {} transactions", v.len());
                                 if let Some(se) = ERROR_STUFF.get() {
                                     se.send(s).unwrap();
                                 }
@@ -197,6 +195,7 @@ impl MerchSales {
             }
         });
 
+        let locale = foobar.cfg.locale();
         TableBuilder::new(ui)
             .auto_shrink([true, false])
             .striped(true)
@@ -257,14 +256,14 @@ impl MerchSales {
                         r.col(|ui| {
                             row.member_name_selector.ui_convert(
                                 ui,
-                                members.members.iter().map(|m| m.display_name.as_str()),
+                                members.iter().map(|m| m.display_name.as_str()),
                                 |m| members.find_member_by_name(m).map(|m| m.code.clone()),
                             );
                         });
 
                         r.col(|ui| {
                             if let Some(code) = row.member_name_selector.get() {
-                                ui.label(format!("{}", code));
+                                ui.label(code.to_string());
                             } else {
                                 ui.label("-");
                             }
@@ -282,12 +281,12 @@ impl MerchSales {
 
                         r.col(|ui| {
                             if let Some(account_nr) = row.rekening_selector.get() {
-                                ui.label(format!("{}", account_nr));
+                                ui.label(account_nr.to_string());
                                 if row.price_text.is_empty() {
                                     if let Some((_, r)) =
                                         row.rekening_selector.as_str().split_once("(")
                                     {
-                                        row.price_text = r.replace(")", "").replace(",", ".");
+                                        row.price_text = r.replace(")", "");
                                     }
                                 }
                             } else {
@@ -300,7 +299,8 @@ impl MerchSales {
                                 row.price = None;
                                 None
                             } else {
-                                if let Ok(c) = row.price_text.parse::<Euro>() {
+                                if let Ok(c) = Euro::parse_localized(&row.price_text, Some(locale))
+                                {
                                     row.price = Some(c);
                                     Some(egui::Color32::GREEN)
                                 } else {
@@ -329,7 +329,9 @@ impl MerchSales {
                                 row.total_price = None;
                                 None
                             } else {
-                                if let Ok(c) = row.total_price_text.parse::<Euro>() {
+                                if let Ok(c) =
+                                    Euro::parse_localized(&row.total_price_text, Some(locale))
+                                {
                                     row.total_price = Some(c);
                                     Some(egui::Color32::GREEN)
                                 } else {
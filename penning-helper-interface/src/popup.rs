@@ -1,6 +1,7 @@
 use std::any::Any;
 
 use egui::{vec2, Align2, Id, Key, TextEdit, Ui, Window};
+use penning_helper_types::{Euro, Locale};
 use regex::Regex;
 
 static ID_GEN: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
@@ -57,10 +58,7 @@ impl Popup {
                 }
             }
         }
-        if open && !self.open {
-            self.open = false;
-            ctx.request_repaint();
-        } else if !open && self.open {
+        if open != self.open {
             self.open = false;
             ctx.request_repaint();
         }
@@ -182,7 +180,14 @@ macro_rules! number_validators {
     };
 }
 
-number_validators!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+number_validators!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, Euro);
+
+/// An amount in the notation of the locale
+impl StringValidator for Locale {
+    fn validate(&self, s: &str) -> bool {
+        Euro::parse_localized(s, Some(*self)).is_ok()
+    }
+}
 
 impl<V: StringValidator + Any> DataStuff for (String, V) {
     fn show_part(&mut self, ui: &mut Ui) -> PopupState {
@@ -0,0 +1,357 @@
+use std::{ops::DerefMut, path::PathBuf};
+
+use egui::{RichText, TextEdit, Ui};
+use egui_extras::{Column, TableBuilder};
+use penning_helper_conscribo::{
+    add_transaction::AddTransaction,
+    multirequest::{refusal, MultiRequest},
+};
+use penning_helper_mail::MailServer;
+use penning_helper_sepa::{
+    reconcile, returns_from, IdRegistry, ReasonCode, ReturnKind, ReturnLog, ReturnedPayment,
+};
+use penning_helper_types::{Date, Euro, Locale};
+
+use crate::{
+    bank_import::read_statements,
+    file_receiver::{FileReceiverResult, FileReceiverSource},
+    rekening_selector::Selector,
+    sepa_stuff::{id_registry_file, return_log_file},
+    FooBar, Relations, ERROR_STUFF,
+};
+
+/// From this many returns a relation shows up in the report
+const REPEATED: usize = 2;
+
+fn send_error(e: impl ToString) {
+    if let Some(s) = ERROR_STUFF.get() {
+        s.send(e.to_string()).unwrap();
+    }
+}
+
+/// Direct debits that came back: entered by hand or read from the bank's
+/// statement, booked back on the debtors account and followed up by email
+#[derive(Clone, Debug, Default)]
+pub struct Returns {
+    log: Option<ReturnLog>,
+    loaded: bool,
+    /// The statement the last import was from
+    file: Option<PathBuf>,
+    imported: Option<String>,
+    member: Selector<String>,
+    amount: String,
+    date: Date,
+    reason: String,
+    kind: Option<ReturnKind>,
+    email_client: Option<MailServer>,
+}
+
+impl Returns {
+    pub fn ui(&mut self, ui: &mut Ui, foobar: &mut FooBar, members: &Relations) {
+        if !self.loaded {
+            self.loaded = true;
+            self.log = ReturnLog::load(&return_log_file()).map_err(send_error).ok();
+            self.email_client = MailServer::new(foobar.cfg.mail(), foobar.cfg.sepa())
+                .map(|m| m.with_locale(foobar.cfg.locale()))
+                .ok();
+        }
+        if self.log.is_none() {
+            ui.label(format!(
+                "The return log at {} could not be read",
+                return_log_file().display()
+            ));
+            return;
+        }
+        self.import_ui(ui, foobar);
+        ui.separator();
+        self.manual_ui(ui, members, foobar.cfg.locale());
+        ui.separator();
+
+        let Some(log) = &self.log else {
+            return;
+        };
+        let unbooked = log.unbooked().count();
+        let follow_ups = log.follow_ups().count();
+        let (mut book, mut mail) = (false, false);
+        ui.horizontal(|ui| {
+            let bank = &foobar.cfg.conscribo().bank_rekening;
+            book = ui
+                .add_enabled(
+                    unbooked > 0 && !bank.is_empty(),
+                    egui::Button::new(format!("Book {} returns in Conscribo", unbooked)),
+                )
+                .on_disabled_hover_text(
+                    "Needs a return to book and the bank account in the settings",
+                )
+                .clicked();
+            mail = ui
+                .add_enabled(
+                    follow_ups > 0 && self.email_client.is_some(),
+                    egui::Button::new(format!("Send {} follow-up emails", follow_ups)),
+                )
+                .on_disabled_hover_text("Only booked returns are followed up")
+                .clicked();
+            if ui.button("Copy repeated failures").clicked() {
+                let report = log.report(REPEATED);
+                ui.output_mut(|o| o.copied_text = report);
+            }
+        });
+        if book {
+            self.book(foobar);
+        }
+        if mail {
+            self.follow_up(foobar, members);
+        }
+
+        let Some(log) = &self.log else {
+            return;
+        };
+        let repeated = log.repeated(REPEATED);
+        TableBuilder::new(ui)
+            .striped(true)
+            .auto_shrink([true, true])
+            .columns(Column::auto(), 6)
+            .header(20.0, |mut h| {
+                for title in [
+                    "Date",
+                    "Relation",
+                    "Amount",
+                    "Reason",
+                    "Booked",
+                    "Followed up",
+                ] {
+                    h.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|b| {
+                let returns = log.returns();
+                b.rows(20.0, returns.len(), |mut r| {
+                    let ret = &returns[returns.len() - 1 - r.index()];
+                    r.col(|ui| {
+                        ui.label(ret.date.to_string());
+                    });
+                    r.col(|ui| {
+                        let text = RichText::new(format!("{} ({})", ret.name, ret.code));
+                        if repeated.contains_key(ret.code.as_str()) {
+                            ui.label(text.color(ui.visuals().warn_fg_color))
+                                .on_hover_text("Returned more than once");
+                        } else {
+                            ui.label(text);
+                        }
+                    });
+                    r.col(|ui| {
+                        ui.label(ret.amount.to_string());
+                    });
+                    r.col(|ui| {
+                        ui.label(format!("{}: {}", ret.kind, ret.reason));
+                    });
+                    r.col(|ui| {
+                        ui.label(if ret.booked { "Yes" } else { "No" });
+                    });
+                    r.col(|ui| {
+                        ui.label(if ret.follow_up_sent { "Yes" } else { "No" });
+                    });
+                });
+            });
+    }
+
+    /// Reads the returns from a camt statement
+    fn import_ui(&mut self, ui: &mut Ui, foobar: &mut FooBar) {
+        ui.horizontal(|ui| {
+            if ui.button("Import returns from bank statement").clicked() {
+                self.file = None;
+                self.imported = None;
+                foobar.files.new_receiver(FileReceiverSource::ReturnFile);
+            }
+            if let Some(r) = foobar.files.get_receiver(FileReceiverSource::ReturnFile) {
+                if let FileReceiverResult::File(f) = r.get_file() {
+                    ui.label(format!("File: {:?}", f));
+                    if self.file.as_deref() != Some(f) {
+                        self.file = Some(f.to_path_buf());
+                        self.import();
+                    }
+                }
+            }
+        });
+        if let Some(imported) = &self.imported {
+            ui.label(imported);
+        }
+    }
+
+    fn import(&mut self) {
+        let (Some(file), Some(log)) = (&self.file, &mut self.log) else {
+            return;
+        };
+        let statements = read_statements(file);
+        let ids = IdRegistry::load(&id_registry_file()).map_err(|e| e.to_string());
+        let (statements, ids) = match statements.and_then(|s| ids.map(|i| (s, i))) {
+            Ok(r) => r,
+            Err(e) => return send_error(e),
+        };
+        let result = reconcile(&statements, ids.payments());
+        let (returned, unknown) = returns_from(&result, ids.payments());
+        let found = returned.len();
+        let added = returned.into_iter().filter(|r| log.add(r.clone())).count();
+        let mut imported = format!("{} returns found, {} new", found, added);
+        for u in &unknown {
+            imported.push_str(&format!("\nNot one of our collections: {}", u));
+        }
+        self.imported = Some(imported);
+        self.save();
+    }
+
+    /// A return the bank reported some other way, like a letter or the app
+    fn manual_ui(&mut self, ui: &mut Ui, members: &Relations, locale: Locale) {
+        ui.heading("Enter a return by hand");
+        ui.horizontal(|ui| {
+            ui.label("Relation");
+            self.member
+                .ui_convert(ui, members.iter().map(|m| m.display_name.as_str()), |n| {
+                    members.find_member_by_name(n).map(|m| m.code.clone())
+                });
+            ui.label("Amount");
+            TextEdit::singleline(&mut self.amount)
+                .hint_text("12.50")
+                .desired_width(60.0)
+                .show(ui);
+            ui.add(egui_extras::DatePickerButton::new(self.date.deref_mut()));
+            ui.label("Reason");
+            TextEdit::singleline(&mut self.reason)
+                .hint_text("AM04")
+                .desired_width(50.0)
+                .show(ui);
+        });
+        let reason = if self.reason.trim().is_empty() {
+            ReasonCode::unspecified()
+        } else {
+            ReasonCode::new(self.reason.trim().to_uppercase())
+        };
+        let kind = self.kind.unwrap_or_else(|| ReturnKind::for_reason(&reason));
+        ui.horizontal(|ui| {
+            for k in [ReturnKind::Return, ReturnKind::Refund, ReturnKind::Reversal] {
+                if ui.radio(kind == k, k.to_string()).clicked() {
+                    self.kind = Some(k);
+                }
+            }
+            ui.label(reason.to_string());
+        });
+        let amount = Euro::parse_localized(&self.amount, Some(locale)).ok();
+        let member = self.member.get().and_then(|code| members.find_member(code));
+        let add = ui
+            .add_enabled(
+                member.is_some() && amount.is_some_and(|a| a > Euro::default()),
+                egui::Button::new("Add return"),
+            )
+            .clicked();
+        if let (true, Some(member), Some(amount), Some(log)) = (add, member, amount, &mut self.log)
+        {
+            let returned = ReturnedPayment::new(
+                &member.code,
+                &member.display_name,
+                amount,
+                self.date,
+                kind,
+                reason,
+            );
+            if !log.add(returned) {
+                send_error("This return was entered already");
+            }
+            self.amount.clear();
+            self.reason.clear();
+            self.kind = None;
+            self.save();
+        }
+    }
+
+    /// Puts the money of every unbooked return back on the debtors account.
+    /// Returns Conscribo refused stay unbooked.
+    fn book(&mut self, foobar: &mut FooBar) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let bank = foobar.cfg.conscribo().bank_rekening.clone();
+        let unbooked = log.unbooked().cloned().collect::<Vec<_>>();
+        let transactions = unbooked
+            .iter()
+            .map(|r| {
+                let reference = r
+                    .end_to_end_id
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-{}", r.code, r.date));
+                AddTransaction::new()
+                    .with_date(*r.date)
+                    .with_description(format!("SEPA {} {}", r.kind, r.reason.code()))
+                    .with_reference(reference)
+                    .with_relation_nr(r.code.clone())
+                    .reverse_debet(bank.clone(), r.amount)
+            })
+            .collect::<Vec<_>>();
+        let res = foobar.conscribo.run(|c| {
+            c.execute(MultiRequest::new().push_all(transactions.into_iter().enumerate().collect()))
+                .and_then(|res| res.responses_owned())
+        });
+        match res {
+            Some(Ok(responses)) => {
+                let mut refused = vec![];
+                for (i, r) in unbooked.iter().enumerate() {
+                    match refusal(&responses, i) {
+                        None => {
+                            log.mark_booked(r);
+                        }
+                        Some(e) => refused.push(format!("{} on {}: {}", r.code, r.date, e)),
+                    }
+                }
+                if !refused.is_empty() {
+                    send_error(format!(
+                        "{} of {} returns were refused: {}",
+                        refused.len(),
+                        unbooked.len(),
+                        refused.join(", ")
+                    ));
+                }
+                self.save();
+            }
+            Some(Err(e)) => send_error(format!("Error: {}", e)),
+            None => send_error("Not connected to Conscribo"),
+        }
+    }
+
+    /// Tells every relation with a booked return that the amount is still open
+    fn follow_up(&mut self, foobar: &mut FooBar, members: &Relations) {
+        let (Some(log), Some(mail_client)) = (&mut self.log, &self.email_client) else {
+            return;
+        };
+        let mail = foobar.cfg.mail();
+        for r in log.follow_ups().cloned().collect::<Vec<_>>() {
+            let Some(member) = members.find_member(&r.code).filter(|m| !m.email.is_empty()) else {
+                send_error(format!("No email for {}", r.name));
+                continue;
+            };
+            match mail_client.send_return_notice(
+                &member.display_name,
+                &member.email,
+                r.amount,
+                r.date,
+                &r.reason.to_string(),
+                &mail.board_line,
+                &mail.name,
+            ) {
+                Ok(()) => {
+                    log.mark_followed_up(&r);
+                }
+                Err(e) => send_error(format!("Error sending mail: {}", e)),
+            }
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        if let Some(log) = &self.log {
+            if let Err(e) = log.save(&return_log_file()) {
+                send_error(e);
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     fs::File,
     io::Write,
     ops::{Add, Deref, DerefMut},
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
@@ -11,48 +12,175 @@ use crate::{
     file_receiver::{FileReceiverResult, FileReceiverSource},
     FooBar, Relations, ERROR_STUFF,
 };
-use chrono::{Days, Local, NaiveDate};
+use chrono::Local;
 use eframe::egui::{self, Ui};
 use egui::RichText;
 use egui_extras::{Column, TableBuilder};
 
-use penning_helper_conscribo::{transactions::UnifiedTransaction, GetTransactionResult};
+use penning_helper_conscribo::{
+    add_transaction::AddTransaction,
+    multirequest::{refusal, MultiRequest},
+    transactions::UnifiedTransaction,
+    GetTransactionResult,
+};
+use penning_helper_invoices::{Invoice, Invoices};
 use penning_helper_mail::MailServer;
+use penning_helper_sepa::{
+    parse_status_report, Direction, IdRegistry, MandateLedger, RejectedPayment, Rejection,
+    RejectionScope, ReturnLog, RunOptions, RunStore, RunSummary, SaveError, Selection, Severity,
+};
 use penning_helper_types::{Date, Euro};
 use rand::Rng;
 
-#[derive(Clone, Debug)]
-struct RelationTransaction {
-    t: Vec<UnifiedTransaction>,
-    name: String,
-    code: String,
-    membership_date: NaiveDate,
-    iban: String,
-    bic: String,
-    email: String,
-    membership: bool,
-    alumni_contributie: Euro,
+fn mandate_ledger_file() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("penning-helper")
+        .join("mandates.json")
 }
 
-impl RelationTransaction {
-    pub fn total_cost(&self) -> Euro {
-        self.t.iter().map(|t| t.cost).sum()
-    }
+pub(crate) fn id_registry_file() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("penning-helper")
+        .join("issued_ids.json")
+}
+
+pub(crate) fn return_log_file() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("penning-helper")
+        .join("returns.json")
+}
+
+pub(crate) fn run_store_file() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("penning-helper")
+        .join("runs.jsonl")
+}
 
-    pub fn all_after(&self, date: Date) -> impl Iterator<Item = &UnifiedTransaction> {
-        self.t.iter().filter(move |t| t.date >= date)
+/// Shows what happened the last time a SEPA file was made
+fn run_summary_ui(summary: &RunSummary, ui: &mut Ui) {
+    if summary.has_errors() {
+        ui.label(
+            RichText::new("Nothing was saved, the files would be refused by the bank")
+                .color(ui.visuals().error_fg_color),
+        );
+    }
+    if !summary.sanitised.is_empty() {
+        ui.collapsing(
+            format!(
+                "{} fields changed to fit the SEPA character set",
+                summary.sanitised.len()
+            ),
+            |ui| {
+                for s in &summary.sanitised {
+                    ui.label(s.to_string());
+                }
+            },
+        );
+    }
+    for d in &summary.diagnostics {
+        let color = match d.severity {
+            Severity::Error => ui.visuals().error_fg_color,
+            Severity::Warning => ui.visuals().warn_fg_color,
+        };
+        ui.label(RichText::new(d.to_string()).color(color));
+    }
+    ui.label(format!(
+        "{} direct debits ({}), {} transfers ({})",
+        summary.debtors, summary.debtors_total, summary.creditors, summary.creditors_total
+    ));
+    for (sequence_type, (count, date)) in &summary.sequence_types {
+        ui.label(format!(
+            "{}: {} direct debits, collected on {}",
+            sequence_type.code(),
+            count,
+            date
+        ));
     }
+    if !summary.derived_bics.is_empty() {
+        ui.label(format!(
+            "BIC derived from the IBAN for: {}",
+            summary.derived_bics.join(", ")
+        ));
+    }
+    if !summary.amended_mandates.is_empty() {
+        ui.label(format!(
+            "Mandate amended for: {}",
+            summary.amended_mandates.join(", ")
+        ));
+    }
+    for (name, run) in &summary.reissued {
+        ui.label(
+            RichText::new(format!("{} was already invoiced in run {}", name, run))
+                .color(ui.visuals().warn_fg_color),
+        );
+    }
+    if !summary.double_collected.is_empty() {
+        ui.label(
+            RichText::new("Nothing was saved, tick \"Collect again\" if this is on purpose")
+                .color(ui.visuals().error_fg_color),
+        );
+    }
+    for d in &summary.double_collected {
+        ui.label(RichText::new(d.to_string()).color(ui.visuals().error_fg_color));
+    }
+    for (name, reason) in &summary.skipped {
+        ui.label(
+            RichText::new(format!("Skipped {}: {}", name, reason))
+                .color(ui.visuals().error_fg_color),
+        );
+    }
+}
 
-    pub fn previous_invoices_left(&self, date: Date) -> Euro {
-        self.t
-            .iter()
-            .filter(|t| t.date < date)
-            .map(|t| t.cost)
-            .sum()
+/// What the last pain.002 status report said
+#[derive(Clone, Debug, Default)]
+struct StatusSummary {
+    rejected: Vec<RejectedPayment>,
+    /// Rejections for payments that aren't in the registry
+    unknown: Vec<Rejection>,
+    error: Option<String>,
+}
+
+impl StatusSummary {
+    fn reversals(&self) -> impl Iterator<Item = &RejectedPayment> {
+        self.rejected.iter().filter(|r| r.needs_reversal())
     }
 
-    fn is_valid(&self) -> bool {
-        !self.iban.is_empty() && !self.bic.is_empty() && !self.email.is_empty()
+    fn ui(&self, ui: &mut Ui) {
+        if let Some(e) = &self.error {
+            ui.label(RichText::new(e).color(ui.visuals().error_fg_color));
+            return;
+        }
+        if self.rejected.is_empty() && self.unknown.is_empty() {
+            ui.label("The bank accepted everything");
+        }
+        for r in &self.rejected {
+            let mut text = format!(
+                "{} ({}): {} refused, {}",
+                r.payment.name, r.payment.code, r.payment.amount, r.reason
+            );
+            if let Some(info) = &r.additional_info {
+                text.push_str(&format!(", {}", info));
+            }
+            if r.needs_reversal() {
+                text.push_str(", already booked as paid");
+            }
+            ui.label(RichText::new(text).color(ui.visuals().warn_fg_color));
+        }
+        for r in &self.unknown {
+            let what = match &r.scope {
+                RejectionScope::Message => "The whole file".to_string(),
+                RejectionScope::PaymentInformation(id) => format!("Block {}", id),
+                RejectionScope::Transaction(id) => format!("Transaction {}", id),
+            };
+            ui.label(
+                RichText::new(format!("{} refused, {}, but it isn't known here", what, r.reason))
+                    .color(ui.visuals().error_fg_color),
+            );
+        }
     }
 }
 
@@ -63,43 +191,29 @@ enum SendMode {
     Real,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-enum Show {
-    #[default]
-    All,
-    OwesUsALot,
-    IsOwedByUs,
-    Contributie,
-    AlumniContributie,
-}
-
-impl Show {
-    fn filter(&self, t: &RelationTransaction) -> bool {
-        match self {
-            Show::All => true,
-            Show::OwesUsALot => t.total_cost() > Euro::from(100),
-            Show::IsOwedByUs => t.total_cost() < Euro::from(-10),
-            Show::Contributie => t.total_cost() > Euro::from(0) && t.membership,
-            Show::AlumniContributie => t.alumni_contributie > Euro::from(0),
-        }
-    }
-}
-
 #[derive(Clone, Debug, Default)]
 pub struct SepaGen {
-    transactions: Vec<RelationTransaction>,
+    invoices: Invoices,
     unifieds: Vec<UnifiedTransaction>,
     unifieds_grabbed: bool,
     sorted: bool,
     idx: usize,
     done: bool,
     send_mode: SendMode,
-    to_send: Vec<RelationTransaction>,
+    to_send: Vec<Invoice>,
     last_send: TimeThing,
     email_client: Option<MailServer>,
     has_tried_mail: bool,
     last_invoice_date: Date,
-    show: Show,
+    show: Selection,
+    summary: Option<RunSummary>,
+    mandates: Option<MandateLedger>,
+    ids: Option<IdRegistry>,
+    status: Option<StatusSummary>,
+    returns: Option<ReturnLog>,
+    runs: Option<RunStore>,
+    /// Saves direct debits for a period that was already collected
+    collect_again: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -116,7 +230,7 @@ impl Default for TimeThing {
         Self(
             Instant::now()
                 .checked_sub(Duration::from_secs(100 * 60))
-                .unwrap_or_else(|| Instant::now()),
+                .unwrap_or_else(Instant::now),
         )
     }
 }
@@ -153,8 +267,47 @@ impl SepaGen {
     pub fn ui(&mut self, ui: &mut Ui, foobar: &mut FooBar, members: &Relations) {
         if !self.has_tried_mail {
             self.has_tried_mail = true;
-            self.email_client = MailServer::new(foobar.cfg.mail(), foobar.cfg.sepa()).ok();
+            self.email_client = MailServer::new(foobar.cfg.mail(), foobar.cfg.sepa())
+                .map(|m| m.with_locale(foobar.cfg.locale()))
+                .ok();
+            self.mandates = match MandateLedger::load(&mandate_ledger_file()) {
+                Ok(l) => Some(l),
+                Err(e) => {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(e.to_string()).unwrap();
+                    }
+                    None
+                }
+            };
+            self.ids = match IdRegistry::load(&id_registry_file()) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(e.to_string()).unwrap();
+                    }
+                    None
+                }
+            };
+            self.returns = match ReturnLog::load(&return_log_file()) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(e.to_string()).unwrap();
+                    }
+                    None
+                }
+            };
+            self.runs = match RunStore::open(&run_store_file()) {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    if let Some(s) = ERROR_STUFF.get() {
+                        s.send(e.to_string()).unwrap();
+                    }
+                    None
+                }
+            };
         }
+        let sepa = foobar.sepa.as_ref();
         let done = if !self.unifieds_grabbed {
             ui.label(format!("Getting transactions{}", ".".repeat(self.idx / 50)));
             // ui.label("This will take a while the first time");
@@ -163,7 +316,7 @@ impl SepaGen {
 
             let r = foobar
                 .conscribo
-                .run(|c| c.get_transactions_faster())
+                .run(|c| c.transactions())
                 .transpose();
             match r {
                 Ok(r) => {
@@ -187,8 +340,12 @@ impl SepaGen {
                     }
                 }
                 Err(e) => {
-                    if let Some(s) = ERROR_STUFF.get() {
-                        s.send(format!("Error: {}", e)).unwrap();
+                    // the error stays until the download is started over
+                    ui.label(
+                        RichText::new(format!("Error: {}", e)).color(ui.visuals().error_fg_color),
+                    );
+                    if ui.button("Try again").clicked() {
+                        foobar.conscribo.run(|c| c.cancel_transactions());
                     }
                 }
             }
@@ -200,90 +357,14 @@ impl SepaGen {
             ui.ctx().request_repaint();
             for _ in 0..min(self.unifieds.len(), 1000) {
                 let t = self.unifieds.remove(0);
-
-                let Some(rel) = members.find_member(&t.code) else {
-                    println!("No relation found for {}", t.code);
-                    continue;
-                };
-                if rel.geen_invoice == 1 {
-                    continue;
-                }
-                let name = rel.display_name.as_str();
-                let iban = rel
-                    .account
-                    .as_ref()
-                    .map(|r| r.iban.as_str())
-                    .unwrap_or_default();
-                let bic = rel
-                    .account
-                    .as_ref()
-                    .map(|r| r.bic.as_str())
-                    .unwrap_or_default();
-                let email = rel.email.as_str();
-                let membership_date = rel
-                    .lidmaatschap_gestart
-                    .unwrap_or_else(|| Local::now().date_naive());
-                let code = &rel.code;
-                // let membership = rel.source == "lid";
-
-                let alumni_contributie = if let Some((start, eind, amount)) =
-                    rel.alumni_lidmaatschap_gestart.and_then(|s| {
-                        let e = rel.alumni_lidmaatschap_be_indigd.unwrap_or_else(|| {
-                            Local::now()
-                                .date_naive()
-                                .checked_add_days(Days::new(1024))
-                                .unwrap()
-                        });
-                        Some((s, e, rel.alumni_contributie))
-                    }) {
-                    if start <= Local::now().date_naive() && eind >= Local::now().date_naive() {
-                        amount
-                    } else {
-                        Euro::from(0)
-                    }
-                } else {
-                    Euro::from(0)
-                };
-
-                if let Some(r) = self
-                    .transactions
-                    .iter_mut()
-                    .find(|t| t.name == name && t.iban == iban && t.email == email)
-                {
-                    r.t.push(t)
-                } else {
-                    self.transactions.push(RelationTransaction {
-                        t: vec![t],
-                        name: name.to_string(),
-                        iban: iban.to_string(),
-                        bic: bic.to_string(),
-                        email: email.to_string(),
-                        code: code.to_string(),
-                        membership_date,
-                        membership: true,
-                        alumni_contributie,
-                    });
-                }
+                self.invoices.add(members, t);
             }
             false
         } else if !self.sorted {
-            let mut transactions = vec![];
-
-            for t in self.transactions.clone() {
-                if t.total_cost() == Euro::default() {
-                    continue;
-                }
-                if t.email.is_empty() {
-                    println!("No email for {}", t.name);
-                    continue;
-                }
-
-                transactions.push(t);
+            for code in self.invoices.unknown() {
+                println!("No relation found for {}", code);
             }
-
-            transactions.sort_by_cached_key(|t| t.name.clone());
-
-            self.transactions = transactions;
+            self.invoices.finish();
             self.sorted = true;
             false
         } else {
@@ -291,103 +372,116 @@ impl SepaGen {
             true
         };
         ui.add_enabled_ui(done, |ui| {
-            if ui.button("Create SEPA file").clicked() {
-                foobar.files.new_receiver(FileReceiverSource::SepaSaveLoc);
-                self.done = false;
-            }
+            ui.horizontal(|ui| {
+                if ui.button("Create SEPA file").clicked() {
+                    foobar.files.new_receiver(FileReceiverSource::SepaSaveLoc);
+                    self.done = false;
+                }
+                ui.checkbox(&mut self.collect_again, "Collect again").on_hover_text(
+                    "Also collect relations that were already collected for this period, like after a return",
+                );
+            });
             if let Some(r) = foobar.files.get_receiver(FileReceiverSource::SepaSaveLoc) {
                 match r.get_file() {
                     FileReceiverResult::File(f) => {
                         ui.label(format!("File: {:?}", f));
                         if !self.done {
-                            let mut creditors = vec![];
-                            let mut debtors = vec![];
-                            for t in self
-                                .transactions
-                                .iter()
-                                .filter(|t| t.total_cost() != Euro::default())
-                                .filter(|t| t.is_valid())
-                                .filter(|t| self.show.filter(t))
-                            {
-                                let total = t.total_cost();
-                                if total < Euro::default() {
-                                    // it's a creditor
-                                    let c = foobar.sepa.new_creditor(
-                                        -total,
-                                        t.name.clone(),
-                                        t.bic.clone(),
-                                        t.iban.clone(),
-                                        "Payment of positive balance".to_string(),
-                                    );
-                                    creditors.push(c);
-                                } else if total > Euro::default() {
-                                    // it's a debtor
-                                    let d = if self.show == Show::AlumniContributie {
-                                        foobar.sepa.new_debtor(
-                                            t.alumni_contributie,
-                                            t.name.clone(),
-                                            t.bic.clone(),
-                                            t.iban.clone(),
-                                            t.code.clone(),
-                                            t.membership_date.into(),
-                                            "Alumni Contributie".to_string(),
-                                        )
-                                    } else if self.show == Show::Contributie {
-                                        foobar.sepa.new_debtor(
-                                            total.clamp(Euro::from(0), Euro::from(50)),
-                                            t.name.clone(),
-                                            t.bic.clone(),
-                                            t.iban.clone(),
-                                            t.code.clone(),
-                                            t.membership_date.into(),
-                                            "Contributie".to_string(),
-                                        )
-                                    } else if total >= 100.into() {
-                                        let total = 100.into();
-                                        foobar.sepa.new_debtor(
-                                            total,
-                                            t.name.clone(),
-                                            t.bic.clone(),
-                                            t.iban.clone(),
-                                            t.code.clone(),
-                                            t.membership_date.into(),
-                                            "Partial invoice of open AEGEE-Delft balance"
-                                                .to_string(),
-                                        )
-                                    } else {
-                                        foobar.sepa.new_debtor(
-                                            total,
-                                            t.name.clone(),
-                                            t.bic.clone(),
-                                            t.iban.clone(),
-                                            t.code.clone(),
-                                            t.membership_date.into(),
-                                            "Invoice of open AEGEE-Delft balance".to_string(),
-                                        )
-                                    };
-                                    debtors.push(d);
-                                } else {
-                                    // nothing
+                            let Some(sepa) = sepa else {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(
+                                        "SEPA settings are invalid, see the Info tab".to_string(),
+                                    )
+                                    .unwrap();
+                                }
+                                self.done = true;
+                                return;
+                            };
+                            // without the ledger every mandate would look new
+                            let Some(mut ledger) = self.mandates.clone() else {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(format!(
+                                        "The mandate ledger at {} could not be read",
+                                        mandate_ledger_file().display()
+                                    ))
+                                    .unwrap();
+                                }
+                                self.done = true;
+                                return;
+                            };
+                            // without the registry ids could be handed out twice
+                            let Some(mut ids) = self.ids.clone() else {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(format!(
+                                        "The id registry at {} could not be read",
+                                        id_registry_file().display()
+                                    ))
+                                    .unwrap();
+                                }
+                                self.done = true;
+                                return;
+                            };
+                            // without the run store nobody is kept from being collected twice
+                            let Some(mut runs) = self.runs.clone() else {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(format!(
+                                        "The run store at {} could not be read",
+                                        run_store_file().display()
+                                    ))
+                                    .unwrap();
+                                }
+                                self.done = true;
+                                return;
+                            };
+                            let options = RunOptions {
+                                selection: self.show,
+                                creditor_references: foobar.cfg.sepa().creditor_references,
+                                collect_again: self.collect_again,
+                                date: Date::today(),
+                            };
+                            let relations =
+                                self.invoices.iter().map(|i| (&i.relation, i.total_cost()));
+                            let generated =
+                                match sepa.generate(relations, options, &ledger, &ids, &runs) {
+                                    Ok(generated) => generated,
+                                    Err(summary) => {
+                                        self.summary = Some(*summary);
+                                        self.done = true;
+                                        return;
+                                    }
+                                };
+                            let summary = generated.summary.clone();
+                            match generated.save(f, &mut runs, &mut ledger, &mut ids) {
+                                Ok(_) => {}
+                                // some files are written and recorded, their
+                                // mandates and ids are used
+                                Err(e @ SaveError::PartlyRecorded(..)) => {
+                                    if let Some(s) = ERROR_STUFF.get() {
+                                        s.send(e.to_string()).unwrap();
+                                    }
+                                }
+                                Err(e) => {
+                                    if let Some(s) = ERROR_STUFF.get() {
+                                        s.send(e.to_string()).unwrap();
+                                    }
+                                    self.done = true;
+                                    return;
+                                }
+                            }
+                            self.runs = Some(runs);
+
+                            if let Err(e) = ledger.save(&mandate_ledger_file()) {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(e.to_string()).unwrap();
                                 }
                             }
-                            let debtors = foobar
-                                .sepa
-                                .new_invoice_payment_information(Date::in_some_days(2), debtors);
-                            let creditors = foobar
-                                .sepa
-                                .new_transfer_payment_information(Date::in_some_days(2), creditors);
-                            let debtors = foobar.sepa.new_invoice_document(debtors);
-                            let creditors = foobar.sepa.new_transfer_document(creditors);
-
-                            let mut debtors_file = f.to_path_buf();
-                            debtors_file.set_extension("invoice.xml");
-                            let debtors_file = File::create(debtors_file).unwrap();
-                            debtors.write(debtors_file).unwrap();
-
-                            let mut creditors_file = f.to_path_buf();
-                            creditors_file.set_extension("transfer.xml");
-                            let creditors_file = File::create(creditors_file).unwrap();
-                            creditors.write(creditors_file).unwrap();
+                            self.mandates = Some(ledger);
+                            if let Err(e) = ids.save(&id_registry_file()) {
+                                if let Some(s) = ERROR_STUFF.get() {
+                                    s.send(e.to_string()).unwrap();
+                                }
+                            }
+                            self.ids = Some(ids);
+                            self.summary = Some(summary);
                             self.done = true;
                         }
                     }
@@ -399,6 +493,10 @@ impl SepaGen {
                     }
                 }
             }
+            if let Some(summary) = &self.summary {
+                run_summary_ui(summary, ui);
+            }
+            self.status_report_ui(ui, foobar);
             ui.horizontal(|ui| {
                 ui.radio_value(&mut self.send_mode, SendMode::Test, "Test")
                     .on_hover_text("Send the emails to the test email address");
@@ -433,21 +531,17 @@ impl SepaGen {
                     match self.send_mode {
                         SendMode::Test => {
                             self.to_send = self
-                                .transactions
+                                .invoices
                                 .iter()
                                 .filter(|t| t.total_cost() != Euro::default())
-                                .take(1)
-                                .cloned()
-                                .filter(|t| self.show.filter(t))
+                                .take(1).filter(|&t| self.show.filter(t, t.total_cost())).cloned()
                                 .collect();
                         }
                         SendMode::Real => {
                             self.to_send = self
-                                .transactions
+                                .invoices
                                 .iter()
-                                .filter(|t| t.total_cost() != Euro::default())
-                                .cloned()
-                                .filter(|t| self.show.filter(t))
+                                .filter(|t| t.total_cost() != Euro::default()).filter(|&t| self.show.filter(t, t.total_cost())).cloned()
                                 .collect()
                         }
                     }
@@ -455,25 +549,25 @@ impl SepaGen {
 
                 ui.collapsing("Filter", |ui| {
                     ui.vertical(|ui| {
-                        ui.radio_value(&mut self.show, Show::All, "All");
+                        ui.radio_value(&mut self.show, Selection::All, "All");
                         ui.radio_value(
                             &mut self.show,
-                            Show::OwesUsALot,
+                            Selection::OwesUsALot,
                             "Owes us more than 100 euros",
                         );
                         ui.radio_value(
                             &mut self.show,
-                            Show::IsOwedByUs,
+                            Selection::IsOwedByUs,
                             "We owe them more than 10 euros",
                         );
                         ui.radio_value(
                             &mut self.show,
-                            Show::Contributie,
+                            Selection::Contribution,
                             "Contributie, meer dan 0 euro, maar max 50 euro geind",
                         );
                         ui.radio_value(
                             &mut self.show,
-                            Show::AlumniContributie,
+                            Selection::AlumniContribution,
                             "Alumni Contributie (houdt rekening met start en eind datum)",
                         );
                     })
@@ -482,7 +576,6 @@ impl SepaGen {
                     if !self.to_send.is_empty()
                         && (self.last_send + Duration::from_secs(5 * 60)) <= Instant::now()
                     {
-                        self.last_send = TimeThing::now();
                         println!("Sending emails");
                         let today = Date::today();
                         for r in self.to_send.drain(0..(20.min(self.to_send.len()))) {
@@ -491,8 +584,20 @@ impl SepaGen {
                                 continue;
                             }
                             let total = r.total_cost();
+                            let reference = self.show.creditor_reference(
+                                foobar.cfg.sepa().creditor_references,
+                                &r.code,
+                                today,
+                            );
 
-                            let pdf = Self::get_pdf(self.last_invoice_date, &r);
+                            let qr = r.qr_payload(sepa, reference.clone(), today);
+
+                            let pdf = r.pdf(
+                                self.last_invoice_date,
+                                reference.as_ref(),
+                                qr.as_deref(),
+                                foobar.cfg.locale(),
+                            );
 
                             let email_address = if matches!(self.send_mode, SendMode::Test) {
                                 foobar.cfg.mail().reply_to.address.as_str()
@@ -509,13 +614,24 @@ impl SepaGen {
                                 pdf,
                                 total,
                                 today,
-                                r.iban.is_empty() || r.bic.is_empty(),
+                                !r.has_bank_details(sepa),
+                                reference.as_ref().map(|r| r.pretty()).as_deref(),
+                                qr.as_deref(),
                                 &foobar.cfg.mail().board_line,
                                 &foobar.cfg.mail().name,
                             ) {
                                 if let Some(s) = ERROR_STUFF.get() {
                                     s.send(format!("Error sending mail: {}", e)).unwrap();
                                 }
+                            } else if matches!(self.send_mode, SendMode::Real) {
+                                if let Some(runs) = &mut self.runs {
+                                    let period = self.show.period(today);
+                                    if let Err(e) = runs.mark_email_sent(&r.code, &period, today) {
+                                        if let Some(s) = ERROR_STUFF.get() {
+                                            s.send(e.to_string()).unwrap();
+                                        }
+                                    }
+                                }
                             }
                         }
                         ui.ctx().request_repaint_after(Duration::from_secs(1));
@@ -542,7 +658,7 @@ impl SepaGen {
         });
 
         TableBuilder::new(ui)
-            .columns(Column::remainder(), 3)
+            .columns(Column::remainder(), 4)
             .header(20.0, |mut r| {
                 r.col(|ui| {
                     ui.label("Name");
@@ -553,13 +669,16 @@ impl SepaGen {
                 r.col(|ui| {
                     ui.label("Get PDF");
                 });
+                r.col(|ui| {
+                    ui.label("Problems");
+                });
             })
             .body(|mut b| {
                 for t in self
-                    .transactions
+                    .invoices
                     .iter()
                     .filter(|t| t.total_cost() != Euro::default())
-                    .filter(|t| self.show.filter(t))
+                    .filter(|t| self.show.filter(t, t.total_cost()))
                 {
                     let amount = t.total_cost();
                     b.row(20.0, |mut r| {
@@ -567,7 +686,7 @@ impl SepaGen {
                             let text = RichText::new(&t.name);
                             let text = if amount > Euro::from(100) {
                                 text.color(ui.visuals().warn_fg_color)
-                            } else if !t.is_valid() {
+                            } else if !t.is_valid(sepa) {
                                 text.color(ui.visuals().error_fg_color)
                             } else {
                                 text
@@ -578,7 +697,7 @@ impl SepaGen {
                             let text = RichText::new(amount.to_string());
                             let text = if amount > Euro::from(100) {
                                 text.color(ui.visuals().warn_fg_color)
-                            } else if !t.is_valid() {
+                            } else if !t.is_valid(sepa) {
                                 text.color(ui.visuals().error_fg_color)
                             } else {
                                 text
@@ -587,7 +706,18 @@ impl SepaGen {
                         });
                         r.col(|ui| {
                             if ui.button("Open PDF").clicked() {
-                                let pdf = Self::get_pdf(self.last_invoice_date, t);
+                                let reference = self.show.creditor_reference(
+                                    foobar.cfg.sepa().creditor_references,
+                                    &t.code,
+                                    Date::today(),
+                                );
+                                let qr = t.qr_payload(sepa, reference.clone(), Date::today());
+                                let pdf = t.pdf(
+                                    self.last_invoice_date,
+                                    reference.as_ref(),
+                                    qr.as_deref(),
+                                    foobar.cfg.locale(),
+                                );
                                 let mut temp_file = std::env::temp_dir();
                                 let mut rng = rand::thread_rng();
                                 let random_name: String = std::iter::repeat(())
@@ -601,23 +731,180 @@ impl SepaGen {
                                 open::that_detached(temp_file).unwrap();
                             }
                         });
+                        r.col(|ui| {
+                            let problems = t.problems(sepa);
+                            if !problems.is_empty() {
+                                ui.label(
+                                    RichText::new(problems.join(", "))
+                                        .color(ui.visuals().error_fg_color),
+                                );
+                            }
+                            let rejection =
+                                self.ids.as_ref().and_then(|ids| ids.last_rejection(&t.code));
+                            if let Some(reason) = rejection {
+                                ui.label(
+                                    RichText::new(format!("Last payment refused: {}", reason))
+                                        .color(ui.visuals().warn_fg_color),
+                                );
+                            }
+                            let returns = self
+                                .returns
+                                .as_ref()
+                                .map(|r| r.failures(&t.code).collect::<Vec<_>>())
+                                .unwrap_or_default();
+                            if let Some(last) = returns.last() {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Returned {} times, last: {}",
+                                        returns.len(),
+                                        last.reason
+                                    ))
+                                    .color(ui.visuals().warn_fg_color),
+                                );
+                            }
+                        });
                     });
                 }
             });
     }
 
-    fn get_pdf(last_invoice_date: Date, r: &RelationTransaction) -> Vec<u8> {
-        let previous = r.previous_invoices_left(last_invoice_date);
-        let t = UnifiedTransaction::create_new_mock(
-            last_invoice_date,
-            "Open costs of previous invoice".to_string(),
-            previous,
-        );
-        let to_show = r.all_after(last_invoice_date);
-        let t = std::iter::once(&t)
-            .chain(to_show)
-            .map(|t| penning_helper_pdf::SimpleTransaction::new(t.cost, &t.description, t.date))
+    /// Reads a pain.002 report from the bank and marks the refused payments in
+    /// the registry
+    fn status_report_ui(&mut self, ui: &mut Ui, foobar: &mut FooBar) {
+        ui.horizontal(|ui| {
+            if ui.button("Import status report").clicked() {
+                self.status = None;
+                foobar.files.new_receiver(FileReceiverSource::StatusReport);
+            }
+            if let Some(r) = foobar.files.get_receiver(FileReceiverSource::StatusReport) {
+                if let FileReceiverResult::File(f) = r.get_file() {
+                    ui.label(format!("File: {:?}", f));
+                    if self.status.is_none() {
+                        self.status = Some(self.import_status_report(f));
+                    }
+                }
+            }
+        });
+        let Some(status) = &self.status else {
+            return;
+        };
+        status.ui(ui);
+        let reversals = status.reversals().count();
+        if reversals > 0
+            && ui
+                .button(format!("Book {} reversals in Conscribo", reversals))
+                .clicked()
+        {
+            self.book_reversals(foobar);
+        }
+    }
+
+    fn import_status_report(&mut self, file: &std::path::Path) -> StatusSummary {
+        let error = |e: String| StatusSummary {
+            error: Some(e),
+            ..Default::default()
+        };
+        let Some(ids) = &mut self.ids else {
+            return error(format!(
+                "The id registry at {} could not be read",
+                id_registry_file().display()
+            ));
+        };
+        let report = match File::open(file)
+            .map_err(|e| e.to_string())
+            .and_then(|f| parse_status_report(f).map_err(|e| e.to_string()))
+        {
+            Ok(r) => r,
+            Err(e) => return error(e),
+        };
+        let (rejected, unknown) = report.rejected_payments(ids.payments());
+        for r in &rejected {
+            ids.reject(r);
+        }
+        if let Err(e) = ids.save(&id_registry_file()) {
+            return error(e.to_string());
+        }
+        StatusSummary {
+            rejected,
+            unknown,
+            ..Default::default()
+        }
+    }
+
+    /// Undoes the settlement of refused payments that were booked as paid, and
+    /// records in the registry which ones Conscribo accepted so they are only
+    /// booked once
+    fn book_reversals(&mut self, foobar: &mut FooBar) {
+        let (Some(status), Some(ids)) = (&mut self.status, &mut self.ids) else {
+            return;
+        };
+        let bank = foobar.cfg.conscribo().bank_rekening.clone();
+        if bank.is_empty() {
+            if let Some(s) = ERROR_STUFF.get() {
+                s.send("Set the bank account in the settings first".to_string())
+                    .unwrap();
+            }
+            return;
+        }
+        let today = Local::now().date_naive();
+        let reversals = status.reversals().cloned().collect::<Vec<_>>();
+        let transactions = reversals
+            .iter()
+            .map(|r| {
+                let t = AddTransaction::new()
+                    .with_date(today)
+                    .with_description(format!("Refused: {}", r.reason))
+                    .with_reference(r.payment.end_to_end_id.clone())
+                    .with_relation_nr(r.payment.code.clone());
+                match r.payment.direction {
+                    Direction::Collection => t.reverse_debet(bank.clone(), r.payment.amount),
+                    Direction::Transfer => t.reverse_credit(bank.clone(), r.payment.amount),
+                }
+            })
             .collect::<Vec<_>>();
-        penning_helper_pdf::create_invoice_pdf(t, &r.name)
+        let res = foobar.conscribo.run(|c| {
+            c.execute(MultiRequest::new().push_all(transactions.into_iter().enumerate().collect()))
+                .and_then(|res| res.responses_owned())
+        });
+        let mut errors = vec![];
+        match res {
+            Some(Ok(responses)) => {
+                let mut refused = vec![];
+                for (i, r) in reversals.iter().enumerate() {
+                    match refusal(&responses, i) {
+                        None => {
+                            ids.reverse(r, today.into());
+                            status
+                                .rejected
+                                .iter_mut()
+                                .filter(|s| {
+                                    s.payment.run == r.payment.run
+                                        && s.payment.end_to_end_id == r.payment.end_to_end_id
+                                })
+                                .for_each(|s| s.payment.reversed = Some(today.into()));
+                        }
+                        Some(e) => refused.push(format!("{}: {}", r.payment.end_to_end_id, e)),
+                    }
+                }
+                if !refused.is_empty() {
+                    errors.push(format!(
+                        "{} of {} reversals were refused: {}",
+                        refused.len(),
+                        reversals.len(),
+                        refused.join(", ")
+                    ));
+                }
+                if let Err(e) = ids.save(&id_registry_file()) {
+                    errors.push(format!("The reversals could not be saved: {}", e));
+                }
+            }
+            Some(Err(e)) => errors.push(format!("Error: {}", e)),
+            None => errors.push("Not connected to Conscribo".to_string()),
+        }
+        if let Some(s) = ERROR_STUFF.get() {
+            for e in errors {
+                s.send(e).unwrap();
+            }
+        }
     }
 }
@@ -1,5 +1,8 @@
-use egui::{vec2, Align2, TextEdit, Ui, Window};
-use penning_helper_config::Config;
+use std::{fmt::Display, str::FromStr};
+
+use egui::{vec2, Align2, RichText, TextEdit, Ui, Window};
+use penning_helper_config::{Config, SchemaVersion};
+use penning_helper_types::{Lenient, Locale};
 
 #[derive(Clone, Debug, Default)]
 pub struct SettingsWindow {
@@ -18,9 +21,7 @@ impl SettingsWindow {
             .resizable(false)
             .scroll([false, true])
             .show(ctx, |ui| self.actual_show(ui));
-        if open && !self.open {
-            self.open = false;
-        } else if !open && self.open {
+        if open != self.open {
             self.open = false;
         }
     }
@@ -28,14 +29,49 @@ impl SettingsWindow {
     fn actual_show(&mut self, ui: &mut Ui) {
         ui.heading("Current Year");
         labelled_row(ui, "Current year:", self.config.year_format_mut(), "2324");
+        ui.vertical(|ui| {
+            ui.label("Amount notation");
+            for locale in Locale::ALL {
+                ui.radio_value(self.config.locale_mut(), locale, locale.name());
+            }
+        });
         ui.heading("SEPA");
-        labelled_row(
+        ui.vertical(|ui| {
+            ui.label("File format");
+            for version in SchemaVersion::ALL {
+                ui.radio_value(
+                    &mut self.config.sepa_mut().schema_version,
+                    version,
+                    version.name(),
+                );
+            }
+        });
+        ui.checkbox(
+            &mut self.config.sepa_mut().creditor_references,
+            "Use RF payment references instead of descriptions",
+        )
+        .on_hover_text("Banks can match transfers made by hand on the reference");
+        ui.label("Lead time in business days");
+        ui.horizontal(|ui| {
+            let lead_times = &mut self.config.sepa_mut().lead_times;
+            for (name, days) in [
+                ("FRST", &mut lead_times.first),
+                ("RCUR", &mut lead_times.recurring),
+                ("OOFF", &mut lead_times.one_off),
+                ("FNAL", &mut lead_times.last),
+                ("Transfers", &mut lead_times.transfer),
+            ] {
+                ui.label(name);
+                ui.add(egui::DragValue::new(days).range(0..=15));
+            }
+        });
+        lenient_row(
             ui,
             "IBAN",
             &mut self.config.sepa_mut().company_iban,
             "NL12ABCD0123456789",
         );
-        labelled_row(
+        lenient_row(
             ui,
             "BIC",
             &mut self.config.sepa_mut().company_bic,
@@ -68,12 +104,23 @@ impl SettingsWindow {
                     .password(true),
             );
         });
+        ui.checkbox(
+            &mut self.config.conscribo_mut().two_fa,
+            "Two-factor authentication",
+        )
+        .on_hover_text("Asks for a code from your authenticator app when logging in");
         labelled_row(
             ui,
             "Merch Sale Account",
             &mut self.config.conscribo_mut().merch_winst_rekening,
             "0000-00",
         );
+        labelled_row(
+            ui,
+            "Bank Account",
+            &mut self.config.conscribo_mut().bank_rekening,
+            "1000",
+        );
         labelled_row(
             ui,
             "Account Name",
@@ -170,6 +217,22 @@ impl SettingsWindow {
     }
 }
 
+/// A [`labelled_row`] that parses what is typed, and says what's wrong with it
+fn lenient_row<T>(ui: &mut Ui, name: &str, value: &mut Lenient<T>, hint: &'static str)
+where
+    T: FromStr + Display,
+    T::Err: Display,
+{
+    let mut line = value.to_string();
+    labelled_row(ui, name, &mut line, hint);
+    if line != value.to_string() {
+        *value = Lenient::new(&line);
+    }
+    if let Some(Err(e)) = value.get() {
+        ui.label(RichText::new(e.to_string()).color(ui.visuals().error_fg_color));
+    }
+}
+
 fn labelled_row(ui: &mut Ui, name: &str, line: &mut String, hint: &'static str) {
     ui.vertical(|ui| {
         ui.label(name);
@@ -36,7 +36,7 @@ impl TurflistImport {
                     .map(|a| a.account_nr.clone())
             });
             if let Some(c) = self.rekening.get() {
-                ui.label(format!("{}", c));
+                ui.label(c.to_string());
             } else {
                 ui.label("No account selected");
             }
@@ -85,12 +85,14 @@ impl TurflistImport {
                                 }
                                 "xlsx" | "xls" => {
                                     if self.price == (0, 0).into() {
+                                        let locale = foobar.cfg.locale();
                                         let res =
                                             foobar.popups.entry("Price".to_string()).or_insert(
-                                                Popup::new_default::<(String, f64)>("Price"),
+                                                Popup::new("Price", (String::new(), locale)),
                                             );
                                         if let Some(v) = res.value::<String>() {
-                                            self.price = v.parse().unwrap_or((0, 0).into());
+                                            self.price = Euro::parse_localized(v, Some(locale))
+                                                .unwrap_or((0, 0).into());
                                             foobar.popups.remove("Price");
                                         }
                                     } else {
@@ -170,6 +172,7 @@ impl TurflistImport {
                                     .collect(),
                             ),
                         )
+                        .and_then(|res| res.responses_owned())
                     });
                     // let res: Option<ConscriboResult<Vec<TransactionResult>>> =
                     //     foobar.conscribo.run(|c| c.do_multi_request(transactions));
@@ -177,7 +180,7 @@ impl TurflistImport {
                         match res {
                             Ok(o) => {
                                 let mut s = String::new();
-                                for r in o.responses_owned_unsafe() {
+                                for r in o {
                                     s.push_str(&format!("{:?}\n", r));
                                 }
                                 if let Some(se) = ERROR_STUFF.get() {
@@ -276,7 +279,7 @@ impl TurflistImport {
                         (
                             member.display_name.clone(),
                             if member.email.is_empty() {
-                                row.row().email.clone().unwrap_or_else(|| String::new())
+                                row.row().email.clone().unwrap_or_else(String::new)
                             } else {
                                 member.email.clone()
                             },
@@ -286,7 +289,7 @@ impl TurflistImport {
                     } else {
                         (
                             row.name.clone(),
-                            row.email.clone().unwrap_or_else(|| String::new()),
+                            row.email.clone().unwrap_or_else(String::new),
                             row.amount,
                             None,
                         )
@@ -317,8 +320,17 @@ impl TurflistImport {
                             if member.is_some() {
                                 ui.label("Member");
                             } else {
-                                let mut iban = row.iban.as_ref().map(String::as_str).unwrap_or("");
-                                ui.text_edit_singleline(&mut iban);
+                                let iban = row.iban.to_string();
+                                let mut iban = iban.as_str();
+                                let mut t = TextEdit::singleline(&mut iban);
+                                let err = row.iban.get().and_then(Result::err);
+                                if err.is_some() {
+                                    t = t.text_color(ui.visuals().error_fg_color);
+                                }
+                                let resp = t.show(ui).response;
+                                if let Some(e) = err {
+                                    resp.on_hover_text(e.to_string());
+                                }
                             }
                         });
                     });
@@ -0,0 +1,233 @@
+use std::ops::Deref;
+
+use chrono::{Days, Local, NaiveDate};
+use penning_helper_conscribo::{entities::Entity, transactions::UnifiedTransaction};
+use penning_helper_sepa::{InvoiceRelation, SEPAConfig};
+#[cfg(feature = "pdf")]
+use penning_helper_types::Locale;
+use penning_helper_types::{CreditorReference, Date, Euro};
+
+use crate::Relations;
+
+/// The open transactions of one relation
+#[derive(Clone, Debug)]
+pub struct Invoice {
+    pub t: Vec<UnifiedTransaction>,
+    pub relation: InvoiceRelation,
+}
+
+impl Deref for Invoice {
+    type Target = InvoiceRelation;
+
+    fn deref(&self) -> &Self::Target {
+        &self.relation
+    }
+}
+
+impl Invoice {
+    fn new(rel: &Entity, today: NaiveDate) -> Self {
+        let account = rel.account.clone().unwrap_or_default();
+        let alumni_end = rel
+            .alumni_lidmaatschap_be_indigd
+            .unwrap_or_else(|| today.checked_add_days(Days::new(1024)).unwrap());
+        let alumni_contributie = match rel.alumni_lidmaatschap_gestart {
+            Some(start) if start <= today && alumni_end >= today => rel.alumni_contributie,
+            _ => Euro::from(0),
+        };
+        Self {
+            t: vec![],
+            relation: InvoiceRelation {
+                name: rel.display_name.clone(),
+                code: rel.code.clone(),
+                membership_date: rel.lidmaatschap_gestart.unwrap_or(today),
+                iban: account.iban,
+                bic: account.bic,
+                email: rel.email.clone(),
+                membership_ended: rel.lidmaatschap_be_indigd.is_some_and(|d| d <= today),
+                alumni_contributie,
+            },
+        }
+    }
+
+    pub fn total_cost(&self) -> Euro {
+        self.t.iter().map(|t| t.cost).sum()
+    }
+
+    pub fn all_after(&self, date: Date) -> impl Iterator<Item = &UnifiedTransaction> {
+        self.t.iter().filter(move |t| t.date >= date)
+    }
+
+    pub fn previous_invoices_left(&self, date: Date) -> Euro {
+        self.t
+            .iter()
+            .filter(|t| t.date < date)
+            .map(|t| t.cost)
+            .sum()
+    }
+
+    pub fn is_valid(&self, sepa: Option<&SEPAConfig>) -> bool {
+        self.problems(sepa).is_empty()
+    }
+
+    /// The EPC QR code payload for what has to be transferred by hand: all of
+    /// it without a mandate, the part over €100 with one. `None` when there
+    /// is nothing to transfer.
+    pub fn qr_payload(
+        &self,
+        sepa: Option<&SEPAConfig>,
+        reference: Option<CreditorReference>,
+        date: Date,
+    ) -> Option<String> {
+        let sepa = sepa?;
+        let by_hand = self.by_hand(Some(sepa), self.total_cost());
+        let text = format!("{} invoice {}", sepa.company_name, date);
+        sepa.epc_qr(by_hand, reference, &text)
+            .ok()
+            .map(|qr| qr.payload())
+    }
+
+    /// The invoice, with everything before `last_invoice_date` summed up as
+    /// what was left open of the previous one
+    #[cfg(feature = "pdf")]
+    pub fn pdf(
+        &self,
+        last_invoice_date: Date,
+        reference: Option<&CreditorReference>,
+        qr: Option<&str>,
+        locale: Locale,
+    ) -> Vec<u8> {
+        let previous = UnifiedTransaction::create_new_mock(
+            last_invoice_date,
+            "Open costs of previous invoice".to_string(),
+            self.previous_invoices_left(last_invoice_date),
+        );
+        let t = std::iter::once(&previous)
+            .chain(self.all_after(last_invoice_date))
+            .map(|t| penning_helper_pdf::SimpleTransaction::new(t.cost, &t.description, t.date))
+            .collect::<Vec<_>>();
+        let reference = reference.map(|r| r.pretty());
+        penning_helper_pdf::create_invoice_pdf(t, &self.name, reference.as_deref(), qr, locale)
+    }
+}
+
+/// Transactions grouped per relation. They can be added a few at a time, so
+/// the interface stays responsive while there are thousands of them.
+#[derive(Clone, Debug, Default)]
+pub struct Invoices {
+    invoices: Vec<Invoice>,
+    /// Codes of transactions without a relation
+    unknown: Vec<String>,
+}
+
+impl Deref for Invoices {
+    type Target = [Invoice];
+
+    fn deref(&self) -> &Self::Target {
+        &self.invoices
+    }
+}
+
+impl Invoices {
+    /// Adds `t` to the invoice of its relation, relations that don't get
+    /// invoices are left out
+    pub fn add(&mut self, relations: &Relations, t: UnifiedTransaction) {
+        let Some(rel) = relations.find_member(&t.code) else {
+            if !self.unknown.contains(&t.code) {
+                self.unknown.push(t.code.clone());
+            }
+            return;
+        };
+        if rel.geen_invoice == 1 {
+            return;
+        }
+        if let Some(i) = self.invoices.iter_mut().find(|i| i.code == rel.code) {
+            i.t.push(t);
+            return;
+        }
+        let mut invoice = Invoice::new(rel, Local::now().date_naive());
+        invoice.t.push(t);
+        self.invoices.push(invoice);
+    }
+
+    /// Leaves out the relations without an open balance and sorts the rest
+    /// by name. Relations without an email address stay in, their problems
+    /// say why they aren't emailed.
+    pub fn finish(&mut self) {
+        self.invoices.retain(|i| i.total_cost() != Euro::default());
+        self.invoices.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// The codes of transactions that no relation has
+    pub fn unknown(&self) -> &[String] {
+        &self.unknown
+    }
+}
+
+/// Groups `transactions` per relation, see [`Invoices::add`]
+pub fn invoices(relations: &Relations, transactions: Vec<UnifiedTransaction>) -> Invoices {
+    let mut invoices = Invoices::default();
+    for t in transactions {
+        invoices.add(relations, t);
+    }
+    invoices.finish();
+    invoices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(code: &str, name: &str, email: &str) -> Entity {
+        Entity {
+            code: code.to_string(),
+            display_name: name.to_string(),
+            email: email.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn transaction(code: &str, cost: Euro) -> UnifiedTransaction {
+        UnifiedTransaction {
+            code: code.to_string(),
+            ..UnifiedTransaction::create_new_mock(Date::today(), String::new(), cost)
+        }
+    }
+
+    #[test]
+    fn transactions_are_grouped_per_relation() {
+        // Jan is in Conscribo twice, as a member and as unknown
+        let relations = Relations::new(&[
+            vec![
+                relation("101", "Jan Jansen", "jan@example.com"),
+                relation("102", "Erika Mustermann", ""),
+            ],
+            vec![relation("901", "Jan Jansen", "JAN@example.com")],
+        ]);
+        let invoices = invoices(
+            &relations,
+            vec![
+                transaction("101", Euro::new(10, 0)),
+                transaction("901", Euro::new(2, 50)),
+                transaction("102", Euro::new(5, 0)),
+                transaction("102", Euro::new(-5, 0)),
+                transaction("999", Euro::new(1, 0)),
+            ],
+        );
+        let open = invoices
+            .iter()
+            .map(|i| (i.code.as_str(), i.total_cost()))
+            .collect::<Vec<_>>();
+        assert_eq!(open, vec![("101", Euro::new(12, 50))]);
+        assert_eq!(invoices.unknown(), ["999".to_string()]);
+    }
+
+    #[test]
+    fn relations_without_an_email_stay_in() {
+        let relations = Relations::new(&[vec![relation("102", "Erika Mustermann", "")]]);
+        let invoices = invoices(&relations, vec![transaction("102", Euro::new(5, 0))]);
+        assert_eq!(invoices.len(), 1);
+        assert!(invoices[0]
+            .problems(None)
+            .contains(&"No email address".to_string()));
+    }
+}
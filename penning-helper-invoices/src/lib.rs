@@ -0,0 +1,8 @@
+//! The invoices both the interface and the command line make out of the
+//! relations and transactions in Conscribo
+
+mod invoice;
+mod relations;
+
+pub use invoice::{invoices, Invoice, Invoices};
+pub use relations::Relations;
@@ -0,0 +1,74 @@
+use std::{collections::HashMap, ops::Index};
+
+use penning_helper_conscribo::entities::Entity;
+
+/// The relations from Conscribo, with people who are in there twice (as a
+/// member and as unknown) merged into one
+#[derive(Debug, Clone, Default)]
+pub struct Relations {
+    remapper: HashMap<String, String>,
+    members: Vec<Entity>,
+}
+
+impl Relations {
+    pub fn new(member_lists: &[Vec<Entity>]) -> Self {
+        let mut remapper = HashMap::new();
+        let mut members: Vec<Entity> = vec![];
+        for l in member_lists {
+            for m in l {
+                if let Some(r) = members.iter().find(|&mem| {
+                    mem.display_name == m.display_name
+                        && mem.email.to_lowercase() == m.email.to_lowercase()
+                }) {
+                    remapper.insert(m.code.clone(), r.code.clone());
+                } else {
+                    remapper.insert(m.code.clone(), m.code.clone());
+                    members.push(m.clone());
+                }
+            }
+        }
+        members.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+        Self { remapper, members }
+    }
+
+    pub fn find_member(&self, code: &str) -> Option<&Entity> {
+        let actual_code = self.remapper.get(code)?.as_str();
+        self.members.iter().find(|m| m.code == actual_code)
+    }
+
+    pub fn find_member_by_name(&self, name: &str) -> Option<&Entity> {
+        self.find_member(&self.members.iter().find(|m| m.display_name == name)?.code)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.members.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.members
+            .iter()
+            .map(|m| m.display_name.clone())
+            .collect()
+    }
+
+    pub fn emails(&self) -> Vec<String> {
+        self.members.iter().map(|m| m.email.clone()).collect()
+    }
+}
+
+impl Index<usize> for Relations {
+    type Output = Entity;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.members[index]
+    }
+}
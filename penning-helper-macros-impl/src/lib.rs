@@ -25,7 +25,6 @@ pub fn command(input: TokenStream, data: TokenStream) -> TokenStream {
         #struct_data
         #to_request_impl
     }
-    .into()
 }
 
 struct Input {
@@ -4,7 +4,8 @@ use lettre::{
     transport::smtp::{authentication::Mechanism, PoolConfig},
     Message, SmtpTransport, Transport,
 };
-use penning_helper_types::{Date, Euro};
+use penning_helper_types::{Date, Euro, Locale};
+use qrcode::{EcLevel, QrCode};
 
 #[derive(Debug, Template)]
 #[template(path = "email.html")]
@@ -13,44 +14,70 @@ struct EmailTemplate<'a> {
     amount: Euro,
     date: Date,
     no_details: bool,
+    /// The RF reference to mention for transfers made by hand
+    reference: Option<&'a str>,
+    /// Whether the QR code to pay with is attached
+    qr: bool,
     company_name: &'a str,
     company_iban: &'a str,
     board_line: &'a str,
     treasurer: &'a str,
+    locale: Locale,
 }
 impl<'a> EmailTemplate<'a> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         name: &'a str,
         amount: Euro,
         date: Date,
         no_details: bool,
+        reference: Option<&'a str>,
+        qr: bool,
         company_name: &'a str,
         company_iban: &'a str,
         board_line: &'a str,
         treasurer: &'a str,
+        locale: Locale,
     ) -> Self {
         Self {
             name,
             amount,
             date,
             no_details,
+            reference,
+            qr,
             company_name,
             company_iban,
             board_line,
             treasurer,
+            locale,
         }
     }
 }
 
+#[derive(Debug, Template)]
+#[template(path = "return.html")]
+struct ReturnTemplate<'a> {
+    name: &'a str,
+    amount: Euro,
+    date: Date,
+    reason: &'a str,
+    company_name: &'a str,
+    company_iban: &'a str,
+    board_line: &'a str,
+    treasurer: &'a str,
+    locale: Locale,
+}
+
 mod filters {
-    use penning_helper_types::Euro;
+    use penning_helper_types::{Euro, Locale};
 
-    pub fn abs_euro(e: &Euro) -> ::askama::Result<String> {
-        Ok(format!("{:-}", e))
+    pub fn abs_euro(e: &Euro, locale: &Locale) -> ::askama::Result<String> {
+        Ok(format!("{:-}", e.localized(*locale)))
     }
 
-    pub fn too_much_result(e: &Euro) -> ::askama::Result<String> {
-        Ok(format!("{:-}", *e - 100.0))
+    pub fn too_much_result(e: &Euro, locale: &Locale) -> ::askama::Result<String> {
+        Ok(format!("{:-}", (*e - 100.0).localized(*locale)))
     }
 
     pub fn owes_or_not(e: &Euro) -> ::askama::Result<bool> {
@@ -70,6 +97,23 @@ pub enum MailError {
     TransportError(#[from] lettre::transport::smtp::Error),
     #[error("MailContentError: {0}")]
     MailContentError(#[from] lettre::error::Error),
+    #[error("QrCodeError: {0}")]
+    QrCodeError(String),
+}
+
+/// `payload` as a png QR code with the error correction EPC069-12 asks for
+fn qr_png(payload: &str) -> Result<Vec<u8>, MailError> {
+    let code = QrCode::with_error_correction_level(payload, EcLevel::M)
+        .map_err(|e| MailError::QrCodeError(e.to_string()))?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(400, 400)
+        .build();
+    let mut png = vec![];
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|e| MailError::QrCodeError(e.to_string()))?;
+    Ok(png)
 }
 
 #[derive(Clone)]
@@ -79,6 +123,7 @@ pub struct MailServer {
     reply_to: Mailbox,
     iban: String,
     name: String,
+    locale: Locale,
 }
 
 impl std::fmt::Debug for MailServer {
@@ -88,6 +133,7 @@ impl std::fmt::Debug for MailServer {
             .field("reply_to", &self.reply_to)
             .field("iban", &self.iban)
             .field("name", &self.name)
+            .field("locale", &self.locale)
             .finish_non_exhaustive()
     }
 }
@@ -107,11 +153,20 @@ impl MailServer {
             sender: sender.build(),
             from: config.from.as_pair().try_into().unwrap(),
             reply_to: config.reply_to.as_pair().try_into().unwrap(),
-            iban: sepa.company_iban.clone(),
+            iban: sepa.company_iban.to_string(),
             name: sepa.company_name.clone(),
+            locale: Locale::default(),
         })
     }
 
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// `qr` is the payload of an EPC QR code for the amount to transfer by
+    /// hand, shown below the payment details
+    #[allow(clippy::too_many_arguments)]
     pub fn send_mail(
         &self,
         name: &str,
@@ -120,29 +175,91 @@ impl MailServer {
         amount: Euro,
         date: Date,
         no_details: bool,
+        reference: Option<&str>,
+        qr: Option<&str>,
         board: &str,
         treasurer: &str,
     ) -> Result<(), MailError> {
+        let qr = qr.map(qr_png).transpose()?;
         let mail_content =
-            EmailTemplate::new(&name, amount, date, no_details, &self.name, &self.iban, board, treasurer)
+            EmailTemplate::new(
+                name,
+                amount,
+                date,
+                no_details,
+                reference,
+                qr.is_some(),
+                &self.name,
+                &self.iban,
+                board,
+                treasurer,
+                self.locale,
+            )
                 .render()
                 .unwrap();
+        let mut parts = MultiPart::mixed()
+            .multipart(MultiPart::related().singlepart(SinglePart::html(mail_content)))
+            .singlepart(Attachment::new_inline("logo".to_string()).body(
+                include_bytes!("../logo.png").to_vec(),
+                "image/png".parse().unwrap(),
+            ));
+        if let Some(qr) = qr {
+            parts = parts.singlepart(
+                Attachment::new_inline("qr".to_string()).body(qr, "image/png".parse().unwrap()),
+            );
+        }
         let email = Message::builder()
             .from(self.from.clone())
             .reply_to(self.reply_to.clone())
             .to((name, email).try_into().unwrap())
             .subject(format!("AEGEE-Delft invoice {}", date))
+            .multipart(
+                parts.singlepart(
+                    Attachment::new("invoice.pdf".to_string())
+                        .body(pdf_file, "application/pdf".parse().unwrap()),
+                ),
+            )?;
+        self.sender.send(&email)?;
+        Ok(())
+    }
+
+    /// Tells the relation their direct debit came back and is still open
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_return_notice(
+        &self,
+        name: &str,
+        email: &str,
+        amount: Euro,
+        date: Date,
+        reason: &str,
+        board: &str,
+        treasurer: &str,
+    ) -> Result<(), MailError> {
+        let mail_content = ReturnTemplate {
+            name,
+            amount,
+            date,
+            reason,
+            company_name: &self.name,
+            company_iban: &self.iban,
+            board_line: board,
+            treasurer,
+            locale: self.locale,
+        }
+        .render()
+        .unwrap();
+        let email = Message::builder()
+            .from(self.from.clone())
+            .reply_to(self.reply_to.clone())
+            .to((name, email).try_into().unwrap())
+            .subject(format!("AEGEE-Delft direct debit returned {}", date))
             .multipart(
                 MultiPart::mixed()
                     .multipart(MultiPart::related().singlepart(SinglePart::html(mail_content)))
                     .singlepart(Attachment::new_inline("logo".to_string()).body(
                         include_bytes!("../logo.png").to_vec(),
                         "image/png".parse().unwrap(),
-                    ))
-                    .singlepart(
-                        Attachment::new("invoice.pdf".to_string())
-                            .body(pdf_file, "application/pdf".parse().unwrap()),
-                    ),
+                    )),
             )?;
         self.sender.send(&email)?;
         Ok(())
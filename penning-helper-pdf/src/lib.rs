@@ -6,7 +6,8 @@ use genpdf::{
 };
 use image::Pixel;
 use once_cell::sync::Lazy;
-use penning_helper_types::{Date, Euro};
+use penning_helper_types::{Date, Euro, Locale};
+use qrcode::{EcLevel, QrCode};
 
 mod turflist;
 
@@ -36,11 +37,11 @@ trait SmallPad: Sized + Element {
 
 impl<E: Element> SmallPad for E {}
 
-fn create_debit_credit_elements(cost: Euro) -> (impl Element, impl Element) {
+fn create_debit_credit_elements(cost: Euro, locale: Locale) -> (impl Element, impl Element) {
     if cost > Euro::default() {
         (
             genpdf::elements::Paragraph::new(StyledString::new(
-                format!("{:-}", cost),
+                format!("{:-}", cost.localized(locale)),
                 genpdf::style::Style::new().with_color(genpdf::style::Color::Rgb(255, 0, 0)),
             )),
             genpdf::elements::Paragraph::new(""),
@@ -49,7 +50,7 @@ fn create_debit_credit_elements(cost: Euro) -> (impl Element, impl Element) {
         (
             genpdf::elements::Paragraph::new(""),
             genpdf::elements::Paragraph::new(StyledString::new(
-                format!("{:-}", cost),
+                format!("{:-}", cost.localized(locale)),
                 genpdf::style::Style::new().with_color(genpdf::style::Color::Rgb(0, 255, 0)),
             )),
         )
@@ -78,7 +79,26 @@ impl<'a> SimpleTransaction<'a> {
     }
 }
 
-pub fn create_invoice_pdf(mut transactions: Vec<SimpleTransaction>, name: &str) -> Vec<u8> {
+/// The EPC QR code for `payload`, about 3cm wide at the default 300 dpi
+fn qr_image(payload: &str) -> Option<genpdf::elements::Image> {
+    let code = QrCode::with_error_correction_level(payload, EcLevel::M).ok()?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(350, 350)
+        .build();
+    let image = image::DynamicImage::ImageRgb8(image::DynamicImage::ImageLuma8(image).to_rgb8());
+    genpdf::elements::Image::from_dynamic_image(image).ok()
+}
+
+/// `qr` is the payload of an EPC QR code for the amount to transfer by hand,
+/// printed below the totals
+pub fn create_invoice_pdf(
+    mut transactions: Vec<SimpleTransaction>,
+    name: &str,
+    reference: Option<&str>,
+    qr: Option<&str>,
+    locale: Locale,
+) -> Vec<u8> {
     transactions.sort_by_key(|v| v.date);
     let total = transactions.iter().map(|t| t.cost).sum::<Euro>();
 
@@ -122,13 +142,19 @@ pub fn create_invoice_pdf(mut transactions: Vec<SimpleTransaction>, name: &str)
     table.set_cell_decorator(genpdf::elements::FrameCellDecorator::new(
         false, false, false,
     ));
-    let header = LinearLayout::vertical()
+    let mut header = LinearLayout::vertical()
         .element(genpdf::elements::Paragraph::new(name))
         .element(genpdf::elements::Paragraph::new(format!(
             "{}",
             Date::today()
         )))
         .element(genpdf::elements::Paragraph::new("AEGEE-Delft"));
+    if let Some(reference) = reference {
+        header.push(genpdf::elements::Paragraph::new(format!(
+            "Payment reference: {}",
+            reference
+        )));
+    }
 
     table.row().element(header).element(image).push().unwrap();
     doc.push(table);
@@ -160,7 +186,7 @@ pub fn create_invoice_pdf(mut transactions: Vec<SimpleTransaction>, name: &str)
         .push()
         .unwrap();
     for t in &transactions {
-        let (debet, credit) = create_debit_credit_elements(t.cost);
+        let (debet, credit) = create_debit_credit_elements(t.cost, locale);
 
         table
             .row()
@@ -185,7 +211,7 @@ pub fn create_invoice_pdf(mut transactions: Vec<SimpleTransaction>, name: &str)
             .unwrap();
     }
 
-    let (debet, credit) = create_debit_credit_elements(total);
+    let (debet, credit) = create_debit_credit_elements(total, locale);
     table
         .row()
         .element(genpdf::elements::PaddedElement::new(
@@ -202,6 +228,14 @@ pub fn create_invoice_pdf(mut transactions: Vec<SimpleTransaction>, name: &str)
         .unwrap();
     doc.push(table);
 
+    if let Some(image) = qr.and_then(qr_image) {
+        doc.push(
+            genpdf::elements::Paragraph::new("Scan with your banking app to pay:")
+                .padded((5, 1, 1, 1)),
+        );
+        doc.push(image.with_alignment(genpdf::Alignment::Left).padded(1));
+    }
+
     const EXPLAINER: &str = "In this invoice the 'Debet' side is money you spent on AEGEE-Delft activities, like social drinks or activities. \
     The 'Credit' side is money you sent AEGEE-Delft, either directly through an invoice like this one, or by declaring costs you made for committees.";
     doc.push(genpdf::elements::Paragraph::new(EXPLAINER).wrap_small_pad());
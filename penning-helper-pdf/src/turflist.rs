@@ -18,7 +18,7 @@ impl<'s> From<(&'s str, &'s TurfListRow)> for SimpleTurfRow<'s> {
     fn from((who, row): (&'s str, &'s TurfListRow)) -> Self {
         Self {
             who,
-            what: row.what.as_ref().map(String::as_str).unwrap_or(""),
+            what: row.what.as_deref().unwrap_or(""),
             total: row.amount,
         }
     }
@@ -0,0 +1,305 @@
+use std::io::Read;
+
+use chrono::NaiveDate;
+use penning_helper_types::{Date, Euro, Iban};
+
+use crate::tree::Element;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CamtError {
+    #[error("Could not read the XML: {0}")]
+    Xml(String),
+    #[error("Not a camt.053 or camt.054 document, it starts with {0}")]
+    UnknownDocument(String),
+    #[error("{0} is missing")]
+    Missing(&'static str),
+    #[error("{element} has an invalid value: {value}")]
+    Invalid {
+        element: &'static str,
+        value: String,
+    },
+}
+
+/// Whether money came in or went out, seen from our account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreditDebit {
+    Credit,
+    Debit,
+}
+
+/// A camt.053 statement or camt.054 notification for one account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub id: String,
+    pub account: Option<Iban>,
+    pub entries: Vec<Entry>,
+}
+
+/// One booking on the account, a direct debit batch is a single entry with a
+/// transaction for every debtor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub amount: Euro,
+    pub direction: CreditDebit,
+    pub reversal: bool,
+    /// Pending and informational entries are not booked
+    pub booked: bool,
+    pub booking_date: Option<Date>,
+    pub value_date: Option<Date>,
+    /// The bank's own reference, `AcctSvcrRef`
+    pub bank_reference: Option<String>,
+    pub transactions: Vec<EntryTransaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryTransaction {
+    pub amount: Euro,
+    pub end_to_end_id: Option<String>,
+    pub mandate_id: Option<String>,
+    pub counterparty_name: Option<String>,
+    pub counterparty_iban: Option<Iban>,
+    pub remittance: Option<String>,
+    /// The reason code of a returned or refunded direct debit, like `MD06`
+    pub return_reason: Option<String>,
+}
+
+impl EntryTransaction {
+    /// The end-to-end id, unless the bank says there was none
+    pub fn end_to_end_id(&self) -> Option<&str> {
+        self.end_to_end_id
+            .as_deref()
+            .filter(|id| *id != "NOTPROVIDED")
+    }
+}
+
+/// Reads the statements in a camt.053 or the notifications in a camt.054 file
+pub fn parse_statements(reader: impl Read) -> Result<Vec<Statement>, CamtError> {
+    let root = Element::parse(reader).map_err(|e| CamtError::Xml(e.to_string()))?;
+    let (message, statement) = match (
+        root.child("BkToCstmrStmt"),
+        root.child("BkToCstmrDbtCdtNtfctn"),
+    ) {
+        (Some(m), _) => (m, "Stmt"),
+        (None, Some(m)) => (m, "Ntfctn"),
+        (None, None) => {
+            let first = root.children.first().unwrap_or(&root);
+            return Err(CamtError::UnknownDocument(first.name.clone()));
+        }
+    };
+    message.children(statement).map(parse_statement).collect()
+}
+
+fn parse_statement(statement: &Element) -> Result<Statement, CamtError> {
+    let id = statement
+        .text_at(&["Id"])
+        .ok_or(CamtError::Missing("Stmt/Id"))?;
+    let account = statement
+        .text_at(&["Acct", "Id", "IBAN"])
+        .map(|i| parse_iban("Acct/Id/IBAN", i))
+        .transpose()?;
+    let entries = statement
+        .children("Ntry")
+        .map(parse_entry)
+        .collect::<Result<_, _>>()?;
+    Ok(Statement {
+        id: id.to_string(),
+        account,
+        entries,
+    })
+}
+
+fn parse_entry(entry: &Element) -> Result<Entry, CamtError> {
+    let amount = parse_amount("Ntry/Amt", entry.text_at(&["Amt"]))?;
+    let direction = parse_direction(entry.text_at(&["CdtDbtInd"]))?;
+    let reversal = entry.text_at(&["RvslInd"]) == Some("true");
+    // older versions have the code directly in Sts
+    let status = entry
+        .text_at(&["Sts", "Cd"])
+        .or_else(|| entry.text_at(&["Sts"]));
+    let details = entry
+        .children("NtryDtls")
+        .flat_map(|d| d.children("TxDtls"))
+        .collect::<Vec<_>>();
+    let transactions = if details.is_empty() {
+        vec![EntryTransaction {
+            amount,
+            end_to_end_id: None,
+            mandate_id: None,
+            counterparty_name: None,
+            counterparty_iban: None,
+            remittance: entry.text_at(&["AddtlNtryInf"]).map(str::to_string),
+            return_reason: None,
+        }]
+    } else {
+        let single = details.len() == 1;
+        details
+            .into_iter()
+            .map(|t| parse_transaction(t, direction, reversal, single.then_some(amount)))
+            .collect::<Result<_, _>>()?
+    };
+    Ok(Entry {
+        amount,
+        direction,
+        reversal,
+        booked: status == Some("BOOK"),
+        booking_date: parse_date("Ntry/BookgDt", entry.find(&["BookgDt"]))?,
+        value_date: parse_date("Ntry/ValDt", entry.find(&["ValDt"]))?,
+        bank_reference: entry.text_at(&["AcctSvcrRef"]).map(str::to_string),
+        transactions,
+    })
+}
+
+/// `entry_amount` is used when the only transaction of an entry has no amount
+fn parse_transaction(
+    transaction: &Element,
+    direction: CreditDebit,
+    reversal: bool,
+    entry_amount: Option<Euro>,
+) -> Result<EntryTransaction, CamtError> {
+    let amount = transaction
+        .text_at(&["AmtDtls", "TxAmt", "Amt"])
+        .or_else(|| transaction.text_at(&["Amt"]));
+    let amount = match (amount, entry_amount) {
+        (None, Some(a)) => a,
+        (amount, _) => parse_amount("TxDtls/Amt", amount)?,
+    };
+    let return_reason = transaction
+        .text_at(&["RtrInf", "Rsn", "Cd"])
+        .map(str::to_string);
+    // money coming in is from the debtor, unless it's a returned direct debit
+    // going back to them
+    let returned = reversal || return_reason.is_some();
+    let party = match (direction, returned) {
+        (CreditDebit::Credit, false) | (CreditDebit::Debit, true) => "Dbtr",
+        (CreditDebit::Debit, false) | (CreditDebit::Credit, true) => "Cdtr",
+    };
+    let parties = transaction.child("RltdPties");
+    let counterparty_name = parties.and_then(|p| {
+        p.text_at(&[party, "Nm"])
+            .or_else(|| p.text_at(&[party, "Pty", "Nm"]))
+    });
+    let account = if party == "Dbtr" {
+        "DbtrAcct"
+    } else {
+        "CdtrAcct"
+    };
+    let counterparty_iban = parties
+        .and_then(|p| p.text_at(&[account, "Id", "IBAN"]))
+        .map(|i| parse_iban("RltdPties/Acct/Id/IBAN", i))
+        .transpose()?;
+    let remittance = transaction.child("RmtInf").and_then(|r| {
+        let unstructured = r
+            .children("Ustrd")
+            .map(|u| u.text.trim())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if unstructured.is_empty() {
+            r.text_at(&["Strd", "CdtrRefInf", "Ref"])
+                .map(str::to_string)
+        } else {
+            Some(unstructured)
+        }
+    });
+    Ok(EntryTransaction {
+        amount,
+        end_to_end_id: transaction
+            .text_at(&["Refs", "EndToEndId"])
+            .map(str::to_string),
+        mandate_id: transaction.text_at(&["Refs", "MndtId"]).map(str::to_string),
+        counterparty_name: counterparty_name.map(str::to_string),
+        counterparty_iban,
+        remittance,
+        return_reason,
+    })
+}
+
+fn parse_amount(element: &'static str, amount: Option<&str>) -> Result<Euro, CamtError> {
+    let amount = amount.ok_or(CamtError::Missing(element))?;
+    amount.parse().map_err(|_| CamtError::Invalid {
+        element,
+        value: amount.to_string(),
+    })
+}
+
+fn parse_direction(direction: Option<&str>) -> Result<CreditDebit, CamtError> {
+    match direction {
+        Some("CRDT") => Ok(CreditDebit::Credit),
+        Some("DBIT") => Ok(CreditDebit::Debit),
+        Some(d) => Err(CamtError::Invalid {
+            element: "Ntry/CdtDbtInd",
+            value: d.to_string(),
+        }),
+        None => Err(CamtError::Missing("Ntry/CdtDbtInd")),
+    }
+}
+
+fn parse_iban(element: &'static str, iban: &str) -> Result<Iban, CamtError> {
+    iban.parse().map_err(|_| CamtError::Invalid {
+        element,
+        value: iban.to_string(),
+    })
+}
+
+/// A `Dt` or the date part of a `DtTm`
+fn parse_date(element: &'static str, date: Option<&Element>) -> Result<Option<Date>, CamtError> {
+    let Some(date) = date.and_then(|d| d.text_at(&["Dt"]).or_else(|| d.text_at(&["DtTm"]))) else {
+        return Ok(None);
+    };
+    let day = date.get(..10).unwrap_or(date);
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map(|d| Some(d.into()))
+        .map_err(|_| CamtError::Invalid {
+            element,
+            value: date.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, iban};
+
+    #[test]
+    fn statements_have_their_entries_and_transactions() {
+        let statements = parse_statements(fixture("camt.053.001.02.xml")).unwrap();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].account, Some(iban("NL44RABO0123456789")));
+        assert_eq!(statements[0].entries.len(), 5);
+        assert_eq!(statements[0].entries[0].transactions.len(), 2);
+    }
+
+    #[test]
+    fn notifications_are_read_like_statements() {
+        let camt_054 = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.08">
+  <BkToCstmrDbtCdtNtfctn>
+    <Ntfctn>
+      <Id>NTFCTN-1</Id>
+      <Ntry>
+        <Amt Ccy="EUR">12.50</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <NtryDtls>
+          <TxDtls>
+            <Refs><EndToEndId>20240301-1-202403-1001</EndToEndId></Refs>
+            <RltdPties><Dbtr><Pty><Nm>Jan Jansen</Nm></Pty></Dbtr></RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+    </Ntfctn>
+  </BkToCstmrDbtCdtNtfctn>
+</Document>"#;
+        let statements = parse_statements(camt_054.as_bytes()).unwrap();
+        let transaction = &statements[0].entries[0].transactions[0];
+        assert_eq!(transaction.end_to_end_id(), Some("20240301-1-202403-1001"));
+        assert_eq!(transaction.counterparty_name.as_deref(), Some("Jan Jansen"));
+    }
+
+    #[test]
+    fn other_documents_are_refused() {
+        assert!(matches!(
+            parse_statements("<Document><CstmrDrctDbtInitn/></Document>".as_bytes()),
+            Err(CamtError::UnknownDocument(n)) if n == "CstmrDrctDbtInitn"
+        ));
+    }
+}
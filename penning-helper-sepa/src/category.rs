@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// What a payment is for. Every category gets its own payment information
+/// block, so the bank books each as a separate amount.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum Category {
+    /// Invoices and payments of open balances
+    #[default]
+    Balance,
+    Contribution,
+    AlumniContribution,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [
+        Category::Balance,
+        Category::Contribution,
+        Category::AlumniContribution,
+    ];
+
+    /// Added to the payment information id. Balances have none, so their
+    /// blocks keep the ids they had before there were categories.
+    pub(crate) fn suffix(&self) -> Option<&'static str> {
+        match self {
+            Category::Balance => None,
+            Category::Contribution => Some("CONTR"),
+            Category::AlumniContribution => Some("ALUMNI"),
+        }
+    }
+
+    /// `RCUR` or `RCUR-CONTR` for direct debits, `TRF` or `TRF-CONTR` for
+    /// transfers
+    pub(crate) fn payment_information_suffix(&self, kind: &str) -> String {
+        match self.suffix() {
+            Some(suffix) => format!("{}-{}", kind, suffix),
+            None => kind.to_string(),
+        }
+    }
+
+    /// The category of a block read back from a file, by the suffix of its
+    /// payment information id
+    pub(crate) fn from_payment_information_id(id: &str) -> Category {
+        Category::ALL
+            .into_iter()
+            .find(|c| {
+                c.suffix()
+                    .is_some_and(|s| id.strip_suffix(s).is_some_and(|i| i.ends_with('-')))
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Category::Balance => "Balance",
+            Category::Contribution => "Contribution",
+            Category::AlumniContribution => "Alumni contribution",
+        })
+    }
+}
@@ -0,0 +1,159 @@
+use penning_helper_types::{Bic, CreditorReference, Euro, Iban};
+
+use crate::{
+    sanitise,
+    validate::{MAX_NAME, MAX_REMITTANCE},
+};
+
+/// The largest amount the EPC QR code allows
+const MAX_AMOUNT: Euro = Euro::from_cents(99_999_999_999);
+/// The most bytes a payload can have
+const MAX_PAYLOAD: usize = 331;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EpcQrError {
+    #[error("A QR code can only ask for an amount between €0.01 and €999999999.99, not {0}")]
+    Amount(Euro),
+    #[error("The QR code payload can have at most 331 bytes, not {0}")]
+    TooLong(usize),
+}
+
+/// The payload of an EPC069-12 QR code ("GiroCode"), which banking apps scan
+/// to fill in a transfer to us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpcQr {
+    bic: Bic,
+    name: String,
+    iban: Iban,
+    amount: Euro,
+    reference: Option<CreditorReference>,
+    text: String,
+}
+
+impl EpcQr {
+    pub(crate) fn new(
+        bic: Bic,
+        name: &str,
+        iban: Iban,
+        amount: Euro,
+        reference: Option<CreditorReference>,
+        text: &str,
+    ) -> Result<Self, EpcQrError> {
+        if amount < Euro::from_cents(1) || amount > MAX_AMOUNT {
+            return Err(EpcQrError::Amount(amount));
+        }
+        let qr = Self {
+            bic,
+            name: sanitise(name, MAX_NAME),
+            iban,
+            amount,
+            reference,
+            text: sanitise(text, MAX_REMITTANCE),
+        };
+        let len = qr.payload().len();
+        if len > MAX_PAYLOAD {
+            return Err(EpcQrError::TooLong(len));
+        }
+        Ok(qr)
+    }
+
+    pub fn amount(&self) -> Euro {
+        self.amount
+    }
+
+    /// Version 002 in UTF-8: service tag, version, character set,
+    /// identification, BIC, name, IBAN, amount, purpose, and the creditor
+    /// reference or else the text. Only one of the last two may be filled in.
+    pub fn payload(&self) -> String {
+        let (reference, text) = match &self.reference {
+            Some(r) => (r.as_str(), ""),
+            None => ("", self.text.as_str()),
+        };
+        let lines = [
+            "BCD",
+            "002",
+            "1",
+            "SCT",
+            self.bic.as_str(),
+            &self.name,
+            self.iban.as_str(),
+            &format!("EUR{}", self.amount.xml_string()),
+            "",
+            reference,
+            text,
+        ];
+        // trailing empty lines may be left out
+        lines.join("\n").trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creditor_reference,
+        testing::{bic, iban},
+        SEPAConfig, SchemaVersion,
+    };
+
+    fn config() -> SEPAConfig {
+        SEPAConfig::new(
+            "AEGEE-Delft",
+            iban("NL44RABO0123456789"),
+            bic("RABONL2U"),
+            "NL00ZZZ404840000000",
+            SchemaVersion::V2019,
+        )
+    }
+
+    #[test]
+    fn payload_has_our_account_and_the_balance() {
+        let qr = config()
+            .epc_qr(Euro::new(12, 50), None, "Invoice AEGEE-Delft 2024-03")
+            .unwrap();
+        assert_eq!(
+            qr.payload(),
+            "BCD\n002\n1\nSCT\nRABONL2U\nAEGEE-Delft\nNL44RABO0123456789\nEUR12.50\n\n\nInvoice AEGEE-Delft 2024-03"
+        );
+    }
+
+    #[test]
+    fn the_reference_replaces_the_text() {
+        // and the empty lines after it go
+        let reference = creditor_reference("1002", "202403").unwrap();
+        let qr = config()
+            .epc_qr(
+                Euro::new(7, 5),
+                Some(reference),
+                "Invoice AEGEE-Delft 2024-03",
+            )
+            .unwrap();
+        assert_eq!(
+            qr.payload(),
+            "BCD\n002\n1\nSCT\nRABONL2U\nAEGEE-Delft\nNL44RABO0123456789\nEUR7.05\n\nRF312024031002"
+        );
+    }
+
+    #[test]
+    fn long_texts_are_sanitised_and_cut_off() {
+        let qr = config()
+            .epc_qr(
+                Euro::new(1, 0),
+                None,
+                &format!("Bijdrage – {}", "x".repeat(200)),
+            )
+            .unwrap();
+        let text = qr.payload().lines().last().unwrap().to_string();
+        assert!(text.starts_with("Bijdrage - x"));
+        assert_eq!(text.chars().count(), 140);
+    }
+
+    #[test]
+    fn nothing_to_pay_has_no_code() {
+        assert_eq!(
+            config().epc_qr(Euro::default(), None, "").unwrap_err(),
+            EpcQrError::Amount(Euro::default())
+        );
+        assert!(config().epc_qr(Euro::new(-5, 0), None, "").is_err());
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use penning_helper_types::{Date, Euro};
+
+use crate::{
+    invoice, transfer, CreditorDetails, DebtorDetails, Diagnostic, DocumentRecord,
+    DoubleCollection, IdRegistry, InvoiceRelation, MandateLedger, Run, RunId, RunStore, SEPAConfig,
+    Sanitised, Selection, SequenceType, Severity, StoreError,
+};
+
+/// What goes in the SEPA files of a run
+#[derive(Clone, Copy, Debug)]
+pub struct RunOptions {
+    pub selection: Selection,
+    /// Every payment gets the RF reference of its invoice
+    pub creditor_references: bool,
+    /// Saves direct debits for a period that was already collected, like
+    /// after a return
+    pub collect_again: bool,
+    pub date: Date,
+}
+
+/// What happened when the SEPA files of a run were made
+#[derive(Clone, Debug, Default)]
+pub struct RunSummary {
+    pub debtors: usize,
+    pub debtors_total: Euro,
+    pub creditors: usize,
+    pub creditors_total: Euro,
+    /// Relations whose BIC was derived from their IBAN
+    pub derived_bics: Vec<String>,
+    /// Relations whose IBAN or mandate changed since the last collection
+    pub amended_mandates: Vec<String>,
+    /// Relations that were already invoiced for this period, with that run
+    pub reissued: Vec<(String, RunId)>,
+    /// Relations that were left out, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Names and descriptions that had to be changed for the bank, the
+    /// originals should be fixed in Conscribo
+    pub sanitised: Vec<Sanitised>,
+    /// Problems the bank would complain about, nothing is saved if one of
+    /// them is an error
+    pub diagnostics: Vec<Diagnostic>,
+    /// Direct debits per sequence type, with their collection date
+    pub sequence_types: BTreeMap<SequenceType, (usize, Date)>,
+    /// Relations an earlier file already collected for this period, nothing
+    /// is saved unless collecting again was asked for
+    pub double_collected: Vec<DoubleCollection>,
+}
+
+impl RunSummary {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// The documents of a run that passed every check. Nothing is recorded until
+/// they are saved.
+pub struct GeneratedRun {
+    pub summary: RunSummary,
+    run: Run,
+    /// The ledger with the collections of this run
+    ledger: MandateLedger,
+    debtors: Option<(invoice::Document, DocumentRecord)>,
+    creditors: Option<(transfer::Document, DocumentRecord)>,
+}
+
+/// A file [`GeneratedRun::save`] wrote
+#[derive(Clone, Debug)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    pub record: DocumentRecord,
+}
+
+/// Why the files of a run could not be saved
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("Could not write the SEPA file: {0}")]
+    Xml(#[from] xml::writer::Error),
+    #[error("Could not write {}: {1}", .0.display())]
+    Io(PathBuf, std::io::Error),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    /// The files in the first field are written and recorded, the rest was
+    /// removed again
+    #[error("Only {} could be recorded, the rest was not saved: {1}", display_paths(.0))]
+    PartlyRecorded(Vec<PathBuf>, StoreError),
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+impl SEPAConfig {
+    /// Collects from or pays out every relation, with its open balance, that
+    /// the selection picks. Relations that can't be in the files are skipped
+    /// with the reason in the summary. Only the summary comes back when the
+    /// bank would refuse the files or someone would be collected twice.
+    pub fn generate<'a>(
+        &self,
+        relations: impl IntoIterator<Item = (&'a InvoiceRelation, Euro)>,
+        options: RunOptions,
+        ledger: &MandateLedger,
+        ids: &IdRegistry,
+        runs: &RunStore,
+    ) -> Result<GeneratedRun, Box<RunSummary>> {
+        let selection = options.selection;
+        let relations = relations
+            .into_iter()
+            .filter(|&(r, total)| total != Euro::default() && selection.filter(r, total))
+            .collect::<Vec<_>>();
+        let mut ledger = ledger.clone();
+        let mut run = self.start_run(ids, options.date);
+        let period = selection.period(run.date());
+        let mut summary = RunSummary::default();
+        let mut creditors = vec![];
+        let mut debtors = vec![];
+        for &(r, total) in &relations {
+            let problems = r.problems(Some(self));
+            let Some((iban, bic)) = r.bank_details().filter(|_| problems.is_empty()) else {
+                summary.skipped.push((r.name.clone(), problems.join(", ")));
+                continue;
+            };
+            let reference =
+                selection.creditor_reference(options.creditor_references, &r.code, run.date());
+            if total < Euro::default() {
+                let details = CreditorDetails {
+                    code: r.code.clone(),
+                    name: r.name.clone(),
+                    amount: -total,
+                    bic,
+                    iban,
+                    description: "Payment of positive balance".to_string(),
+                    reference,
+                };
+                match self.new_creditor(&mut run, &period, details) {
+                    Ok(c) => {
+                        let c = c.with_category(selection.category());
+                        if c.bic_derived() {
+                            summary.derived_bics.push(r.name.clone());
+                        }
+                        summary.creditors += 1;
+                        summary.creditors_total += c.amount();
+                        creditors.push(c);
+                    }
+                    Err(e) => summary.skipped.push((r.name.clone(), e.to_string())),
+                }
+            } else {
+                let sequence_type = match r.sequence_type(selection, &ledger) {
+                    Ok(sequence_type) => sequence_type,
+                    Err(e) => {
+                        summary.skipped.push((r.name.clone(), e.to_string()));
+                        continue;
+                    }
+                };
+                let (amount, description) = r.collection(selection, total);
+                let (id, signature_date) = ledger.mandate(&r.code, r.membership_date.into());
+                let mandate = self.new_mandate(id, signature_date, iban);
+                let amendment = ledger.amendment(&r.code, &mandate);
+                let details = DebtorDetails {
+                    code: r.code.clone(),
+                    name: r.name.clone(),
+                    amount,
+                    bic,
+                    mandate,
+                    amendment,
+                    sequence_type,
+                    description: description.to_string(),
+                    reference,
+                };
+                match self.new_debtor(&mut run, &period, details) {
+                    Ok(d) => {
+                        let d = d.with_category(selection.category());
+                        if d.bic_derived() {
+                            summary.derived_bics.push(r.name.clone());
+                        }
+                        if d.amendment().is_some() {
+                            summary.amended_mandates.push(r.name.clone());
+                        }
+                        summary.debtors += 1;
+                        summary.debtors_total += d.amount();
+                        debtors.push(d);
+                    }
+                    Err(e) => summary.skipped.push((r.name.clone(), e.to_string())),
+                }
+            }
+        }
+
+        let debtors = self.new_invoice_payment_informations(&mut run, debtors);
+        for p in &debtors {
+            // a type can have a block per category
+            let (count, _) = summary
+                .sequence_types
+                .entry(p.sequence_type())
+                .or_insert((0, p.collection_date()));
+            *count += p.num_transactions() as usize;
+            for d in p.debtors() {
+                ledger.record(
+                    d.code(),
+                    d.mandate(),
+                    p.sequence_type(),
+                    p.collection_date(),
+                );
+            }
+        }
+        let creditors = self.new_transfer_payment_informations(&mut run, creditors);
+        let mut debtors = self.new_invoice_document(&run, debtors);
+        let mut creditors = self.new_transfer_document(&run, creditors);
+        for i in run.reissued() {
+            let first = i.original.as_deref().and_then(|id| ids.find(id));
+            if let (Some((r, _)), Some(first)) =
+                (relations.iter().find(|(r, _)| r.code == i.code), first)
+            {
+                summary.reissued.push((r.name.clone(), first.run.clone()));
+            }
+        }
+        summary.sanitised.extend(debtors.sanitise());
+        summary.sanitised.extend(creditors.sanitise());
+
+        // a file without transactions is left out instead of refused
+        if summary.debtors > 0 {
+            summary.diagnostics.extend(debtors.validate());
+        }
+        if summary.creditors > 0 {
+            summary.diagnostics.extend(creditors.validate());
+        }
+        if summary.has_errors() {
+            return Err(Box::new(summary));
+        }
+        let debtors_record = DocumentRecord::direct_debit(&run, &debtors);
+        let creditors_record = DocumentRecord::credit_transfer(&run, &creditors);
+        if summary.debtors > 0 && !options.collect_again {
+            summary.double_collected = runs.double_collections(&debtors_record);
+            if !summary.double_collected.is_empty() {
+                return Err(Box::new(summary));
+            }
+        }
+
+        Ok(GeneratedRun {
+            debtors: (summary.debtors > 0).then_some((debtors, debtors_record)),
+            creditors: (summary.creditors > 0).then_some((creditors, creditors_record)),
+            summary,
+            run,
+            ledger,
+        })
+    }
+}
+
+impl GeneratedRun {
+    /// Writes `<output>.invoice.xml` with the direct debits and
+    /// `<output>.transfer.xml` with the transfers, leaving out a file without
+    /// payments. Only once both are on disk and in `runs` is the run recorded
+    /// in `ledger` and `ids`, which still have to be saved by the caller. A
+    /// file that can't be recorded in `runs` is removed again.
+    pub fn save(
+        self,
+        output: &Path,
+        runs: &mut RunStore,
+        ledger: &mut MandateLedger,
+        ids: &mut IdRegistry,
+    ) -> Result<Vec<SavedFile>, SaveError> {
+        // written to memory first, the store keeps the hash
+        let mut files = vec![];
+        if let Some((document, record)) = self.debtors {
+            let mut xml = vec![];
+            document.write(&mut xml)?;
+            files.push((output.with_extension("invoice.xml"), xml, record));
+        }
+        if let Some((document, record)) = self.creditors {
+            let mut xml = vec![];
+            document.write(&mut xml)?;
+            files.push((output.with_extension("transfer.xml"), xml, record));
+        }
+
+        let mut saved: Vec<SavedFile> = vec![];
+        for (path, xml, record) in files {
+            if let Err(e) = std::fs::write(&path, &xml) {
+                // half a run would be handed to the bank without its history
+                for s in &saved {
+                    let _ = std::fs::remove_file(&s.path);
+                }
+                return Err(SaveError::Io(path, e));
+            }
+            saved.push(SavedFile {
+                path,
+                record: record.with_file(&xml),
+            });
+        }
+
+        // double collections were checked when the run was made
+        let mut recorded = vec![];
+        let mut error = None;
+        for s in saved {
+            if error.is_none() {
+                match runs.record_again(s.record.clone()) {
+                    Ok(()) => {
+                        recorded.push(s);
+                        continue;
+                    }
+                    Err(e) => error = Some(e),
+                }
+            }
+            // a file without its history would be collected again next time
+            let _ = std::fs::remove_file(&s.path);
+        }
+        if recorded.is_empty() {
+            if let Some(e) = error {
+                return Err(SaveError::Store(e));
+            }
+        }
+
+        // the files can go to the bank now, so their ids and mandates are used
+        *ledger = self.ledger;
+        ids.record(&self.run);
+        match error {
+            Some(e) => Err(SaveError::PartlyRecorded(
+                recorded.into_iter().map(|s| s.path).collect(),
+                e,
+            )),
+            None => Ok(recorded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use penning_helper_types::Lenient;
+
+    use crate::testing::{config, date};
+
+    fn relation(code: &str, iban: &str) -> InvoiceRelation {
+        InvoiceRelation {
+            name: format!("Relation {}", code),
+            code: code.to_string(),
+            membership_date: *date(2023, 9, 1),
+            iban: iban.into(),
+            bic: Lenient::Missing,
+            email: format!("{}@example.com", code),
+            membership_ended: false,
+            alumni_contributie: Euro::default(),
+        }
+    }
+
+    fn options() -> RunOptions {
+        RunOptions {
+            selection: Selection::All,
+            creditor_references: false,
+            collect_again: false,
+            // the documents are checked against the time they are made
+            date: Date::today(),
+        }
+    }
+
+    fn output(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "penning-helper-generate-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("march")
+    }
+
+    #[test]
+    fn balances_are_collected_or_paid_out() {
+        let jan = relation("1001", "NL91ABNA0417164300");
+        let erika = relation("1002", "NL44RABO0123456789");
+        let nobody = InvoiceRelation {
+            iban: Lenient::Missing,
+            ..relation("1003", "")
+        };
+        let relations = [
+            (&jan, Euro::new(25, 0)),
+            (&erika, Euro::new(-20, 0)),
+            (&nobody, Euro::new(5, 0)),
+        ];
+        let generated = config()
+            .generate(
+                relations,
+                options(),
+                &MandateLedger::default(),
+                &IdRegistry::default(),
+                &RunStore::default(),
+            )
+            .unwrap();
+        let summary = &generated.summary;
+        assert_eq!(
+            (summary.debtors, summary.debtors_total),
+            (1, Euro::new(25, 0))
+        );
+        assert_eq!(
+            (summary.creditors, summary.creditors_total),
+            (1, Euro::new(20, 0))
+        );
+        assert_eq!(
+            summary.skipped,
+            vec![("Relation 1003".to_string(), "No IBAN".to_string())]
+        );
+        assert_eq!(
+            summary
+                .sequence_types
+                .get(&SequenceType::First)
+                .map(|t| t.0),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn a_run_is_recorded_once_its_files_are_written() {
+        let jan = relation("1001", "NL91ABNA0417164300");
+        let output = output("recorded");
+        let mut ledger = MandateLedger::default();
+        let mut ids = IdRegistry::default();
+        let mut runs = RunStore::open(&output.with_extension("jsonl")).unwrap();
+        let generate = |ledger: &MandateLedger, ids: &IdRegistry, runs: &RunStore| {
+            config().generate([(&jan, Euro::new(25, 0))], options(), ledger, ids, runs)
+        };
+
+        let missing = output.with_file_name("missing").join("march");
+        let generated = generate(&ledger, &ids, &runs).unwrap();
+        assert!(matches!(
+            generated.save(&missing, &mut runs, &mut ledger, &mut ids),
+            Err(SaveError::Io(..))
+        ));
+        assert!(ledger.is_empty());
+        assert!(ids.payments().is_empty());
+        assert!(runs.documents().is_empty());
+
+        let generated = generate(&ledger, &ids, &runs).unwrap();
+        let saved = generated
+            .save(&output, &mut runs, &mut ledger, &mut ids)
+            .unwrap();
+        assert_eq!(saved.len(), 1);
+        assert!(output.with_extension("invoice.xml").exists());
+        assert!(!output.with_extension("transfer.xml").exists());
+        assert_eq!(ledger.len(), 1);
+        assert_eq!(ids.payments().len(), 1);
+        assert_eq!(runs.documents().len(), 1);
+
+        let again = generate(&ledger, &ids, &runs).err().unwrap();
+        assert_eq!(again.double_collected.len(), 1);
+        let (first_run, _) = ids.runs().next().unwrap();
+        assert_eq!(
+            again.reissued,
+            vec![("Relation 1001".to_string(), first_run.clone())]
+        );
+        let _ = std::fs::remove_dir_all(output.parent().unwrap());
+    }
+
+    #[test]
+    fn nothing_is_kept_when_the_run_store_cannot_be_written() {
+        let jan = relation("1001", "NL91ABNA0417164300");
+        let output = output("unrecorded");
+        let store = output.with_extension("jsonl");
+        let mut runs = RunStore::open(&store).unwrap();
+        // appending to a directory fails
+        std::fs::create_dir_all(&store).unwrap();
+        let mut ledger = MandateLedger::default();
+        let mut ids = IdRegistry::default();
+        let generated = config()
+            .generate([(&jan, Euro::new(25, 0))], options(), &ledger, &ids, &runs)
+            .unwrap();
+        assert!(matches!(
+            generated.save(&output, &mut runs, &mut ledger, &mut ids),
+            Err(SaveError::Store(_))
+        ));
+        assert!(!output.with_extension("invoice.xml").exists());
+        assert!(ledger.is_empty());
+        assert!(ids.payments().is_empty());
+        let _ = std::fs::remove_dir_all(output.parent().unwrap());
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+};
+
+use penning_helper_types::{CreditorReference, CreditorReferenceError, Date};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reconcile::Settlement, store, validate::MAX_ID, Payment, ReasonCode, RejectedPayment,
+    StoreError,
+};
+
+/// One time of making SEPA files, `20240301-1` for the first run that day
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RunId(String);
+
+impl RunId {
+    fn new(date: Date, number: usize) -> Self {
+        Self(format!("{}-{}", date.format("%Y%m%d"), number))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for RunId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An end-to-end id and who it was for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuedId {
+    pub id: String,
+    /// The run that issued it
+    pub run: RunId,
+    pub code: String,
+    pub period: String,
+    /// The id of the first payment for the same relation and period, when
+    /// this one invoices it again
+    #[serde(default)]
+    pub original: Option<String>,
+}
+
+/// The ids of every run that was saved, so a relation is never invoiced
+/// twice for the same period without noticing. The payments are kept too, to
+/// match the bank statements against.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdRegistry {
+    runs: BTreeMap<RunId, Date>,
+    issued: Vec<IssuedId>,
+    #[serde(default)]
+    payments: Vec<Payment>,
+}
+
+impl IdRegistry {
+    /// Reads the registry at `path`, a missing file is an empty registry
+    pub fn load(path: &Path) -> Result<Self, StoreError> {
+        store::load("id registry", path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StoreError> {
+        store::save("id registry", path, self)
+    }
+
+    /// The id for the next run on `date`
+    pub fn next_run_id(&self, date: Date) -> RunId {
+        let runs_that_day = self.runs.values().filter(|d| **d == date).count();
+        RunId::new(date, runs_that_day + 1)
+    }
+
+    pub fn runs(&self) -> impl Iterator<Item = (&RunId, &Date)> {
+        self.runs.iter()
+    }
+
+    pub fn issued(&self) -> &[IssuedId] {
+        &self.issued
+    }
+
+    pub fn find(&self, id: &str) -> Option<&IssuedId> {
+        self.issued.iter().find(|i| i.id == id)
+    }
+
+    pub fn payments(&self) -> &[Payment] {
+        &self.payments
+    }
+
+    /// Keeps the run, the ids it issued and its payments
+    pub fn record(&mut self, run: &Run) {
+        self.runs.insert(run.id.clone(), run.date);
+        self.issued.extend(run.issued.iter().cloned());
+        self.payments.extend(run.payments.iter().cloned());
+    }
+
+    /// Payments for `code`, oldest first
+    pub fn payments_for<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a Payment> {
+        self.payments.iter().filter(move |p| p.code == code)
+    }
+
+    /// The reason the last payment for `code` was refused, if it was
+    pub fn last_rejection(&self, code: &str) -> Option<&ReasonCode> {
+        let last = self.payments.iter().rfind(|p| p.code == code)?;
+        last.rejection.as_ref()
+    }
+
+    /// Marks the payment as refused, false if it isn't known
+    pub fn reject(&mut self, rejected: &RejectedPayment) -> bool {
+        let payment = self.payments.iter_mut().find(|p| {
+            p.run == rejected.payment.run && p.end_to_end_id == rejected.payment.end_to_end_id
+        });
+        match payment {
+            Some(p) => {
+                p.rejection = Some(rejected.reason.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the booking of the refused payment as undone on `date`, false if
+    /// it isn't known
+    pub fn reverse(&mut self, rejected: &RejectedPayment, date: Date) -> bool {
+        let payment = self.payments.iter_mut().find(|p| {
+            p.run == rejected.payment.run && p.end_to_end_id == rejected.payment.end_to_end_id
+        });
+        match payment {
+            Some(p) => {
+                p.reversed = Some(date);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the payment of `settlement` as settled, false if it isn't known
+    pub fn settle(&mut self, settlement: &Settlement) -> bool {
+        let payment = self.payments.iter_mut().find(|p| {
+            p.run == settlement.payment.run && p.end_to_end_id == settlement.payment.end_to_end_id
+        });
+        match payment {
+            Some(p) => {
+                p.settled = Some(settlement.date);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// An end-to-end id handed out by a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndToEndId {
+    pub id: String,
+    /// Set when an earlier run already invoiced the same relation and period,
+    /// the run that did so first
+    pub reissued_from: Option<RunId>,
+}
+
+/// Why a run can't hand out an end-to-end id
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EndToEndIdError {
+    #[error("The end-to-end id {0} is longer than {} characters", MAX_ID)]
+    TooLong(String),
+    #[error("{code} is already in this run for {period}")]
+    Duplicate { code: String, period: String },
+}
+
+/// Hands out the ids for one run. Everything is derived from the run id, the
+/// relation code and the invoice period, so the same input gives the same
+/// ids.
+#[derive(Debug, Clone)]
+pub struct Run {
+    id: RunId,
+    date: Date,
+    /// Short form of our name in front of message and payment information ids
+    prefix: String,
+    previous: HashMap<(String, String), IssuedId>,
+    issued: Vec<IssuedId>,
+    payments: Vec<Payment>,
+}
+
+impl Run {
+    pub(crate) fn new(company_name: &str, registry: &IdRegistry, date: Date) -> Self {
+        let prefix = company_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .take(10)
+            .collect::<String>()
+            .to_uppercase();
+        // the first id for every code and period, later ones link back to it
+        let mut previous = HashMap::new();
+        for i in registry.issued.iter().filter(|i| i.original.is_none()) {
+            previous
+                .entry((i.code.clone(), i.period.clone()))
+                .or_insert_with(|| i.clone());
+        }
+        Self {
+            id: registry.next_run_id(date),
+            date,
+            prefix,
+            previous,
+            issued: vec![],
+            payments: vec![],
+        }
+    }
+
+    pub fn id(&self) -> &RunId {
+        &self.id
+    }
+
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// The ids this run handed out for relations and periods an earlier run
+    /// already invoiced
+    pub fn reissued(&self) -> impl Iterator<Item = &IssuedId> {
+        self.issued.iter().filter(|i| i.original.is_some())
+    }
+
+    pub fn payments(&self) -> &[Payment] {
+        &self.payments
+    }
+
+    /// Who the end-to-end id was handed out for
+    pub(crate) fn issued_for(&self, end_to_end_id: &str) -> Option<&IssuedId> {
+        self.issued.iter().rfind(|i| i.id == end_to_end_id)
+    }
+
+    pub(crate) fn expect(&mut self, payment: Payment) {
+        self.payments.push(payment);
+    }
+
+    /// Remembers the block the payment with `end_to_end_id` is sent in
+    pub(crate) fn place(&mut self, end_to_end_id: &str, payment_information_id: &str) {
+        if let Some(p) = self
+            .payments
+            .iter_mut()
+            .rfind(|p| p.end_to_end_id == end_to_end_id)
+        {
+            p.payment_information_id = Some(payment_information_id.to_string());
+        }
+    }
+
+    /// `kind` tells the files of a run apart, `DD` or `CT`
+    pub fn message_id(&self, kind: &str) -> String {
+        format!("{}-{}-{}", self.prefix, self.id, kind)
+    }
+
+    /// `suffix` tells the blocks of a run apart, like the sequence type
+    pub fn payment_information_id(&self, suffix: &str) -> String {
+        format!("{}-{}-{}", self.prefix, self.id, suffix)
+    }
+
+    /// The id for invoicing `code` over `period`, which is whatever the caller
+    /// uses to tell invoices apart, like `202403` or `C2024`. Ids longer than
+    /// 35 characters and a second id for the same code and period are errors,
+    /// as the bank could not tell the payments apart. Invoicing the same code
+    /// and period as an earlier run gives a new id, linked to the first one.
+    pub fn end_to_end_id(
+        &mut self,
+        code: &str,
+        period: &str,
+    ) -> Result<EndToEndId, EndToEndIdError> {
+        if self
+            .issued
+            .iter()
+            .any(|i| i.code == code && i.period == period)
+        {
+            return Err(EndToEndIdError::Duplicate {
+                code: code.to_string(),
+                period: period.to_string(),
+            });
+        }
+        let id = format!("{}-{}-{}", self.id, period, code);
+        if id.chars().count() > MAX_ID {
+            return Err(EndToEndIdError::TooLong(id));
+        }
+        let key = (code.to_string(), period.to_string());
+        let first = self.previous.get(&key);
+        self.issued.push(IssuedId {
+            id: id.clone(),
+            run: self.id.clone(),
+            code: code.to_string(),
+            period: period.to_string(),
+            original: first.map(|i| i.id.clone()),
+        });
+        Ok(EndToEndId {
+            id,
+            reissued_from: first.map(|i| i.run.clone()),
+        })
+    }
+}
+
+/// The structured reference for invoicing `code` over `period`, the same
+/// periods as `Run::end_to_end_id`. It stays the same across runs, so a
+/// relation can pay a reissued invoice with the reference of the first one.
+pub fn creditor_reference(
+    code: &str,
+    period: &str,
+) -> Result<CreditorReference, CreditorReferenceError> {
+    CreditorReference::new(&format!("{}{}", period, code))
+}
+
+/// The relation code in a reference made by `creditor_reference`, `None`
+/// for any other reference: one that merely starts with digits could be
+/// anyone's
+pub fn code_from_reference(reference: &CreditorReference) -> Option<&str> {
+    let reference = reference.reference();
+    // `C2024` and `A2024` for contributions, `202403` for balances
+    let (year, month) = match reference.as_bytes().first()? {
+        b'C' | b'A' => (reference.get(1..5)?, None),
+        _ => (reference.get(..4)?, Some(reference.get(4..6)?)),
+    };
+    let is_year = year.starts_with("20") && year.bytes().all(|b| b.is_ascii_digit());
+    let is_month = month.is_none_or(|m| {
+        m.bytes().all(|b| b.is_ascii_digit()) && (1..=12).contains(&m.parse::<u8>().unwrap_or(0))
+    });
+    let period = if month.is_some() { 6 } else { 5 };
+    (is_year && is_month && reference.len() > period).then(|| &reference[period..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{config, date};
+
+    #[test]
+    fn ids_come_from_the_run() {
+        let run = config().start_run(&IdRegistry::default(), date(2024, 3, 1));
+        assert_eq!(run.id().as_str(), "20240301-1");
+        assert_eq!(run.message_id("DD"), "AEGEEDELFT-20240301-1-DD");
+        assert_eq!(
+            run.payment_information_id("FRST"),
+            "AEGEEDELFT-20240301-1-FRST"
+        );
+    }
+
+    #[test]
+    fn end_to_end_ids_are_reproducible() {
+        let registry = IdRegistry::default();
+        let mut run = config().start_run(&registry, date(2024, 3, 1));
+        let first = run.end_to_end_id("1001", "202403").unwrap();
+        assert_eq!(
+            first,
+            EndToEndId {
+                id: "20240301-1-202403-1001".to_string(),
+                reissued_from: None
+            }
+        );
+        // making the same run again gives the same ids
+        let mut again = config().start_run(&registry, date(2024, 3, 1));
+        assert_eq!(again.end_to_end_id("1001", "202403"), Ok(first));
+    }
+
+    #[test]
+    fn long_end_to_end_ids_are_refused() {
+        let mut run = config().start_run(&IdRegistry::default(), date(2024, 3, 1));
+        // cutting it off could give two long codes the same id
+        assert_eq!(
+            run.end_to_end_id(&"9".repeat(20), "202403"),
+            Err(EndToEndIdError::TooLong(format!(
+                "20240301-1-202403-{}",
+                "9".repeat(20)
+            )))
+        );
+    }
+
+    #[test]
+    fn a_second_id_for_the_same_code_and_period_is_refused() {
+        let mut run = config().start_run(&IdRegistry::default(), date(2024, 3, 1));
+        run.end_to_end_id("1001", "202403").unwrap();
+        assert_eq!(
+            run.end_to_end_id("1001", "202403"),
+            Err(EndToEndIdError::Duplicate {
+                code: "1001".to_string(),
+                period: "202403".to_string()
+            })
+        );
+        assert!(run.end_to_end_id("1001", "202404").is_ok());
+    }
+
+    #[test]
+    fn the_registry_numbers_runs_and_survives_saving() {
+        let mut registry = IdRegistry::default();
+        let mut run = config().start_run(&registry, date(2024, 3, 1));
+        let id = run.end_to_end_id("1001", "202403").unwrap().id;
+        registry.record(&run);
+
+        let path =
+            std::env::temp_dir().join(format!("penning-helper-ids-{}.json", std::process::id()));
+        registry.save(&path).unwrap();
+        let registry = IdRegistry::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(registry.find(&id).unwrap().code, "1001");
+        let second = config().start_run(&registry, date(2024, 3, 1));
+        assert_eq!(second.id().as_str(), "20240301-2");
+    }
+
+    #[test]
+    fn a_later_run_issues_a_new_id_for_the_same_period() {
+        let mut registry = IdRegistry::default();
+        let mut run = config().start_run(&registry, date(2024, 3, 1));
+        let first = run.end_to_end_id("1001", "202403").unwrap();
+        registry.record(&run);
+
+        let mut second = config().start_run(&registry, date(2024, 3, 1));
+        let reissued = second.end_to_end_id("1001", "202403").unwrap();
+        assert_eq!(reissued.id, "20240301-2-202403-1001");
+        assert_eq!(reissued.reissued_from, Some(run.id().clone()));
+        assert_eq!(
+            second
+                .end_to_end_id("1001", "202405")
+                .unwrap()
+                .reissued_from,
+            None
+        );
+        assert!(second.end_to_end_id("1001", "202403").is_err());
+        assert_eq!(
+            second
+                .reissued()
+                .map(|i| i.code.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1001"]
+        );
+
+        // a third run still links back to the first payment
+        registry.record(&second);
+        assert_eq!(
+            registry.find(&reissued.id).unwrap().original,
+            Some(first.id.clone())
+        );
+        let mut third = config().start_run(&registry, date(2024, 3, 2));
+        let again = third.end_to_end_id("1001", "202403").unwrap();
+        assert_eq!(again.reissued_from, Some(run.id().clone()));
+        assert_eq!(
+            third.reissued().next().unwrap().original,
+            Some(first.id.clone())
+        );
+    }
+
+    #[test]
+    fn creditor_references_round_trip() {
+        let balance = creditor_reference("1002", "202403").unwrap();
+        assert_eq!(balance.as_str(), "RF312024031002");
+        assert_eq!(code_from_reference(&balance), Some("1002"));
+        let contribution = creditor_reference("1002", "C2024").unwrap();
+        assert_eq!(code_from_reference(&contribution), Some("1002"));
+    }
+
+    #[test]
+    fn references_from_elsewhere_give_no_code() {
+        assert_eq!(
+            code_from_reference(&"RF18539007547034".parse().unwrap()),
+            None
+        );
+        // a period, but nothing after it
+        let bare = CreditorReference::new("202403").unwrap();
+        assert_eq!(code_from_reference(&bare), None);
+        let month = CreditorReference::new("2024131002").unwrap();
+        assert_eq!(code_from_reference(&month), None);
+        assert!(creditor_reference("10-02", "202403").is_err());
+    }
+}
@@ -1,32 +1,214 @@
 use std::io::Write;
 
-use penning_helper_types::{Date, Euro};
-use xml::EventWriter;
+use penning_helper_types::{Bic, CreditorReference, Date, Euro, Iban};
+use xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig, EventWriter};
 
-use crate::ToXml;
+use crate::{
+    sanitise::{Sanitised, Sanitiser},
+    schema::SchemaVersion,
+    validate::{Diagnostic, Problem, Validator, MAX_NAME, MAX_REMITTANCE},
+    Account, Amendment, Category, Instruction, Mandate, SequenceType, ToXml,
+};
 
 use self::invoice_gen::DocumentString;
 
 mod invoice_gen;
 
 pub struct Document {
+    version: SchemaVersion,
     header: Header,
     payment_information: Vec<PaymentInformation>,
 }
 impl Document {
-    pub(crate) fn new(header: Header, payment_information: Vec<PaymentInformation>) -> Document {
+    pub(crate) fn new(
+        version: SchemaVersion,
+        header: Header,
+        payment_information: Vec<PaymentInformation>,
+    ) -> Document {
         Document {
+            version,
             header,
             payment_information,
         }
     }
 
+    pub fn version(&self) -> SchemaVersion {
+        self.version
+    }
+
+    pub fn message_id(&self) -> &str {
+        &self.header.message_id
+    }
+
+    pub fn payment_information(&self) -> &[PaymentInformation] {
+        &self.payment_information
+    }
+
+    /// Every debtor with the date of its block, to [`diff`](crate::diff)
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.payment_information
+            .iter()
+            .flat_map(|p| {
+                p.debtors.iter().map(|d| Instruction {
+                    end_to_end_id: d.invoice_id.clone(),
+                    name: d.name.clone(),
+                    iban: d.mandate.debtor_iban.clone(),
+                    amount: d.amount,
+                    date: p.collection_date,
+                    description: match &d.reference {
+                        Some(r) => r.to_string(),
+                        None => d.description.clone(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Takes the debtor with `end_to_end_id` out, and its block when that
+    /// was the last one in it. The totals are counted again.
+    pub fn remove_debtor(&mut self, end_to_end_id: &str) -> Option<Debtor> {
+        let (block, idx) = self
+            .payment_information
+            .iter()
+            .enumerate()
+            .find_map(|(b, p)| {
+                let idx = p
+                    .debtors
+                    .iter()
+                    .position(|d| d.invoice_id == end_to_end_id)?;
+                Some((b, idx))
+            })?;
+        let debtor = self.payment_information[block].debtors.remove(idx);
+        if self.payment_information[block].debtors.is_empty() {
+            self.payment_information.remove(block);
+        }
+        self.update_totals();
+        Some(debtor)
+    }
+
+    /// Adds the debtors of `other`. A block of `other` joins the block with
+    /// the same id, or else the one with the same collection, or becomes a
+    /// block of its own. The header stays ours, with the totals counted again.
+    pub fn merge(&mut self, other: Document) {
+        for block in other.payment_information {
+            let blocks = &self.payment_information;
+            let same = blocks
+                .iter()
+                .position(|p| p.payment_information_id == block.payment_information_id)
+                .or_else(|| blocks.iter().position(|p| p.same_collection(&block)));
+            match same {
+                Some(i) => self.payment_information[i].debtors.extend(block.debtors),
+                None => self.payment_information.push(block),
+            }
+        }
+        self.update_totals();
+    }
+
+    fn update_totals(&mut self) {
+        for p in &mut self.payment_information {
+            p.num_transactions = p.debtors.len() as u32;
+            p.control_sum = p.debtors.iter().map(|d| d.amount).sum();
+        }
+        let header = &mut self.header;
+        header.number_of_transactions = self
+            .payment_information
+            .iter()
+            .map(|p| p.num_transactions)
+            .sum();
+        header.control_sum = self.payment_information.iter().map(|p| p.control_sum).sum();
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_xml_doc(self) -> DocumentString {
         DocumentString::from(self)
     }
 
+    /// Brings names and descriptions into the SEPA character set and lengths,
+    /// returning what was changed
+    pub fn sanitise(&mut self) -> Vec<Sanitised> {
+        let mut s = Sanitiser::default();
+        s.field("GrpHdr", "InitgPty/Nm", &mut self.header.name, MAX_NAME);
+        for (idx, p) in self.payment_information.iter_mut().enumerate() {
+            let location = format!("PmtInf {}", idx + 1);
+            s.field(&location, "Cdtr/Nm", &mut p.creditor_name, MAX_NAME);
+            for d in &mut p.debtors {
+                let location = format!("{} / {}", location, d.name);
+                s.field(&location, "Dbtr/Nm", &mut d.name, MAX_NAME);
+                s.field(&location, "RmtInf/Ustrd", &mut d.description, MAX_REMITTANCE);
+            }
+        }
+        s.finish()
+    }
+
+    /// Checks the rules of the schema and the EPC guidelines that the types
+    /// don't already enforce. Nothing is wrong when this is empty.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut v = Validator::default();
+        let header = &self.header;
+        let created = v.creation_date(&header.creation_date_time);
+        v.id("GrpHdr", "MsgId", &header.message_id);
+        v.text("GrpHdr", "InitgPty/Nm", &header.name, MAX_NAME);
+        v.id("GrpHdr", "InitgPty/Id", &header.id);
+        v.totals(
+            "GrpHdr",
+            (header.number_of_transactions, header.control_sum),
+            self.payment_information
+                .iter()
+                .flat_map(|p| p.debtors.iter().map(|d| d.amount)),
+        );
+
+        for (idx, p) in self.payment_information.iter().enumerate() {
+            let location = format!("PmtInf {}", idx + 1);
+            v.unique_id(&location, "PmtInfId", &p.payment_information_id);
+            v.text(&location, "Cdtr/Nm", &p.creditor_name, MAX_NAME);
+            v.id(&location, "CdtrSchmeId", &p.creditor_id);
+            v.date(&location, "ReqdColltnDt", p.collection_date, created, true);
+            v.totals(
+                &location,
+                (p.num_transactions, p.control_sum),
+                p.debtors.iter().map(|d| d.amount),
+            );
+
+            for d in &p.debtors {
+                let location = format!("{} / {}", location, d.name);
+                v.unique_id(&location, "EndToEndId", &d.invoice_id);
+                v.amount(&location, "InstdAmt", d.amount);
+                v.id(&location, "MndtId", &d.mandate.id);
+                if d.mandate.signature_date > p.collection_date {
+                    let problem = Problem::SignatureAfterCollection {
+                        signed: d.mandate.signature_date,
+                        collection: p.collection_date,
+                    };
+                    v.push(&location, "DtOfSgntr", problem);
+                }
+                if let Some(amendment) = &d.amendment {
+                    if let Some(id) = &amendment.original_mandate_id {
+                        v.id(&location, "OrgnlMndtId", id);
+                    }
+                    if let Some(id) = &amendment.original_creditor_id {
+                        v.id(&location, "OrgnlCdtrSchmeId", id);
+                    }
+                }
+                v.text(&location, "Dbtr/Nm", &d.name, MAX_NAME);
+                // the description isn't sent when there is a creditor reference
+                if d.reference.is_none() {
+                    v.text(&location, "RmtInf/Ustrd", &d.description, MAX_REMITTANCE);
+                }
+            }
+        }
+        v.finish()
+    }
+
     pub fn write<W: Write>(self, writer: W) -> xml::writer::Result<()> {
-        let mut writer = EventWriter::new(writer);
+        self.write_with_config(writer, EmitterConfig::new())
+    }
+
+    pub(crate) fn write_with_config<W: Write>(
+        self,
+        writer: W,
+        config: EmitterConfig,
+    ) -> xml::writer::Result<()> {
+        let mut writer = EventWriter::new_with_config(writer, config);
         self.write_xml(&mut writer)?;
         writer.inner_mut().flush()?;
         Ok(())
@@ -34,6 +216,12 @@ impl Document {
 
     fn write_xml<W: Write>(self, writer: &mut EventWriter<W>) -> xml::writer::Result<()> {
         let doc = self.to_xml_doc();
+        // older xml-rs versions spell the encoding in lower case
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("UTF-8"),
+            standalone: None,
+        })?;
         let xml = doc.to_xml();
         for event in xml {
             writer.write(event)?;
@@ -43,7 +231,7 @@ impl Document {
 }
 
 pub struct Header {
-    /// date-randomhash
+    /// AEGEEDELFT-20240301-1-DD
     message_id: String,
     creation_date_time: String,
     number_of_transactions: u32,
@@ -76,8 +264,9 @@ impl Header {
 pub struct PaymentInformation {
     payment_information_id: String,
     creditor_name: String,
-    creditor_iban: String,
-    creditor_bic: String,
+    creditor_iban: Iban,
+    creditor_bic: Bic,
+    sequence_type: SequenceType,
     collection_date: Date,
     control_sum: Euro,
     num_transactions: u32,
@@ -86,22 +275,24 @@ pub struct PaymentInformation {
 }
 
 impl PaymentInformation {
+    /// `totals` are the number of transactions and their sum, which a parsed
+    /// file states itself
     pub(crate) fn new(
         payment_information_id: String,
-        creditor_name: String,
-        creditor_iban: String,
-        creditor_bic: String,
-        collection_date: Date,
-        control_sum: Euro,
-        num_transactions: u32,
+        creditor: Account,
         creditor_id: String,
+        sequence_type: SequenceType,
+        collection_date: Date,
+        totals: (u32, Euro),
         debtors: Vec<Debtor>,
     ) -> Self {
+        let (num_transactions, control_sum) = totals;
         Self {
             payment_information_id,
-            creditor_name,
-            creditor_iban,
-            creditor_bic,
+            creditor_name: creditor.name,
+            creditor_iban: creditor.iban,
+            creditor_bic: creditor.bic,
+            sequence_type,
             collection_date,
             control_sum,
             num_transactions,
@@ -110,6 +301,10 @@ impl PaymentInformation {
         }
     }
 
+    pub fn payment_information_id(&self) -> &str {
+        &self.payment_information_id
+    }
+
     pub fn control_sum(&self) -> Euro {
         self.control_sum
     }
@@ -117,6 +312,46 @@ impl PaymentInformation {
     pub fn num_transactions(&self) -> u32 {
         self.num_transactions
     }
+
+    pub fn sequence_type(&self) -> SequenceType {
+        self.sequence_type
+    }
+
+    pub fn collection_date(&self) -> Date {
+        self.collection_date
+    }
+
+    pub fn debtors(&self) -> &[Debtor] {
+        &self.debtors
+    }
+
+    /// Collected on the same day, with the same sequence type and category,
+    /// for the same creditor
+    fn same_collection(&self, other: &PaymentInformation) -> bool {
+        let category = |p: &PaymentInformation| p.debtors.first().map(|d| d.category);
+        self.collection_date == other.collection_date
+            && self.sequence_type == other.sequence_type
+            && self.creditor_iban == other.creditor_iban
+            && self.creditor_id == other.creditor_id
+            && category(self) == category(other)
+    }
+}
+
+/// Who to collect from and what for, see [`crate::SEPAConfig::new_debtor`]
+#[derive(Debug, Clone)]
+pub struct DebtorDetails {
+    /// The relation code, which the mandate ledger tracks
+    pub code: String,
+    pub name: String,
+    pub amount: Euro,
+    /// Looked up from the IBAN of the mandate when missing
+    pub bic: Option<Bic>,
+    pub mandate: Mandate,
+    pub amendment: Option<Amendment>,
+    pub sequence_type: SequenceType,
+    pub description: String,
+    /// Sent as structured remittance instead of the description
+    pub reference: Option<CreditorReference>,
 }
 
 #[derive(Debug)]
@@ -124,37 +359,220 @@ pub struct Debtor {
     invoice_id: String,
     amount: Euro,
     name: String,
-    bic: String,
-    iban: String,
+    bic: Option<Bic>,
+    bic_derived: bool,
+    /// The relation code, which the mandate ledger tracks
     code: String,
-    membership_date: Date,
+    mandate: Mandate,
+    amendment: Option<Amendment>,
+    sequence_type: SequenceType,
     description: String,
+    reference: Option<CreditorReference>,
+    category: Category,
 }
 
 impl Debtor {
-    pub(crate) fn new(
-        invoice_id: String,
-        amount: Euro,
-        name: String,
-        bic: String,
-        iban: String,
-        code: String,
-        membership_date: Date,
-        description: String,
-    ) -> Self {
+    pub(crate) fn new(invoice_id: String, details: DebtorDetails, bic_derived: bool) -> Self {
         Self {
             invoice_id,
-            amount,
-            name,
-            bic,
-            iban,
-            code,
-            membership_date,
-            description,
+            amount: details.amount,
+            name: details.name,
+            bic: details.bic,
+            bic_derived,
+            code: details.code,
+            mandate: details.mandate,
+            amendment: details.amendment,
+            sequence_type: details.sequence_type,
+            description: details.description,
+            reference: details.reference,
+            category: Category::default(),
         }
     }
 
+    /// Puts the debtor in the payment information block of `category`
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
     pub fn amount(&self) -> Euro {
         self.amount
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the BIC was looked up from the IBAN instead of given
+    pub fn bic_derived(&self) -> bool {
+        self.bic_derived
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn mandate(&self) -> &Mandate {
+        &self.mandate
+    }
+
+    pub fn amendment(&self) -> Option<&Amendment> {
+        self.amendment.as_ref()
+    }
+
+    pub fn sequence_type(&self) -> SequenceType {
+        self.sequence_type
+    }
+
+    pub fn creditor_reference(&self) -> Option<&CreditorReference> {
+        self.reference.as_ref()
+    }
+
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    pub(crate) fn end_to_end_id(&self) -> &str {
+        &self.invoice_id
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use xml::EmitterConfig;
+
+    use super::*;
+    use crate::{
+        creditor_reference,
+        testing::{assert_golden, bic, date, iban},
+        DebtorDetails, OriginalDebtorAccount,
+    };
+
+    fn our_account() -> Account {
+        Account {
+            name: "AEGEE-Delft".to_string(),
+            iban: iban("NL44RABO0123456789"),
+            bic: bic("RABONL2U"),
+        }
+    }
+
+    /// The document in `tests/golden`. The newer versions don't need a BIC for
+    /// the other party, so the second member only has one in the old version.
+    pub(crate) fn document(version: SchemaVersion) -> Document {
+        let second_bic = match version {
+            SchemaVersion::V2009 => Some(bic("COBADEFFXXX")),
+            SchemaVersion::V2019 => None,
+        };
+        let jan = Debtor::new(
+            "AEGEEDELFT-0000000000000001".to_string(),
+            DebtorDetails {
+                code: "1001".to_string(),
+                name: "Jan Jansen".to_string(),
+                amount: Euro::new(12, 50),
+                bic: Some(bic("ABNANL2A")),
+                mandate: Mandate::new(
+                    "1001",
+                    date(2023, 9, 1),
+                    iban("NL91ABNA0417164300"),
+                    "NL00ZZZ404840000000",
+                ),
+                amendment: Some(Amendment {
+                    original_mandate_id: Some("AEGEE-1001".to_string()),
+                    original_creditor_id: None,
+                    original_debtor_account: Some(OriginalDebtorAccount::Iban(iban(
+                        "NL02ABNA0123456789",
+                    ))),
+                }),
+                sequence_type: SequenceType::Recurring,
+                description: "Contributie".to_string(),
+                reference: None,
+            },
+            false,
+        );
+        let erika = Debtor::new(
+            "AEGEEDELFT-0000000000000002".to_string(),
+            DebtorDetails {
+                code: "1002".to_string(),
+                name: "Erika Mustermann".to_string(),
+                amount: Euro::new(7, 5),
+                bic: second_bic,
+                mandate: Mandate::new(
+                    "1002",
+                    date(2022, 2, 14),
+                    iban("DE89370400440532013000"),
+                    "NL00ZZZ404840000000",
+                ),
+                amendment: None,
+                sequence_type: SequenceType::First,
+                description: "Invoice of open AEGEE-Delft balance".to_string(),
+                reference: Some(creditor_reference("1002", "202403").unwrap()),
+            },
+            false,
+        );
+        let recurring = PaymentInformation::new(
+            "AEGEEDELFT-00000000000000aa".to_string(),
+            our_account(),
+            "NL00ZZZ404840000000".to_string(),
+            SequenceType::Recurring,
+            date(2024, 3, 4),
+            (1, Euro::new(12, 50)),
+            vec![jan],
+        );
+        let first = PaymentInformation::new(
+            "AEGEEDELFT-00000000000000ab".to_string(),
+            our_account(),
+            "NL00ZZZ404840000000".to_string(),
+            SequenceType::First,
+            date(2024, 3, 7),
+            (1, Euro::new(7, 5)),
+            vec![erika],
+        );
+        let header = Header::new(
+            "20240301-00000000000000bb".to_string(),
+            "2024-03-01T12:00:00".to_string(),
+            2,
+            Euro::new(19, 55),
+            "AEGEE-Delft".to_string(),
+            "NL00ZZZ404840000000".to_string(),
+        );
+        Document::new(version, header, vec![first, recurring])
+    }
+
+    #[test]
+    fn pain_008_001_02() {
+        assert_golden("pain.008.001.02.xml", |w, c| {
+            document(SchemaVersion::V2009)
+                .write_with_config(w, c)
+                .unwrap()
+        });
+    }
+
+    #[test]
+    fn pain_008_001_08() {
+        assert_golden("pain.008.001.08.xml", |w, c| {
+            document(SchemaVersion::V2019)
+                .write_with_config(w, c)
+                .unwrap()
+        });
+    }
+
+    #[test]
+    fn golden_documents_validate() {
+        for version in SchemaVersion::ALL {
+            assert_eq!(document(version).validate(), vec![]);
+        }
+    }
+
+    #[test]
+    fn creditor_reference_replaces_the_unstructured_remittance() {
+        let mut out = vec![];
+        document(SchemaVersion::V2019)
+            .write_with_config(&mut out, EmitterConfig::new())
+            .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert_eq!(xml.matches("<Ustrd>").count(), 1);
+        assert!(
+            xml.contains("<Cd>SCOR</Cd></CdOrPrtry><Issr>ISO</Issr></Tp><Ref>RF312024031002</Ref>")
+        );
+    }
 }
@@ -1,17 +1,21 @@
 use xml::writer::XmlEvent;
 
-use crate::ToXml;
+use crate::{
+    schema::{financial_institution, remittance_information, SchemaVersion, SchemaVersionExt},
+    OriginalDebtorAccount, ToXml,
+};
 
 pub struct DocumentString {
+    pub(super) version: SchemaVersion,
     pub(super) header: HeaderString,
     pub(super) payment_information: Vec<PaymentInformationString>,
 }
 
 impl ToXml for DocumentString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         let mut v = vec![
             XmlEvent::start_element("Document")
-                .default_ns("urn:iso:std:iso:20022:tech:xsd:pain.008.001.02")
+                .default_ns(self.version.direct_debit_namespace())
                 .ns("xsi", "http://www.w3.org/2001/XMLSchema-instance")
                 .into(),
             XmlEvent::start_element("CstmrDrctDbtInitn").into(),
@@ -27,19 +31,21 @@ impl ToXml for DocumentString {
 
 impl From<super::Document> for DocumentString {
     fn from(value: super::Document) -> Self {
+        let version = value.version;
         Self {
+            version,
             header: value.header.into(),
             payment_information: value
                 .payment_information
                 .into_iter()
-                .map(|p| p.into())
+                .map(|p| PaymentInformationString::new(p, version))
                 .collect(),
         }
     }
 }
 
 pub struct HeaderString {
-    /// date-randomhash
+    /// AEGEEDELFT-20240301-1-DD
     pub(super) message_id: String,
     pub(super) creation_date_time: String,
     pub(super) number_of_transactions: String,
@@ -49,30 +55,30 @@ pub struct HeaderString {
 }
 
 impl ToXml for HeaderString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         vec![
             XmlEvent::start_element("GrpHdr").into(),
             XmlEvent::start_element("MsgId").into(),
-            XmlEvent::characters(&self.message_id).into(),
+            XmlEvent::characters(&self.message_id),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CreDtTm").into(),
-            XmlEvent::characters(&self.creation_date_time).into(),
+            XmlEvent::characters(&self.creation_date_time),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("NbOfTxs").into(),
-            XmlEvent::characters(&self.number_of_transactions).into(),
+            XmlEvent::characters(&self.number_of_transactions),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CtrlSum").into(),
-            XmlEvent::characters(&self.control_sum).into(),
+            XmlEvent::characters(&self.control_sum),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("InitgPty").into(),
             XmlEvent::start_element("Nm").into(),
-            XmlEvent::characters(&self.name).into(),
+            XmlEvent::characters(&self.name),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("Id").into(),
             XmlEvent::start_element("OrgId").into(),
             XmlEvent::start_element("Othr").into(),
             XmlEvent::start_element("Id").into(),
-            XmlEvent::characters(&self.id).into(),
+            XmlEvent::characters(&self.id),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
@@ -97,6 +103,7 @@ impl From<super::Header> for HeaderString {
 }
 
 pub struct PaymentInformationString {
+    pub(super) version: SchemaVersion,
     pub(super) payment_information_id: String,
     /// AEGEE-Delft
     pub(super) creditor_name: String,
@@ -104,7 +111,9 @@ pub struct PaymentInformationString {
     pub(super) creditor_iban: String,
     /// AEGEE-Delft BIC
     pub(super) creditor_bic: String,
-    /// today + 3 days
+    /// FRST, RCUR, OOFF or FNAL
+    pub(super) sequence_type: &'static str,
+    /// today + lead time of the sequence type
     pub(super) collection_date: String,
     /// Total amount of all invoices
     pub(super) control_sum: String,
@@ -115,81 +124,79 @@ pub struct PaymentInformationString {
 }
 
 impl ToXml for PaymentInformationString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         let mut xml = vec![
             XmlEvent::start_element("PmtInf").into(),
             XmlEvent::start_element("PmtInfId").into(),
-            XmlEvent::characters(&self.payment_information_id).into(),
+            XmlEvent::characters(&self.payment_information_id),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("PmtMtd").into(),
-            XmlEvent::characters("DD").into(),
+            XmlEvent::characters("DD"),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("BtchBookg").into(),
-            XmlEvent::characters("true").into(),
+            XmlEvent::characters("true"),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("NbOfTxs").into(),
-            XmlEvent::characters(&self.num_transactions).into(),
+            XmlEvent::characters(&self.num_transactions),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CtrlSum").into(),
-            XmlEvent::characters(&self.control_sum).into(),
+            XmlEvent::characters(&self.control_sum),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("PmtTpInf").into(),
             XmlEvent::start_element("SvcLvl").into(),
             XmlEvent::start_element("Cd").into(),
-            XmlEvent::characters("SEPA").into(),
+            XmlEvent::characters("SEPA"),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("LclInstrm").into(),
             XmlEvent::start_element("Cd").into(),
-            XmlEvent::characters("CORE").into(),
+            XmlEvent::characters("CORE"),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("SeqTp").into(),
-            XmlEvent::characters("RCUR").into(),
+            XmlEvent::characters(self.sequence_type),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("ReqdColltnDt").into(),
-            XmlEvent::characters(&self.collection_date).into(),
+            XmlEvent::characters(&self.collection_date),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("Cdtr").into(),
             XmlEvent::start_element("Nm").into(),
-            XmlEvent::characters(&self.creditor_name).into(),
+            XmlEvent::characters(&self.creditor_name),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CdtrAcct").into(),
             XmlEvent::start_element("Id").into(),
             XmlEvent::start_element("IBAN").into(),
-            XmlEvent::characters(&self.creditor_iban).into(),
+            XmlEvent::characters(&self.creditor_iban),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CdtrAgt").into(),
-            XmlEvent::start_element("FinInstnId").into(),
-            XmlEvent::start_element("BIC").into(),
-            XmlEvent::characters(&self.creditor_bic).into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
+        ];
+        xml.extend(financial_institution(Some(self.creditor_bic.as_str()), self.version));
+        xml.extend([
             XmlEvent::end_element().into(),
             XmlEvent::start_element("ChrgBr").into(),
-            XmlEvent::characters("SLEV").into(),
+            XmlEvent::characters("SLEV"),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("CdtrSchmeId").into(),
             XmlEvent::start_element("Id").into(),
             XmlEvent::start_element("PrvtId").into(),
             XmlEvent::start_element("Othr").into(),
             XmlEvent::start_element("Id").into(),
-            XmlEvent::characters(&self.creditor_id).into(),
+            XmlEvent::characters(&self.creditor_id),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("SchmeNm").into(),
             XmlEvent::start_element("Prtry").into(),
-            XmlEvent::characters("SEPA").into(),
+            XmlEvent::characters("SEPA"),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
-        ];
+        ]);
 
         xml.extend(self.debtors.iter().flat_map(|d| d.to_xml()));
         // xml.push(XmlEvent::end_element().into());
@@ -198,101 +205,203 @@ impl ToXml for PaymentInformationString {
     }
 }
 
-impl From<super::PaymentInformation> for PaymentInformationString {
-    fn from(value: super::PaymentInformation) -> Self {
+impl PaymentInformationString {
+    fn new(value: super::PaymentInformation, version: SchemaVersion) -> Self {
         Self {
+            version,
             payment_information_id: value.payment_information_id,
             creditor_name: value.creditor_name,
-            creditor_iban: value.creditor_iban,
-            creditor_bic: value.creditor_bic,
+            creditor_iban: value.creditor_iban.to_string(),
+            creditor_bic: value.creditor_bic.to_string(),
+            sequence_type: value.sequence_type.code(),
             collection_date: value.collection_date.to_string(),
             control_sum: value.control_sum.xml_string(),
             num_transactions: value.num_transactions.to_string(),
             creditor_id: value.creditor_id,
-            debtors: value.debtors.into_iter().map(|d| d.into()).collect(),
+            debtors: value
+                .debtors
+                .into_iter()
+                .map(|d| DebtorString::new(d, version))
+                .collect(),
         }
     }
 }
 
 pub struct DebtorString {
-    /// AEGEEDELFT-random-hash
+    pub(super) version: SchemaVersion,
+    /// 20240301-1-202403-1001, see `Run::end_to_end_id`
     pub(super) invoice_id: String,
     pub(super) amount: String,
     pub(super) name: String,
-    pub(super) bic: String,
+    pub(super) bic: Option<String>,
     pub(super) iban: String,
-    /// Member code
     pub(super) mandate_id: String,
-    /// Date membership started
     pub(super) mandate_date: String,
+    pub(super) amendment: Option<AmendmentString>,
     pub(super) description: String,
+    pub(super) reference: Option<String>,
 }
 
 impl ToXml for DebtorString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
-        vec![
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
+        let mut xml = vec![
             XmlEvent::start_element("DrctDbtTxInf").into(),
             XmlEvent::start_element("PmtId").into(),
             XmlEvent::start_element("EndToEndId").into(),
-            XmlEvent::characters(&self.invoice_id).into(),
+            XmlEvent::characters(&self.invoice_id),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("InstdAmt")
                 .attr("Ccy", "EUR")
                 .into(),
-            XmlEvent::characters(&self.amount).into(),
+            XmlEvent::characters(&self.amount),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("DrctDbtTx").into(),
             XmlEvent::start_element("MndtRltdInf").into(),
             XmlEvent::start_element("MndtId").into(),
-            XmlEvent::characters(&self.mandate_id).into(),
+            XmlEvent::characters(&self.mandate_id),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("DtOfSgntr").into(),
-            XmlEvent::characters(&self.mandate_date).into(),
+            XmlEvent::characters(&self.mandate_date),
             XmlEvent::end_element().into(),
+        ];
+        if let Some(amendment) = &self.amendment {
+            xml.extend(amendment.to_xml());
+        }
+        xml.extend([
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("DbtrAgt").into(),
-            XmlEvent::start_element("FinInstnId").into(),
-            XmlEvent::start_element("BIC").into(),
-            XmlEvent::characters(&self.bic).into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
+        ]);
+        xml.extend(financial_institution(self.bic.as_deref(), self.version));
+        xml.extend([
             XmlEvent::end_element().into(),
             XmlEvent::start_element("Dbtr").into(),
             XmlEvent::start_element("Nm").into(),
-            XmlEvent::characters(&self.name).into(),
+            XmlEvent::characters(&self.name),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("DbtrAcct").into(),
             XmlEvent::start_element("Id").into(),
             XmlEvent::start_element("IBAN").into(),
-            XmlEvent::characters(&self.iban).into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::start_element("RmtInf").into(),
-            XmlEvent::start_element("Ustrd").into(),
-            XmlEvent::characters(&self.description).into(),
+            XmlEvent::characters(&self.iban),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
-        ]
+        ]);
+        xml.extend(remittance_information(
+            &self.description,
+            self.reference.as_deref(),
+        ));
+        xml.push(XmlEvent::end_element().into());
+        xml
     }
 }
 
-
-impl From<super::Debtor> for DebtorString {
-    fn from(value: super::Debtor) -> Self {
+impl DebtorString {
+    fn new(value: super::Debtor, version: SchemaVersion) -> Self {
         Self {
+            version,
             invoice_id: value.invoice_id,
             amount: value.amount.xml_string(),
             name: value.name,
-            bic: value.bic,
-            iban: value.iban,
-            mandate_id: value.code.to_string(),
-            mandate_date: value.membership_date.to_string(),
+            bic: value.bic.map(|b| b.to_string()),
+            iban: value.mandate.debtor_iban.to_string(),
+            mandate_id: value.mandate.id,
+            mandate_date: value.mandate.signature_date.to_string(),
+            amendment: value.amendment.map(AmendmentString::from),
             description: value.description,
+            reference: value.reference.map(|r| r.to_string()),
+        }
+    }
+}
+
+pub struct AmendmentString {
+    pub(super) original_mandate_id: Option<String>,
+    pub(super) original_creditor_id: Option<String>,
+    /// The old IBAN, or SMNDA when the member moved to another bank
+    pub(super) original_debtor_account: Option<OriginalDebtorAccountString>,
+}
+
+pub enum OriginalDebtorAccountString {
+    Iban(String),
+    OtherBank,
+}
+
+impl ToXml for AmendmentString {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
+        let mut xml = vec![
+            XmlEvent::start_element("AmdmntInd").into(),
+            XmlEvent::characters("true"),
+            XmlEvent::end_element().into(),
+            XmlEvent::start_element("AmdmntInfDtls").into(),
+        ];
+        if let Some(id) = &self.original_mandate_id {
+            xml.extend([
+                XmlEvent::start_element("OrgnlMndtId").into(),
+                XmlEvent::characters(id),
+                XmlEvent::end_element().into(),
+            ]);
+        }
+        if let Some(id) = &self.original_creditor_id {
+            xml.extend([
+                XmlEvent::start_element("OrgnlCdtrSchmeId").into(),
+                XmlEvent::start_element("Id").into(),
+                XmlEvent::start_element("PrvtId").into(),
+                XmlEvent::start_element("Othr").into(),
+                XmlEvent::start_element("Id").into(),
+                XmlEvent::characters(id),
+                XmlEvent::end_element().into(),
+                XmlEvent::start_element("SchmeNm").into(),
+                XmlEvent::start_element("Prtry").into(),
+                XmlEvent::characters("SEPA"),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+            ]);
+        }
+        match &self.original_debtor_account {
+            Some(OriginalDebtorAccountString::Iban(iban)) => xml.extend([
+                XmlEvent::start_element("OrgnlDbtrAcct").into(),
+                XmlEvent::start_element("Id").into(),
+                XmlEvent::start_element("IBAN").into(),
+                XmlEvent::characters(iban),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+            ]),
+            Some(OriginalDebtorAccountString::OtherBank) => xml.extend([
+                XmlEvent::start_element("OrgnlDbtrAcct").into(),
+                XmlEvent::start_element("Id").into(),
+                XmlEvent::start_element("Othr").into(),
+                XmlEvent::start_element("Id").into(),
+                XmlEvent::characters("SMNDA"),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+            ]),
+            None => {}
+        }
+        xml.push(XmlEvent::end_element().into());
+        xml
+    }
+}
+
+impl From<crate::Amendment> for AmendmentString {
+    fn from(value: crate::Amendment) -> Self {
+        Self {
+            original_mandate_id: value.original_mandate_id,
+            original_creditor_id: value.original_creditor_id,
+            original_debtor_account: value.original_debtor_account.map(|a| match a {
+                OriginalDebtorAccount::Iban(iban) => {
+                    OriginalDebtorAccountString::Iban(iban.to_string())
+                }
+                OriginalDebtorAccount::OtherBank => OriginalDebtorAccountString::OtherBank,
+            }),
         }
     }
 }
\ No newline at end of file
@@ -1,185 +1,671 @@
+mod camt;
+mod category;
+mod epc_qr;
+mod generate;
+mod ids;
 mod invoice;
+mod lines;
+mod mandate;
+mod mt940;
+mod pain;
+mod reconcile;
+mod returns;
+mod run_store;
+mod sanitise;
+mod schema;
+mod selection;
+mod sequence;
+mod status;
+mod store;
 mod transfer;
+mod tree;
+mod validate;
+
+#[cfg(test)]
+mod testing;
+
+pub use camt::{parse_statements, CamtError, CreditDebit, Entry, EntryTransaction, Statement};
+pub use category::Category;
+pub use epc_qr::{EpcQr, EpcQrError};
+pub use generate::{GeneratedRun, RunOptions, RunSummary, SaveError, SavedFile};
+pub use ids::{
+    code_from_reference, creditor_reference, EndToEndId, EndToEndIdError, IdRegistry, IssuedId,
+    Run, RunId,
+};
+pub use lines::{suggest, Counterparty, LedgerItem, StatementLine, SuggestedOn, Suggestion};
+pub use mandate::{Amendment, Mandate, OriginalDebtorAccount};
+pub use mt940::{parse_mt940, Mt940Error};
+pub use pain::{diff, parse_credit_transfer, parse_direct_debit, Change, Instruction, PainError};
+pub use reconcile::{
+    reconcile, Direction, MatchedOn, Payment, Reconciliation, Settlement, Unmatched,
+    UnmatchedReason,
+};
+pub use returns::{returns_from, ReturnKind, ReturnLog, ReturnedPayment};
+pub use run_store::{
+    file_hash, DocumentRecord, DoubleCollection, EmailRecord, RecordedPayment, RunStore,
+    RunStoreError,
+};
+pub use sanitise::{sanitise, Sanitised};
+pub use schema::SchemaVersion;
+use schema::SchemaVersionExt;
+pub use selection::{InvoiceRelation, Selection};
+pub use sequence::{
+    LeadTimes, MandateFinished, MandateIdReused, MandateLedger, MandateState, SequenceType,
+    SignedMandate,
+};
+pub use status::{
+    parse_status_report, ReasonCode, RejectedPayment, Rejection, RejectionScope, StatusReport,
+    StatusReportError,
+};
+pub use store::StoreError;
+pub use validate::{is_sepa_char, Diagnostic, Problem, Severity};
+
+pub use invoice::DebtorDetails;
+pub use transfer::CreditorDetails;
 
 use invoice::Debtor;
+use reconcile::TRANSFER_SUFFIX;
 // use invoice_gen::{DebtorString, DocumentString, HeaderString, PaymentInformationString};
 
-use penning_helper_types::{Date, Euro};
-use rand::{thread_rng, Rng};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+
+use penning_helper_types::{
+    Bic, BicDirectory, BicError, CreditorReference, Date, Euro, Iban, IbanError, Lenient,
+};
 use xml::writer::XmlEvent;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SEPAConfigError {
+    #[error("Company IBAN is invalid: {0}")]
+    Iban(#[from] IbanError),
+    #[error("Company BIC is invalid: {0}")]
+    Bic(#[from] BicError),
+}
+
+/// The value of a config field that has to be filled in, or its error
+fn required<T>(value: &Lenient<T>, missing: T::Err) -> Result<T, T::Err>
+where
+    T: FromStr + Clone,
+    T::Err: Clone,
+{
+    match value.get() {
+        None => Err(missing),
+        Some(value) => value.cloned().map_err(Clone::clone),
+    }
+}
+
+/// Returned when no BIC was given and none could be derived from the IBAN
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("No BIC given and none known for {}", .0.pretty())]
+pub struct MissingBic(pub Iban);
+
+/// Why a debtor or creditor could not be added to a run
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum PaymentError {
+    #[error(transparent)]
+    MissingBic(#[from] MissingBic),
+    #[error(transparent)]
+    EndToEndId(#[from] EndToEndIdError),
+}
+
+/// An account of ours that a payment information block collects on or pays
+/// from
+#[derive(Debug, Clone)]
+pub(crate) struct Account {
+    pub(crate) name: String,
+    pub(crate) iban: Iban,
+    pub(crate) bic: Bic,
+}
+
+#[derive(Debug, Clone)]
 pub struct SEPAConfig {
     pub company_name: String,
-    pub company_iban: String,
-    pub company_bic: String,
+    pub company_iban: Iban,
+    pub company_bic: Bic,
     pub company_id: String,
+    pub schema_version: SchemaVersion,
+    pub lead_times: LeadTimes,
+    bic_directory: Option<Arc<BicDirectory>>,
 }
 
 impl SEPAConfig {
     fn new(
         creditor_name: impl ToString,
-        creditor_iban: impl ToString,
-        creditor_bic: impl ToString,
+        creditor_iban: Iban,
+        creditor_bic: Bic,
         creditor_id: impl ToString,
+        schema_version: SchemaVersion,
     ) -> Self {
         Self {
             company_name: creditor_name.to_string(),
-            company_iban: creditor_iban.to_string(),
-            company_bic: creditor_bic.to_string(),
+            company_iban: creditor_iban,
+            company_bic: creditor_bic,
             company_id: creditor_id.to_string(),
+            schema_version,
+            lead_times: LeadTimes::default(),
+            bic_directory: None,
         }
     }
 
-    pub fn from_config(cfg: &penning_helper_config::SEPAConfig) -> Self {
-        Self::new(
+    pub fn with_lead_times(mut self, lead_times: LeadTimes) -> Self {
+        self.lead_times = lead_times;
+        self
+    }
+
+    /// The earliest date a collection of `sequence_type` can ask for when
+    /// the file is made on `created`, skipping weekends and TARGET2 holidays
+    pub fn collection_date(&self, created: Date, sequence_type: SequenceType) -> Date {
+        created.add_business_days(sequence_type.lead_days(&self.lead_times))
+    }
+
+    /// The earliest execution date of transfers in a file made on `created`
+    pub fn execution_date(&self, created: Date) -> Date {
+        created.add_business_days(self.lead_times.transfer)
+    }
+
+    /// A QR code for a relation to pay their open `balance` to us, with the
+    /// creditor reference of the invoice or else `text`
+    pub fn epc_qr(
+        &self,
+        balance: Euro,
+        reference: Option<CreditorReference>,
+        text: &str,
+    ) -> Result<EpcQr, EpcQrError> {
+        EpcQr::new(
+            self.company_bic.clone(),
+            &self.company_name,
+            self.company_iban.clone(),
+            balance,
+            reference,
+            text,
+        )
+    }
+
+    /// Use `directory` instead of the bundled one to fill in missing BICs
+    pub fn with_bic_directory(mut self, directory: Arc<BicDirectory>) -> Self {
+        self.bic_directory = Some(directory);
+        self
+    }
+
+    pub fn bic_directory(&self) -> &BicDirectory {
+        self.bic_directory
+            .as_deref()
+            .unwrap_or_else(|| BicDirectory::bundled())
+    }
+
+    /// The given BIC, or the one derived from the IBAN. The flag tells whether
+    /// it was derived. Without either the BIC is left out if the schema version
+    /// allows it.
+    pub fn resolve_bic(
+        &self,
+        bic: Option<Bic>,
+        iban: &Iban,
+    ) -> Result<(Option<Bic>, bool), MissingBic> {
+        if let Some(bic) = bic {
+            return Ok((Some(bic), false));
+        }
+        match self.bic_directory().lookup(iban) {
+            Some(bic) => Ok((Some(bic), true)),
+            None if self.schema_version.bic_optional() => Ok((None, false)),
+            None => Err(MissingBic(iban.clone())),
+        }
+    }
+
+    pub fn from_config(cfg: &penning_helper_config::SEPAConfig) -> Result<Self, SEPAConfigError> {
+        Ok(Self::new(
             &cfg.company_name,
-            &cfg.company_iban,
-            &cfg.company_bic,
+            required(&cfg.company_iban, IbanError::Empty)?,
+            required(&cfg.company_bic, BicError::Empty)?,
             &cfg.company_id,
+            cfg.schema_version,
         )
+        .with_lead_times(cfg.lead_times))
     }
 
+    /// A new run on `date`, numbered after the ones in `registry`
+    pub fn start_run(&self, registry: &IdRegistry, date: Date) -> Run {
+        Run::new(&self.company_name, registry, date)
+    }
+
+    /// A mandate for `debtor_iban` with our creditor id
+    pub fn new_mandate(&self, id: impl ToString, signature_date: Date, debtor_iban: Iban) -> Mandate {
+        Mandate::new(id, signature_date, debtor_iban, &self.company_id)
+    }
+
+    fn company_account(&self) -> Account {
+        Account {
+            name: self.company_name.clone(),
+            iban: self.company_iban.clone(),
+            bic: self.company_bic.clone(),
+        }
+    }
+
+    /// The end-to-end id comes from `run`, for the code of the debtor and
+    /// `period`. The run keeps the payment to reconcile the bank statements
+    /// with.
     pub fn new_debtor(
         &self,
-        amount: Euro,
-        name: String,
-        bic: String,
-        iban: String,
-        code: String,
-        membership_date: Date,
-        description: String,
-    ) -> Debtor {
-        let id = thread_rng().gen::<u64>();
-        let invoice_id = format!(
-            "{}-{:0>16x}",
-            self.company_name.to_uppercase().replace('-', ""),
-            id
-        );
-        Debtor::new(
-            invoice_id,
-            amount,
-            name,
-            bic,
-            iban,
-            code,
-            membership_date,
-            description,
-        )
+        run: &mut Run,
+        period: &str,
+        mut details: DebtorDetails,
+    ) -> Result<Debtor, PaymentError> {
+        let (bic, bic_derived) =
+            self.resolve_bic(details.bic.take(), &details.mandate.debtor_iban)?;
+        details.bic = bic;
+        let end_to_end_id = run.end_to_end_id(&details.code, period)?.id;
+        run.expect(Payment {
+            run: run.id().clone(),
+            end_to_end_id: end_to_end_id.clone(),
+            code: details.code.clone(),
+            name: details.name.clone(),
+            iban: details.mandate.debtor_iban.clone(),
+            amount: details.amount,
+            direction: Direction::Collection,
+            sequence_type: Some(details.sequence_type),
+            payment_information_id: None,
+            settled: None,
+            rejection: None,
+            reversed: None,
+        });
+        Ok(Debtor::new(end_to_end_id, details, bic_derived))
+    }
+
+    /// Splits `debtors` into one payment information block per collection
+    /// date, sequence type and category, ordered by date. Each type is
+    /// collected on the earliest date its lead time allows after the run. The
+    /// run remembers the block of every payment.
+    pub fn new_invoice_payment_informations(
+        &self,
+        run: &mut Run,
+        debtors: Vec<Debtor>,
+    ) -> Vec<invoice::PaymentInformation> {
+        let created = run.date();
+        let mut groups: BTreeMap<(Date, SequenceType, Category), Vec<Debtor>> = BTreeMap::new();
+        for debtor in debtors {
+            let sequence_type = debtor.sequence_type();
+            groups
+                .entry((
+                    self.collection_date(created, sequence_type),
+                    sequence_type,
+                    debtor.category(),
+                ))
+                .or_default()
+                .push(debtor);
+        }
+        groups
+            .into_iter()
+            .map(|((date, sequence_type, category), debtors)| {
+                self.new_invoice_payment_information(run, date, sequence_type, category, debtors)
+            })
+            .collect()
     }
 
+    /// One block with all of `debtors`, which should have `sequence_type`
     pub fn new_invoice_payment_information(
         &self,
+        run: &mut Run,
         collection_date: Date,
+        sequence_type: SequenceType,
+        category: Category,
         debtors: Vec<Debtor>,
     ) -> invoice::PaymentInformation {
-        let id = thread_rng().gen::<u64>();
-        let payment_information_id = format!(
-            "{}-{:0>16x}",
-            self.company_name.to_uppercase().replace('-', ""),
-            id
+        let payment_information_id = run.payment_information_id(
+            &category.payment_information_suffix(sequence_type.code()),
         );
+        for d in &debtors {
+            run.place(d.end_to_end_id(), &payment_information_id);
+        }
         let costs = debtors.iter().map(|d| d.amount()).sum::<Euro>();
         invoice::PaymentInformation::new(
             payment_information_id,
-            self.company_name.clone(),
-            self.company_iban.clone(),
-            self.company_bic.clone(),
-            collection_date,
-            costs,
-            debtors.len() as u32,
+            self.company_account(),
             self.company_id.clone(),
+            sequence_type,
+            collection_date,
+            (debtors.len() as u32, costs),
             debtors,
         )
     }
 
     pub fn new_invoice_document(
         &self,
-        payment_info: invoice::PaymentInformation,
+        run: &Run,
+        payment_info: Vec<invoice::PaymentInformation>,
     ) -> invoice::Document {
         let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
         let now = now.to_string();
-        let id = thread_rng().gen::<u64>();
-        let message_id = format!(
-            "{}-{:0>16x}",
-            Date::today().to_string().replace('-', ""),
-            id
-        );
         let header = invoice::Header::new(
-            message_id,
+            run.message_id(Direction::Collection.message_kind()),
             now,
-            payment_info.num_transactions(),
-            payment_info.control_sum(),
+            payment_info.iter().map(|p| p.num_transactions()).sum(),
+            payment_info.iter().map(|p| p.control_sum()).sum(),
             self.company_name.clone(),
             self.company_id.clone(),
         );
-        invoice::Document::new(header, vec![payment_info])
+        invoice::Document::new(self.schema_version, header, payment_info)
     }
 
+    /// The end-to-end id comes from `run`, for the code of the creditor and
+    /// `period`. The run keeps the payment to reconcile the bank statements
+    /// with.
     pub fn new_creditor(
         &self,
-        amount: Euro,
-        name: String,
-        bic: String,
-        iban: String,
-        description: String,
-    ) -> transfer::Creditor {
-        let id = thread_rng().gen::<u64>();
-        let invoice_id = format!(
-            "{}-{:0>16x}",
-            self.company_name.to_uppercase().replace('-', ""),
-            id
-        );
-        transfer::Creditor::new(invoice_id, amount, bic, name, iban, description)
+        run: &mut Run,
+        period: &str,
+        mut details: CreditorDetails,
+    ) -> Result<transfer::Creditor, PaymentError> {
+        let (bic, bic_derived) = self.resolve_bic(details.bic.take(), &details.iban)?;
+        details.bic = bic;
+        let end_to_end_id = run.end_to_end_id(&details.code, period)?.id;
+        run.expect(Payment {
+            run: run.id().clone(),
+            end_to_end_id: end_to_end_id.clone(),
+            code: details.code.clone(),
+            name: details.name.clone(),
+            iban: details.iban.clone(),
+            amount: details.amount,
+            direction: Direction::Transfer,
+            sequence_type: None,
+            payment_information_id: None,
+            settled: None,
+            rejection: None,
+            reversed: None,
+        });
+        Ok(transfer::Creditor::new(end_to_end_id, details, bic_derived))
+    }
+
+    /// Splits `creditors` into one payment information block per category,
+    /// all executed on the earliest date the lead time allows after the run.
+    /// The run remembers the block of every payment.
+    pub fn new_transfer_payment_informations(
+        &self,
+        run: &mut Run,
+        creditors: Vec<transfer::Creditor>,
+    ) -> Vec<transfer::PaymentInformation> {
+        let execution_date = self.execution_date(run.date());
+        let mut groups: BTreeMap<Category, Vec<transfer::Creditor>> = BTreeMap::new();
+        for creditor in creditors {
+            groups.entry(creditor.category()).or_default().push(creditor);
+        }
+        groups
+            .into_iter()
+            .map(|(category, creditors)| {
+                self.new_transfer_payment_information(run, execution_date, category, creditors)
+            })
+            .collect()
     }
 
+    /// One block with all of `creditors`
     pub fn new_transfer_payment_information(
         &self,
+        run: &mut Run,
         execution_date: Date,
+        category: Category,
         creditors: Vec<transfer::Creditor>,
     ) -> transfer::PaymentInformation {
-        let id = thread_rng().gen::<u64>();
-        let payment_information_id = format!(
-            "{}-{:0>16x}",
-            self.company_name.to_uppercase().replace('-', ""),
-            id
-        );
+        let payment_information_id =
+            run.payment_information_id(&category.payment_information_suffix(TRANSFER_SUFFIX));
+        for c in &creditors {
+            run.place(c.end_to_end_id(), &payment_information_id);
+        }
         let costs = creditors.iter().map(|d| d.amount()).sum::<Euro>();
         transfer::PaymentInformation::new(
             payment_information_id,
-            creditors.len() as u32,
-            costs,
+            self.company_account(),
             execution_date,
-            self.company_name.clone(),
-            self.company_iban.clone(),
-            self.company_bic.clone(),
+            (creditors.len() as u32, costs),
             creditors,
         )
     }
 
     pub fn new_transfer_document(
         &self,
-        payment_info: transfer::PaymentInformation,
+        run: &Run,
+        payment_info: Vec<transfer::PaymentInformation>,
     ) -> transfer::Document {
         let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
         let now = now.to_string();
-        let id = thread_rng().gen::<u64>();
-        let message_id = format!(
-            "{}-{:0>16x}",
-            Date::today().to_string().replace('-', ""),
-            id
-        );
         let header = transfer::Header::new(
-            message_id,
+            run.message_id(Direction::Transfer.message_kind()),
             now,
-            payment_info.number_of_transactions(),
-            payment_info.control_sum(),
+            payment_info.iter().map(|p| p.number_of_transactions()).sum(),
+            payment_info.iter().map(|p| p.control_sum()).sum(),
             self.company_name.clone(),
         );
-        transfer::Document::new(header, vec![payment_info])
+        transfer::Document::new(self.schema_version, header, payment_info)
     }
 }
 
 trait ToXml {
-    fn to_xml(&self) -> Vec<XmlEvent>;
+    fn to_xml(&self) -> Vec<XmlEvent<'_>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{bic, config, date, debit, iban};
+
+    #[test]
+    fn bic_is_only_optional_in_newer_versions() {
+        let unknown_bank = iban("DE89370400440532013000");
+        let config = |schema_version| {
+            SEPAConfig::new(
+                "AEGEE-Delft",
+                iban("NL44RABO0123456789"),
+                bic("RABONL2U"),
+                "NL00ZZZ404840000000",
+                schema_version,
+            )
+        };
+
+        assert!(config(SchemaVersion::V2009)
+            .resolve_bic(None, &unknown_bank)
+            .is_err());
+        assert!(matches!(
+            config(SchemaVersion::V2019).resolve_bic(None, &unknown_bank),
+            Ok((None, false))
+        ));
+        assert!(matches!(
+            config(SchemaVersion::V2009).resolve_bic(None, &iban("NL91ABNA0417164300")),
+            Ok((Some(_), true))
+        ));
+    }
+
+    #[test]
+    fn collections_skip_the_weekend() {
+        // a Thursday evening file isn't collected on Saturday
+        let thursday = date(2024, 3, 7);
+        assert_eq!(
+            config().collection_date(thursday, SequenceType::Recurring),
+            date(2024, 3, 11)
+        );
+    }
+
+    #[test]
+    fn lead_times_come_from_the_config() {
+        let quick = LeadTimes {
+            first: 1,
+            ..LeadTimes::default()
+        };
+        assert_eq!(
+            config()
+                .with_lead_times(quick)
+                .collection_date(date(2024, 3, 7), SequenceType::First),
+            date(2024, 3, 8)
+        );
+    }
+
+    #[test]
+    fn debtors_are_split_per_sequence_type() {
+        let config = config();
+        let mut run = config.start_run(&IdRegistry::default(), date(2024, 3, 1));
+        let mut debtor = |code: &str, amount, sequence_type| {
+            let details = DebtorDetails {
+                amount,
+                sequence_type,
+                bic: None,
+                ..debit(&config, code)
+            };
+            config.new_debtor(&mut run, "202403", details).unwrap()
+        };
+        let debtors = vec![
+            debtor("1001", Euro::new(12, 50), SequenceType::Recurring),
+            debtor("1002", Euro::new(10, 0), SequenceType::First),
+            debtor("1004", Euro::new(5, 0), SequenceType::OneOff),
+            debtor("1005", Euro::new(2, 50), SequenceType::First),
+        ];
+        let blocks = config.new_invoice_payment_informations(&mut run, debtors);
+        let summary = blocks
+            .iter()
+            .map(|b| {
+                (
+                    b.sequence_type(),
+                    b.collection_date(),
+                    b.num_transactions(),
+                    b.control_sum(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    SequenceType::Recurring,
+                    date(2024, 3, 5),
+                    1,
+                    Euro::new(12, 50)
+                ),
+                (SequenceType::First, date(2024, 3, 8), 2, Euro::new(12, 50)),
+                (SequenceType::OneOff, date(2024, 3, 8), 1, Euro::new(5, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn documents_have_the_ids_and_totals_of_the_run() {
+        let config = config();
+        let mut run = config.start_run(&IdRegistry::default(), date(2024, 3, 1));
+        let debtors = vec![
+            config
+                .new_debtor(&mut run, "202403", debit(&config, "1001"))
+                .unwrap(),
+            config
+                .new_debtor(
+                    &mut run,
+                    "202403",
+                    DebtorDetails {
+                        sequence_type: SequenceType::OneOff,
+                        amount: Euro::new(5, 0),
+                        ..debit(&config, "1004")
+                    },
+                )
+                .unwrap(),
+        ];
+        let blocks = config.new_invoice_payment_informations(&mut run, debtors);
+        let mut out = vec![];
+        config
+            .new_invoice_document(&run, blocks)
+            .write(&mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("<PmtInf>").count(), 2);
+        assert!(out.contains("<GrpHdr><MsgId>AEGEEDELFT-20240301-1-DD</MsgId>"));
+        assert!(out.contains("<PmtInfId>AEGEEDELFT-20240301-1-OOFF</PmtInfId>"));
+        assert!(out.contains("<EndToEndId>20240301-1-202403-1004</EndToEndId>"));
+        assert!(out.contains("<NbOfTxs>2</NbOfTxs><CtrlSum>15.00</CtrlSum>"));
+    }
+
+    #[test]
+    fn categories_get_their_own_blocks() {
+        let config = config();
+        let mut run = config.start_run(&IdRegistry::default(), date(2024, 3, 1));
+        let mut debtors = vec![];
+        for (code, sequence_type, category) in [
+            ("1001", SequenceType::Recurring, Category::Contribution),
+            ("1002", SequenceType::Recurring, Category::Contribution),
+            ("1003", SequenceType::Recurring, Category::Balance),
+            ("1004", SequenceType::First, Category::AlumniContribution),
+        ] {
+            let details = DebtorDetails {
+                sequence_type,
+                ..debit(&config, code)
+            };
+            let debtor = config.new_debtor(&mut run, "202403", details).unwrap();
+            debtors.push(debtor.with_category(category));
+        }
+
+        let blocks = config.new_invoice_payment_informations(&mut run, debtors);
+        let summary = blocks
+            .iter()
+            .map(|b| {
+                (
+                    b.payment_information_id(),
+                    b.collection_date(),
+                    b.num_transactions(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("AEGEEDELFT-20240301-1-RCUR", date(2024, 3, 5), 1),
+                ("AEGEEDELFT-20240301-1-RCUR-CONTR", date(2024, 3, 5), 2),
+                ("AEGEEDELFT-20240301-1-FRST-ALUMNI", date(2024, 3, 8), 1),
+            ]
+        );
+        // every payment knows its block, for the status reports
+        let payment = |code| run.payments().iter().find(|p| p.code == code).unwrap();
+        assert!(payment("1002").in_payment_information("AEGEEDELFT-20240301-1-RCUR-CONTR"));
+        assert!(!payment("1002").in_payment_information("AEGEEDELFT-20240301-1-RCUR"));
+        assert!(payment("1003").in_payment_information("AEGEEDELFT-20240301-1-RCUR"));
+    }
+
+    #[test]
+    fn transfers_are_split_per_category() {
+        let config = config();
+        let mut run = config.start_run(&IdRegistry::default(), date(2024, 3, 1));
+        let mut creditors = vec![];
+        for (code, category) in [
+            ("1005", Category::Balance),
+            ("1006", Category::Contribution),
+        ] {
+            let creditor = config
+                .new_creditor(
+                    &mut run,
+                    "202403",
+                    CreditorDetails {
+                        code: code.to_string(),
+                        name: "Erika Mustermann".to_string(),
+                        amount: Euro::new(5, 0),
+                        bic: Some(bic("ABNANL2A")),
+                        iban: iban("NL91ABNA0417164300"),
+                        description: "Payment of positive balance".to_string(),
+                        reference: None,
+                    },
+                )
+                .unwrap();
+            creditors.push(creditor.with_category(category));
+        }
+        let transfers = config.new_transfer_payment_informations(&mut run, creditors);
+        assert_eq!(
+            transfers
+                .iter()
+                .map(|b| (b.payment_information_id(), b.number_of_transactions()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("AEGEEDELFT-20240301-1-TRF", 1),
+                ("AEGEEDELFT-20240301-1-TRF-CONTR", 1)
+            ]
+        );
+        let payment = run.payments().iter().find(|p| p.code == "1006").unwrap();
+        assert!(payment.in_payment_information("AEGEEDELFT-20240301-1-TRF-CONTR"));
+
+        let mut out = vec![];
+        config
+            .new_transfer_document(&run, transfers)
+            .write(&mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("<NbOfTxs>2</NbOfTxs><CtrlSum>10.00</CtrlSum>"));
+    }
 }
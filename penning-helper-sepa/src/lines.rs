@@ -0,0 +1,323 @@
+use penning_helper_types::{CreditorReference, Date, Euro, Iban};
+
+use crate::{
+    camt::{CreditDebit, Statement},
+    ids::code_from_reference,
+    reconcile::{Unmatched, UnmatchedReason},
+};
+
+/// One movement on the account, the same whether it came from camt or MT940
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementLine {
+    pub statement: String,
+    pub booking_date: Option<Date>,
+    pub value_date: Option<Date>,
+    pub direction: CreditDebit,
+    pub amount: Euro,
+    pub reversal: bool,
+    pub booked: bool,
+    pub counterparty_iban: Option<Iban>,
+    pub counterparty_name: Option<String>,
+    pub remittance: Option<String>,
+    pub end_to_end_id: Option<String>,
+    pub bank_reference: Option<String>,
+}
+
+impl StatementLine {
+    /// Positive when money came in
+    pub fn signed_amount(&self) -> Euro {
+        match self.direction {
+            CreditDebit::Credit => self.amount,
+            CreditDebit::Debit => -self.amount,
+        }
+    }
+}
+
+impl Statement {
+    /// A line for every transaction, a batch entry gives one per debtor
+    pub fn lines(&self) -> Vec<StatementLine> {
+        self.entries
+            .iter()
+            .flat_map(|entry| {
+                entry.transactions.iter().map(|t| StatementLine {
+                    statement: self.id.clone(),
+                    booking_date: entry.booking_date,
+                    value_date: entry.value_date,
+                    direction: entry.direction,
+                    amount: t.amount,
+                    reversal: entry.reversal,
+                    booked: entry.booked,
+                    counterparty_iban: t.counterparty_iban.clone(),
+                    counterparty_name: t.counterparty_name.clone(),
+                    remittance: t.remittance.clone(),
+                    end_to_end_id: t.end_to_end_id().map(str::to_string),
+                    bank_reference: entry.bank_reference.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Unmatched {
+    /// The statement line that couldn't be matched
+    pub fn line(&self) -> StatementLine {
+        let t = &self.transaction;
+        StatementLine {
+            statement: self.statement.clone(),
+            booking_date: self.date,
+            value_date: None,
+            direction: self.direction,
+            amount: t.amount,
+            reversal: matches!(self.reason, UnmatchedReason::Returned(_)),
+            booked: self.reason != UnmatchedReason::NotBooked,
+            counterparty_iban: t.counterparty_iban.clone(),
+            counterparty_name: t.counterparty_name.clone(),
+            remittance: t.remittance.clone(),
+            end_to_end_id: t.end_to_end_id().map(str::to_string),
+            bank_reference: None,
+        }
+    }
+}
+
+/// A relation a statement line could be from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterparty {
+    pub code: String,
+    pub name: String,
+    pub iban: Option<Iban>,
+}
+
+/// A booking on the relation's debtors or creditors account a statement
+/// line could pay for. A positive amount is owed to us.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerItem {
+    pub code: String,
+    pub reference: String,
+    pub amount: Euro,
+    pub date: Date,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestedOn {
+    /// The remittance has the structured creditor reference of an invoice
+    CreditorReference,
+    /// The remittance has the reference of a booking
+    Reference,
+    /// The relation's IBAN
+    Iban,
+    /// The relation's name, the IBAN is unknown or new
+    Name,
+}
+
+/// Who a statement line is probably from and which bookings it pays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub code: String,
+    pub on: SuggestedOn,
+    /// Indexes into the items, empty when the amount doesn't add up
+    pub items: Vec<usize>,
+}
+
+/// Finds the relation of `line` on the creditor reference of an invoice,
+/// then on a booking reference in the remittance, then on IBAN, then on name,
+/// and the bookings it pays: the one with the same amount or else all of them
+/// if they add up to it
+pub fn suggest(
+    line: &StatementLine,
+    relations: &[Counterparty],
+    items: &[LedgerItem],
+) -> Option<Suggestion> {
+    let remittance = line.remittance.as_deref().unwrap_or_default();
+    if let Some(reference) = CreditorReference::find_in(remittance) {
+        let relation = code_from_reference(&reference)
+            .and_then(|code| relations.iter().find(|r| r.code.eq_ignore_ascii_case(code)));
+        if let Some(relation) = relation {
+            return Some(Suggestion {
+                code: relation.code.clone(),
+                on: SuggestedOn::CreditorReference,
+                items: paid_items(line, &relation.code, items),
+            });
+        }
+    }
+    let remittance = remittance.to_lowercase();
+    let referenced = items
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.reference.len() >= 4 && remittance.contains(&i.reference.to_lowercase()))
+        .collect::<Vec<_>>();
+    if let Some((_, first)) = referenced.first() {
+        if referenced.iter().all(|(_, i)| i.code == first.code) {
+            return Some(Suggestion {
+                code: first.code.clone(),
+                on: SuggestedOn::Reference,
+                items: referenced.iter().map(|(n, _)| *n).collect(),
+            });
+        }
+    }
+    let (code, on) = relation(line, relations)?;
+    Some(Suggestion {
+        code: code.to_string(),
+        on,
+        items: paid_items(line, code, items),
+    })
+}
+
+/// The item of `code` with the amount of the line, or else all of them if
+/// they add up to it
+fn paid_items(line: &StatementLine, code: &str, items: &[LedgerItem]) -> Vec<usize> {
+    let own = items
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.code == code)
+        .collect::<Vec<_>>();
+    let amount = line.signed_amount();
+    match own.iter().rfind(|(_, i)| i.amount == amount) {
+        Some((n, _)) => vec![*n],
+        None if own.iter().map(|(_, i)| i.amount).sum::<Euro>() == amount => {
+            own.iter().map(|(n, _)| *n).collect()
+        }
+        None => vec![],
+    }
+}
+
+/// The only relation with the line's IBAN, or else the only one with its name
+fn relation<'r>(
+    line: &StatementLine,
+    relations: &'r [Counterparty],
+) -> Option<(&'r str, SuggestedOn)> {
+    let only = |found: Vec<&'r Counterparty>| match found[..] {
+        [r] => Some(r.code.as_str()),
+        _ => None,
+    };
+    if let Some(iban) = &line.counterparty_iban {
+        let found = relations
+            .iter()
+            .filter(|r| r.iban.as_ref() == Some(iban))
+            .collect();
+        if let Some(code) = only(found) {
+            return Some((code, SuggestedOn::Iban));
+        }
+    }
+    let name = normalise(line.counterparty_name.as_deref()?);
+    let found = relations
+        .iter()
+        .filter(|r| normalise(&r.name) == name)
+        .collect();
+    only(found).map(|code| (code, SuggestedOn::Name))
+}
+
+/// Banks send names in capitals and with odd spacing
+fn normalise(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_mt940,
+        testing::{date, fixture, iban},
+    };
+
+    fn relations() -> Vec<Counterparty> {
+        vec![
+            Counterparty {
+                code: "1003".to_string(),
+                name: "P. Pietersen".to_string(),
+                iban: None,
+            },
+            Counterparty {
+                code: "1004".to_string(),
+                name: "Karel de Vries".to_string(),
+                iban: Some(iban("NL02ABNA0123456789")),
+            },
+        ]
+    }
+
+    fn items() -> Vec<LedgerItem> {
+        let item = |code: &str, reference: &str, euros| LedgerItem {
+            code: code.to_string(),
+            reference: reference.to_string(),
+            amount: Euro::from(euros),
+            date: date(2024, 2, 1),
+        };
+        vec![
+            item("1003", "T0011-01", 5),
+            item("1003", "T0012-03", 17),
+            item("1004", "T0012-04", -5),
+        ]
+    }
+
+    fn lines() -> Vec<StatementLine> {
+        parse_mt940(fixture("mt940.sta")).unwrap()[0].lines()
+    }
+
+    #[test]
+    fn a_reference_in_the_description_suggests_the_item() {
+        let suggestion = suggest(&lines()[1], &relations(), &items()).unwrap();
+        assert_eq!(suggestion.code, "1003");
+        assert_eq!(suggestion.on, SuggestedOn::Reference);
+        assert_eq!(suggestion.items, vec![1]);
+    }
+
+    #[test]
+    fn a_known_iban_suggests_the_relation() {
+        let suggestion = suggest(&lines()[3], &relations(), &items()).unwrap();
+        assert_eq!(suggestion.code, "1004");
+        assert_eq!(suggestion.on, SuggestedOn::Iban);
+        // a gift doesn't pay what we owe them
+        assert_eq!(suggestion.items, Vec::<usize>::new());
+    }
+
+    #[test]
+    fn a_name_suggests_the_items_that_add_up() {
+        let mut paid_in_full = lines()[1].clone();
+        paid_in_full.remittance = None;
+        paid_in_full.amount = Euro::new(22, 0);
+        let suggestion = suggest(&paid_in_full, &relations(), &items()).unwrap();
+        assert_eq!(suggestion.on, SuggestedOn::Name);
+        assert_eq!(suggestion.items, vec![0, 1]);
+    }
+
+    #[test]
+    fn collections_of_ours_are_not_suggested() {
+        assert_eq!(suggest(&lines()[0], &relations(), &items()), None);
+    }
+
+    #[test]
+    fn a_creditor_reference_suggests_the_relation() {
+        // a manual transfer with the reference from the invoice
+        let line = StatementLine {
+            statement: "1".to_string(),
+            booking_date: Some(date(2024, 3, 12)),
+            value_date: None,
+            direction: CreditDebit::Credit,
+            amount: Euro::new(7, 5),
+            reversal: false,
+            booked: true,
+            counterparty_iban: Some(iban("NL02ABNA0123456789")),
+            counterparty_name: Some("E MUSTERMANN".to_string()),
+            remittance: Some("rf31 2024 0310 02".to_string()),
+            end_to_end_id: None,
+            bank_reference: None,
+        };
+        let relations = vec![Counterparty {
+            code: "1002".to_string(),
+            name: "Erika Mustermann".to_string(),
+            iban: Some(iban("DE89370400440532013000")),
+        }];
+        let items = vec![LedgerItem {
+            code: "1002".to_string(),
+            reference: "T0013-01".to_string(),
+            amount: Euro::new(7, 5),
+            date: date(2024, 3, 1),
+        }];
+        let suggestion = suggest(&line, &relations, &items).unwrap();
+        assert_eq!(suggestion.on, SuggestedOn::CreditorReference);
+        assert_eq!(suggestion.code, "1002");
+        assert_eq!(suggestion.items, vec![0]);
+    }
+}
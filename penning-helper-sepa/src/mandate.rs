@@ -0,0 +1,76 @@
+use penning_helper_types::{Date, Iban};
+use serde::{Deserialize, Serialize};
+
+/// The authorisation a member signed to have their account debited
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mandate {
+    pub id: String,
+    pub signature_date: Date,
+    /// The account the mandate covers
+    pub debtor_iban: Iban,
+    /// Our creditor scheme id at the time of signing
+    pub creditor_id: String,
+}
+
+impl Mandate {
+    pub fn new(
+        id: impl ToString,
+        signature_date: Date,
+        debtor_iban: Iban,
+        creditor_id: impl ToString,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            signature_date,
+            debtor_iban,
+            creditor_id: creditor_id.to_string(),
+        }
+    }
+
+    /// What changed compared to the mandate used for `previous`ly collecting,
+    /// `None` when nothing did.
+    pub fn amendment_since(&self, previous: &Mandate) -> Option<Amendment> {
+        let amendment = Amendment {
+            original_mandate_id: (self.id != previous.id).then(|| previous.id.clone()),
+            original_creditor_id: (self.creditor_id != previous.creditor_id)
+                .then(|| previous.creditor_id.clone()),
+            original_debtor_account: (self.debtor_iban != previous.debtor_iban).then(|| {
+                if same_bank(&self.debtor_iban, &previous.debtor_iban) {
+                    OriginalDebtorAccount::Iban(previous.debtor_iban.clone())
+                } else {
+                    OriginalDebtorAccount::OtherBank
+                }
+            }),
+        };
+        (!amendment.is_empty()).then_some(amendment)
+    }
+}
+
+fn same_bank(a: &Iban, b: &Iban) -> bool {
+    a.country() == b.country() && a.bank_code().is_some() && a.bank_code() == b.bank_code()
+}
+
+/// `AmdmntInfDtls`, the parts of a mandate that changed since the last
+/// collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Amendment {
+    pub original_mandate_id: Option<String>,
+    pub original_creditor_id: Option<String>,
+    pub original_debtor_account: Option<OriginalDebtorAccount>,
+}
+
+impl Amendment {
+    pub fn is_empty(&self) -> bool {
+        self.original_mandate_id.is_none()
+            && self.original_creditor_id.is_none()
+            && self.original_debtor_account.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginalDebtorAccount {
+    /// A new account at the same bank
+    Iban(Iban),
+    /// The member moved to another bank, written as `SMNDA`
+    OtherBank,
+}
@@ -0,0 +1,384 @@
+use std::io::Read;
+
+use penning_helper_types::{Date, Euro, Iban};
+
+use crate::camt::{CreditDebit, Entry, EntryTransaction, Statement};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Mt940Error {
+    #[error("Could not read the file: {0}")]
+    Io(String),
+    #[error("{0} is missing")]
+    Missing(&'static str),
+    #[error("{field} has an invalid value: {value}")]
+    Invalid { field: &'static str, value: String },
+}
+
+/// The subfields banks put in a structured `:86:`, like `/EREF/`
+const INFO_TAGS: &[&str] = &[
+    "ADDR", "BENM", "BIC", "CNTP", "CSID", "EREF", "ID", "IBAN", "ISDT", "MARF", "NAME", "ORDP",
+    "PREF", "PURP", "REMI", "RTRN", "TRCD", "TRTP", "ULTB", "ULTD",
+];
+
+/// Reads the statements in an MT940 file, or the interim reports in an MT942
+/// file, into the same model as camt
+pub fn parse_mt940(mut reader: impl Read) -> Result<Vec<Statement>, Mt940Error> {
+    let mut bytes = vec![];
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| Mt940Error::Io(e.to_string()))?;
+    // older exports aren't UTF-8, the SWIFT character set is ASCII anyway
+    let text = String::from_utf8_lossy(&bytes);
+    let mut statements = vec![];
+    let mut current: Option<Statement> = None;
+    let mut previous = "";
+    for (tag, lines) in fields(&text) {
+        let after_line = previous == "61";
+        previous = tag;
+        if tag == "20" {
+            statements.extend(current.take());
+            current = Some(Statement {
+                id: lines.concat(),
+                account: None,
+                entries: vec![],
+            });
+            continue;
+        }
+        let statement = current.as_mut().ok_or(Mt940Error::Missing(":20:"))?;
+        match tag {
+            "25" => statement.account = parse_account(&lines.concat()),
+            "28C" => statement.id = format!("{}/{}", statement.id, lines.concat()),
+            "61" => statement.entries.push(parse_line(&lines)?),
+            // the information belongs to the line before it, anywhere else
+            // it's about the whole statement
+            "86" if after_line => {
+                if let Some(entry) = statement.entries.last_mut() {
+                    add_information(&mut entry.transactions[0], &lines);
+                }
+            }
+            _ => {}
+        }
+    }
+    statements.extend(current);
+    Ok(statements)
+}
+
+/// Every `:tag:` field with its lines, without the SWIFT blocks around them
+fn fields(text: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut fields: Vec<(&str, Vec<&str>)> = vec![];
+    let mut in_field = false;
+    for line in text.lines() {
+        let mut line = line.trim_end();
+        if line.starts_with('{') {
+            // `{1:...}{2:...}{4:` may have the first field on the same line
+            match line.find("{4:") {
+                Some(i) => line = &line[i + 3..],
+                None => continue,
+            }
+        }
+        if line.starts_with('-') {
+            in_field = false;
+            continue;
+        }
+        if let Some((tag, value)) = field_start(line) {
+            fields.push((tag, vec![value]));
+            in_field = true;
+        } else if in_field && !line.is_empty() {
+            if let Some((_, lines)) = fields.last_mut() {
+                lines.push(line);
+            }
+        }
+    }
+    fields
+}
+
+/// `:61:rest` gives `("61", "rest")`
+fn field_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(':')?;
+    let (tag, value) = rest.split_once(':')?;
+    let valid = (2..=3).contains(&tag.len())
+        && tag[..2].bytes().all(|b| b.is_ascii_digit())
+        && tag[2..].bytes().all(|b| b.is_ascii_uppercase());
+    valid.then_some((tag, value))
+}
+
+/// `:25:` is an IBAN, optionally after a BIC and before the currency
+fn parse_account(account: &str) -> Option<Iban> {
+    let account = account.rsplit('/').next()?.trim();
+    account.parse().ok().or_else(|| {
+        let without_currency = account.get(..account.len().checked_sub(3)?)?;
+        without_currency.parse().ok()
+    })
+}
+
+/// A `:61:` statement line: value date, optional entry date, debit/credit
+/// mark, optional funds code, amount, transaction type, our reference, `//`
+/// the bank's reference, and the supplementary details on the next line
+fn parse_line(lines: &[&str]) -> Result<Entry, Mt940Error> {
+    let invalid = |value: &str| Mt940Error::Invalid {
+        field: ":61:",
+        value: value.to_string(),
+    };
+    let line = lines[0];
+    let value_date = line
+        .get(..6)
+        .and_then(parse_yymmdd)
+        .ok_or_else(|| invalid(line))?;
+    let mut rest = &line[6..];
+    let mut booking_date = None;
+    if rest.len() >= 4 && rest[..4].bytes().all(|b| b.is_ascii_digit()) {
+        booking_date = Some(entry_date(value_date, &rest[..4]).ok_or_else(|| invalid(line))?);
+        rest = &rest[4..];
+    }
+    let (direction, reversal, mark) = match rest.get(..2) {
+        Some("RC") => (CreditDebit::Debit, true, 2),
+        Some("RD") => (CreditDebit::Credit, true, 2),
+        Some("EC") => (CreditDebit::Credit, false, 2),
+        Some("ED") => (CreditDebit::Debit, false, 2),
+        _ => match rest.get(..1) {
+            Some("C") => (CreditDebit::Credit, false, 1),
+            Some("D") => (CreditDebit::Debit, false, 1),
+            _ => return Err(invalid(line)),
+        },
+    };
+    rest = &rest[mark..];
+    // the third letter of the currency code, hardly used
+    if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        rest = &rest[1..];
+    }
+    let amount_len = rest
+        .find(|c: char| !c.is_ascii_digit() && c != ',')
+        .unwrap_or(rest.len());
+    let amount = parse_amount(&rest[..amount_len]).ok_or_else(|| invalid(line))?;
+    // the transaction type, like `NTRF` or `N541`
+    let references = rest.get(amount_len + 4..).unwrap_or_default();
+    let (ours, bank) = match references.split_once("//") {
+        Some((ours, bank)) => (ours, Some(bank)),
+        None => (references, None),
+    };
+    // `EREF` and `NONREF` only say the reference is elsewhere
+    let end_to_end_id = Some(ours.trim())
+        .filter(|r| !r.is_empty() && !matches!(*r, "NONREF" | "EREF" | "PREF" | "MARF"))
+        .map(str::to_string);
+    // some banks put the counterparty's account in the supplementary details
+    let counterparty_iban = lines[1..]
+        .iter()
+        .find_map(|l| l.split_whitespace().next()?.parse().ok());
+    Ok(Entry {
+        amount,
+        direction,
+        reversal,
+        booked: true,
+        booking_date: booking_date.or(Some(value_date)),
+        value_date: Some(value_date),
+        bank_reference: bank
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .map(str::to_string),
+        transactions: vec![EntryTransaction {
+            amount,
+            end_to_end_id,
+            mandate_id: None,
+            counterparty_name: None,
+            counterparty_iban,
+            remittance: None,
+            return_reason: None,
+        }],
+    })
+}
+
+fn parse_yymmdd(date: &str) -> Option<Date> {
+    let year = 2000 + date.get(..2)?.parse::<i32>().ok()?;
+    let month = date.get(2..4)?.parse().ok()?;
+    let day = date.get(4..6)?.parse().ok()?;
+    Date::new(year, month, day)
+}
+
+/// The entry date only has a month and day, around new year it's in another
+/// year than the value date
+fn entry_date(value_date: Date, mmdd: &str) -> Option<Date> {
+    use chrono::Datelike;
+    let month = mmdd.get(..2)?.parse::<u32>().ok()?;
+    let day = mmdd.get(2..4)?.parse().ok()?;
+    let year = match (value_date.month(), month) {
+        (1, 12) => value_date.year() - 1,
+        (12, 1) => value_date.year() + 1,
+        _ => value_date.year(),
+    };
+    Date::new(year, month, day)
+}
+
+fn parse_amount(amount: &str) -> Option<Euro> {
+    amount.replace(',', ".").parse().ok()
+}
+
+/// Fills the transaction from a `:86:`, structured with `/TAG/value` subfields
+/// or else free text
+fn add_information(transaction: &mut EntryTransaction, lines: &[&str]) {
+    let text = lines.concat();
+    if !text.starts_with('/') {
+        transaction.remittance = Some(lines.join(" ").trim().to_string());
+        if transaction.counterparty_iban.is_none() {
+            transaction.counterparty_iban = text.split_whitespace().find_map(|w| w.parse().ok());
+        }
+        return;
+    }
+    for (tag, value) in subfields(&text) {
+        let value = value.trim_matches('/').trim();
+        let value = Some(value).filter(|v| !v.is_empty());
+        match tag {
+            "EREF" => {
+                if let Some(v) = value.filter(|v| *v != "NOTPROVIDED") {
+                    transaction.end_to_end_id = Some(v.to_string());
+                }
+            }
+            "MARF" => transaction.mandate_id = value.map(str::to_string),
+            "NAME" => transaction.counterparty_name = value.map(str::to_string),
+            "IBAN" => transaction.counterparty_iban = value.and_then(|v| v.parse().ok()),
+            "RTRN" => transaction.return_reason = value.map(str::to_string),
+            "REMI" => transaction.remittance = value.and_then(remittance),
+            "CNTP" => {
+                // IBAN/BIC/name/city
+                let parts = value.unwrap_or_default().split('/').collect::<Vec<_>>();
+                if let Some(iban) = parts.first().and_then(|i| i.parse().ok()) {
+                    transaction.counterparty_iban = Some(iban);
+                }
+                if let Some(name) = parts.get(2).filter(|n| !n.is_empty()) {
+                    transaction.counterparty_name = Some(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `/EREF/x//REMI/y/` gives `[("EREF", "x/"), ("REMI", "y/")]`
+fn subfields(text: &str) -> Vec<(&str, &str)> {
+    let mut subfields = vec![];
+    let mut rest = text;
+    while let Some(after) = rest.strip_prefix('/') {
+        let Some((tag, value)) = after.split_once('/') else {
+            break;
+        };
+        let end = next_tag(value).unwrap_or(value.len());
+        subfields.push((tag, &value[..end]));
+        rest = &value[end..];
+    }
+    subfields
+}
+
+/// Where the next known `/TAG/` starts
+fn next_tag(value: &str) -> Option<usize> {
+    value.match_indices('/').map(|(i, _)| i).find(|i| {
+        let after = &value[i + 1..];
+        INFO_TAGS
+            .iter()
+            .any(|t| after.starts_with(t) && after[t.len()..].starts_with('/'))
+    })
+}
+
+/// `USTD//text` is free text, `STRD/CUR/reference` a structured reference
+fn remittance(value: &str) -> Option<String> {
+    let value = if let Some(text) = value.strip_prefix("USTD//") {
+        text
+    } else if value.starts_with("STRD/") {
+        value.rsplit('/').find(|p| !p.is_empty())?
+    } else {
+        value
+    };
+    Some(value.trim_matches('/').trim().to_string()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        reconcile,
+        testing::{config, date, debit, fixture, iban},
+        DebtorDetails, IdRegistry, UnmatchedReason,
+    };
+
+    #[test]
+    fn statements_have_the_account_and_the_lines() {
+        let statements = parse_mt940(fixture("mt940.sta")).unwrap();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].id, "P240307000000001/00042");
+        assert_eq!(statements[0].account, Some(iban("NL44RABO0123456789")));
+        assert_eq!(statements[0].lines().len(), 4);
+    }
+
+    #[test]
+    fn structured_details_are_normalised() {
+        let statements = parse_mt940(fixture("mt940.sta")).unwrap();
+        let lines = statements[0].lines();
+        assert_eq!(lines[0].value_date, Some(date(2024, 3, 7)));
+        assert_eq!(lines[0].amount, Euro::new(12, 50));
+        assert_eq!(lines[0].direction, CreditDebit::Credit);
+        assert_eq!(
+            lines[0].end_to_end_id.as_deref(),
+            Some("20240301-1-202403-1001")
+        );
+        assert_eq!(lines[0].counterparty_iban, Some(iban("NL91ABNA0417164300")));
+        assert_eq!(lines[0].counterparty_name.as_deref(), Some("JAN JANSEN"));
+        assert_eq!(lines[0].remittance.as_deref(), Some("Contributie maart"));
+        assert_eq!(lines[0].bank_reference.as_deref(), Some("00000000001001"));
+        assert_eq!(lines[1].end_to_end_id, None);
+    }
+
+    #[test]
+    fn returns_keep_their_reason() {
+        let statements = parse_mt940(fixture("mt940.sta")).unwrap();
+        let lines = statements[0].lines();
+        assert_eq!(lines[2].direction, CreditDebit::Debit);
+        assert_eq!(
+            statements[0].entries[2].transactions[0]
+                .return_reason
+                .as_deref(),
+            Some("MD06")
+        );
+        assert_eq!(
+            lines[2].counterparty_iban,
+            Some(iban("DE89370400440532013000"))
+        );
+    }
+
+    #[test]
+    fn unstructured_details_are_kept_as_remittance() {
+        let statements = parse_mt940(fixture("mt940.sta")).unwrap();
+        let lines = statements[0].lines();
+        assert_eq!(lines[3].value_date, Some(date(2024, 3, 8)));
+        assert_eq!(lines[3].counterparty_iban, Some(iban("NL02ABNA0123456789")));
+        assert_eq!(
+            lines[3].remittance.as_deref(),
+            Some("Gift for the borrel from K. de Vries")
+        );
+    }
+
+    #[test]
+    fn statements_reconcile_like_camt() {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let mut run = config.start_run(&registry, date(2024, 3, 1));
+        for (code, debtor_iban, amount) in [
+            ("1001", "NL91ABNA0417164300", Euro::new(12, 50)),
+            ("1002", "DE89370400440532013000", Euro::new(10, 0)),
+        ] {
+            let details = DebtorDetails {
+                amount,
+                mandate: config.new_mandate(code, date(2023, 9, 1), iban(debtor_iban)),
+                ..debit(&config, code)
+            };
+            config.new_debtor(&mut run, "202403", details).unwrap();
+        }
+        registry.record(&run);
+
+        let statements = parse_mt940(fixture("mt940.sta")).unwrap();
+        let result = reconcile(&statements, registry.payments());
+        assert_eq!(result.settled.len(), 1);
+        assert_eq!(result.settled[0].payment.code, "1001");
+        assert_eq!(
+            result.unmatched[1].reason,
+            UnmatchedReason::Returned(Some("MD06".to_string()))
+        );
+    }
+}
@@ -0,0 +1,652 @@
+use std::io::Read;
+
+use chrono::NaiveDate;
+use penning_helper_types::{Bic, CreditorReference, Date, Euro, Iban};
+
+use crate::{
+    invoice, transfer, tree::Element, Account, Amendment, Category, CreditorDetails, DebtorDetails,
+    Mandate, OriginalDebtorAccount, SchemaVersion, SchemaVersionExt, SequenceType,
+};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum PainError {
+    #[error("Could not read the XML: {0}")]
+    Xml(String),
+    #[error("Not a {expected} document, it starts with {found}")]
+    UnknownDocument {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Unsupported schema {0}")]
+    UnknownVersion(String),
+    #[error("{0} is missing")]
+    Missing(&'static str),
+    #[error("{element} has an invalid value: {value}")]
+    Invalid {
+        element: &'static str,
+        value: String,
+    },
+}
+
+/// Reads a pain.008 direct debit file, as written by `invoice::Document::write`
+/// or by another tool, back into the model.
+///
+/// What the XML doesn't have is filled in: the relation code is the mandate
+/// id, the category comes from the payment information id and a BIC is never
+/// derived. A `Ref` that isn't an RF creditor reference is kept as the
+/// description.
+pub fn parse_direct_debit(reader: impl Read) -> Result<invoice::Document, PainError> {
+    let (root, version) = document(reader, "CstmrDrctDbtInitn", |v| v.direct_debit_namespace())?;
+    let message = root
+        .child("CstmrDrctDbtInitn")
+        .ok_or(PainError::Missing("CstmrDrctDbtInitn"))?;
+    let blocks = message
+        .children("PmtInf")
+        .map(parse_direct_debit_block)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (message_id, created, totals, name) = parse_header(message, || {
+        let debtors = blocks.iter().flat_map(|p| p.debtors());
+        (debtors.clone().count(), debtors.map(|d| d.amount()).sum())
+    })?;
+    let header = invoice::Header::new(
+        message_id,
+        created,
+        totals.0,
+        totals.1,
+        name,
+        message
+            .text_at(&["GrpHdr", "InitgPty", "Id", "OrgId", "Othr", "Id"])
+            .or_else(|| message.text_at(&["GrpHdr", "InitgPty", "Id", "PrvtId", "Othr", "Id"]))
+            .unwrap_or_default()
+            .to_string(),
+    );
+    Ok(invoice::Document::new(version, header, blocks))
+}
+
+/// Reads a pain.001 credit transfer file back into the model, the same way as
+/// [`parse_direct_debit`]
+pub fn parse_credit_transfer(reader: impl Read) -> Result<transfer::Document, PainError> {
+    let (root, version) = document(reader, "CstmrCdtTrfInitn", |v| {
+        v.credit_transfer_namespace()
+    })?;
+    let message = root
+        .child("CstmrCdtTrfInitn")
+        .ok_or(PainError::Missing("CstmrCdtTrfInitn"))?;
+    let blocks = message
+        .children("PmtInf")
+        .map(parse_credit_transfer_block)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (message_id, created, totals, name) = parse_header(message, || {
+        let creditors = blocks.iter().flat_map(|p| p.creditors());
+        (
+            creditors.clone().count(),
+            creditors.map(|c| c.amount()).sum(),
+        )
+    })?;
+    let header = transfer::Header::new(message_id, created, totals.0, totals.1, name);
+    Ok(transfer::Document::new(version, header, blocks))
+}
+
+/// The root and the schema version of its namespace. Without a namespace the
+/// file is taken to be the 2009 version.
+fn document(
+    reader: impl Read,
+    message: &'static str,
+    namespace: impl Fn(&SchemaVersion) -> &'static str,
+) -> Result<(Element, SchemaVersion), PainError> {
+    let root = Element::parse(reader).map_err(|e| PainError::Xml(e.to_string()))?;
+    if root.child(message).is_none() {
+        let first = root.children.first().unwrap_or(&root);
+        return Err(PainError::UnknownDocument {
+            expected: message,
+            found: first.name.clone(),
+        });
+    }
+    let version = match root.namespace.as_deref() {
+        None => SchemaVersion::V2009,
+        Some(ns) => [SchemaVersion::V2009, SchemaVersion::V2019]
+            .into_iter()
+            .find(|v| namespace(v) == ns)
+            .ok_or_else(|| PainError::UnknownVersion(ns.to_string()))?,
+    };
+    Ok((root, version))
+}
+
+/// Message id, creation time, number of transactions and control sum, and
+/// the initiating party. The totals are optional in the schema, `totals`
+/// counts them when they're left out.
+fn parse_header(
+    message: &Element,
+    totals: impl Fn() -> (usize, Euro),
+) -> Result<(String, String, (u32, Euro), String), PainError> {
+    let message_id = message
+        .text_at(&["GrpHdr", "MsgId"])
+        .ok_or(PainError::Missing("GrpHdr/MsgId"))?;
+    let created = message
+        .text_at(&["GrpHdr", "CreDtTm"])
+        .ok_or(PainError::Missing("GrpHdr/CreDtTm"))?;
+    let (count, sum) = totals();
+    let count = match message.text_at(&["GrpHdr", "NbOfTxs"]) {
+        Some(n) => parse_count("GrpHdr/NbOfTxs", n)?,
+        None => count as u32,
+    };
+    let sum = match message.text_at(&["GrpHdr", "CtrlSum"]) {
+        Some(s) => parse_amount("GrpHdr/CtrlSum", Some(s))?,
+        None => sum,
+    };
+    let name = message
+        .text_at(&["GrpHdr", "InitgPty", "Nm"])
+        .unwrap_or_default();
+    Ok((
+        message_id.to_string(),
+        created.to_string(),
+        (count, sum),
+        name.to_string(),
+    ))
+}
+
+fn parse_direct_debit_block(block: &Element) -> Result<invoice::PaymentInformation, PainError> {
+    let id = block
+        .text_at(&["PmtInfId"])
+        .ok_or(PainError::Missing("PmtInf/PmtInfId"))?;
+    let sequence_type = match block.text_at(&["PmtTpInf", "SeqTp"]) {
+        Some(code) => SequenceType::ALL
+            .into_iter()
+            .find(|s| s.code() == code)
+            .ok_or_else(|| PainError::Invalid {
+                element: "PmtTpInf/SeqTp",
+                value: code.to_string(),
+            })?,
+        None => return Err(PainError::Missing("PmtTpInf/SeqTp")),
+    };
+    let collection_date = parse_date("PmtInf/ReqdColltnDt", block.text_at(&["ReqdColltnDt"]))?;
+    let creditor_id = block
+        .text_at(&["CdtrSchmeId", "Id", "PrvtId", "Othr", "Id"])
+        .ok_or(PainError::Missing("CdtrSchmeId"))?;
+    let category = Category::from_payment_information_id(id);
+    let debtors = block
+        .children("DrctDbtTxInf")
+        .map(|d| parse_debtor(d, creditor_id, sequence_type).map(|d| d.with_category(category)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(invoice::PaymentInformation::new(
+        id.to_string(),
+        Account {
+            name: block
+                .text_at(&["Cdtr", "Nm"])
+                .unwrap_or_default()
+                .to_string(),
+            iban: parse_iban(
+                "CdtrAcct/Id/IBAN",
+                block.text_at(&["CdtrAcct", "Id", "IBAN"]),
+            )?,
+            bic: parse_agent("CdtrAgt", block.child("CdtrAgt"))?
+                .ok_or(PainError::Missing("CdtrAgt/FinInstnId/BIC"))?,
+        },
+        creditor_id.to_string(),
+        sequence_type,
+        collection_date,
+        block_totals(block, debtors.iter().map(|d| d.amount()))?,
+        debtors,
+    ))
+}
+
+fn parse_debtor(
+    debtor: &Element,
+    creditor_id: &str,
+    sequence_type: SequenceType,
+) -> Result<invoice::Debtor, PainError> {
+    let mandate = debtor
+        .find(&["DrctDbtTx", "MndtRltdInf"])
+        .ok_or(PainError::Missing("DrctDbtTx/MndtRltdInf"))?;
+    let mandate_id = mandate
+        .text_at(&["MndtId"])
+        .ok_or(PainError::Missing("MndtRltdInf/MndtId"))?;
+    let iban = parse_iban(
+        "DbtrAcct/Id/IBAN",
+        debtor.text_at(&["DbtrAcct", "Id", "IBAN"]),
+    )?;
+    let amendment = match mandate.text_at(&["AmdmntInd"]) {
+        Some("true") => mandate
+            .child("AmdmntInfDtls")
+            .map(parse_amendment)
+            .transpose()?,
+        _ => None,
+    };
+    let (description, reference) = parse_remittance(debtor);
+    let details = DebtorDetails {
+        code: mandate_id.to_string(),
+        name: debtor
+            .text_at(&["Dbtr", "Nm"])
+            .unwrap_or_default()
+            .to_string(),
+        amount: parse_amount("DrctDbtTxInf/InstdAmt", debtor.text_at(&["InstdAmt"]))?,
+        bic: parse_agent("DbtrAgt", debtor.child("DbtrAgt"))?,
+        mandate: Mandate::new(
+            mandate_id,
+            parse_date("MndtRltdInf/DtOfSgntr", mandate.text_at(&["DtOfSgntr"]))?,
+            iban,
+            creditor_id,
+        ),
+        amendment,
+        sequence_type,
+        description,
+        reference,
+    };
+    Ok(invoice::Debtor::new(end_to_end_id(debtor)?, details, false))
+}
+
+fn parse_amendment(details: &Element) -> Result<Amendment, PainError> {
+    let original_debtor_account = match details.find(&["OrgnlDbtrAcct", "Id"]) {
+        Some(id) if id.text_at(&["Othr", "Id"]) == Some("SMNDA") => {
+            Some(OriginalDebtorAccount::OtherBank)
+        }
+        Some(id) => Some(OriginalDebtorAccount::Iban(parse_iban(
+            "OrgnlDbtrAcct/Id/IBAN",
+            id.text_at(&["IBAN"]),
+        )?)),
+        // SMNDA used to go in the debtor agent
+        None => (details.text_at(&["OrgnlDbtrAgt", "FinInstnId", "Othr", "Id"]) == Some("SMNDA"))
+            .then_some(OriginalDebtorAccount::OtherBank),
+    };
+    Ok(Amendment {
+        original_mandate_id: details.text_at(&["OrgnlMndtId"]).map(str::to_string),
+        original_creditor_id: details
+            .text_at(&["OrgnlCdtrSchmeId", "Id", "PrvtId", "Othr", "Id"])
+            .map(str::to_string),
+        original_debtor_account,
+    })
+}
+
+fn parse_credit_transfer_block(block: &Element) -> Result<transfer::PaymentInformation, PainError> {
+    let id = block
+        .text_at(&["PmtInfId"])
+        .ok_or(PainError::Missing("PmtInf/PmtInfId"))?;
+    // a date or a date and time since pain.001.001.09
+    let execution_date = block
+        .text_at(&["ReqdExctnDt", "Dt"])
+        .or_else(|| block.text_at(&["ReqdExctnDt", "DtTm"]))
+        .or_else(|| block.text_at(&["ReqdExctnDt"]));
+    let execution_date = parse_date("PmtInf/ReqdExctnDt", execution_date)?;
+    let category = Category::from_payment_information_id(id);
+    let creditors = block
+        .children("CdtTrfTxInf")
+        .map(|c| parse_creditor(c).map(|c| c.with_category(category)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(transfer::PaymentInformation::new(
+        id.to_string(),
+        Account {
+            name: block
+                .text_at(&["Dbtr", "Nm"])
+                .unwrap_or_default()
+                .to_string(),
+            iban: parse_iban(
+                "DbtrAcct/Id/IBAN",
+                block.text_at(&["DbtrAcct", "Id", "IBAN"]),
+            )?,
+            bic: parse_agent("DbtrAgt", block.child("DbtrAgt"))?
+                .ok_or(PainError::Missing("DbtrAgt/FinInstnId/BIC"))?,
+        },
+        execution_date,
+        block_totals(block, creditors.iter().map(|c| c.amount()))?,
+        creditors,
+    ))
+}
+
+fn parse_creditor(creditor: &Element) -> Result<transfer::Creditor, PainError> {
+    let (description, reference) = parse_remittance(creditor);
+    let details = CreditorDetails {
+        // only used for new end-to-end ids, the parsed one is kept
+        code: String::new(),
+        name: creditor
+            .text_at(&["Cdtr", "Nm"])
+            .unwrap_or_default()
+            .to_string(),
+        amount: parse_amount(
+            "CdtTrfTxInf/Amt/InstdAmt",
+            creditor.text_at(&["Amt", "InstdAmt"]),
+        )?,
+        bic: parse_agent("CdtrAgt", creditor.child("CdtrAgt"))?,
+        iban: parse_iban(
+            "CdtrAcct/Id/IBAN",
+            creditor.text_at(&["CdtrAcct", "Id", "IBAN"]),
+        )?,
+        description,
+        reference,
+    };
+    Ok(transfer::Creditor::new(
+        end_to_end_id(creditor)?,
+        details,
+        false,
+    ))
+}
+
+fn end_to_end_id(transaction: &Element) -> Result<String, PainError> {
+    transaction
+        .text_at(&["PmtId", "EndToEndId"])
+        .map(str::to_string)
+        .ok_or(PainError::Missing("PmtId/EndToEndId"))
+}
+
+/// The block's number of transactions and control sum, counted when the file
+/// leaves them out
+fn block_totals(
+    block: &Element,
+    amounts: impl Iterator<Item = Euro> + Clone,
+) -> Result<(u32, Euro), PainError> {
+    let count = match block.text_at(&["NbOfTxs"]) {
+        Some(n) => parse_count("PmtInf/NbOfTxs", n)?,
+        None => amounts.clone().count() as u32,
+    };
+    let sum = match block.text_at(&["CtrlSum"]) {
+        Some(s) => parse_amount("PmtInf/CtrlSum", Some(s))?,
+        None => amounts.sum(),
+    };
+    Ok((count, sum))
+}
+
+/// The description and the creditor reference of `RmtInf`
+fn parse_remittance(transaction: &Element) -> (String, Option<CreditorReference>) {
+    let Some(remittance) = transaction.child("RmtInf") else {
+        return (String::new(), None);
+    };
+    let mut description = remittance
+        .children("Ustrd")
+        .map(|u| u.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let reference = remittance.text_at(&["Strd", "CdtrRefInf", "Ref"]);
+    let parsed = reference.and_then(|r| CreditorReference::parse(r).ok());
+    if let (Some(r), None) = (reference, &parsed) {
+        if description.is_empty() {
+            description = r.to_string();
+        }
+    }
+    (description, parsed)
+}
+
+/// The BIC in `FinInstnId`, `None` when there is none or it's `NOTPROVIDED`
+fn parse_agent(element: &'static str, agent: Option<&Element>) -> Result<Option<Bic>, PainError> {
+    let Some(institution) = agent.and_then(|a| a.child("FinInstnId")) else {
+        return Ok(None);
+    };
+    let bic = [SchemaVersion::V2019, SchemaVersion::V2009]
+        .iter()
+        .find_map(|v| institution.text_at(&[v.bic_tag()]));
+    bic.map(|b| {
+        b.parse().map_err(|_| PainError::Invalid {
+            element,
+            value: b.to_string(),
+        })
+    })
+    .transpose()
+}
+
+fn parse_count(element: &'static str, count: &str) -> Result<u32, PainError> {
+    count.parse().map_err(|_| PainError::Invalid {
+        element,
+        value: count.to_string(),
+    })
+}
+
+fn parse_amount(element: &'static str, amount: Option<&str>) -> Result<Euro, PainError> {
+    let amount = amount.ok_or(PainError::Missing(element))?;
+    amount.parse().map_err(|_| PainError::Invalid {
+        element,
+        value: amount.to_string(),
+    })
+}
+
+fn parse_iban(element: &'static str, iban: Option<&str>) -> Result<Iban, PainError> {
+    let iban = iban.ok_or(PainError::Missing(element))?;
+    iban.parse().map_err(|_| PainError::Invalid {
+        element,
+        value: iban.to_string(),
+    })
+}
+
+fn parse_date(element: &'static str, date: Option<&str>) -> Result<Date, PainError> {
+    let date = date.ok_or(PainError::Missing(element))?;
+    let day = date.get(..10).unwrap_or(date);
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map(Date::from)
+        .map_err(|_| PainError::Invalid {
+            element,
+            value: date.to_string(),
+        })
+}
+
+/// One transaction of a file, what [`diff`] compares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub end_to_end_id: String,
+    pub name: String,
+    pub iban: Iban,
+    pub amount: Euro,
+    /// The collection or execution date
+    pub date: Date,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(Instruction),
+    Removed(Instruction),
+    /// The same debtor or creditor with another amount, name, account or
+    /// description
+    Changed {
+        old: Instruction,
+        new: Instruction,
+    },
+}
+
+/// What changed from `old` to `new`. Transactions are the same when their
+/// end-to-end id is, or else when they're the only ones to the same IBAN.
+/// Ids and dates differ between any two runs, so they don't count as changes.
+pub fn diff(old: &[Instruction], new: &[Instruction]) -> Vec<Change> {
+    let mut old_left = old.iter().collect::<Vec<_>>();
+    let mut new_left = vec![];
+    let mut changes = vec![];
+    for n in new {
+        match old_left
+            .iter()
+            .position(|o| o.end_to_end_id == n.end_to_end_id)
+        {
+            Some(i) => changes.extend(changed(old_left.remove(i), n)),
+            None => new_left.push(n),
+        }
+    }
+    let only = |left: &[&Instruction], iban: &Iban| {
+        let mut found = left.iter().enumerate().filter(|(_, i)| &i.iban == iban);
+        match (found.next(), found.next()) {
+            (Some((n, _)), None) => Some(n),
+            _ => None,
+        }
+    };
+    let mut added = vec![];
+    for n in &new_left {
+        let same = only(&old_left, &n.iban).filter(|_| only(&new_left, &n.iban).is_some());
+        match same {
+            Some(i) => changes.extend(changed(old_left.remove(i), n)),
+            None => added.push(Change::Added((*n).clone())),
+        }
+    }
+    changes.extend(old_left.into_iter().cloned().map(Change::Removed));
+    changes.extend(added);
+    changes
+}
+
+fn changed(old: &Instruction, new: &Instruction) -> Option<Change> {
+    let same = old.name == new.name
+        && old.iban == new.iban
+        && old.amount == new.amount
+        && old.description == new.description;
+    (!same).then(|| Change::Changed {
+        old: old.clone(),
+        new: new.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use xml::EmitterConfig;
+
+    use super::*;
+    use crate::testing::{assert_golden, date, golden, iban};
+
+    #[test]
+    fn direct_debits_are_written_back_the_same() {
+        for name in ["pain.008.001.02.xml", "pain.008.001.08.xml"] {
+            let file = golden(name);
+            assert_golden(name, |w, c| {
+                parse_direct_debit(&file[..])
+                    .unwrap()
+                    .write_with_config(w, c)
+                    .unwrap()
+            });
+        }
+    }
+
+    #[test]
+    fn transfers_are_written_back_the_same() {
+        for name in ["pain.001.001.03.xml", "pain.001.001.09.xml"] {
+            let file = golden(name);
+            assert_golden(name, |w, c| {
+                parse_credit_transfer(&file[..])
+                    .unwrap()
+                    .write_with_config(w, c)
+                    .unwrap()
+            });
+        }
+    }
+
+    #[test]
+    fn direct_debits_have_their_details() {
+        let parsed = parse_direct_debit(&golden("pain.008.001.08.xml")[..]).unwrap();
+        assert_eq!(parsed.version(), SchemaVersion::V2019);
+        assert_eq!(parsed.message_id(), "20240301-00000000000000bb");
+        assert_eq!(
+            parsed.instructions(),
+            invoice::tests::document(SchemaVersion::V2019).instructions()
+        );
+        let first = &parsed.payment_information()[0].debtors()[0];
+        assert_eq!(first.code(), "1002");
+        assert_eq!(first.sequence_type(), SequenceType::First);
+        assert_eq!(
+            first.creditor_reference().map(|r| r.as_str()),
+            Some("RF312024031002")
+        );
+        let recurring = &parsed.payment_information()[1].debtors()[0];
+        assert_eq!(recurring.mandate().creditor_id, "NL00ZZZ404840000000");
+        assert_eq!(
+            recurring
+                .amendment()
+                .unwrap()
+                .original_mandate_id
+                .as_deref(),
+            Some("AEGEE-1001")
+        );
+    }
+
+    #[test]
+    fn a_removed_debtor_takes_its_block_and_totals_along() {
+        let file = golden("pain.008.001.08.xml");
+        let parsed = parse_direct_debit(&file[..]).unwrap();
+        let mut without = parse_direct_debit(&file[..]).unwrap();
+        let removed = without
+            .remove_debtor("AEGEEDELFT-0000000000000002")
+            .unwrap();
+        assert_eq!(removed.name(), "Erika Mustermann");
+        assert!(without
+            .remove_debtor("AEGEEDELFT-0000000000000002")
+            .is_none());
+        assert_eq!(without.payment_information().len(), 1);
+        let changes = diff(&parsed.instructions(), &without.instructions());
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], Change::Removed(i) if i.name == "Erika Mustermann"));
+
+        let mut out = vec![];
+        without
+            .write_with_config(&mut out, EmitterConfig::new())
+            .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("<NbOfTxs>1</NbOfTxs><CtrlSum>12.50</CtrlSum><InitgPty>"));
+    }
+
+    #[test]
+    fn merged_halves_give_the_file_back() {
+        let file = golden("pain.008.001.08.xml");
+        let parsed = parse_direct_debit(&file[..]).unwrap();
+        let mut without = parse_direct_debit(&file[..]).unwrap();
+        without.remove_debtor("AEGEEDELFT-0000000000000001");
+        let mut only = parse_direct_debit(&file[..]).unwrap();
+        only.remove_debtor("AEGEEDELFT-0000000000000002");
+        without.merge(only);
+        assert_eq!(without.payment_information().len(), 2);
+        assert!(diff(&parsed.instructions(), &without.instructions()).is_empty());
+        assert_eq!(without.validate(), vec![]);
+        // merged payment informations go last, which is where the recurring
+        // one was, so put back together it is the file it was parsed from
+        assert_golden("pain.008.001.08.xml", |w, c| {
+            without.write_with_config(w, c).unwrap()
+        });
+    }
+
+    #[test]
+    fn diff_ignores_new_ids_and_dates() {
+        // another run pays the same people with new ids
+        let old = parse_direct_debit(&golden("pain.008.001.08.xml")[..])
+            .unwrap()
+            .instructions();
+        let mut new = old.clone();
+        for (n, i) in new.iter_mut().enumerate() {
+            i.end_to_end_id = format!("20240401-1-202404-100{}", n + 1);
+            i.date = date(2024, 4, 2);
+        }
+        assert!(diff(&old, &new).is_empty());
+
+        new[1].amount = Euro::new(15, 0);
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            &changes[0],
+            Change::Changed { old, new }
+                if old.amount == Euro::new(12, 50) && new.amount == Euro::new(15, 0)
+        ));
+    }
+
+    #[test]
+    fn diff_lists_removed_and_added_people() {
+        let old = parse_direct_debit(&golden("pain.008.001.08.xml")[..])
+            .unwrap()
+            .instructions();
+        let mut new = old.clone();
+        new.remove(0);
+        new.push(Instruction {
+            end_to_end_id: "20240401-1-202404-1003".to_string(),
+            name: "Piet Pietersen".to_string(),
+            iban: iban("NL02ABNA0123456789"),
+            amount: Euro::new(5, 0),
+            date: date(2024, 4, 2),
+            description: "Contributie".to_string(),
+        });
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(&changes[0], Change::Removed(i) if i.name == "Erika Mustermann"));
+        assert!(matches!(&changes[1], Change::Added(i) if i.name == "Piet Pietersen"));
+    }
+
+    #[test]
+    fn other_documents_and_versions_are_refused() {
+        assert!(matches!(
+            parse_direct_debit(&golden("pain.001.001.09.xml")[..]),
+            Err(PainError::UnknownDocument { .. })
+        ));
+        let unknown = String::from_utf8(golden("pain.008.001.08.xml"))
+            .unwrap()
+            .replace("pain.008.001.08", "pain.008.003.02");
+        assert!(matches!(
+            parse_direct_debit(unknown.as_bytes()),
+            Err(PainError::UnknownVersion(v)) if v.ends_with("pain.008.003.02")
+        ));
+    }
+}
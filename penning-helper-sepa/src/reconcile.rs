@@ -0,0 +1,463 @@
+use std::{collections::HashMap, fmt::Display};
+
+use penning_helper_types::{Date, Euro, Iban};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camt::{CreditDebit, Entry, EntryTransaction, Statement},
+    ReasonCode, RunId, SequenceType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// A direct debit, the relation pays us
+    Collection,
+    /// A credit transfer, we pay the relation
+    Transfer,
+}
+
+impl Direction {
+    /// Ends the message id of the file, `DD` or `CT`
+    pub(crate) fn message_kind(self) -> &'static str {
+        match self {
+            Direction::Collection => "DD",
+            Direction::Transfer => "CT",
+        }
+    }
+
+    /// How the payment shows up on our statement
+    fn booked_as(self) -> CreditDebit {
+        match self {
+            Direction::Collection => CreditDebit::Credit,
+            Direction::Transfer => CreditDebit::Debit,
+        }
+    }
+}
+
+/// Ends the payment information id of the transfers
+pub(crate) const TRANSFER_SUFFIX: &str = "TRF";
+
+/// A direct debit or transfer that was sent to the bank
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment {
+    pub run: RunId,
+    pub end_to_end_id: String,
+    /// The relation code
+    pub code: String,
+    pub name: String,
+    pub iban: Iban,
+    pub amount: Euro,
+    pub direction: Direction,
+    /// Only for direct debits
+    #[serde(default)]
+    pub sequence_type: Option<SequenceType>,
+    /// The block it was sent in, unknown for runs from before it was kept
+    #[serde(default)]
+    pub payment_information_id: Option<String>,
+    /// When the bank booked it
+    #[serde(default)]
+    pub settled: Option<Date>,
+    /// Why the bank refused it
+    #[serde(default)]
+    pub rejection: Option<ReasonCode>,
+    /// When the booking of a refused payment that was settled was undone
+    #[serde(default)]
+    pub reversed: Option<Date>,
+}
+
+impl Payment {
+    /// Ended the payment information id of its block before there were
+    /// categories, the sequence type for direct debits
+    fn payment_information_suffix(&self) -> &'static str {
+        match self.sequence_type {
+            Some(s) => s.code(),
+            None => TRANSFER_SUFFIX,
+        }
+    }
+
+    /// Whether the file with `message_id` was made by the run of this payment
+    pub fn in_message(&self, message_id: &str) -> bool {
+        message_id.ends_with(&format!("-{}-{}", self.run, self.direction.message_kind()))
+    }
+
+    /// Whether the block with `payment_information_id` has this payment
+    pub fn in_payment_information(&self, payment_information_id: &str) -> bool {
+        match &self.payment_information_id {
+            Some(id) => id == payment_information_id,
+            None => payment_information_id.ends_with(&format!(
+                "-{}-{}",
+                self.run,
+                self.payment_information_suffix()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedOn {
+    EndToEndId,
+    IbanAndAmount,
+}
+
+/// A statement line that belongs to one of our payments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settlement {
+    pub payment: Payment,
+    pub date: Date,
+    pub bank_reference: Option<String>,
+    pub matched_on: MatchedOn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UnmatchedReason {
+    #[error("not booked yet")]
+    NotBooked,
+    #[error("returned by the bank{}", .0.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default())]
+    Returned(Option<String>),
+    #[error("the payment with this id was for {expected}")]
+    AmountDiffers { expected: Euro },
+    #[error("already settled on {0}")]
+    AlreadySettled(Date),
+    #[error("the payment with this id was refused: {0}")]
+    Rejected(ReasonCode),
+    #[error("{0} payments have this IBAN and amount")]
+    Ambiguous(usize),
+    #[error("no payment matches")]
+    NoMatch,
+}
+
+/// A statement line that couldn't be matched to a payment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmatched {
+    pub statement: String,
+    pub date: Option<Date>,
+    pub direction: CreditDebit,
+    pub transaction: EntryTransaction,
+    pub reason: UnmatchedReason,
+}
+
+impl Display for Unmatched {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = match self.direction {
+            CreditDebit::Credit => "+",
+            CreditDebit::Debit => "-",
+        };
+        let date = self.date.map(|d| d.to_string()).unwrap_or_default();
+        let t = &self.transaction;
+        write!(
+            f,
+            "{} {} {}{} {}",
+            self.statement,
+            date,
+            sign,
+            t.amount,
+            t.counterparty_name.as_deref().unwrap_or("unknown")
+        )?;
+        if let Some(iban) = &t.counterparty_iban {
+            write!(f, " {}", iban.pretty())?;
+        }
+        if let Some(id) = t.end_to_end_id() {
+            write!(f, " [{}]", id)?;
+        }
+        if let Some(r) = &t.remittance {
+            write!(f, " \"{}\"", r)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub settled: Vec<Settlement>,
+    pub unmatched: Vec<Unmatched>,
+}
+
+impl Reconciliation {
+    /// One line per unmatched statement line, to check by hand
+    pub fn report(&self) -> String {
+        self.unmatched.iter().map(|u| format!("{}\n", u)).collect()
+    }
+}
+
+/// Matches every statement line against `payments`, first on end-to-end id,
+/// then on IBAN and amount. Payments that are already settled only match on
+/// their id, so importing a statement twice doesn't settle anything twice.
+pub fn reconcile(statements: &[Statement], payments: &[Payment]) -> Reconciliation {
+    let mut matcher = Matcher {
+        payments,
+        taken: HashMap::new(),
+        result: Reconciliation::default(),
+    };
+    for statement in statements {
+        for entry in &statement.entries {
+            for transaction in &entry.transactions {
+                matcher.transaction(&statement.id, entry, transaction);
+            }
+        }
+    }
+    matcher.result
+}
+
+struct Matcher<'p> {
+    payments: &'p [Payment],
+    /// Payments matched during this reconciliation, with the date they were
+    /// settled on
+    taken: HashMap<usize, Date>,
+    result: Reconciliation,
+}
+
+impl Matcher<'_> {
+    fn transaction(&mut self, statement: &str, entry: &Entry, transaction: &EntryTransaction) {
+        let date = entry.booking_date.or(entry.value_date);
+        let unmatched = |reason| Unmatched {
+            statement: statement.to_string(),
+            date,
+            direction: entry.direction,
+            transaction: transaction.clone(),
+            reason,
+        };
+        if !entry.booked {
+            self.result
+                .unmatched
+                .push(unmatched(UnmatchedReason::NotBooked));
+            return;
+        }
+        if entry.reversal || transaction.return_reason.is_some() {
+            let reason = UnmatchedReason::Returned(transaction.return_reason.clone());
+            self.result.unmatched.push(unmatched(reason));
+            return;
+        }
+        let found = self.find(entry.direction, transaction);
+        match found {
+            Ok((index, matched_on)) => {
+                let date = date.unwrap_or_else(Date::today);
+                self.taken.insert(index, date);
+                self.result.settled.push(Settlement {
+                    payment: self.payments[index].clone(),
+                    date,
+                    bank_reference: entry.bank_reference.clone(),
+                    matched_on,
+                });
+            }
+            Err(reason) => self.result.unmatched.push(unmatched(reason)),
+        }
+    }
+
+    fn is_open(&self, index: usize) -> bool {
+        self.payments[index].rejection.is_none() && self.settled_on(index).is_none()
+    }
+
+    /// Before or during this reconciliation
+    fn settled_on(&self, index: usize) -> Option<Date> {
+        self.payments[index]
+            .settled
+            .or_else(|| self.taken.get(&index).copied())
+    }
+
+    fn find(
+        &self,
+        direction: CreditDebit,
+        transaction: &EntryTransaction,
+    ) -> Result<(usize, MatchedOn), UnmatchedReason> {
+        let candidates = self
+            .payments
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.direction.booked_as() == direction);
+        if let Some(id) = transaction.end_to_end_id() {
+            // an id issued again in a later run belongs to the latest one
+            let with_id = candidates
+                .clone()
+                .filter(|(_, p)| p.end_to_end_id == id)
+                .collect::<Vec<_>>();
+            if let Some((i, _)) = with_id
+                .iter()
+                .rev()
+                .find(|(i, p)| p.amount == transaction.amount && self.is_open(*i))
+            {
+                return Ok((*i, MatchedOn::EndToEndId));
+            }
+            if let Some((i, p)) = with_id
+                .iter()
+                .rev()
+                .find(|(_, p)| p.amount == transaction.amount)
+            {
+                if let Some(reason) = &p.rejection {
+                    return Err(UnmatchedReason::Rejected(reason.clone()));
+                }
+                if let Some(date) = self.settled_on(*i) {
+                    return Err(UnmatchedReason::AlreadySettled(date));
+                }
+            }
+            if let Some((_, p)) = with_id.last() {
+                return Err(UnmatchedReason::AmountDiffers { expected: p.amount });
+            }
+        }
+        let Some(iban) = &transaction.counterparty_iban else {
+            return Err(UnmatchedReason::NoMatch);
+        };
+        let matches = candidates
+            .filter(|(i, p)| &p.iban == iban && p.amount == transaction.amount && self.is_open(*i))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        match matches[..] {
+            [i] => Ok((i, MatchedOn::IbanAndAmount)),
+            [] => Err(UnmatchedReason::NoMatch),
+            _ => Err(UnmatchedReason::Ambiguous(matches.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_statements,
+        testing::{bic, config, date, debit, fixture, iban},
+        CreditorDetails, DebtorDetails, IdRegistry,
+    };
+
+    /// Collections from 1001 and 1002 and a transfer to 1003, like in the
+    /// fixture
+    fn registry() -> IdRegistry {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let mut run = config.start_run(&registry, date(2024, 3, 1));
+        for (code, debtor_iban, amount) in [
+            ("1001", "NL91ABNA0417164300", Euro::new(12, 50)),
+            ("1002", "DE89370400440532013000", Euro::new(10, 0)),
+        ] {
+            let details = DebtorDetails {
+                amount,
+                mandate: config.new_mandate(code, date(2023, 9, 1), iban(debtor_iban)),
+                ..debit(&config, code)
+            };
+            config.new_debtor(&mut run, "202403", details).unwrap();
+        }
+        config
+            .new_creditor(
+                &mut run,
+                "202403",
+                CreditorDetails {
+                    code: "1003".to_string(),
+                    name: "Piet Pietersen".to_string(),
+                    amount: Euro::new(25, 0),
+                    bic: Some(bic("INGBNL2A")),
+                    iban: iban("NL20INGB0001234567"),
+                    description: "Payment of positive balance".to_string(),
+                    reference: None,
+                },
+            )
+            .unwrap();
+        registry.record(&run);
+        registry
+    }
+
+    fn notification(transactions: &str) -> Vec<Statement> {
+        let xml = format!(
+            r#"<Document><BkToCstmrDbtCdtNtfctn><Ntfctn><Id>1</Id><Ntry>
+                <Amt>21.50</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+                <BookgDt><DtTm>2024-03-06T10:00:00</DtTm></BookgDt>
+                <NtryDtls>{}</NtryDtls>
+            </Ntry></Ntfctn></BkToCstmrDbtCdtNtfctn></Document>"#,
+            transactions
+        );
+        parse_statements(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn payments_settle_on_end_to_end_id_or_iban_and_amount() {
+        let registry = registry();
+        let statements = parse_statements(fixture("camt.053.001.02.xml")).unwrap();
+        let result = reconcile(&statements, registry.payments());
+        let settled = result
+            .settled
+            .iter()
+            .map(|s| (s.payment.code.as_str(), s.date, s.matched_on))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            settled,
+            vec![
+                ("1001", date(2024, 3, 6), MatchedOn::EndToEndId),
+                ("1002", date(2024, 3, 6), MatchedOn::EndToEndId),
+                ("1003", date(2024, 3, 7), MatchedOn::IbanAndAmount),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_rest_is_reported_with_a_reason() {
+        let registry = registry();
+        let statements = parse_statements(fixture("camt.053.001.02.xml")).unwrap();
+        let result = reconcile(&statements, registry.payments());
+        let reasons = result
+            .unmatched
+            .iter()
+            .map(|u| u.reason.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                UnmatchedReason::Returned(Some("MD06".to_string())),
+                UnmatchedReason::NoMatch,
+                UnmatchedReason::NotBooked,
+            ]
+        );
+        assert!(result.report().contains("K. de Vries"));
+        assert!(result
+            .report()
+            .contains("\"Borrel maart\": no payment matches"));
+    }
+
+    #[test]
+    fn settled_payments_are_not_settled_again() {
+        let mut registry = registry();
+        let statements = parse_statements(fixture("camt.053.001.02.xml")).unwrap();
+        for s in &reconcile(&statements, registry.payments()).settled {
+            assert!(registry.settle(s));
+        }
+        let statements = notification(
+            "<TxDtls><Refs><EndToEndId>20240301-1-202403-1001</EndToEndId></Refs>
+                <Amt>12.50</Amt></TxDtls>",
+        );
+        let result = reconcile(&statements, registry.payments());
+        assert_eq!(result.settled, vec![]);
+        assert_eq!(
+            result.unmatched[0].reason,
+            UnmatchedReason::AlreadySettled(date(2024, 3, 6))
+        );
+    }
+
+    #[test]
+    fn a_different_amount_does_not_settle() {
+        let registry = registry();
+        let statements = notification(
+            "<TxDtls><Refs><EndToEndId>20240301-1-202403-1002</EndToEndId></Refs>
+                <Amt>9.00</Amt></TxDtls>",
+        );
+        let result = reconcile(&statements, registry.payments());
+        assert_eq!(result.settled, vec![]);
+        assert_eq!(
+            result.unmatched[0].reason,
+            UnmatchedReason::AmountDiffers {
+                expected: Euro::new(10, 0)
+            }
+        );
+    }
+
+    #[test]
+    fn rejected_payments_are_not_settled() {
+        let mut payments = registry().payments().to_vec();
+        payments[0].rejection = Some(ReasonCode::new("AM04"));
+        let statements = notification(
+            "<TxDtls><Refs><EndToEndId>20240301-1-202403-1001</EndToEndId></Refs>
+                <Amt>12.50</Amt></TxDtls>",
+        );
+        let result = reconcile(&statements, &payments);
+        assert_eq!(result.settled, vec![]);
+        assert_eq!(
+            result.unmatched[0].reason,
+            UnmatchedReason::Rejected(ReasonCode::new("AM04"))
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use penning_helper_types::{Date, Euro};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camt::CreditDebit,
+    reconcile::{Direction, Reconciliation, Unmatched, UnmatchedReason},
+    store::{self, StoreError},
+    Payment, ReasonCode, RunId,
+};
+
+/// What kind of R-transaction took the money back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReturnKind {
+    /// The debtor's bank sent the collection back
+    Return,
+    /// The debtor asked their bank for the money back
+    Refund,
+    /// We undid the collection ourselves
+    Reversal,
+}
+
+impl ReturnKind {
+    /// The kind a bank return with `reason` is
+    pub fn for_reason(reason: &ReasonCode) -> Self {
+        match reason.code() {
+            "MD06" | "MD01" => ReturnKind::Refund,
+            _ => ReturnKind::Return,
+        }
+    }
+}
+
+impl Display for ReturnKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReturnKind::Return => "Return",
+            ReturnKind::Refund => "Refund",
+            ReturnKind::Reversal => "Reversal",
+        })
+    }
+}
+
+/// A direct debit that came back after it was collected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnedPayment {
+    /// The relation code
+    pub code: String,
+    pub name: String,
+    pub amount: Euro,
+    /// When the money left our account
+    pub date: Date,
+    pub kind: ReturnKind,
+    pub reason: ReasonCode,
+    /// The run and end-to-end id of the collection, unknown when entered by
+    /// hand
+    #[serde(default)]
+    pub run: Option<RunId>,
+    #[serde(default)]
+    pub end_to_end_id: Option<String>,
+    /// The reverse booking is in Conscribo
+    #[serde(default)]
+    pub booked: bool,
+    /// The relation got an email about it
+    #[serde(default)]
+    pub follow_up_sent: bool,
+}
+
+impl ReturnedPayment {
+    /// A return entered by hand
+    pub fn new(
+        code: impl ToString,
+        name: impl ToString,
+        amount: Euro,
+        date: Date,
+        kind: ReturnKind,
+        reason: ReasonCode,
+    ) -> Self {
+        Self {
+            code: code.to_string(),
+            name: name.to_string(),
+            amount,
+            date,
+            kind,
+            reason,
+            run: None,
+            end_to_end_id: None,
+            booked: false,
+            follow_up_sent: false,
+        }
+    }
+
+    /// A return of `payment`
+    pub fn of(payment: &Payment, date: Date, kind: ReturnKind, reason: ReasonCode) -> Self {
+        Self {
+            run: Some(payment.run.clone()),
+            end_to_end_id: Some(payment.end_to_end_id.clone()),
+            ..Self::new(
+                &payment.code,
+                &payment.name,
+                payment.amount,
+                date,
+                kind,
+                reason,
+            )
+        }
+    }
+
+    /// Whether both are about the same money leaving our account
+    fn same_as(&self, other: &Self) -> bool {
+        self.code == other.code
+            && self.amount == other.amount
+            && self.date == other.date
+            && self.end_to_end_id == other.end_to_end_id
+    }
+}
+
+impl Display for ReturnedPayment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({}) {} {}: {}",
+            self.date, self.name, self.code, self.amount, self.kind, self.reason
+        )?;
+        if let Some(id) = &self.end_to_end_id {
+            write!(f, " [{}]", id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every returned collection, to book them, follow them up and see who keeps
+/// failing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReturnLog {
+    returns: Vec<ReturnedPayment>,
+}
+
+impl ReturnLog {
+    /// Reads the log at `path`, a missing file is an empty log
+    pub fn load(path: &Path) -> Result<Self, StoreError> {
+        store::load("return log", path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StoreError> {
+        store::save("return log", path, self)
+    }
+
+    pub fn returns(&self) -> &[ReturnedPayment] {
+        &self.returns
+    }
+
+    /// Keeps the return, false if it was logged already
+    pub fn add(&mut self, returned: ReturnedPayment) -> bool {
+        if self.returns.iter().any(|r| r.same_as(&returned)) {
+            return false;
+        }
+        self.returns.push(returned);
+        true
+    }
+
+    /// Returns without a reverse booking yet
+    pub fn unbooked(&self) -> impl Iterator<Item = &ReturnedPayment> {
+        self.returns.iter().filter(|r| !r.booked)
+    }
+
+    /// Booked returns the relation wasn't told about yet
+    pub fn follow_ups(&self) -> impl Iterator<Item = &ReturnedPayment> {
+        self.returns
+            .iter()
+            .filter(|r| r.booked && !r.follow_up_sent)
+    }
+
+    pub fn mark_booked(&mut self, returned: &ReturnedPayment) -> bool {
+        self.mark(returned, |r| r.booked = true)
+    }
+
+    pub fn mark_followed_up(&mut self, returned: &ReturnedPayment) -> bool {
+        self.mark(returned, |r| r.follow_up_sent = true)
+    }
+
+    fn mark(&mut self, returned: &ReturnedPayment, f: impl FnOnce(&mut ReturnedPayment)) -> bool {
+        match self.returns.iter_mut().find(|r| r.same_as(returned)) {
+            Some(r) => {
+                f(r);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns for `code`, oldest first
+    pub fn failures<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a ReturnedPayment> {
+        self.returns.iter().filter(move |r| r.code == code)
+    }
+
+    /// The relations with at least `min` returns, by code
+    pub fn repeated(&self, min: usize) -> BTreeMap<&str, Vec<&ReturnedPayment>> {
+        let mut by_code = BTreeMap::<&str, Vec<&ReturnedPayment>>::new();
+        for r in &self.returns {
+            by_code.entry(r.code.as_str()).or_default().push(r);
+        }
+        by_code.retain(|_, r| r.len() >= min);
+        by_code
+    }
+
+    /// One block per relation with at least `min` returns
+    pub fn report(&self, min: usize) -> String {
+        let mut report = String::new();
+        for (code, returns) in self.repeated(min) {
+            let name = returns.last().map(|r| r.name.as_str()).unwrap_or_default();
+            report.push_str(&format!("{} ({}): {} returns\n", name, code, returns.len()));
+            for r in returns {
+                report.push_str(&format!("  {}\n", r));
+            }
+        }
+        report
+    }
+}
+
+/// The returned collections among the statement lines `reconciliation`
+/// couldn't match, and the returns that don't belong to any of `payments`
+pub fn returns_from(
+    reconciliation: &Reconciliation,
+    payments: &[Payment],
+) -> (Vec<ReturnedPayment>, Vec<Unmatched>) {
+    let mut returned = vec![];
+    let mut unknown = vec![];
+    for u in &reconciliation.unmatched {
+        let UnmatchedReason::Returned(reason) = &u.reason else {
+            continue;
+        };
+        // the money of a returned collection leaves our account
+        if u.direction != CreditDebit::Debit {
+            continue;
+        }
+        let Some(payment) = returned_payment(u, payments) else {
+            unknown.push(u.clone());
+            continue;
+        };
+        let reason = reason
+            .as_ref()
+            .map(ReasonCode::new)
+            .unwrap_or_else(ReasonCode::unspecified);
+        let kind = ReturnKind::for_reason(&reason);
+        let date = u.date.unwrap_or_else(Date::today);
+        returned.push(ReturnedPayment::of(payment, date, kind, reason));
+    }
+    (returned, unknown)
+}
+
+/// The collection a return is for, on end-to-end id or else on a single
+/// match of IBAN and amount
+fn returned_payment<'p>(u: &Unmatched, payments: &'p [Payment]) -> Option<&'p Payment> {
+    let transaction = &u.transaction;
+    let collections = payments
+        .iter()
+        .filter(|p| p.direction == Direction::Collection);
+    if let Some(id) = transaction.end_to_end_id() {
+        // an id issued again in a later run belongs to the latest one
+        if let Some(p) = collections.clone().rfind(|p| p.end_to_end_id == id) {
+            return Some(p);
+        }
+    }
+    let iban = transaction.counterparty_iban.as_ref()?;
+    let matches = collections
+        .filter(|p| &p.iban == iban && p.amount == transaction.amount)
+        .collect::<Vec<_>>();
+    match matches[..] {
+        [p] => Some(p),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_statements, reconcile,
+        testing::{config, date, debit, fixture, iban},
+        DebtorDetails, IdRegistry,
+    };
+
+    /// The collection from 1002 that comes back in the fixture
+    fn returned() -> Vec<ReturnedPayment> {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let mut run = config.start_run(&registry, date(2024, 3, 1));
+        let details = DebtorDetails {
+            name: "Erika Mustermann".to_string(),
+            mandate: config.new_mandate("1002", date(2023, 9, 1), iban("DE89370400440532013000")),
+            ..debit(&config, "1002")
+        };
+        config.new_debtor(&mut run, "202403", details).unwrap();
+        registry.record(&run);
+
+        let statements = parse_statements(fixture("camt.053.001.02.xml")).unwrap();
+        let result = reconcile(&statements, registry.payments());
+        let (returned, unknown) = returns_from(&result, registry.payments());
+        assert_eq!(unknown, vec![]);
+        returned
+    }
+
+    fn again(reason: &str) -> ReturnedPayment {
+        ReturnedPayment::new(
+            "1002",
+            "Erika Mustermann",
+            Euro::new(10, 0),
+            date(2024, 4, 8),
+            ReturnKind::Return,
+            ReasonCode::new(reason),
+        )
+    }
+
+    #[test]
+    fn returns_are_found_in_the_statement() {
+        let returned = returned();
+        assert_eq!(returned.len(), 1);
+        assert_eq!(returned[0].code, "1002");
+        assert_eq!(returned[0].date, date(2024, 3, 7));
+        assert_eq!(returned[0].kind, ReturnKind::Refund);
+        assert_eq!(returned[0].reason, ReasonCode::new("MD06"));
+        assert_eq!(
+            returned[0].end_to_end_id.as_deref(),
+            Some("20240301-1-202403-1002")
+        );
+    }
+
+    #[test]
+    fn the_same_return_is_logged_once() {
+        let returned = returned();
+        let mut log = ReturnLog::default();
+        assert!(log.add(returned[0].clone()));
+        // importing the same statement again adds nothing
+        assert!(!log.add(returned[0].clone()));
+        assert_eq!(log.returns().len(), 1);
+    }
+
+    #[test]
+    fn returns_are_booked_and_then_followed_up() {
+        let returned = returned();
+        let mut log = ReturnLog::default();
+        log.add(returned[0].clone());
+        log.add(again("AM04"));
+        assert_eq!(log.unbooked().count(), 2);
+        assert_eq!(log.follow_ups().count(), 0);
+        assert!(log.mark_booked(&returned[0]));
+        assert_eq!(log.unbooked().count(), 1);
+        assert_eq!(log.follow_ups().count(), 1);
+        assert!(log.mark_followed_up(&returned[0]));
+        assert_eq!(log.follow_ups().count(), 0);
+    }
+
+    #[test]
+    fn repeated_returns_are_reported() {
+        let returned = returned();
+        let mut log = ReturnLog::default();
+        log.add(returned[0].clone());
+        log.add(again("AM04"));
+        assert_eq!(log.failures("1002").count(), 2);
+        assert_eq!(log.repeated(2).keys().collect::<Vec<_>>(), vec![&"1002"]);
+        assert!(log.repeated(3).is_empty());
+        let report = log.report(2);
+        assert!(report.starts_with("Erika Mustermann (1002): 2 returns\n"));
+        assert!(report.contains("AM04 (insufficient funds)"));
+    }
+}
@@ -0,0 +1,516 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use penning_helper_types::{Date, Euro};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{invoice, store, transfer, Category, Direction, Run, RunId, StoreError};
+
+/// One member in a saved file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedPayment {
+    /// The relation code
+    pub code: String,
+    pub name: String,
+    pub amount: Euro,
+    pub category: Category,
+    /// The invoice period, like `202403` or `C2024`
+    pub period: String,
+    pub end_to_end_id: String,
+    /// The collection or execution date
+    pub date: Date,
+}
+
+/// A pain.008 or pain.001 file as it was saved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub run: RunId,
+    pub message_id: String,
+    pub direction: Direction,
+    pub created: Date,
+    /// SHA-256 of the file as it was written, in lowercase hex
+    pub file_hash: String,
+    pub payments: Vec<RecordedPayment>,
+}
+
+impl DocumentRecord {
+    /// The debtors of `document`, which `run` made. Their codes and periods
+    /// are those the run issued their end-to-end ids for.
+    pub fn direct_debit(run: &Run, document: &invoice::Document) -> Self {
+        let payments = document
+            .payment_information()
+            .iter()
+            .flat_map(|p| {
+                p.debtors().iter().map(|d| {
+                    recorded(
+                        run,
+                        d.end_to_end_id(),
+                        d.name(),
+                        d.amount(),
+                        d.category(),
+                        p.collection_date(),
+                    )
+                })
+            })
+            .collect();
+        Self::new(run, document.message_id(), Direction::Collection, payments)
+    }
+
+    /// The creditors of `document`, which `run` made
+    pub fn credit_transfer(run: &Run, document: &transfer::Document) -> Self {
+        let payments = document
+            .payment_information()
+            .iter()
+            .flat_map(|p| {
+                p.creditors().iter().map(|c| {
+                    recorded(
+                        run,
+                        c.end_to_end_id(),
+                        c.name(),
+                        c.amount(),
+                        c.category(),
+                        p.execution_date(),
+                    )
+                })
+            })
+            .collect();
+        Self::new(run, document.message_id(), Direction::Transfer, payments)
+    }
+
+    fn new(
+        run: &Run,
+        message_id: &str,
+        direction: Direction,
+        payments: Vec<RecordedPayment>,
+    ) -> Self {
+        Self {
+            run: run.id().clone(),
+            message_id: message_id.to_string(),
+            direction,
+            created: run.date(),
+            file_hash: String::new(),
+            payments,
+        }
+    }
+
+    /// Keeps the hash of `xml`, the file as it was written
+    pub fn with_file(mut self, xml: &[u8]) -> Self {
+        self.file_hash = file_hash(xml);
+        self
+    }
+
+    pub fn total(&self) -> Euro {
+        self.payments.iter().map(|p| p.amount).sum()
+    }
+}
+
+fn recorded(
+    run: &Run,
+    end_to_end_id: &str,
+    name: &str,
+    amount: Euro,
+    category: Category,
+    date: Date,
+) -> RecordedPayment {
+    let (code, period) = match run.issued_for(end_to_end_id) {
+        Some(i) => (i.code.clone(), i.period.clone()),
+        None => (String::new(), String::new()),
+    };
+    RecordedPayment {
+        code,
+        name: name.to_string(),
+        amount,
+        category,
+        period,
+        end_to_end_id: end_to_end_id.to_string(),
+        date,
+    }
+}
+
+/// SHA-256 of `bytes` in lowercase hex, to tell later whether a file is the
+/// one that was recorded
+pub fn file_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The invoice email for a period went out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailRecord {
+    pub code: String,
+    pub period: String,
+    pub date: Date,
+}
+
+/// A member who would be collected for a period that an earlier file
+/// already collected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleCollection {
+    pub code: String,
+    pub name: String,
+    pub period: String,
+    /// The run and message id of the earlier file
+    pub run: RunId,
+    pub message_id: String,
+    pub date: Date,
+}
+
+impl Display for DoubleCollection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) was already collected for {} on {} in {}",
+            self.name, self.code, self.period, self.date, self.message_id
+        )
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RunStoreError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("{} members were already collected for the same period, first {}", .0.len(), .0[0])]
+    AlreadyCollected(Vec<DoubleCollection>),
+}
+
+/// One line of the file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Document(DocumentRecord),
+    EmailSent(EmailRecord),
+}
+
+/// Every file that was saved and every invoice email that was sent, so the
+/// treasurer can see who was debited when and nobody is collected twice for
+/// the same period by accident. The file is only ever appended to.
+#[derive(Debug, Clone, Default)]
+pub struct RunStore {
+    path: PathBuf,
+    documents: Vec<DocumentRecord>,
+    emails: Vec<EmailRecord>,
+}
+
+impl RunStore {
+    /// Reads the store at `path`, a missing file is an empty store
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        let mut this = Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        for event in store::load_lines("run store", path)? {
+            this.apply(event);
+        }
+        Ok(this)
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Document(d) => self.documents.push(d),
+            Event::EmailSent(e) => self.emails.push(e),
+        }
+    }
+
+    fn append(&mut self, event: Event) -> Result<(), StoreError> {
+        store::append("run store", &self.path, &event)?;
+        self.apply(event);
+        Ok(())
+    }
+
+    /// Oldest first
+    pub fn documents(&self) -> &[DocumentRecord] {
+        &self.documents
+    }
+
+    pub fn emails(&self) -> &[EmailRecord] {
+        &self.emails
+    }
+
+    /// Keeps `document`, unless it collects a member for a period an earlier
+    /// file already collected
+    pub fn record(&mut self, document: DocumentRecord) -> Result<(), RunStoreError> {
+        let double = self.double_collections(&document);
+        if !double.is_empty() {
+            return Err(RunStoreError::AlreadyCollected(double));
+        }
+        Ok(self.append(Event::Document(document))?)
+    }
+
+    /// Keeps `document` without checking it, for collecting a period again
+    /// on purpose, like after the first collection came back
+    pub fn record_again(&mut self, document: DocumentRecord) -> Result<(), StoreError> {
+        self.append(Event::Document(document))
+    }
+
+    pub fn mark_email_sent(
+        &mut self,
+        code: &str,
+        period: &str,
+        date: Date,
+    ) -> Result<(), StoreError> {
+        self.append(Event::EmailSent(EmailRecord {
+            code: code.to_string(),
+            period: period.to_string(),
+            date,
+        }))
+    }
+
+    pub fn email_sent(&self, code: &str, period: &str) -> bool {
+        self.emails
+            .iter()
+            .any(|e| e.code == code && e.period == period)
+    }
+
+    /// Every payment for `code` in every file, oldest first
+    pub fn history<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> impl Iterator<Item = (&'a DocumentRecord, &'a RecordedPayment)> {
+        self.documents
+            .iter()
+            .flat_map(|d| d.payments.iter().map(move |p| (d, p)))
+            .filter(move |(_, p)| p.code == code)
+    }
+
+    /// The first file that collected `code` for `period`
+    pub fn collected(
+        &self,
+        code: &str,
+        period: &str,
+    ) -> Option<(&DocumentRecord, &RecordedPayment)> {
+        self.documents
+            .iter()
+            .filter(|d| d.direction == Direction::Collection)
+            .flat_map(|d| d.payments.iter().map(move |p| (d, p)))
+            .find(|(_, p)| p.code == code && p.period == period)
+    }
+
+    /// The members `document` would collect for a period that was already
+    /// collected. Transfers are never double.
+    pub fn double_collections(&self, document: &DocumentRecord) -> Vec<DoubleCollection> {
+        if document.direction != Direction::Collection {
+            return vec![];
+        }
+        document
+            .payments
+            .iter()
+            .filter(|p| !p.code.is_empty())
+            .filter_map(|p| {
+                let (earlier, collected) = self.collected(&p.code, &p.period)?;
+                Some(DoubleCollection {
+                    code: p.code.clone(),
+                    name: p.name.clone(),
+                    period: p.period.clone(),
+                    run: earlier.run.clone(),
+                    message_id: earlier.message_id.clone(),
+                    date: collected.date,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{bic, config, date, debit, iban},
+        CreditorDetails, DebtorDetails, IdRegistry, SEPAConfig,
+    };
+
+    fn store_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "penning-helper-runs-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// A direct debit file collecting €25 from each of `collections`, a code
+    /// and a period
+    fn direct_debit(
+        config: &SEPAConfig,
+        registry: &IdRegistry,
+        date: Date,
+        collections: &[(&str, &str, Category)],
+    ) -> (Run, invoice::Document) {
+        let mut run = config.start_run(registry, date);
+        let debtors = collections
+            .iter()
+            .map(|(code, period, category)| {
+                let details = DebtorDetails {
+                    name: format!("Member {}", code),
+                    amount: Euro::new(25, 0),
+                    ..debit(config, code)
+                };
+                config
+                    .new_debtor(&mut run, period, details)
+                    .unwrap()
+                    .with_category(*category)
+            })
+            .collect();
+        let blocks = config.new_invoice_payment_informations(&mut run, debtors);
+        let document = config.new_invoice_document(&run, blocks);
+        (run, document)
+    }
+
+    fn credit_transfer(config: &SEPAConfig, registry: &IdRegistry) -> DocumentRecord {
+        let mut run = config.start_run(registry, date(2024, 3, 1));
+        let creditor = config
+            .new_creditor(
+                &mut run,
+                "202403",
+                CreditorDetails {
+                    code: "1003".to_string(),
+                    name: "Member 1003".to_string(),
+                    amount: Euro::new(7, 50),
+                    bic: Some(bic("ABNANL2A")),
+                    iban: iban("DE89370400440532013000"),
+                    description: "Refund".to_string(),
+                    reference: None,
+                },
+            )
+            .unwrap();
+        let blocks = config.new_transfer_payment_informations(&mut run, vec![creditor]);
+        DocumentRecord::credit_transfer(&run, &config.new_transfer_document(&run, blocks))
+    }
+
+    #[test]
+    fn records_keep_the_hash_and_the_payments() {
+        let config = config();
+        let (run, invoice) = direct_debit(
+            &config,
+            &IdRegistry::default(),
+            date(2024, 3, 1),
+            &[
+                ("1001", "C2024", Category::Contribution),
+                ("1002", "202403", Category::Balance),
+            ],
+        );
+        let record = DocumentRecord::direct_debit(&run, &invoice);
+        let mut xml = vec![];
+        invoice.write(&mut xml).unwrap();
+        let record = record.with_file(&xml);
+        assert_eq!(record.file_hash, file_hash(&xml));
+        assert_eq!(record.file_hash.len(), 64);
+        assert_eq!(record.message_id, "AEGEEDELFT-20240301-1-DD");
+        assert_eq!(record.total(), Euro::new(50, 0));
+
+        let transfers = credit_transfer(&config, &IdRegistry::default());
+        assert_eq!(transfers.direction, Direction::Transfer);
+        assert_eq!(transfers.payments[0].code, "1003");
+    }
+
+    #[test]
+    fn everything_is_read_back_from_the_file() {
+        let config = config();
+        let (run, invoice) = direct_debit(
+            &config,
+            &IdRegistry::default(),
+            date(2024, 3, 1),
+            &[
+                ("1001", "C2024", Category::Contribution),
+                ("1002", "202403", Category::Balance),
+            ],
+        );
+        let path = store_path("read-back");
+        let mut store = RunStore::open(&path).unwrap();
+        store
+            .record(DocumentRecord::direct_debit(&run, &invoice))
+            .unwrap();
+        store
+            .record(credit_transfer(&config, &IdRegistry::default()))
+            .unwrap();
+        store
+            .mark_email_sent("1001", "C2024", date(2024, 3, 1))
+            .unwrap();
+
+        let store = RunStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(store.documents().len(), 2);
+        assert!(store.email_sent("1001", "C2024"));
+        assert!(!store.email_sent("1002", "202403"));
+        let (document, payment) = store.collected("1001", "C2024").unwrap();
+        assert_eq!(document.run, *run.id());
+        assert_eq!(payment.category, Category::Contribution);
+        assert_eq!(payment.amount, Euro::new(25, 0));
+        assert!(store.collected("1001", "C2025").is_none());
+        // transfers are not collections
+        assert!(store.collected("1003", "202403").is_none());
+        assert_eq!(store.history("1003").count(), 1);
+    }
+
+    #[test]
+    fn collecting_the_same_period_twice_is_refused() {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let (run, invoice) = direct_debit(
+            &config,
+            &registry,
+            date(2024, 3, 1),
+            &[("1001", "C2024", Category::Contribution)],
+        );
+        let path = store_path("double");
+        let mut store = RunStore::open(&path).unwrap();
+        store
+            .record(DocumentRecord::direct_debit(&run, &invoice))
+            .unwrap();
+        registry.record(&run);
+
+        let (second, invoice) = direct_debit(
+            &config,
+            &registry,
+            date(2024, 3, 8),
+            &[
+                ("1001", "C2024", Category::Contribution),
+                ("1001", "202403", Category::Balance),
+            ],
+        );
+        let again = DocumentRecord::direct_debit(&second, &invoice);
+        let result = store.record(again);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(RunStoreError::AlreadyCollected(double)) => {
+                assert_eq!(double.len(), 1);
+                assert_eq!(double[0].code, "1001");
+                assert_eq!(double[0].period, "C2024");
+                assert_eq!(double[0].run, *run.id());
+            }
+            other => panic!("expected a double collection, got {:?}", other),
+        }
+        assert_eq!(store.documents().len(), 1);
+    }
+
+    #[test]
+    fn collecting_again_on_purpose_is_kept() {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let collections = [("1001", "C2024", Category::Contribution)];
+        let (run, invoice) = direct_debit(&config, &registry, date(2024, 3, 1), &collections);
+        let path = store_path("again");
+        let mut store = RunStore::open(&path).unwrap();
+        store
+            .record(DocumentRecord::direct_debit(&run, &invoice))
+            .unwrap();
+        registry.record(&run);
+
+        let (second, invoice) = direct_debit(&config, &registry, date(2024, 3, 8), &collections);
+        store
+            .record_again(DocumentRecord::direct_debit(&second, &invoice))
+            .unwrap();
+        // and paying someone twice is never blocked
+        let transfers = credit_transfer(&config, &registry);
+        store.record(transfers.clone()).unwrap();
+        store.record(transfers).unwrap();
+        assert_eq!(store.history("1001").count(), 2);
+        assert_eq!(RunStore::open(&path).unwrap().documents().len(), 4);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use crate::validate::is_sepa_char;
+
+/// A field that was changed to fit the SEPA character set or length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sanitised {
+    /// The block or transaction, with the original name
+    pub location: String,
+    /// The XML element, like `Dbtr/Nm`
+    pub element: &'static str,
+    pub original: String,
+    pub sanitised: String,
+}
+
+impl Display for Sanitised {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {}: \"{}\" became \"{}\"",
+            self.location, self.element, self.original, self.sanitised
+        )
+    }
+}
+
+/// What to write instead of a character outside the SEPA set, `None` drops it
+fn replacement(c: char) -> Option<&'static str> {
+    let s = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'ď' | 'đ' | 'ð' => "d",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'ĥ' | 'ħ' => "h",
+        'Ĥ' | 'Ħ' => "H",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'ĳ' => "ij",
+        'Ĳ' => "IJ",
+        'ĵ' => "j",
+        'Ĵ' => "J",
+        'ķ' => "k",
+        'Ķ' => "K",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' | 'Ș' => "S",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'Ţ' | 'Ť' | 'Ŧ' | 'Ț' => "T",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ŵ' => "w",
+        'Ŵ' => "W",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'Ý' | 'Ÿ' | 'Ŷ' => "Y",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        '&' => "+",
+        '_' | '–' | '—' | '‐' => "-",
+        '"' | '‘' | '’' | '‚' | '‛' | '“' | '”' | '„' | '´' | '`' => "'",
+        ';' => ",",
+        '!' => ".",
+        '€' => "EUR",
+        '@' => "(at)",
+        '[' | '{' | '<' => "(",
+        ']' | '}' | '>' => ")",
+        '\\' => "/",
+        '×' => "x",
+        c if c.is_whitespace() => " ",
+        _ => return None,
+    };
+    Some(s)
+}
+
+/// `value` in the SEPA character set and at most `max` characters long
+pub fn sanitise(value: &str, max: usize) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if is_sepa_char(c) {
+            out.push(c);
+        } else if let Some(r) = replacement(c) {
+            out.push_str(r);
+        }
+    }
+    // replacing and dropping can leave runs of spaces
+    let out = out.split_whitespace().collect::<Vec<_>>().join(" ");
+    out.chars()
+        .take(max)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Collects the changes for one document
+#[derive(Debug, Default)]
+pub(crate) struct Sanitiser {
+    changes: Vec<Sanitised>,
+}
+
+impl Sanitiser {
+    pub(crate) fn field(
+        &mut self,
+        location: &str,
+        element: &'static str,
+        value: &mut String,
+        max: usize,
+    ) {
+        let sanitised = sanitise(value, max);
+        if sanitised != *value {
+            let original = std::mem::replace(value, sanitised.clone());
+            self.changes.push(Sanitised {
+                location: location.to_string(),
+                element,
+                original,
+                sanitised,
+            });
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<Sanitised> {
+        self.changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letters_are_transliterated() {
+        assert_eq!(
+            sanitise("Zoë Müller-Straße & Søn", 70),
+            "Zoe Muller-Strasse + Son"
+        );
+        assert_eq!(sanitise("Borrel 🍺 «Œuvre»", 70), "Borrel OEuvre");
+    }
+
+    #[test]
+    fn long_values_are_cut_off() {
+        assert_eq!(sanitise("Ångström", 3), "Ang");
+        assert_eq!(sanitise("a  b", 2), "a");
+    }
+
+    #[test]
+    fn valid_values_stay_the_same() {
+        assert_eq!(
+            sanitise("Payment of positive balance", 140),
+            "Payment of positive balance"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use xml::writer::XmlEvent;
+
+pub use penning_helper_config::SchemaVersion;
+
+pub(crate) trait SchemaVersionExt {
+    fn direct_debit_namespace(&self) -> &'static str;
+    fn credit_transfer_namespace(&self) -> &'static str;
+    /// The element holding a BIC inside `FinInstnId`
+    fn bic_tag(&self) -> &'static str;
+    /// Whether the BIC of the other party may be left out
+    fn bic_optional(&self) -> bool;
+}
+
+impl SchemaVersionExt for SchemaVersion {
+    fn direct_debit_namespace(&self) -> &'static str {
+        match self {
+            SchemaVersion::V2009 => "urn:iso:std:iso:20022:tech:xsd:pain.008.001.02",
+            SchemaVersion::V2019 => "urn:iso:std:iso:20022:tech:xsd:pain.008.001.08",
+        }
+    }
+
+    fn credit_transfer_namespace(&self) -> &'static str {
+        match self {
+            SchemaVersion::V2009 => "urn:iso:std:iso:20022:tech:xsd:pain.001.001.03",
+            SchemaVersion::V2019 => "urn:iso:std:iso:20022:tech:xsd:pain.001.001.09",
+        }
+    }
+
+    fn bic_tag(&self) -> &'static str {
+        match self {
+            SchemaVersion::V2009 => "BIC",
+            SchemaVersion::V2019 => "BICFI",
+        }
+    }
+
+    fn bic_optional(&self) -> bool {
+        matches!(self, SchemaVersion::V2019)
+    }
+}
+
+/// `FinInstnId` with the BIC, or with the EPC "NOTPROVIDED" placeholder when
+/// there is none.
+pub(crate) fn financial_institution(
+    bic: Option<&str>,
+    version: SchemaVersion,
+) -> Vec<XmlEvent<'_>> {
+    let mut v = vec![XmlEvent::start_element("FinInstnId").into()];
+    match bic {
+        Some(bic) => {
+            v.push(XmlEvent::start_element(version.bic_tag()).into());
+            v.push(XmlEvent::characters(bic));
+            v.push(XmlEvent::end_element().into());
+        }
+        None => {
+            v.push(XmlEvent::start_element("Othr").into());
+            v.push(XmlEvent::start_element("Id").into());
+            v.push(XmlEvent::characters("NOTPROVIDED"));
+            v.push(XmlEvent::end_element().into());
+            v.push(XmlEvent::end_element().into());
+        }
+    }
+    v.push(XmlEvent::end_element().into());
+    v
+}
+
+/// `RmtInf` with the structured creditor reference when there is one, and
+/// otherwise the description. SEPA allows only one of the two.
+pub(crate) fn remittance_information<'a>(
+    description: &'a str,
+    reference: Option<&'a str>,
+) -> Vec<XmlEvent<'a>> {
+    let mut v = vec![XmlEvent::start_element("RmtInf").into()];
+    match reference {
+        Some(reference) => v.extend([
+            XmlEvent::start_element("Strd").into(),
+            XmlEvent::start_element("CdtrRefInf").into(),
+            XmlEvent::start_element("Tp").into(),
+            XmlEvent::start_element("CdOrPrtry").into(),
+            XmlEvent::start_element("Cd").into(),
+            XmlEvent::characters("SCOR"),
+            XmlEvent::end_element().into(),
+            XmlEvent::end_element().into(),
+            XmlEvent::start_element("Issr").into(),
+            XmlEvent::characters("ISO"),
+            XmlEvent::end_element().into(),
+            XmlEvent::end_element().into(),
+            XmlEvent::start_element("Ref").into(),
+            XmlEvent::characters(reference),
+            XmlEvent::end_element().into(),
+            XmlEvent::end_element().into(),
+            XmlEvent::end_element().into(),
+        ]),
+        None => v.extend([
+            XmlEvent::start_element("Ustrd").into(),
+            XmlEvent::characters(description),
+            XmlEvent::end_element().into(),
+        ]),
+    }
+    v.push(XmlEvent::end_element().into());
+    v
+}
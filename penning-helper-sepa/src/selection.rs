@@ -0,0 +1,299 @@
+use chrono::NaiveDate;
+use penning_helper_types::{Bic, BicDirectory, CreditorReference, Date, Euro, Iban, Lenient};
+
+use crate::{
+    creditor_reference, Category, MandateFinished, MandateLedger, SEPAConfig, SequenceType,
+};
+
+/// Which relations to invoice, both the command line and the interface pick
+/// one of these
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// Every open balance
+    #[default]
+    All,
+    /// Balances over 100 euros
+    OwesUsALot,
+    /// Balances under -10 euros, which we pay out
+    IsOwedByUs,
+    /// The yearly contribution, at most 50 euros
+    Contribution,
+    /// The yearly alumni contribution
+    AlumniContribution,
+}
+
+impl Selection {
+    /// What an invoice is for, relations are only invoiced once per period.
+    /// Contributions are yearly, balances monthly.
+    pub fn period(&self, date: Date) -> String {
+        match self {
+            Selection::Contribution => format!("C{}", date.format("%Y")),
+            Selection::AlumniContribution => format!("A{}", date.format("%Y")),
+            _ => date.format("%Y%m").to_string(),
+        }
+    }
+
+    /// Each kind of invoice goes in its own block, so the bank books it
+    /// separately
+    pub fn category(&self) -> Category {
+        match self {
+            Selection::Contribution => Category::Contribution,
+            Selection::AlumniContribution => Category::AlumniContribution,
+            _ => Category::Balance,
+        }
+    }
+
+    /// The RF reference of the invoice for `code` when they're turned on, the
+    /// same in the file, the pdf and the email
+    pub fn creditor_reference(
+        &self,
+        enabled: bool,
+        code: &str,
+        date: Date,
+    ) -> Option<CreditorReference> {
+        if !enabled {
+            return None;
+        }
+        creditor_reference(code, &self.period(date)).ok()
+    }
+
+    /// Whether `relation` with an open balance of `total` is invoiced
+    pub fn filter(&self, relation: &InvoiceRelation, total: Euro) -> bool {
+        match self {
+            Selection::All => true,
+            Selection::OwesUsALot => total > Euro::from(100),
+            Selection::IsOwedByUs => total < Euro::from(-10),
+            Selection::Contribution => total > Euro::from(0),
+            Selection::AlumniContribution => relation.alumni_contributie > Euro::from(0),
+        }
+    }
+}
+
+/// What invoicing needs to know about a relation, the open balance is kept
+/// by whoever collects the transactions
+#[derive(Clone, Debug)]
+pub struct InvoiceRelation {
+    pub name: String,
+    pub code: String,
+    pub membership_date: NaiveDate,
+    pub iban: Lenient<Iban>,
+    pub bic: Lenient<Bic>,
+    pub email: String,
+    /// The membership has ended, so there won't be any later collections
+    pub membership_ended: bool,
+    pub alumni_contributie: Euro,
+}
+
+impl InvoiceRelation {
+    /// The IBAN and, unless it's left empty, the BIC. A missing BIC can be
+    /// derived from the IBAN when the SEPA file is made.
+    pub fn bank_details(&self) -> Option<(Iban, Option<Bic>)> {
+        let iban = self.iban.valid()?.clone();
+        match &self.bic {
+            Lenient::Missing => Some((iban, None)),
+            Lenient::Valid(bic) => Some((iban, Some(bic.clone()))),
+            Lenient::Invalid { .. } => None,
+        }
+    }
+
+    pub fn has_bank_details(&self, sepa: Option<&SEPAConfig>) -> bool {
+        match self.bank_details() {
+            Some((_, Some(_))) => true,
+            Some((iban, None)) => bic_resolvable(sepa, &iban),
+            None => false,
+        }
+    }
+
+    /// Everything that keeps this relation out of the SEPA file
+    pub fn problems(&self, sepa: Option<&SEPAConfig>) -> Vec<String> {
+        let mut problems = vec![];
+        if self.email.is_empty() {
+            problems.push("No email address".to_string());
+        }
+        match self.iban.get() {
+            None => problems.push("No IBAN".to_string()),
+            Some(Err(e)) => problems.push(e.to_string()),
+            Some(Ok(iban)) if self.bic.is_missing() && !bic_resolvable(sepa, iban) => {
+                problems.push("No BIC, and it can't be derived from the IBAN".to_string())
+            }
+            Some(Ok(_)) => {}
+        }
+        if let Some(Err(e)) = self.bic.get() {
+            problems.push(e.to_string());
+        }
+        problems
+    }
+
+    /// Follows the mandate's history. The last collection after a membership
+    /// ended closes the mandate, and a new mandate for an alumni contribution
+    /// is only used once. Either way the next collection needs a new mandate.
+    pub fn sequence_type(
+        &self,
+        selection: Selection,
+        ledger: &MandateLedger,
+    ) -> Result<SequenceType, MandateFinished> {
+        let one_off = self.membership_ended || selection == Selection::AlumniContribution;
+        Ok(match ledger.next_sequence_type(&self.code)? {
+            SequenceType::Recurring if self.membership_ended => SequenceType::Final,
+            SequenceType::First if one_off => SequenceType::OneOff,
+            sequence_type => sequence_type,
+        })
+    }
+
+    /// The amount to collect of the open balance `total` and its
+    /// description, balances are collected 100 euros at a time
+    pub fn collection(&self, selection: Selection, total: Euro) -> (Euro, &'static str) {
+        match selection {
+            Selection::AlumniContribution => (self.alumni_contributie, "Alumni Contributie"),
+            Selection::Contribution => (total.clamp(Euro::from(0), Euro::from(50)), "Contributie"),
+            _ if total >= 100.into() => (100.into(), "Partial invoice of open AEGEE-Delft balance"),
+            _ => (total, "Invoice of open AEGEE-Delft balance"),
+        }
+    }
+
+    /// The amount of the open balance `total` left to transfer by hand, after
+    /// the direct debit if there is one
+    pub fn by_hand(&self, sepa: Option<&SEPAConfig>, total: Euro) -> Euro {
+        if self.has_bank_details(sepa) {
+            total - Euro::from(100)
+        } else {
+            total
+        }
+    }
+}
+
+/// Whether a missing BIC is no problem for this IBAN
+fn bic_resolvable(sepa: Option<&SEPAConfig>, iban: &Iban) -> bool {
+    match sepa {
+        Some(sepa) => sepa.resolve_bic(None, iban).is_ok(),
+        None => BicDirectory::bundled().lookup(iban).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{config, date, iban};
+
+    fn jan() -> InvoiceRelation {
+        InvoiceRelation {
+            name: "Jan Jansen".to_string(),
+            code: "1001".to_string(),
+            membership_date: *date(2023, 9, 1),
+            iban: "NL91ABNA0417164300".into(),
+            bic: Lenient::Missing,
+            email: "jan@example.com".to_string(),
+            membership_ended: false,
+            alumni_contributie: Euro::default(),
+        }
+    }
+
+    #[test]
+    fn a_derivable_bic_is_no_problem() {
+        assert_eq!(jan().problems(Some(&config())), Vec::<String>::new());
+        assert!(jan().has_bank_details(Some(&config())));
+    }
+
+    #[test]
+    fn every_problem_is_listed() {
+        let nobody = InvoiceRelation {
+            email: String::new(),
+            iban: "NL00ABNA0000000000".into(),
+            ..jan()
+        };
+        assert_eq!(nobody.problems(Some(&config())).len(), 2);
+        assert!(!nobody.has_bank_details(Some(&config())));
+    }
+
+    #[test]
+    fn balances_are_collected_100_euros_at_a_time() {
+        assert_eq!(
+            jan().collection(Selection::All, Euro::new(120, 0)),
+            (
+                Euro::from(100),
+                "Partial invoice of open AEGEE-Delft balance"
+            )
+        );
+        assert_eq!(
+            jan()
+                .collection(Selection::Contribution, Euro::new(120, 0))
+                .0,
+            Euro::from(50)
+        );
+    }
+
+    #[test]
+    fn the_rest_is_transferred_by_hand() {
+        assert_eq!(
+            jan().by_hand(Some(&config()), Euro::new(120, 0)),
+            Euro::from(20)
+        );
+        let without_account = InvoiceRelation {
+            iban: Lenient::Missing,
+            ..jan()
+        };
+        assert_eq!(
+            without_account.by_hand(Some(&config()), Euro::new(120, 0)),
+            Euro::new(120, 0)
+        );
+    }
+
+    #[test]
+    fn selections_filter_on_the_balance() {
+        assert!(Selection::OwesUsALot.filter(&jan(), Euro::new(120, 0)));
+        assert!(!Selection::IsOwedByUs.filter(&jan(), Euro::new(120, 0)));
+        assert!(!Selection::AlumniContribution.filter(&jan(), Euro::new(120, 0)));
+    }
+
+    #[test]
+    fn contributions_are_yearly_and_balances_monthly() {
+        assert_eq!(Selection::Contribution.period(date(2024, 3, 1)), "C2024");
+        assert_eq!(Selection::All.period(date(2024, 3, 1)), "202403");
+    }
+
+    #[test]
+    fn leaving_members_get_a_final_collection() {
+        let mut ledger = MandateLedger::default();
+        assert_eq!(
+            jan().sequence_type(Selection::All, &ledger),
+            Ok(SequenceType::First)
+        );
+        let mandate = config().new_mandate("1001", date(2023, 9, 1), iban("NL91ABNA0417164300"));
+        ledger.record("1001", &mandate, SequenceType::First, date(2024, 1, 4));
+        let leaving = InvoiceRelation {
+            membership_ended: true,
+            ..jan()
+        };
+        assert_eq!(
+            leaving.sequence_type(Selection::All, &ledger),
+            Ok(SequenceType::Final)
+        );
+        assert_eq!(
+            leaving.sequence_type(Selection::AlumniContribution, &ledger),
+            Ok(SequenceType::Final)
+        );
+
+        ledger.record("1001", &mandate, SequenceType::Final, date(2024, 2, 4));
+        assert!(leaving.sequence_type(Selection::All, &ledger).is_err());
+    }
+
+    #[test]
+    fn alumni_sign_a_new_mandate_for_every_contribution() {
+        let mut ledger = MandateLedger::default();
+        assert_eq!(
+            jan().sequence_type(Selection::AlumniContribution, &ledger),
+            Ok(SequenceType::OneOff)
+        );
+        let mandate = config().new_mandate("1001", date(2023, 9, 1), iban("NL91ABNA0417164300"));
+        ledger.record("1001", &mandate, SequenceType::OneOff, date(2024, 1, 4));
+        assert!(jan()
+            .sequence_type(Selection::AlumniContribution, &ledger)
+            .is_err());
+
+        ledger.sign("1001", "1001-2025", date(2024, 12, 1)).unwrap();
+        assert_eq!(
+            jan().sequence_type(Selection::AlumniContribution, &ledger),
+            Ok(SequenceType::OneOff)
+        );
+    }
+}
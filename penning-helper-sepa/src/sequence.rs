@@ -0,0 +1,340 @@
+use std::{collections::HashMap, path::Path};
+
+use penning_helper_types::Date;
+use serde::{Deserialize, Serialize};
+
+pub use penning_helper_config::LeadTimes;
+
+use crate::{store, Amendment, Mandate, StoreError};
+
+/// Where a collection sits in the life of its mandate (`SeqTp`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SequenceType {
+    /// The first collection of a recurring mandate
+    First,
+    Recurring,
+    /// A mandate that is only used once
+    OneOff,
+    /// The last collection of a recurring mandate
+    Final,
+}
+
+impl SequenceType {
+    pub const ALL: [SequenceType; 4] = [
+        SequenceType::First,
+        SequenceType::Recurring,
+        SequenceType::OneOff,
+        SequenceType::Final,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            SequenceType::First => "FRST",
+            SequenceType::Recurring => "RCUR",
+            SequenceType::OneOff => "OOFF",
+            SequenceType::Final => "FNAL",
+        }
+    }
+
+    /// Business days between creating the file and the collection date
+    pub fn lead_days(&self, lead_times: &LeadTimes) -> u64 {
+        match self {
+            SequenceType::First => lead_times.first,
+            SequenceType::Recurring => lead_times.recurring,
+            SequenceType::OneOff => lead_times.one_off,
+            SequenceType::Final => lead_times.last,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MandateState {
+    pub first_collection: Date,
+    pub last_collection: Date,
+    /// A final or one-off collection was made, the mandate can't be used again
+    pub finished: bool,
+    /// The mandate as it was at the last collection
+    #[serde(default)]
+    pub mandate: Option<Mandate>,
+}
+
+/// A mandate that was signed but hasn't been collected under yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedMandate {
+    pub id: String,
+    pub signature_date: Date,
+}
+
+/// Returned for a relation whose mandate was closed by a final or one-off
+/// collection, until a new one is recorded with [`MandateLedger::sign`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "Mandate {mandate_id} was closed by the collection on {last_collection}, a new mandate has to be signed first"
+)]
+pub struct MandateFinished {
+    pub mandate_id: String,
+    pub last_collection: Date,
+}
+
+/// Returned when a new mandate reuses the id of the one it replaces
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Mandate id {0} was already used, a new mandate needs a new id")]
+pub struct MandateIdReused(pub String);
+
+/// Remembers which mandates have been collected before, so the next
+/// collection gets the right sequence type and amendment details.
+///
+/// Relations are tracked by their code, which stays the same when their
+/// mandate id changes. Until a relation signs a new mandate, its first one
+/// has the code as id and was signed when the membership started.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MandateLedger {
+    mandates: HashMap<String, MandateState>,
+    /// New mandates, by relation code, for the next collection
+    #[serde(default)]
+    signed: HashMap<String, SignedMandate>,
+}
+
+impl MandateLedger {
+    /// Reads the ledger at `path`, a missing file is an empty ledger
+    pub fn load(path: &Path) -> Result<Self, StoreError> {
+        store::load("mandate ledger", path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StoreError> {
+        store::save("mandate ledger", path, self)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&MandateState> {
+        self.mandates.get(code)
+    }
+
+    /// Records a new mandate for `code`, used from its next collection on.
+    /// A running mandate is amended with the old id, a finished one starts
+    /// over.
+    pub fn sign(
+        &mut self,
+        code: &str,
+        id: impl ToString,
+        signature_date: Date,
+    ) -> Result<(), MandateIdReused> {
+        let id = id.to_string();
+        let previous = self.mandates.get(code).and_then(|s| s.mandate.as_ref());
+        if previous.is_some_and(|m| m.id == id) {
+            return Err(MandateIdReused(id));
+        }
+        self.signed
+            .insert(code.to_string(), SignedMandate { id, signature_date });
+        Ok(())
+    }
+
+    /// The id and signature date of the mandate to collect `code` under:
+    /// a newly signed one, the one used before, or the first one
+    pub fn mandate(&self, code: &str, membership_date: Date) -> (String, Date) {
+        if let Some(signed) = self.signed.get(code) {
+            return (signed.id.clone(), signed.signature_date);
+        }
+        match self.mandates.get(code).and_then(|s| s.mandate.as_ref()) {
+            Some(m) => (m.id.clone(), m.signature_date),
+            None => (code.to_string(), membership_date),
+        }
+    }
+
+    /// The sequence type for the next collection of a recurring mandate. A
+    /// finished mandate can't be used again, it starts over once a new one
+    /// is signed.
+    pub fn next_sequence_type(&self, code: &str) -> Result<SequenceType, MandateFinished> {
+        match self.mandates.get(code) {
+            None => Ok(SequenceType::First),
+            Some(state) if !state.finished => Ok(SequenceType::Recurring),
+            Some(_) if self.signed.contains_key(code) => Ok(SequenceType::First),
+            Some(state) => Err(MandateFinished {
+                mandate_id: state
+                    .mandate
+                    .as_ref()
+                    .map_or_else(|| code.to_string(), |m| m.id.clone()),
+                last_collection: state.last_collection,
+            }),
+        }
+    }
+
+    /// How `mandate` differs from the one used at the last collection. A
+    /// finished mandate is replaced by a new one, not amended.
+    pub fn amendment(&self, code: &str, mandate: &Mandate) -> Option<Amendment> {
+        let state = self.mandates.get(code).filter(|s| !s.finished)?;
+        mandate.amendment_since(state.mandate.as_ref()?)
+    }
+
+    /// Records a collection under `mandate` on `date`
+    pub fn record(
+        &mut self,
+        code: &str,
+        mandate: &Mandate,
+        sequence_type: SequenceType,
+        date: Date,
+    ) {
+        let finished = matches!(sequence_type, SequenceType::OneOff | SequenceType::Final);
+        if self.signed.get(code).is_some_and(|s| s.id == mandate.id) {
+            self.signed.remove(code);
+        }
+        match self.mandates.get_mut(code) {
+            Some(state) if !state.finished && sequence_type != SequenceType::First => {
+                state.last_collection = date;
+                state.finished = finished;
+                state.mandate = Some(mandate.clone());
+            }
+            _ => {
+                self.mandates.insert(
+                    code.to_string(),
+                    MandateState {
+                        first_collection: date,
+                        last_collection: date,
+                        finished,
+                        mandate: Some(mandate.clone()),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mandates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mandates.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{config, date, iban},
+        OriginalDebtorAccount,
+    };
+
+    fn mandate(id: &str, debtor_iban: &str) -> Mandate {
+        config().new_mandate(id, date(2023, 9, 1), iban(debtor_iban))
+    }
+
+    #[test]
+    fn a_new_mandate_starts_with_first() {
+        assert_eq!(
+            MandateLedger::default().next_sequence_type("1002"),
+            Ok(SequenceType::First)
+        );
+    }
+
+    #[test]
+    fn after_the_first_collection_it_recurs() {
+        let mut ledger = MandateLedger::default();
+        ledger.record(
+            "1001",
+            &mandate("1001", "NL91ABNA0417164300"),
+            SequenceType::First,
+            date(2024, 1, 4),
+        );
+        assert_eq!(
+            ledger.next_sequence_type("1001"),
+            Ok(SequenceType::Recurring)
+        );
+    }
+
+    #[test]
+    fn a_finished_mandate_starts_over_once_a_new_one_is_signed() {
+        let mut ledger = MandateLedger::default();
+        ledger.record(
+            "1003",
+            &mandate("1003", "NL91ABNA0417164300"),
+            SequenceType::Final,
+            date(2024, 1, 4),
+        );
+        assert_eq!(
+            ledger.next_sequence_type("1003"),
+            Err(MandateFinished {
+                mandate_id: "1003".to_string(),
+                last_collection: date(2024, 1, 4),
+            })
+        );
+        assert_eq!(
+            ledger.sign("1003", "1003", date(2024, 3, 1)),
+            Err(MandateIdReused("1003".to_string()))
+        );
+
+        ledger.sign("1003", "1003-2", date(2024, 3, 1)).unwrap();
+        assert_eq!(ledger.next_sequence_type("1003"), Ok(SequenceType::First));
+        let (id, signed) = ledger.mandate("1003", date(2023, 9, 1));
+        assert_eq!((id.as_str(), signed), ("1003-2", date(2024, 3, 1)));
+        let renewed = config().new_mandate(id, signed, iban("NL91ABNA0417164300"));
+        assert_eq!(ledger.amendment("1003", &renewed), None);
+        ledger.record("1003", &renewed, SequenceType::First, date(2024, 4, 4));
+        assert_eq!(
+            ledger.next_sequence_type("1003"),
+            Ok(SequenceType::Recurring)
+        );
+    }
+
+    #[test]
+    fn nothing_to_amend_without_changes() {
+        let old = mandate("1001", "NL91ABNA0417164300");
+        let mut ledger = MandateLedger::default();
+        assert_eq!(ledger.amendment("1001", &old), None);
+        ledger.record("1001", &old, SequenceType::First, date(2024, 1, 4));
+        assert_eq!(ledger.amendment("1001", &old), None);
+    }
+
+    #[test]
+    fn a_new_account_at_the_same_bank_is_amended_with_the_old_iban() {
+        let mut ledger = MandateLedger::default();
+        ledger.record(
+            "1001",
+            &mandate("1001", "NL91ABNA0417164300"),
+            SequenceType::First,
+            date(2024, 1, 4),
+        );
+        assert_eq!(
+            ledger.amendment("1001", &mandate("1001", "NL02ABNA0123456789")),
+            Some(Amendment {
+                original_mandate_id: None,
+                original_creditor_id: None,
+                original_debtor_account: Some(OriginalDebtorAccount::Iban(iban(
+                    "NL91ABNA0417164300"
+                ))),
+            })
+        );
+    }
+
+    #[test]
+    fn a_new_mandate_at_another_bank_is_amended_until_it_is_used() {
+        let mut ledger = MandateLedger::default();
+        ledger.record(
+            "1001",
+            &mandate("1001", "NL91ABNA0417164300"),
+            SequenceType::First,
+            date(2024, 1, 4),
+        );
+        ledger
+            .sign("1001", "AEGEE-1001", date(2024, 1, 20))
+            .unwrap();
+        let (id, signed) = ledger.mandate("1001", date(2023, 9, 1));
+        let other_bank = config().new_mandate(id, signed, iban("NL20INGB0001234567"));
+        assert_eq!(
+            ledger.amendment("1001", &other_bank),
+            Some(Amendment {
+                original_mandate_id: Some("1001".to_string()),
+                original_creditor_id: None,
+                original_debtor_account: Some(OriginalDebtorAccount::OtherBank),
+            })
+        );
+
+        ledger.record(
+            "1001",
+            &other_bank,
+            SequenceType::Recurring,
+            date(2024, 2, 4),
+        );
+        assert_eq!(ledger.amendment("1001", &other_bank), None);
+        assert_eq!(ledger.mandate("1001", date(2023, 9, 1)).0, "AEGEE-1001");
+    }
+}
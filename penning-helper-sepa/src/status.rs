@@ -0,0 +1,373 @@
+use std::{fmt::Display, io::Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{reconcile::Direction, tree::Element, Payment};
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StatusReportError {
+    #[error("Could not read the XML: {0}")]
+    Xml(String),
+    #[error("Not a pain.002 status report, it starts with {0}")]
+    UnknownDocument(String),
+    #[error("{0} is missing")]
+    Missing(&'static str),
+}
+
+/// An ISO 20022 status reason, like `AC04`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReasonCode(String);
+
+impl ReasonCode {
+    pub fn new(code: impl ToString) -> Self {
+        Self(code.to_string())
+    }
+
+    /// What the bank sends when it gives no reason
+    pub fn unspecified() -> Self {
+        Self::new("MS03")
+    }
+
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// The meaning of the codes banks send for SEPA payments
+    pub fn description(&self) -> Option<&'static str> {
+        let d = match self.0.as_str() {
+            "AC01" => "incorrect account number",
+            "AC04" => "account closed",
+            "AC06" => "account blocked",
+            "AC13" => "debtor account is a consumer account",
+            "AG01" => "transaction forbidden on this account",
+            "AG02" => "invalid bank operation code",
+            "AM04" => "insufficient funds",
+            "AM05" => "duplicate",
+            "BE05" => "unknown creditor",
+            "CNOR" => "creditor bank is not registered",
+            "DNOR" => "debtor bank is not registered",
+            "FF01" => "invalid file format",
+            "MD01" => "no mandate",
+            "MD02" => "missing mandate information",
+            "MD06" => "refund requested by the debtor",
+            "MD07" => "debtor deceased",
+            "MS02" => "refused by the debtor",
+            "MS03" => "reason not specified",
+            "RC01" => "incorrect BIC",
+            "RR01" => "missing debtor account or identification",
+            "RR02" => "missing debtor name or address",
+            "RR03" => "missing creditor name or address",
+            "RR04" => "regulatory reason",
+            "SL01" => "specific service offered by the debtor bank",
+            _ => return None,
+        };
+        Some(d)
+    }
+}
+
+impl Display for ReasonCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.description() {
+            Some(d) => write!(f, "{} ({})", self.0, d),
+            None => f.write_str(&self.0),
+        }
+    }
+}
+
+/// What a rejection is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RejectionScope {
+    /// The whole file
+    Message,
+    /// One payment information block, by `PmtInfId`
+    PaymentInformation(String),
+    /// One transaction, by end-to-end id
+    Transaction(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub scope: RejectionScope,
+    pub reason: ReasonCode,
+    pub additional_info: Option<String>,
+}
+
+/// A pain.002 report about a file we sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusReport {
+    pub message_id: String,
+    pub original_message_id: String,
+    /// Like `pain.008.001.02`
+    pub original_message_type: String,
+    pub rejections: Vec<Rejection>,
+}
+
+/// A payment the bank refused, with the reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedPayment {
+    pub payment: Payment,
+    pub reason: ReasonCode,
+    pub additional_info: Option<String>,
+}
+
+impl RejectedPayment {
+    /// A settled payment was booked already, so the booking has to be undone
+    /// once
+    pub fn needs_reversal(&self) -> bool {
+        self.payment.settled.is_some() && self.payment.reversed.is_none()
+    }
+}
+
+impl StatusReport {
+    fn direction(&self) -> Option<Direction> {
+        if self.original_message_type.starts_with("pain.008") {
+            Some(Direction::Collection)
+        } else if self.original_message_type.starts_with("pain.001") {
+            Some(Direction::Transfer)
+        } else {
+            None
+        }
+    }
+
+    /// The payments each rejection is about, and the rejections that don't
+    /// belong to any of `payments`
+    pub fn rejected_payments(
+        &self,
+        payments: &[Payment],
+    ) -> (Vec<RejectedPayment>, Vec<Rejection>) {
+        let direction = self.direction();
+        let mut rejected = vec![];
+        let mut unknown = vec![];
+        for rejection in &self.rejections {
+            let matches = payments
+                .iter()
+                .filter(|p| direction.is_none_or(|d| p.direction == d))
+                .filter(|p| match &rejection.scope {
+                    RejectionScope::Message => p.in_message(&self.original_message_id),
+                    RejectionScope::PaymentInformation(id) => p.in_payment_information(id),
+                    RejectionScope::Transaction(id) => p.end_to_end_id == *id,
+                })
+                .collect::<Vec<_>>();
+            // an id issued again in a later run belongs to the latest one
+            let matches = match rejection.scope {
+                RejectionScope::Transaction(_) => matches.last().into_iter().copied().collect(),
+                _ => matches,
+            };
+            if matches.is_empty() {
+                unknown.push(rejection.clone());
+            }
+            rejected.extend(matches.into_iter().map(|p| RejectedPayment {
+                payment: p.clone(),
+                reason: rejection.reason.clone(),
+                additional_info: rejection.additional_info.clone(),
+            }));
+        }
+        (rejected, unknown)
+    }
+}
+
+/// Reads a pain.002 customer payment status report
+pub fn parse_status_report(reader: impl Read) -> Result<StatusReport, StatusReportError> {
+    let root = Element::parse(reader).map_err(|e| StatusReportError::Xml(e.to_string()))?;
+    let Some(report) = root.child("CstmrPmtStsRpt") else {
+        let first = root.children.first().unwrap_or(&root);
+        return Err(StatusReportError::UnknownDocument(first.name.clone()));
+    };
+    let message_id = report
+        .text_at(&["GrpHdr", "MsgId"])
+        .ok_or(StatusReportError::Missing("GrpHdr/MsgId"))?;
+    let group = report
+        .child("OrgnlGrpInfAndSts")
+        .ok_or(StatusReportError::Missing("OrgnlGrpInfAndSts"))?;
+    let original_message_id = group
+        .text_at(&["OrgnlMsgId"])
+        .ok_or(StatusReportError::Missing("OrgnlGrpInfAndSts/OrgnlMsgId"))?;
+    let mut rejections = vec![];
+    if group.text_at(&["GrpSts"]) == Some("RJCT") {
+        rejections.push(rejection(RejectionScope::Message, group));
+    }
+    for block in report.children("OrgnlPmtInfAndSts") {
+        let id = block.text_at(&["OrgnlPmtInfId"]).unwrap_or_default();
+        if block.text_at(&["PmtInfSts"]) == Some("RJCT") {
+            let scope = RejectionScope::PaymentInformation(id.to_string());
+            rejections.push(rejection(scope, block));
+        }
+        for transaction in block.children("TxInfAndSts") {
+            if transaction.text_at(&["TxSts"]) != Some("RJCT") {
+                continue;
+            }
+            let Some(end_to_end_id) = transaction.text_at(&["OrgnlEndToEndId"]) else {
+                continue;
+            };
+            let scope = RejectionScope::Transaction(end_to_end_id.to_string());
+            rejections.push(rejection(scope, transaction));
+        }
+    }
+    Ok(StatusReport {
+        message_id: message_id.to_string(),
+        original_message_id: original_message_id.to_string(),
+        original_message_type: group
+            .text_at(&["OrgnlMsgNmId"])
+            .unwrap_or_default()
+            .to_string(),
+        rejections,
+    })
+}
+
+/// The first reason and all additional information in `element/StsRsnInf`
+fn rejection(scope: RejectionScope, element: &Element) -> Rejection {
+    let reasons = element.children("StsRsnInf").collect::<Vec<_>>();
+    let reason = reasons
+        .iter()
+        .find_map(|r| r.text_at(&["Rsn", "Cd"]))
+        .map(ReasonCode::new)
+        .unwrap_or_else(ReasonCode::unspecified);
+    let additional_info = reasons
+        .iter()
+        .flat_map(|r| r.children("AddtlInf"))
+        .map(|a| a.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Rejection {
+        scope,
+        reason,
+        additional_info: Some(additional_info).filter(|a| !a.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_statements, reconcile,
+        testing::{config, date, debit, fixture},
+        DebtorDetails, IdRegistry, SequenceType,
+    };
+
+    /// The run the status report in the fixture is about
+    fn registry() -> IdRegistry {
+        let config = config();
+        let mut registry = IdRegistry::default();
+        let mut run = config.start_run(&registry, date(2024, 3, 1));
+        for (code, sequence_type) in [
+            ("1001", SequenceType::Recurring),
+            ("1002", SequenceType::First),
+            ("1004", SequenceType::First),
+            ("1005", SequenceType::Recurring),
+        ] {
+            let details = DebtorDetails {
+                sequence_type,
+                ..debit(&config, code)
+            };
+            config.new_debtor(&mut run, "202403", details).unwrap();
+        }
+        registry.record(&run);
+        registry
+    }
+
+    #[test]
+    fn reports_are_read_with_their_rejections() {
+        let report = parse_status_report(fixture("pain.002.001.03.xml")).unwrap();
+        assert_eq!(report.original_message_id, "AEGEEDELFT-20240301-1-DD");
+        assert_eq!(report.rejections.len(), 3);
+        assert_eq!(
+            report.rejections[0].additional_info.as_deref(),
+            Some("Saldo ontoereikend")
+        );
+    }
+
+    #[test]
+    fn rejections_are_matched_to_transactions_and_blocks() {
+        let registry = registry();
+        let report = parse_status_report(fixture("pain.002.001.03.xml")).unwrap();
+        let (rejected, unknown) = report.rejected_payments(registry.payments());
+        let rejected_codes = rejected
+            .iter()
+            .map(|r| (r.payment.code.as_str(), r.reason.code()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rejected_codes,
+            vec![("1001", "AM04"), ("1002", "MD01"), ("1004", "MD01")]
+        );
+        assert_eq!(
+            unknown.iter().map(|r| &r.scope).collect::<Vec<_>>(),
+            vec![&RejectionScope::Transaction("SOMEONE-ELSE-1".to_string())]
+        );
+        // nothing was settled yet, so nothing has to be booked back
+        assert!(rejected.iter().all(|r| !r.needs_reversal()));
+    }
+
+    #[test]
+    fn reason_codes_are_explained() {
+        assert_eq!(
+            ReasonCode::new("AM04").to_string(),
+            "AM04 (insufficient funds)"
+        );
+    }
+
+    #[test]
+    fn the_registry_remembers_the_last_rejection() {
+        let mut registry = registry();
+        let report = parse_status_report(fixture("pain.002.001.03.xml")).unwrap();
+        let (rejected, _) = report.rejected_payments(registry.payments());
+        for r in &rejected {
+            assert!(registry.reject(r));
+        }
+        assert_eq!(
+            registry.last_rejection("1001"),
+            Some(&ReasonCode::new("AM04"))
+        );
+        assert_eq!(registry.last_rejection("1005"), None);
+    }
+
+    #[test]
+    fn rejected_payments_do_not_settle() {
+        let mut registry = registry();
+        let report = parse_status_report(fixture("pain.002.001.03.xml")).unwrap();
+        let (rejected, _) = report.rejected_payments(registry.payments());
+        for r in &rejected {
+            registry.reject(r);
+        }
+        // a rejected payment never reaches the statement
+        let statements = parse_statements(
+            r#"<Document><BkToCstmrStmt><Stmt><Id>1</Id><Ntry>
+                <Amt>10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+                <NtryDtls><TxDtls><Refs><EndToEndId>20240301-1-202403-1001</EndToEndId></Refs>
+                </TxDtls></NtryDtls>
+            </Ntry></Stmt></BkToCstmrStmt></Document>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let result = reconcile(&statements, registry.payments());
+        assert_eq!(result.settled, vec![]);
+    }
+
+    #[test]
+    fn settled_payments_are_reversed_once() {
+        let mut registry = registry();
+        let statements = parse_statements(
+            r#"<Document><BkToCstmrStmt><Stmt><Id>1</Id><Ntry>
+                <Amt>10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+                <NtryDtls><TxDtls><Refs><EndToEndId>20240301-1-202403-1001</EndToEndId></Refs>
+                </TxDtls></NtryDtls>
+            </Ntry></Stmt></BkToCstmrStmt></Document>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        for s in &reconcile(&statements, registry.payments()).settled {
+            assert!(registry.settle(s));
+        }
+        let report = parse_status_report(fixture("pain.002.001.03.xml")).unwrap();
+        let (rejected, _) = report.rejected_payments(registry.payments());
+        let reversals = rejected
+            .iter()
+            .filter(|r| r.needs_reversal())
+            .collect::<Vec<_>>();
+        assert_eq!(reversals.len(), 1);
+        assert_eq!(reversals[0].payment.code, "1001");
+        assert!(registry.reverse(reversals[0], date(2024, 3, 20)));
+        // importing the same report again doesn't book it back a second time
+        let (rejected, _) = report.rejected_payments(registry.payments());
+        assert!(rejected.iter().all(|r| !r.needs_reversal()));
+    }
+}
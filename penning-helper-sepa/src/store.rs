@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Reading or writing one of the files the sepa crate keeps between runs
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StoreError {
+    #[error("Could not read or write the {0}: {1}")]
+    Io(&'static str, String),
+    #[error("The {0} is invalid: {1}")]
+    Invalid(&'static str, String),
+}
+
+/// Reads the JSON file at `path`, a missing file gives the default
+pub(crate) fn load<T: DeserializeOwned + Default>(
+    what: &'static str,
+    path: &Path,
+) -> Result<T, StoreError> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).map_err(|e| StoreError::Invalid(what, e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(StoreError::Io(what, e.to_string())),
+    }
+}
+
+pub(crate) fn save<T: Serialize>(
+    what: &'static str,
+    path: &Path,
+    value: &T,
+) -> Result<(), StoreError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| StoreError::Io(what, e.to_string()))?;
+    }
+    let s = serde_json::to_string_pretty(value)
+        .map_err(|e| StoreError::Invalid(what, e.to_string()))?;
+    std::fs::write(path, s).map_err(|e| StoreError::Io(what, e.to_string()))
+}
+
+/// Reads the JSON lines file at `path`, a missing file has no lines
+pub(crate) fn load_lines<T: DeserializeOwned>(
+    what: &'static str,
+    path: &Path,
+) -> Result<Vec<T>, StoreError> {
+    let s = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(StoreError::Io(what, e.to_string())),
+    };
+    s.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(idx, l)| {
+            serde_json::from_str(l)
+                .map_err(|e| StoreError::Invalid(what, format!("line {}: {}", idx + 1, e)))
+        })
+        .collect()
+}
+
+/// Adds `value` as one line at the end of the file at `path`, leaving what is
+/// already there alone
+pub(crate) fn append<T: Serialize>(
+    what: &'static str,
+    path: &Path,
+    value: &T,
+) -> Result<(), StoreError> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| StoreError::Io(what, e.to_string()))?;
+    }
+    let mut line =
+        serde_json::to_string(value).map_err(|e| StoreError::Invalid(what, e.to_string()))?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .map_err(|e| StoreError::Io(what, e.to_string()))
+}
@@ -0,0 +1,81 @@
+//! Small helpers for the tests of the modules in this crate
+
+use std::path::PathBuf;
+
+use penning_helper_types::{Bic, Date, Euro, Iban};
+use xml::EmitterConfig;
+
+use crate::{DebtorDetails, SEPAConfig, SchemaVersion, SequenceType};
+
+pub(crate) fn iban(s: &str) -> Iban {
+    s.parse().unwrap()
+}
+
+pub(crate) fn bic(s: &str) -> Bic {
+    s.parse().unwrap()
+}
+
+pub(crate) fn date(year: i32, month: u32, day: u32) -> Date {
+    Date::new(year, month, day).unwrap()
+}
+
+/// Our own account and creditor id, in the old schema version
+pub(crate) fn config() -> SEPAConfig {
+    SEPAConfig::new(
+        "AEGEE-Delft",
+        iban("NL44RABO0123456789"),
+        bic("RABONL2U"),
+        "NL00ZZZ404840000000",
+        SchemaVersion::V2009,
+    )
+}
+
+/// A recurring collection of €10 from `code`, change what the test is about
+pub(crate) fn debit(config: &SEPAConfig, code: &str) -> DebtorDetails {
+    DebtorDetails {
+        code: code.to_string(),
+        name: "Jan Jansen".to_string(),
+        amount: Euro::new(10, 0),
+        bic: Some(bic("ABNANL2A")),
+        mandate: config.new_mandate(code, date(2023, 9, 1), iban("NL91ABNA0417164300")),
+        amendment: None,
+        sequence_type: SequenceType::Recurring,
+        description: "Contributie".to_string(),
+        reference: None,
+    }
+}
+
+/// `tests/fixtures/<name>`
+pub(crate) fn fixture(name: &str) -> std::fs::File {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    std::fs::File::open(path).unwrap()
+}
+
+pub(crate) fn golden(name: &str) -> Vec<u8> {
+    std::fs::read(golden_path(name)).unwrap()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name)
+}
+
+/// Compares against `tests/golden/<name>`, run with `UPDATE_GOLDEN=1` to
+/// write the current output instead.
+pub(crate) fn assert_golden(name: &str, write: impl FnOnce(&mut Vec<u8>, EmitterConfig)) {
+    let mut out = vec![];
+    write(&mut out, EmitterConfig::new().perform_indent(true));
+    let out = String::from_utf8(out).unwrap() + "\n";
+
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &out).unwrap();
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(out, expected, "{} differs from the golden file", name);
+}
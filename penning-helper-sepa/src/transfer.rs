@@ -1,33 +1,196 @@
 use std::io::Write;
 
-use penning_helper_types::{Date, Euro};
-use xml::EventWriter;
+use penning_helper_types::{Bic, CreditorReference, Date, Euro, Iban};
+use xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig, EventWriter};
 
-use crate::ToXml;
+use crate::{
+    sanitise::{Sanitised, Sanitiser},
+    schema::SchemaVersion,
+    validate::{Diagnostic, Validator, MAX_NAME, MAX_REMITTANCE},
+    Account, Category, Instruction, ToXml,
+};
 
 use self::transfer_gen::DocumentString;
 
 mod transfer_gen;
 
 pub struct Document {
+    version: SchemaVersion,
     header: Header,
     payment_information: Vec<PaymentInformation>,
 }
 
 impl Document {
-    pub(crate) fn new(header: Header, payment_information: Vec<PaymentInformation>) -> Document {
+    pub(crate) fn new(
+        version: SchemaVersion,
+        header: Header,
+        payment_information: Vec<PaymentInformation>,
+    ) -> Document {
         Document {
+            version,
             header,
             payment_information,
         }
     }
 
+    pub fn version(&self) -> SchemaVersion {
+        self.version
+    }
+
+    pub fn message_id(&self) -> &str {
+        &self.header.message_id
+    }
+
+    pub fn payment_information(&self) -> &[PaymentInformation] {
+        &self.payment_information
+    }
+
+    /// Every creditor with the date of its block, to [`diff`](crate::diff)
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.payment_information
+            .iter()
+            .flat_map(|p| {
+                p.creditors.iter().map(|c| Instruction {
+                    end_to_end_id: c.id.clone(),
+                    name: c.name.clone(),
+                    iban: c.iban.clone(),
+                    amount: c.amount,
+                    date: p.execution_date,
+                    description: match &c.reference {
+                        Some(r) => r.to_string(),
+                        None => c.description.clone(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Takes the creditor with `end_to_end_id` out, and its block when that
+    /// was the last one in it. The totals are counted again.
+    pub fn remove_creditor(&mut self, end_to_end_id: &str) -> Option<Creditor> {
+        let (block, idx) = self
+            .payment_information
+            .iter()
+            .enumerate()
+            .find_map(|(b, p)| {
+                let idx = p.creditors.iter().position(|c| c.id == end_to_end_id)?;
+                Some((b, idx))
+            })?;
+        let creditor = self.payment_information[block].creditors.remove(idx);
+        if self.payment_information[block].creditors.is_empty() {
+            self.payment_information.remove(block);
+        }
+        self.update_totals();
+        Some(creditor)
+    }
+
+    /// Adds the creditors of `other`. A block of `other` joins the block with
+    /// the same id, or else the one executed the same way, or becomes a block
+    /// of its own. The header stays ours, with the totals counted again.
+    pub fn merge(&mut self, other: Document) {
+        for block in other.payment_information {
+            let blocks = &self.payment_information;
+            let same = blocks
+                .iter()
+                .position(|p| p.payment_information_id == block.payment_information_id)
+                .or_else(|| blocks.iter().position(|p| p.same_execution(&block)));
+            match same {
+                Some(i) => self.payment_information[i]
+                    .creditors
+                    .extend(block.creditors),
+                None => self.payment_information.push(block),
+            }
+        }
+        self.update_totals();
+    }
+
+    fn update_totals(&mut self) {
+        for p in &mut self.payment_information {
+            p.number_of_transactions = p.creditors.len() as u32;
+            p.control_sum = p.creditors.iter().map(|c| c.amount).sum();
+        }
+        let header = &mut self.header;
+        header.number_of_transactions = self
+            .payment_information
+            .iter()
+            .map(|p| p.number_of_transactions)
+            .sum();
+        header.control_sum = self.payment_information.iter().map(|p| p.control_sum).sum();
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_xml_doc(self) -> DocumentString {
         DocumentString::from(self)
     }
 
+    /// Brings names and descriptions into the SEPA character set and lengths,
+    /// returning what was changed
+    pub fn sanitise(&mut self) -> Vec<Sanitised> {
+        let mut s = Sanitiser::default();
+        s.field("GrpHdr", "InitgPty/Nm", &mut self.header.name, MAX_NAME);
+        for (idx, p) in self.payment_information.iter_mut().enumerate() {
+            let location = format!("PmtInf {}", idx + 1);
+            s.field(&location, "Dbtr/Nm", &mut p.debtor_name, MAX_NAME);
+            for c in &mut p.creditors {
+                let location = format!("{} / {}", location, c.name);
+                s.field(&location, "Cdtr/Nm", &mut c.name, MAX_NAME);
+                s.field(&location, "RmtInf/Ustrd", &mut c.description, MAX_REMITTANCE);
+            }
+        }
+        s.finish()
+    }
+
+    /// Checks the rules of the schema and the EPC guidelines that the types
+    /// don't already enforce. Nothing is wrong when this is empty.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut v = Validator::default();
+        let header = &self.header;
+        let created = v.creation_date(&header.creation_date_time);
+        v.id("GrpHdr", "MsgId", &header.message_id);
+        v.text("GrpHdr", "InitgPty/Nm", &header.name, MAX_NAME);
+        v.totals(
+            "GrpHdr",
+            (header.number_of_transactions, header.control_sum),
+            self.payment_information
+                .iter()
+                .flat_map(|p| p.creditors.iter().map(|c| c.amount)),
+        );
+
+        for (idx, p) in self.payment_information.iter().enumerate() {
+            let location = format!("PmtInf {}", idx + 1);
+            v.unique_id(&location, "PmtInfId", &p.payment_information_id);
+            v.text(&location, "Dbtr/Nm", &p.debtor_name, MAX_NAME);
+            v.date(&location, "ReqdExctnDt", p.execution_date, created, false);
+            v.totals(
+                &location,
+                (p.number_of_transactions, p.control_sum),
+                p.creditors.iter().map(|c| c.amount),
+            );
+
+            for c in &p.creditors {
+                let location = format!("{} / {}", location, c.name);
+                v.unique_id(&location, "EndToEndId", &c.id);
+                v.amount(&location, "InstdAmt", c.amount);
+                v.text(&location, "Cdtr/Nm", &c.name, MAX_NAME);
+                // the description isn't sent when there is a creditor reference
+                if c.reference.is_none() {
+                    v.text(&location, "RmtInf/Ustrd", &c.description, MAX_REMITTANCE);
+                }
+            }
+        }
+        v.finish()
+    }
+
     pub fn write<W: Write>(self, writer: W) -> xml::writer::Result<()> {
-        let mut writer = EventWriter::new(writer);
+        self.write_with_config(writer, EmitterConfig::new())
+    }
+
+    pub(crate) fn write_with_config<W: Write>(
+        self,
+        writer: W,
+        config: EmitterConfig,
+    ) -> xml::writer::Result<()> {
+        let mut writer = EventWriter::new_with_config(writer, config);
         self.write_xml(&mut writer)?;
         writer.inner_mut().flush()?;
         Ok(())
@@ -35,6 +198,12 @@ impl Document {
 
     fn write_xml<W: Write>(self, writer: &mut EventWriter<W>) -> xml::writer::Result<()> {
         let doc = self.to_xml_doc();
+        // older xml-rs versions spell the encoding in lower case
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("UTF-8"),
+            standalone: None,
+        })?;
         let xml = doc.to_xml();
         for event in xml {
             writer.write(event)?;
@@ -75,34 +244,38 @@ pub struct PaymentInformation {
     control_sum: Euro,
     execution_date: Date,
     debtor_name: String,
-    debtor_iban: String,
-    debtor_bic: String,
+    debtor_iban: Iban,
+    debtor_bic: Bic,
     creditors: Vec<Creditor>,
 }
 
 impl PaymentInformation {
+    /// `totals` are the number of transactions and their sum, which a parsed
+    /// file states itself
     pub(crate) fn new(
         payment_information_id: String,
-        number_of_transactions: u32,
-        control_sum: Euro,
+        debtor: Account,
         execution_date: Date,
-        debtor_name: String,
-        debtor_iban: String,
-        debtor_bic: String,
+        totals: (u32, Euro),
         creditors: Vec<Creditor>,
     ) -> Self {
+        let (number_of_transactions, control_sum) = totals;
         Self {
             payment_information_id,
             number_of_transactions,
             control_sum,
             execution_date,
-            debtor_name,
-            debtor_iban,
-            debtor_bic,
+            debtor_name: debtor.name,
+            debtor_iban: debtor.iban,
+            debtor_bic: debtor.bic,
             creditors,
         }
     }
 
+    pub fn payment_information_id(&self) -> &str {
+        &self.payment_information_id
+    }
+
     pub fn number_of_transactions(&self) -> u32 {
         self.number_of_transactions
     }
@@ -110,38 +283,300 @@ impl PaymentInformation {
     pub fn control_sum(&self) -> Euro {
         self.control_sum
     }
+
+    pub fn execution_date(&self) -> Date {
+        self.execution_date
+    }
+
+    pub fn creditors(&self) -> &[Creditor] {
+        &self.creditors
+    }
+
+    /// Executed on the same day and category, from the same account
+    fn same_execution(&self, other: &PaymentInformation) -> bool {
+        let category = |p: &PaymentInformation| p.creditors.first().map(|c| c.category);
+        self.execution_date == other.execution_date
+            && self.debtor_iban == other.debtor_iban
+            && category(self) == category(other)
+    }
+}
+
+/// Who to pay and what for, see [`crate::SEPAConfig::new_creditor`]
+#[derive(Debug, Clone)]
+pub struct CreditorDetails {
+    /// The relation code, which goes in the end-to-end id
+    pub code: String,
+    pub name: String,
+    pub amount: Euro,
+    /// Looked up from the IBAN when missing
+    pub bic: Option<Bic>,
+    pub iban: Iban,
+    pub description: String,
+    /// Sent as structured remittance instead of the description
+    pub reference: Option<CreditorReference>,
 }
 
 #[derive(Debug)]
 pub struct Creditor {
     id: String,
     amount: Euro,
-    bic: String,
+    bic: Option<Bic>,
+    bic_derived: bool,
     name: String,
-    iban: String,
+    iban: Iban,
     description: String,
+    reference: Option<CreditorReference>,
+    category: Category,
 }
 
 impl Creditor {
-    pub(crate) fn new(
-        id: String,
-        amount: Euro,
-        bic: String,
-        name: String,
-        iban: String,
-        description: String,
-    ) -> Self {
+    pub(crate) fn new(id: String, details: CreditorDetails, bic_derived: bool) -> Self {
         Self {
             id,
-            amount,
-            bic,
-            name,
-            iban,
-            description,
+            amount: details.amount,
+            bic: details.bic,
+            bic_derived,
+            name: details.name,
+            iban: details.iban,
+            description: details.description,
+            reference: details.reference,
+            category: Category::default(),
         }
     }
 
+    /// Puts the creditor in the payment information block of `category`
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn creditor_reference(&self) -> Option<&CreditorReference> {
+        self.reference.as_ref()
+    }
+
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    pub(crate) fn end_to_end_id(&self) -> &str {
+        &self.id
+    }
+
     pub fn amount(&self) -> Euro {
         self.amount
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the BIC was looked up from the IBAN instead of given
+    pub fn bic_derived(&self) -> bool {
+        self.bic_derived
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creditor_reference,
+        testing::{assert_golden, bic, date, iban},
+        CreditorDetails, Problem, Severity,
+    };
+
+    fn our_account() -> Account {
+        Account {
+            name: "AEGEE-Delft".to_string(),
+            iban: iban("NL44RABO0123456789"),
+            bic: bic("RABONL2U"),
+        }
+    }
+
+    /// The document in `tests/golden`. The newer versions don't need a BIC for
+    /// the other party, so the second member only has one in the old version.
+    fn document(version: SchemaVersion) -> Document {
+        let second_bic = match version {
+            SchemaVersion::V2009 => Some(bic("COBADEFFXXX")),
+            SchemaVersion::V2019 => None,
+        };
+        let creditors = vec![
+            Creditor::new(
+                "AEGEEDELFT-0000000000000003".to_string(),
+                CreditorDetails {
+                    code: String::new(),
+                    name: "Jan Jansen".to_string(),
+                    amount: Euro::new(20, 0),
+                    bic: Some(bic("ABNANL2A")),
+                    iban: iban("NL91ABNA0417164300"),
+                    description: "Payment of positive balance".to_string(),
+                    reference: None,
+                },
+                false,
+            ),
+            Creditor::new(
+                "AEGEEDELFT-0000000000000004".to_string(),
+                CreditorDetails {
+                    code: String::new(),
+                    name: "Erika Mustermann".to_string(),
+                    amount: Euro::new(3, 10),
+                    bic: second_bic,
+                    iban: iban("DE89370400440532013000"),
+                    description: "Payment of positive balance".to_string(),
+                    reference: Some(creditor_reference("1002", "202403").unwrap()),
+                },
+                false,
+            ),
+        ];
+        let payment_information = PaymentInformation::new(
+            "AEGEEDELFT-00000000000000cc".to_string(),
+            our_account(),
+            date(2024, 3, 4),
+            (2, Euro::new(23, 10)),
+            creditors,
+        );
+        let header = Header::new(
+            "20240301-00000000000000dd".to_string(),
+            "2024-03-01T12:00:00".to_string(),
+            2,
+            Euro::new(23, 10),
+            "AEGEE-Delft".to_string(),
+        );
+        Document::new(version, header, vec![payment_information])
+    }
+
+    /// A transfer to someone whose name is too long and not in the SEPA
+    /// character set
+    fn long_name(payment_information_id: &str, date: Date, stated: (u32, Euro)) -> Document {
+        let creditor = Creditor::new(
+            "AEGEEDELFT-0000000000000003".to_string(),
+            CreditorDetails {
+                code: String::new(),
+                name: "Jürgen ".to_string() + &"van ".repeat(20),
+                amount: Euro::new(20, 0),
+                bic: None,
+                iban: iban("NL91ABNA0417164300"),
+                description: "Payment of positive balance".to_string(),
+                reference: None,
+            },
+            false,
+        );
+        let payment_information = PaymentInformation::new(
+            payment_information_id.to_string(),
+            our_account(),
+            date,
+            stated,
+            vec![creditor],
+        );
+        let header = Header::new(
+            "20240301-00000000000000dd".to_string(),
+            "2024-03-01T12:00:00".to_string(),
+            1,
+            Euro::new(20, 0),
+            "AEGEE-Delft".to_string(),
+        );
+        Document::new(SchemaVersion::V2009, header, vec![payment_information])
+    }
+
+    #[test]
+    fn pain_001_001_03() {
+        assert_golden("pain.001.001.03.xml", |w, c| {
+            document(SchemaVersion::V2009)
+                .write_with_config(w, c)
+                .unwrap()
+        });
+    }
+
+    #[test]
+    fn pain_001_001_09() {
+        assert_golden("pain.001.001.09.xml", |w, c| {
+            document(SchemaVersion::V2019)
+                .write_with_config(w, c)
+                .unwrap()
+        });
+    }
+
+    #[test]
+    fn golden_documents_validate() {
+        for version in SchemaVersion::ALL {
+            assert_eq!(document(version).validate(), vec![]);
+        }
+    }
+
+    #[test]
+    fn validation_reports_each_problem() {
+        let document = long_name(
+            "AEGEEDELFT-00000000000000cc/",
+            date(2024, 2, 28),
+            (2, Euro::new(23, 10)),
+        );
+        let diagnostics = document
+            .validate()
+            .into_iter()
+            .map(|d| (d.severity, d.element, d.problem))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Severity::Error, "PmtInfId", Problem::InvalidId),
+                (
+                    Severity::Error,
+                    "ReqdExctnDt",
+                    Problem::DateBeforeCreation {
+                        date: date(2024, 2, 28),
+                        created: date(2024, 3, 1)
+                    }
+                ),
+                (
+                    Severity::Error,
+                    "NbOfTxs",
+                    Problem::NumberOfTransactions {
+                        stated: 2,
+                        actual: 1
+                    }
+                ),
+                (
+                    Severity::Error,
+                    "CtrlSum",
+                    Problem::ControlSum {
+                        stated: Euro::new(23, 10),
+                        actual: Euro::new(20, 0)
+                    }
+                ),
+                (
+                    Severity::Error,
+                    "Cdtr/Nm",
+                    Problem::TooLong {
+                        length: 87,
+                        max: 70
+                    }
+                ),
+                (
+                    Severity::Warning,
+                    "Cdtr/Nm",
+                    Problem::InvalidCharacters("ü".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitised_documents_validate() {
+        let mut document = long_name(
+            "AEGEEDELFT-00000000000000cc",
+            date(2024, 3, 4),
+            (1, Euro::new(20, 0)),
+        );
+        assert_eq!(document.validate().len(), 2);
+
+        let changes = document.sanitise();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].element, "Cdtr/Nm");
+        // the location keeps the original name so it can be found in Conscribo
+        assert!(changes[0].location.starts_with("PmtInf 1 / Jürgen van"));
+        assert_eq!(changes[0].sanitised.chars().count(), 70);
+        assert!(changes[0].sanitised.starts_with("Jurgen van"));
+        assert_eq!(document.validate(), vec![]);
+        assert_eq!(document.sanitise(), vec![]);
+    }
 }
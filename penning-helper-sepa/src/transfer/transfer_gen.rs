@@ -1,17 +1,21 @@
 use xml::writer::XmlEvent;
 
-use crate::ToXml;
+use crate::{
+    schema::{financial_institution, remittance_information, SchemaVersion, SchemaVersionExt},
+    ToXml,
+};
 
 pub struct DocumentString {
+    version: SchemaVersion,
     header: HeaderString,
     payment_information: Vec<PaymentInformationString>,
 }
 
 impl ToXml for DocumentString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         let mut v = vec![
             XmlEvent::start_element("Document")
-                .default_ns("urn:iso:std:iso:20022:tech:xsd:pain.001.001.03")
+                .default_ns(self.version.credit_transfer_namespace())
                 .ns("xsi", "http://www.w3.org/2001/XMLSchema-instance")
                 .into(),
             XmlEvent::start_element("CstmrCdtTrfInitn").into(),
@@ -28,19 +32,23 @@ impl ToXml for DocumentString {
 
 impl From<super::Document> for DocumentString {
     fn from(value: super::Document) -> Self {
+        let version = value.version;
         Self {
+            version,
             header: value.header.into(),
             payment_information: value
                 .payment_information
                 .into_iter()
-                .map(|payment_information| payment_information.into())
+                .map(|payment_information| {
+                    PaymentInformationString::new(payment_information, version)
+                })
                 .collect(),
         }
     }
 }
 
 struct HeaderString {
-    /// date-randomhash
+    /// AEGEEDELFT-20240301-1-CT
     message_id: String,
     creation_date_time: String,
     number_of_transactions: String,
@@ -49,7 +57,7 @@ struct HeaderString {
 }
 
 impl ToXml for HeaderString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         vec![
             XmlEvent::start_element("GrpHdr").into(),
             XmlEvent::start_element("MsgId").into(),
@@ -87,6 +95,7 @@ impl From<super::Header> for HeaderString {
 }
 
 struct PaymentInformationString {
+    version: SchemaVersion,
     payment_information_id: String,
     number_of_transactions: String,
     control_sum: String,
@@ -98,7 +107,7 @@ struct PaymentInformationString {
 }
 
 impl ToXml for PaymentInformationString {
-    fn to_xml(&self) -> Vec<XmlEvent> {
+    fn to_xml(&self) -> Vec<XmlEvent<'_>> {
         let mut v = vec![
             XmlEvent::start_element("PmtInf").into(),
             XmlEvent::start_element("PmtInfId").into(),
@@ -124,7 +133,17 @@ impl ToXml for PaymentInformationString {
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("ReqdExctnDt").into(),
-            XmlEvent::characters(&self.execution_date),
+        ];
+        // a date or a date and time since pain.001.001.09
+        match self.version {
+            SchemaVersion::V2009 => v.push(XmlEvent::characters(&self.execution_date)),
+            SchemaVersion::V2019 => v.extend([
+                XmlEvent::start_element("Dt").into(),
+                XmlEvent::characters(&self.execution_date),
+                XmlEvent::end_element().into(),
+            ]),
+        }
+        v.extend([
             XmlEvent::end_element().into(),
             XmlEvent::start_element("Dbtr").into(),
             XmlEvent::start_element("Nm").into(),
@@ -139,16 +158,14 @@ impl ToXml for PaymentInformationString {
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::start_element("DbtrAgt").into(),
-            XmlEvent::start_element("FinInstnId").into(),
-            XmlEvent::start_element("BIC").into(),
-            XmlEvent::characters(&self.debtor_bic),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
+        ]);
+        v.extend(financial_institution(Some(self.debtor_bic.as_str()), self.version));
+        v.extend([
             XmlEvent::end_element().into(),
             XmlEvent::start_element("ChrgBr").into(),
             XmlEvent::characters("SLEV"),
             XmlEvent::end_element().into(),
-        ];
+        ]);
         for creditor in &self.creditors {
             v.extend(creditor.to_xml());
         }
@@ -157,37 +174,40 @@ impl ToXml for PaymentInformationString {
     }
 }
 
-impl From<super::PaymentInformation> for PaymentInformationString {
-    fn from(value: super::PaymentInformation) -> Self {
+impl PaymentInformationString {
+    fn new(value: super::PaymentInformation, version: SchemaVersion) -> Self {
         Self {
+            version,
             payment_information_id: value.payment_information_id,
             number_of_transactions: value.number_of_transactions.to_string(),
             control_sum: value.control_sum.xml_string(),
             execution_date: value.execution_date.to_string(),
             debtor_name: value.debtor_name,
-            debtor_iban: value.debtor_iban,
-            debtor_bic: value.debtor_bic,
+            debtor_iban: value.debtor_iban.to_string(),
+            debtor_bic: value.debtor_bic.to_string(),
             creditors: value
                 .creditors
                 .into_iter()
-                .map(|creditor| creditor.into())
+                .map(|creditor| CreditorString::new(creditor, version))
                 .collect(),
         }
     }
 }
 
 struct CreditorString {
+    version: SchemaVersion,
     id: String,
     amount: String,
-    bic: String,
+    bic: Option<String>,
     name: String,
     iban: String,
     description: String,
+    reference: Option<String>,
 }
 
 impl ToXml for CreditorString {
-    fn to_xml(&self) -> Vec<xml::writer::XmlEvent> {
-        vec![
+    fn to_xml(&self) -> Vec<xml::writer::XmlEvent<'_>> {
+        let mut v = vec![
             XmlEvent::start_element("CdtTrfTxInf").into(),
             XmlEvent::start_element("PmtId").into(),
             XmlEvent::start_element("EndToEndId").into(),
@@ -201,13 +221,14 @@ impl ToXml for CreditorString {
             XmlEvent::characters(&self.amount),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
-            XmlEvent::start_element("CdtrAgt").into(),
-            XmlEvent::start_element("FinInstnId").into(),
-            XmlEvent::start_element("BIC").into(),
-            XmlEvent::characters(&self.bic),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
+        ];
+        // the creditor agent is optional, it's only left out when there is no BIC
+        if let Some(bic) = &self.bic {
+            v.push(XmlEvent::start_element("CdtrAgt").into());
+            v.extend(financial_institution(Some(bic.as_str()), self.version));
+            v.push(XmlEvent::end_element().into());
+        }
+        v.extend([
             XmlEvent::start_element("Cdtr").into(),
             XmlEvent::start_element("Nm").into(),
             XmlEvent::characters(&self.name),
@@ -220,25 +241,27 @@ impl ToXml for CreditorString {
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
             XmlEvent::end_element().into(),
-            XmlEvent::start_element("RmtInf").into(),
-            XmlEvent::start_element("Ustrd").into(),
-            XmlEvent::characters(&self.description),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
-            XmlEvent::end_element().into(),
-        ]
+        ]);
+        v.extend(remittance_information(
+            &self.description,
+            self.reference.as_deref(),
+        ));
+        v.push(XmlEvent::end_element().into());
+        v
     }
 }
 
-impl From<super::Creditor> for CreditorString {
-    fn from(value: super::Creditor) -> Self {
+impl CreditorString {
+    fn new(value: super::Creditor, version: SchemaVersion) -> Self {
         Self {
+            version,
             id: value.id,
             amount: value.amount.xml_string(),
-            bic: value.bic,
+            bic: value.bic.map(|b| b.to_string()),
             name: value.name,
-            iban: value.iban,
+            iban: value.iban.to_string(),
             description: value.description,
+            reference: value.reference.map(|r| r.to_string()),
         }
     }
 }
@@ -0,0 +1,65 @@
+use std::io::Read;
+
+use xml::reader::{EventReader, XmlEvent};
+
+/// An element of a parsed XML document. Names are without their prefix,
+/// the namespace is kept apart.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) namespace: Option<String>,
+    pub(crate) text: String,
+    pub(crate) children: Vec<Element>,
+}
+
+impl Element {
+    /// Reads the whole document and returns its root element
+    pub(crate) fn parse(reader: impl Read) -> Result<Self, xml::reader::Error> {
+        let mut stack: Vec<Element> = vec![];
+        let mut root = Element::default();
+        for event in EventReader::new(reader) {
+            match event? {
+                XmlEvent::StartElement { name, .. } => stack.push(Element {
+                    name: name.local_name,
+                    namespace: name.namespace,
+                    ..Default::default()
+                }),
+                XmlEvent::Characters(s) | XmlEvent::CData(s) => {
+                    if let Some(e) = stack.last_mut() {
+                        e.text.push_str(&s);
+                    }
+                }
+                XmlEvent::EndElement { .. } => {
+                    // the reader already checks that start and end match
+                    let Some(e) = stack.pop() else { continue };
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(e),
+                        None => root = e,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(root)
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The element at `path` below this one, taking the first match each step
+    pub(crate) fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |e, name| e.child(name))
+    }
+
+    /// The trimmed text at `path`, `None` when it's missing or empty
+    pub(crate) fn text_at(&self, path: &[&str]) -> Option<&str> {
+        self.find(path)
+            .map(|e| e.text.trim())
+            .filter(|t| !t.is_empty())
+    }
+}
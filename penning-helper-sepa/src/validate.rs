@@ -0,0 +1,215 @@
+use std::{collections::HashSet, fmt::Display};
+
+use chrono::NaiveDateTime;
+use penning_helper_types::{Date, Euro};
+
+/// Names of parties (`Nm`)
+pub(crate) const MAX_NAME: usize = 70;
+/// Unstructured remittance information (`Ustrd`)
+pub(crate) const MAX_REMITTANCE: usize = 140;
+/// Message, payment information, end-to-end, mandate and creditor ids
+pub(crate) const MAX_ID: usize = 35;
+/// How far ahead a direct debit may be sent to the bank
+const MAX_DAYS_AHEAD: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The bank might convert or refuse it
+    Warning,
+    /// The bank will refuse the file
+    Error,
+}
+
+/// One problem found in a document before it's written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The block or transaction, like `PmtInf 1 / Jan Jansen`
+    pub location: String,
+    /// The XML element, like `Dbtr/Nm`
+    pub element: &'static str,
+    pub problem: Problem,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}: {}", self.location, self.element, self.problem)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Problem {
+    #[error("is empty")]
+    Empty,
+    #[error("is {length} characters long, at most {max} are allowed")]
+    TooLong { length: usize, max: usize },
+    #[error("contains characters outside the SEPA character set: {0}")]
+    InvalidCharacters(String),
+    #[error("can't start or end with '/' or contain '//'")]
+    InvalidId,
+    #[error("{0} is used more than once")]
+    DuplicateId(String),
+    #[error("{0} is not between €0.01 and €999999999.99")]
+    AmountOutOfRange(Euro),
+    #[error("says {stated} but the transactions add up to {actual}")]
+    ControlSum { stated: Euro, actual: Euro },
+    #[error("says {stated} but there are {actual} transactions")]
+    NumberOfTransactions { stated: u32, actual: u32 },
+    #[error("there are no transactions")]
+    NoTransactions,
+    #[error("{0} is not a valid date and time")]
+    InvalidDateTime(String),
+    #[error("{date} is before the file is created on {created}")]
+    DateBeforeCreation { date: Date, created: Date },
+    #[error("{date} is more than {MAX_DAYS_AHEAD} days after the file is created on {created}")]
+    DateTooFarAhead { date: Date, created: Date },
+    #[error("{0} is a weekend day or TARGET2 holiday, the bank will move it")]
+    NotBusinessDay(Date),
+    #[error("the mandate was signed on {signed}, after the collection on {collection}")]
+    SignatureAfterCollection { signed: Date, collection: Date },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::InvalidCharacters(_)
+            | Problem::DateTooFarAhead { .. }
+            | Problem::NotBusinessDay(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// The characters every bank in the SEPA area has to accept
+pub fn is_sepa_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c)
+}
+
+/// Collects the diagnostics for one document
+#[derive(Debug, Default)]
+pub(crate) struct Validator {
+    diagnostics: Vec<Diagnostic>,
+    seen_ids: HashSet<String>,
+}
+
+impl Validator {
+    pub(crate) fn push(&mut self, location: &str, element: &'static str, problem: Problem) {
+        self.diagnostics.push(Diagnostic {
+            severity: problem.severity(),
+            location: location.to_string(),
+            element,
+            problem,
+        });
+    }
+
+    pub(crate) fn text(&mut self, location: &str, element: &'static str, value: &str, max: usize) {
+        let length = value.chars().count();
+        if value.trim().is_empty() {
+            self.push(location, element, Problem::Empty);
+        } else if length > max {
+            self.push(location, element, Problem::TooLong { length, max });
+        }
+        let mut invalid = value
+            .chars()
+            .filter(|c| !is_sepa_char(*c))
+            .collect::<Vec<_>>();
+        invalid.sort_unstable();
+        invalid.dedup();
+        if !invalid.is_empty() {
+            let invalid = invalid.into_iter().collect();
+            self.push(location, element, Problem::InvalidCharacters(invalid));
+        }
+    }
+
+    pub(crate) fn id(&mut self, location: &str, element: &'static str, value: &str) {
+        self.text(location, element, value, MAX_ID);
+        if value.starts_with('/') || value.ends_with('/') || value.contains("//") {
+            self.push(location, element, Problem::InvalidId);
+        }
+    }
+
+    /// An id that has to be unique within the document
+    pub(crate) fn unique_id(&mut self, location: &str, element: &'static str, value: &str) {
+        self.id(location, element, value);
+        if !self.seen_ids.insert(value.to_string()) {
+            self.push(location, element, Problem::DuplicateId(value.to_string()));
+        }
+    }
+
+    pub(crate) fn amount(&mut self, location: &str, element: &'static str, amount: Euro) {
+        if amount < Euro::new(0, 1) || amount > Euro::new(999_999_999, 99) {
+            self.push(location, element, Problem::AmountOutOfRange(amount));
+        }
+    }
+
+    pub(crate) fn totals(
+        &mut self,
+        location: &str,
+        (stated_count, stated_sum): (u32, Euro),
+        amounts: impl Iterator<Item = Euro>,
+    ) {
+        let (count, sum) = amounts.fold((0, Euro::default()), |(c, s), a| (c + 1, s + a));
+        if count == 0 {
+            self.push(location, "NbOfTxs", Problem::NoTransactions);
+        } else if stated_count != count {
+            let problem = Problem::NumberOfTransactions {
+                stated: stated_count,
+                actual: count,
+            };
+            self.push(location, "NbOfTxs", problem);
+        }
+        if stated_sum != sum {
+            let problem = Problem::ControlSum {
+                stated: stated_sum,
+                actual: sum,
+            };
+            self.push(location, "CtrlSum", problem);
+        }
+    }
+
+    /// The date part of `CreDtTm`
+    pub(crate) fn creation_date(&mut self, creation_date_time: &str) -> Option<Date> {
+        let date = NaiveDateTime::parse_from_str(creation_date_time, "%Y-%m-%dT%H:%M:%S")
+            .ok()
+            .map(|d| d.date());
+        if date.is_none() {
+            let problem = Problem::InvalidDateTime(creation_date_time.to_string());
+            self.push("GrpHdr", "CreDtTm", problem);
+        }
+        date.map(Date::from)
+    }
+
+    /// A collection or execution date, compared with the creation date
+    pub(crate) fn date(
+        &mut self,
+        location: &str,
+        element: &'static str,
+        date: Date,
+        created: Option<Date>,
+        limit_ahead: bool,
+    ) {
+        if !date.is_business_day() {
+            self.push(location, element, Problem::NotBusinessDay(date));
+        }
+        let Some(created) = created else {
+            return;
+        };
+        if date < created {
+            self.push(
+                location,
+                element,
+                Problem::DateBeforeCreation { date, created },
+            );
+        } else if limit_ahead && (*date - *created).num_days() > MAX_DAYS_AHEAD {
+            self.push(
+                location,
+                element,
+                Problem::DateTooFarAhead { date, created },
+            );
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
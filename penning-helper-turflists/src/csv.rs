@@ -1,7 +1,7 @@
-use std::{collections::HashMap, io::Read, num::ParseFloatError, path::Path};
+use std::{collections::HashMap, io::Read, path::Path};
 
 use csv::Reader;
-use penning_helper_types::Euro;
+use penning_helper_types::{Euro, Lenient, Locale, ParseEuroError};
 use serde::{Deserialize, Serialize};
 
 use crate::turflist::{TurfList, TurfListRow};
@@ -31,12 +31,18 @@ fn default_email() -> String {
 }
 
 impl TryFrom<CsvEntry> for TurfListRow {
-    type Error = ParseFloatError;
+    type Error = ParseEuroError;
 
     fn try_from(value: CsvEntry) -> Result<Self, Self::Error> {
-        let total = value.total.parse::<f64>()?;
-        println!("{}: {}", total, Euro::from(total));
-        let mut e = Self::new(value.name, value.email, Euro::from(total), None);
+        // Loyverse writes totals like `2.333` for a third of a tab, which
+        // round to the cent like they always have
+        let total = value.total.parse::<Euro>().or_else(|e| {
+            match value.total.trim().parse::<f64>() {
+                Ok(total) if total.is_finite() => Ok(Euro::from(total)),
+                _ => Err(e),
+            }
+        })?;
+        let mut e = Self::new(value.name, value.email, total, Lenient::Missing);
         e.set_what(value.description);
         Ok(e)
     }
@@ -47,20 +53,21 @@ pub enum CsvReadError {
     #[error("CSV error: {0}")]
     CsvError(#[from] csv::Error),
     #[error("Parse error: {0}")]
-    EuroParseError(#[from] ParseFloatError),
+    EuroParseError(#[from] ParseEuroError),
 }
 
 pub fn try_loyverse<R: Read>(mut rdr: Reader<R>) -> Result<TurfList, CsvReadError> {
     let mut list = vec![];
-    let mut t = 0.0;
+    let mut t = Euro::default();
     for result in rdr.deserialize() {
         // println!("{:?}", result);
         let record: CsvEntry = result?;
         if record.payment_type != "AEGEE-DELFT" {
             continue;
         }
-        t += record.total.parse::<f64>()?;
-        list.push(record.try_into()?);
+        let row: TurfListRow = record.try_into()?;
+        t += row.amount;
+        list.push(row);
     }
     println!("Total: {}", t);
     Ok(TurfList::new(list))
@@ -75,10 +82,10 @@ struct TurffEntry {
 }
 
 impl TryFrom<TurffEntry> for TurfListRow {
-    type Error = ParseFloatError;
+    type Error = ParseEuroError;
 
     fn try_from(value: TurffEntry) -> Result<Self, Self::Error> {
-        let mut e = Self::new(value.name, default_email(), Euro::from(0.0), None);
+        let mut e = Self::new(value.name, default_email(), Euro::from(0.0), Lenient::Missing);
         let mut acc = vec![];
         for (k, v) in value.data {
             if k == "UID" {
@@ -90,7 +97,10 @@ impl TryFrom<TurffEntry> for TurfListRow {
                 Euro::from(v)
             } else if let Some(v) = v.as_str() {
                 println!("{}: {}", k, v);
-                Euro::from(v.replace(',', ".").parse::<f64>()?)
+                // Turff writes `12,50`, but a dot is read as decimals as well
+                // when that's the only way to read it
+                Euro::parse_localized(v, None)
+                    .or_else(|_| Euro::parse_localized(v, Some(Locale::Nl)))?
             } else {
                 println!("Skipping {}", k);
                 continue;
@@ -127,9 +137,28 @@ pub fn read_csv(r: impl AsRef<Path>) -> Result<TurfList, CsvReadError> {
     let r = r.as_ref();
     println!("Trying loyverse");
     if let Ok(l) = try_loyverse(csv::ReaderBuilder::new().from_path(r)?) {
-        return Ok(l);
+        Ok(l)
     } else {
         println!("Trying turff");
-        return try_turff(csv::ReaderBuilder::new().delimiter(b';').from_path(r)?);
+        try_turff(csv::ReaderBuilder::new().delimiter(b';').from_path(r)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turff_reads_both_decimal_separators() {
+        let csv = "Naam;UID;Bier;Fris;Borrel\nJan Jansen;1;12,50;€ 2.50;1.234,00\n";
+        let list = try_turff(
+            csv::ReaderBuilder::new()
+                .delimiter(b';')
+                .from_reader(csv.as_bytes()),
+        )
+        .unwrap();
+        let row = list.iter().next().unwrap();
+        assert_eq!(row.name, "Jan Jansen");
+        assert_eq!(row.amount, Euro::new(1249, 0));
     }
 }
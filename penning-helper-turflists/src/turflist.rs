@@ -1,4 +1,4 @@
-use penning_helper_types::Euro;
+use penning_helper_types::{Euro, Iban, Lenient};
 use textdistance::nstr::damerau_levenshtein;
 
 use crate::{
@@ -32,7 +32,7 @@ impl TurfList {
                 new_rows.push(row);
             }
         }
-        new_rows.retain_mut(|e| e.name != "");
+        new_rows.retain_mut(|e| !e.name.is_empty());
         new_rows.sort_by_key(|r| r.amount);
         new_rows.reverse();
         self.rows = new_rows;
@@ -61,12 +61,12 @@ pub struct TurfListRow {
     pub name: String,
     pub email: Option<String>,
     pub amount: Euro,
-    pub iban: Option<String>,
+    pub iban: Lenient<Iban>,
     pub what: Option<String>,
 }
 
 impl TurfListRow {
-    pub fn new(name: String, email: String, amount: Euro, iban: Option<String>) -> Self {
+    pub fn new(name: String, email: String, amount: Euro, iban: Lenient<Iban>) -> Self {
         Self {
             name,
             email: Some(email),
@@ -81,7 +81,7 @@ impl TurfListRow {
             name,
             email: None,
             amount,
-            iban: None,
+            iban: Lenient::Missing,
             what: None,
         }
     }
@@ -100,7 +100,7 @@ impl TurfListRow {
                 .as_ref()
                 .ok_or(crate::matcher::MatchError::NoMatch)?,
         };
-        if target == "" {
+        if target.is_empty() {
             return Err(crate::matcher::MatchError::NoMatch);
         }
         for (i, name) in options.iter().enumerate() {
@@ -128,9 +128,9 @@ impl TurfListRow {
     }
 
     pub fn best_idx(&self, names: &[String], emails: &[String]) -> Option<(usize, Euro)> {
-        let idx = match self.best_email_match(&emails) {
+        let idx = match self.best_email_match(emails) {
             Ok(idx) => idx,
-            Err(_) => match self.best_name_match(&names) {
+            Err(_) => match self.best_name_match(names) {
                 Ok(idx) => idx,
                 Err(_) => {
                     println!("No match found for {}", self.name);
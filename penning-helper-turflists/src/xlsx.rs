@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use calamine::{DataType, Reader};
-use penning_helper_types::Euro;
+use penning_helper_types::{Euro, Lenient};
 
 use crate::turflist::{TurfList, TurfListRow};
 
@@ -57,7 +57,7 @@ pub fn read_excel(p: impl AsRef<Path>, cost: Euro) -> Result<TurfList, XlsxError
     let mut rows = data.rows();
     let header = rows.next().unwrap();
     let mut indices = ColumnIndices::default();
-    for (idx, content) in header.into_iter().enumerate() {
+    for (idx, content) in header.iter().enumerate() {
         let content = content.to_string().to_lowercase();
         match content.as_str() {
             "first name" => indices.first_name = idx,
@@ -74,14 +74,17 @@ pub fn read_excel(p: impl AsRef<Path>, cost: Euro) -> Result<TurfList, XlsxError
     }
 
     for item in rows {
-        let iban = item.get(indices.iban).map(|x| x.to_string());
-        let iban = if iban == Some("".to_string()) {
-            None
-        } else {
-            iban
-        };
+        let iban = item
+            .get(indices.iban)
+            .map(|x| Lenient::new(&x.to_string()))
+            .unwrap_or_default();
         let cost = if indices.price != 999 {
-            item.get(indices.price).unwrap().as_f64().map(|f| Euro::from(f)).unwrap_or(cost)
+            let price = item.get(indices.price).unwrap();
+            price
+                .get_string()
+                .and_then(|s| s.parse::<Euro>().ok())
+                .or_else(|| price.as_f64().map(Euro::from))
+                .unwrap_or(cost)
         } else {
             cost
         };
@@ -91,8 +94,8 @@ pub fn read_excel(p: impl AsRef<Path>, cost: Euro) -> Result<TurfList, XlsxError
             } else {
                 format!(
                     "{} {}",
-                    item.get(indices.first_name).unwrap().to_string(),
-                    item.get(indices.last_name).unwrap().to_string()
+                    item.get(indices.first_name).unwrap(),
+                    item.get(indices.last_name).unwrap()
                 )
             },
             item.get(indices.email).unwrap().to_string(),
@@ -0,0 +1,132 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+mod directory;
+
+pub(crate) use directory::bank_code;
+pub use directory::{BicDirectory, BicDirectoryError};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BicError {
+    #[error("BIC is empty")]
+    Empty,
+    #[error("BIC should be 8 or 11 characters, not {0}")]
+    WrongLength(usize),
+    #[error("BIC bank code '{0}' should be four letters")]
+    InvalidBankCode(String),
+    #[error("BIC country code '{0}' should be two letters")]
+    InvalidCountry(String),
+    #[error("BIC contains invalid character '{0}'")]
+    InvalidCharacter(char),
+}
+
+/// A checked BIC (ISO 9362), uppercase without spaces. Both the 8 and 11
+/// character forms are accepted.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bic(String);
+
+impl Bic {
+    pub fn new(s: &str) -> Result<Self, BicError> {
+        let bic: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if bic.is_empty() {
+            return Err(BicError::Empty);
+        }
+        if let Some(c) = bic.chars().find(|c| !c.is_ascii_alphanumeric()) {
+            return Err(BicError::InvalidCharacter(c));
+        }
+        if bic.len() != 8 && bic.len() != 11 {
+            return Err(BicError::WrongLength(bic.len()));
+        }
+        if !bic[..4].bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(BicError::InvalidBankCode(bic[..4].to_string()));
+        }
+        if !bic[4..6].bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(BicError::InvalidCountry(bic[4..6].to_string()));
+        }
+        Ok(Self(bic))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn bank_code(&self) -> &str {
+        &self.0[..4]
+    }
+
+    pub fn country(&self) -> &str {
+        &self.0[4..6]
+    }
+
+    pub fn location(&self) -> &str {
+        &self.0[6..8]
+    }
+
+    pub fn branch(&self) -> Option<&str> {
+        self.0.get(8..).filter(|b| !b.is_empty())
+    }
+}
+
+impl FromStr for Bic {
+    type Err = BicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Display for Bic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Bic {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Serialize for Bic {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bic {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bic, BicError};
+
+    #[test]
+    fn parses_bics() {
+        let bic: Bic = "abna nl2a".parse().unwrap();
+        assert_eq!(bic.as_str(), "ABNANL2A");
+        assert_eq!(bic.country(), "NL");
+        assert_eq!(bic.branch(), None);
+        let bic: Bic = "DEUTDEFF500".parse().unwrap();
+        assert_eq!(bic.branch(), Some("500"));
+        assert_eq!("ABNANL2".parse::<Bic>(), Err(BicError::WrongLength(7)));
+        assert_eq!(
+            "AB1ANL2A".parse::<Bic>(),
+            Err(BicError::InvalidBankCode("AB1A".to_string()))
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use crate::Iban;
+
+use super::Bic;
+
+const BUNDLED: &str = include_str!("bic_directory.csv");
+
+/// Where the bank code sits in the BBAN, per country. Countries that aren't
+/// listed can't be looked up.
+const BANK_CODE_POSITIONS: &[(&str, usize, usize)] = &[
+    ("AT", 0, 5),
+    ("BE", 0, 3),
+    ("CH", 0, 5),
+    ("DE", 0, 8),
+    ("DK", 0, 4),
+    ("ES", 0, 4),
+    ("FI", 0, 3),
+    ("FR", 0, 5),
+    ("GB", 0, 4),
+    ("IE", 0, 4),
+    ("IT", 1, 6),
+    ("LI", 0, 5),
+    ("LU", 0, 3),
+    ("MC", 0, 5),
+    ("NL", 0, 4),
+    ("NO", 0, 4),
+    ("PT", 0, 4),
+    ("SE", 0, 3),
+    ("SM", 1, 6),
+];
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BicDirectoryError {
+    #[error("Could not read BIC directory: {0}")]
+    Io(String),
+    #[error("Line {line} of the BIC directory is invalid: {reason}")]
+    InvalidLine { line: usize, reason: String },
+}
+
+/// Maps the bank code inside an IBAN to the bank's BIC.
+///
+/// The directory is a plain `country,bank code,BIC` file so it can be updated
+/// without touching code; lines starting with `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct BicDirectory {
+    entries: HashMap<(String, String), Bic>,
+}
+
+impl BicDirectory {
+    pub fn parse(s: &str) -> Result<Self, BicDirectoryError> {
+        let mut entries = HashMap::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| BicDirectoryError::InvalidLine {
+                line: idx + 1,
+                reason,
+            };
+            let mut parts = line.split(',').map(str::trim);
+            let (Some(country), Some(code), Some(bic), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid("expected country,bank code,BIC".to_string()));
+            };
+            let bic: Bic = bic
+                .parse()
+                .map_err(|e: super::BicError| invalid(e.to_string()))?;
+            entries.insert(
+                (country.to_ascii_uppercase(), code.to_ascii_uppercase()),
+                bic,
+            );
+        }
+        Ok(Self { entries })
+    }
+
+    /// The directory that ships with penning-helper
+    pub fn bundled() -> &'static Self {
+        static BUNDLED_DIRECTORY: OnceLock<BicDirectory> = OnceLock::new();
+        BUNDLED_DIRECTORY
+            .get_or_init(|| Self::parse(BUNDLED).expect("bundled BIC directory is invalid"))
+    }
+
+    /// The bundled directory, with the entries from `path` on top. A missing
+    /// file is not an error.
+    pub fn bundled_with_overrides(path: &Path) -> Result<Self, BicDirectoryError> {
+        let mut directory = Self::bundled().clone();
+        match std::fs::read_to_string(path) {
+            Ok(s) => directory.extend(Self::parse(&s)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(BicDirectoryError::Io(e.to_string())),
+        }
+        Ok(directory)
+    }
+
+    /// Adds the entries of `other`, replacing the ones that are already known
+    pub fn extend(&mut self, other: BicDirectory) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn lookup(&self, iban: &Iban) -> Option<Bic> {
+        let code = iban.bank_code()?;
+        self.entries
+            .get(&(iban.country().to_string(), code.to_string()))
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+pub(crate) fn bank_code(iban: &Iban) -> Option<&str> {
+    let (_, start, end) = BANK_CODE_POSITIONS
+        .iter()
+        .find(|(c, _, _)| *c == iban.country())?;
+    iban.bban().get(*start..*end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BicDirectory;
+    use crate::{Bic, Iban};
+
+    #[test]
+    fn looks_up_dutch_banks() {
+        let directory = BicDirectory::bundled();
+        let iban: Iban = "NL91ABNA0417164300".parse().unwrap();
+        assert_eq!(directory.lookup(&iban), Some("ABNANL2A".parse().unwrap()));
+        let unknown: Iban = "DE89370400440532013000".parse().unwrap();
+        assert_eq!(directory.lookup(&unknown), None);
+    }
+
+    #[test]
+    fn overrides_extend_the_directory() {
+        let mut directory = BicDirectory::bundled().clone();
+        directory
+            .extend(BicDirectory::parse("# Commerzbank Köln\nDE,37040044,COBADEFFXXX\n").unwrap());
+        let iban: Iban = "DE89370400440532013000".parse().unwrap();
+        assert_eq!(
+            directory.lookup(&iban),
+            Some("COBADEFFXXX".parse::<Bic>().unwrap())
+        );
+        assert!(BicDirectory::parse("NL,ABNA").is_err());
+        assert!(BicDirectory::parse("NL,ABNA,ABNA").is_err());
+    }
+}
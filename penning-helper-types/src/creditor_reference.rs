@@ -0,0 +1,213 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The most characters the reference itself can have, after `RF` and the
+/// check digits
+const MAX_REFERENCE: usize = 21;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CreditorReferenceError {
+    #[error("Creditor reference is empty")]
+    Empty,
+    #[error("Creditor reference contains invalid character '{0}'")]
+    InvalidCharacter(char),
+    #[error("Creditor reference can have at most 25 characters, not {0}")]
+    TooLong(usize),
+    #[error("Creditor reference should start with RF")]
+    NotRf,
+    #[error("Creditor reference checksum is incorrect, probably a typo")]
+    Checksum,
+}
+
+/// An ISO 11649 structured creditor reference, like `RF18539007547034`,
+/// stored in electronic format (uppercase, no spaces)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CreditorReference(String);
+
+impl CreditorReference {
+    /// Adds `RF` and the check digits to `reference`
+    pub fn new(reference: &str) -> Result<Self, CreditorReferenceError> {
+        let reference = normalise(reference)?;
+        if reference.len() > MAX_REFERENCE {
+            return Err(CreditorReferenceError::TooLong(reference.len() + 4));
+        }
+        let check = 98 - mod97(&format!("{}RF00", reference));
+        Ok(Self(format!("RF{:02}{}", check, reference)))
+    }
+
+    /// Checks a complete reference, with `RF` and the check digits
+    pub fn parse(s: &str) -> Result<Self, CreditorReferenceError> {
+        let s = normalise(s)?;
+        if s.len() > MAX_REFERENCE + 4 {
+            return Err(CreditorReferenceError::TooLong(s.len()));
+        }
+        if !s.starts_with("RF") {
+            return Err(CreditorReferenceError::NotRf);
+        }
+        let check_digits = s.get(2..4).unwrap_or_default();
+        if s.len() < 5 || !check_digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(CreditorReferenceError::Checksum);
+        }
+        if mod97(&format!("{}{}", &s[4..], &s[..4])) != 1 {
+            return Err(CreditorReferenceError::Checksum);
+        }
+        Ok(Self(s))
+    }
+
+    /// The first reference in `text`, written together or in groups of four
+    pub fn find_in(text: &str) -> Option<Self> {
+        let words = text
+            .split(|c: char| c.is_whitespace() || c == '/' || c == ',' || c == ';')
+            .collect::<Vec<_>>();
+        for (i, word) in words.iter().enumerate() {
+            if !word.to_ascii_uppercase().starts_with("RF") {
+                continue;
+            }
+            let mut found = Self::parse(word).ok();
+            let mut grouped = word.to_string();
+            // a shorter grouping can have valid check digits by chance, so
+            // the longest one wins
+            for group in words[i + 1..]
+                .iter()
+                .take_while(|w| (1..=4).contains(&w.len()))
+            {
+                grouped.push_str(group);
+                if let Ok(r) = Self::parse(&grouped) {
+                    found = Some(r);
+                }
+            }
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn check_digits(&self) -> &str {
+        &self.0[2..4]
+    }
+
+    /// The reference without `RF` and the check digits
+    pub fn reference(&self) -> &str {
+        &self.0[4..]
+    }
+
+    /// Groups of four, the way it's printed on invoices
+    pub fn pretty(&self) -> String {
+        self.0
+            .as_bytes()
+            .chunks(4)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn normalise(s: &str) -> Result<String, CreditorReferenceError> {
+    let s: String = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if s.is_empty() {
+        return Err(CreditorReferenceError::Empty);
+    }
+    if let Some(c) = s.chars().find(|c| !c.is_ascii_alphanumeric()) {
+        return Err(CreditorReferenceError::InvalidCharacter(c));
+    }
+    Ok(s)
+}
+
+/// ISO 7064 mod 97-10, letters count as 10 to 35
+fn mod97(s: &str) -> u32 {
+    s.bytes().fold(0, |acc, b| match b {
+        b'0'..=b'9' => (acc * 10 + (b - b'0') as u32) % 97,
+        _ => (acc * 100 + (b - b'A' + 10) as u32) % 97,
+    })
+}
+
+impl FromStr for CreditorReference {
+    type Err = CreditorReferenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for CreditorReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for CreditorReference {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CreditorReference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreditorReference, CreditorReferenceError};
+
+    #[test]
+    fn adds_check_digits() {
+        let r = CreditorReference::new("539007547034").unwrap();
+        assert_eq!(r.as_str(), "RF18539007547034");
+        assert_eq!(r.pretty(), "RF18 5390 0754 7034");
+        assert_eq!(r.reference(), "539007547034");
+        assert_eq!(
+            CreditorReference::new("a2024 1001").unwrap().as_str(),
+            "RF50A20241001"
+        );
+        assert_eq!(
+            CreditorReference::new("1234567890123456789012"),
+            Err(CreditorReferenceError::TooLong(26))
+        );
+    }
+
+    #[test]
+    fn parses_and_finds_references() {
+        let r: CreditorReference = "rf18 5390 0754 7034".parse().unwrap();
+        assert_eq!(r.as_str(), "RF18539007547034");
+        assert_eq!(
+            "RF19539007547034".parse::<CreditorReference>(),
+            Err(CreditorReferenceError::Checksum)
+        );
+        assert_eq!(
+            "NL18539007547034".parse::<CreditorReference>(),
+            Err(CreditorReferenceError::NotRf)
+        );
+        assert_eq!(
+            "RF18-5390".parse::<CreditorReference>(),
+            Err(CreditorReferenceError::InvalidCharacter('-'))
+        );
+        assert_eq!(
+            CreditorReference::find_in("Betaling RF18539007547034 borrel"),
+            Some(r.clone())
+        );
+        assert_eq!(
+            CreditorReference::find_in("kenmerk RF18 5390 0754 7034 maart"),
+            Some(r)
+        );
+        assert_eq!(CreditorReference::find_in("RFID tags for the borrel"), None);
+    }
+}
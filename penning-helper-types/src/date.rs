@@ -3,7 +3,7 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use chrono::{Datelike, Days, NaiveDate};
+use chrono::{Datelike, Days, NaiveDate, Weekday};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
@@ -60,6 +60,59 @@ impl Date {
             date: self.date.checked_add_days(Days::new(days)).unwrap(),
         }
     }
+
+    /// Easter Sunday in `year`, with the anonymous Gregorian algorithm
+    pub fn easter(year: i32) -> Self {
+        let a = year % 19;
+        let b = year / 100;
+        let c = year % 100;
+        let d = b / 4;
+        let e = b % 4;
+        let f = (b + 8) / 25;
+        let g = (b - f + 1) / 3;
+        let h = (19 * a + b - d - g + 15) % 30;
+        let i = c / 4;
+        let k = c % 4;
+        let l = (32 + 2 * e + 2 * i - h - k) % 7;
+        let m = (a + 11 * h + 22 * l) / 451;
+        let month = (h + l - 7 * m + 114) / 31;
+        let day = (h + l - 7 * m + 114) % 31 + 1;
+        Self::new(year, month as u32, day as u32).unwrap()
+    }
+
+    /// New Year's Day, Good Friday, Easter Monday, Labour Day and both
+    /// Christmas days, when TARGET2 is closed and no SEPA payment settles
+    pub fn is_target2_holiday(&self) -> bool {
+        let easter = Self::easter(self.date.year());
+        let fixed = matches!(
+            (self.date.month(), self.date.day()),
+            (1, 1) | (5, 1) | (12, 25) | (12, 26)
+        );
+        fixed
+            || self.date.checked_add_days(Days::new(2)) == Some(easter.date)
+            || self.date.checked_sub_days(Days::new(1)) == Some(easter.date)
+    }
+
+    /// A weekday that isn't a TARGET2 holiday
+    pub fn is_business_day(&self) -> bool {
+        !matches!(self.date.weekday(), Weekday::Sat | Weekday::Sun) && !self.is_target2_holiday()
+    }
+
+    /// The business day `days` business days later. With 0 it's this day, or
+    /// the next business day if this isn't one.
+    pub fn add_business_days(self, days: u64) -> Self {
+        let mut date = self;
+        while !date.is_business_day() {
+            date = date.add_days(1);
+        }
+        for _ in 0..days {
+            date = date.add_days(1);
+            while !date.is_business_day() {
+                date = date.add_days(1);
+            }
+        }
+        date
+    }
 }
 
 impl Deref for Date {
@@ -120,3 +173,44 @@ impl<'de> Deserialize<'de> for Date {
             .ok_or_else(|| serde::de::Error::custom(format!("{} is not a valid date", s)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Date;
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn easter() {
+        assert_eq!(Date::easter(2024), date(2024, 3, 31));
+        assert_eq!(Date::easter(2025), date(2025, 4, 20));
+        assert_eq!(Date::easter(2038), date(2038, 4, 25));
+    }
+
+    #[test]
+    fn target2_holidays() {
+        assert!(date(2024, 3, 29).is_target2_holiday());
+        assert!(date(2024, 4, 1).is_target2_holiday());
+        assert!(date(2024, 5, 1).is_target2_holiday());
+        assert!(date(2024, 12, 26).is_target2_holiday());
+        // Ascension and King's Day are only Dutch holidays
+        assert!(date(2024, 5, 9).is_business_day());
+        assert!(!date(2024, 4, 27).is_target2_holiday());
+        assert!(!date(2024, 3, 30).is_business_day());
+    }
+
+    #[test]
+    fn business_days_skip_weekends_and_holidays() {
+        // Thursday evening plus two lands on Monday, not Saturday
+        assert_eq!(date(2024, 3, 7).add_business_days(2), date(2024, 3, 11));
+        // over Easter
+        assert_eq!(date(2024, 3, 28).add_business_days(1), date(2024, 4, 2));
+        // over Christmas and New Year
+        assert_eq!(date(2024, 12, 24).add_business_days(2), date(2024, 12, 30));
+        assert_eq!(date(2024, 12, 31).add_business_days(1), date(2025, 1, 2));
+        assert_eq!(date(2024, 3, 9).add_business_days(0), date(2024, 3, 11));
+        assert_eq!(date(2024, 3, 11).add_business_days(0), date(2024, 3, 11));
+    }
+}
@@ -2,52 +2,132 @@ use std::{
     fmt::{Debug, Display},
     iter::Sum,
     ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
-    str::FromStr, hash::Hash,
+    str::FromStr,
 };
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Serialize,
+};
 
-#[derive(PartialEq, Clone, Copy, Default, PartialOrd)]
-pub struct Euro(f64);
+mod locale;
 
-impl Eq for Euro {}
+pub use locale::{Locale, LocalizedEuro, ParseEuroError};
 
-impl Ord for Euro {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.partial_cmp(&other.0).unwrap()
-    }
-}
+/// An amount of money, stored as a whole number of cents so that sums and
+/// differences are always exact.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub struct Euro(i64);
 
 impl Euro {
     pub fn xml_string(&self) -> String {
-        format!("{:.2}", self.0)
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        format!("{}{}.{:02}", sign, abs / 100, abs % 100)
     }
 
     pub fn new(euros: i32, cents: i32) -> Self {
-        let cents = cents as f64 / 100.0;
-        let euros = euros as f64;
-        Euro(euros + cents)
+        Euro(euros as i64 * 100 + cents as i64)
+    }
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Euro(cents)
+    }
+
+    pub const fn cents(&self) -> i64 {
+        self.0
+    }
+
+    pub fn abs(self) -> Self {
+        Euro(self.0.abs())
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Euro)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Euro)
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(Euro)
     }
 
-    /// rounds to the nearest cent
-    fn round(mut self) -> Self {
-        self.0 = (self.0 * 100.0).round() / 100.0;
-        self
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(Euro)
+    }
+
+    /// Multiplies by a factor, rounding half a cent to the nearest even cent
+    /// (banker's rounding). Returns `None` if the factor is not finite or the
+    /// result doesn't fit.
+    pub fn checked_mul_f64(self, rhs: f64) -> Option<Self> {
+        cents_from_f64(self.0 as f64 * rhs).map(Euro)
+    }
+
+    /// Parses a plain decimal number like `12.5`, `-3` or `0.07` without going
+    /// through floating point, with at most two decimals.
+    fn parse_decimal(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+        if whole.is_empty() && frac.is_empty() {
+            return None;
+        }
+        if !whole
+            .bytes()
+            .chain(frac.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().ok()?
+        };
+        if frac.len() > 2 {
+            return None;
+        }
+        let mut digits = frac.bytes().map(|b| (b - b'0') as i64);
+        let tens = digits.next().unwrap_or(0);
+        let ones = digits.next().unwrap_or(0);
+        let cents = whole.checked_mul(100)?.checked_add(tens * 10 + ones)?;
+        Some(Euro(if negative { -cents } else { cents }))
     }
 }
 
-impl Hash for Euro {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.to_string().hash(state)
+fn cents_from_f64(cents: f64) -> Option<i64> {
+    let cents = cents.round_ties_even();
+    if cents.is_finite() && cents >= i64::MIN as f64 && cents <= i64::MAX as f64 {
+        Some(cents as i64)
+    } else {
+        None
     }
 }
 
 impl FromStr for Euro {
-    type Err = &'static str;
+    type Err = ParseEuroError;
 
+    /// Accepts everything [`Euro::parse_localized`] does without a locale,
+    /// plus scientific notation like `1e2` for a whole number of cents.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let f = s.parse::<f64>().map_err(|_| "Invalid Euro format")?;
-        Ok(Euro::from(f))
+        Euro::parse_localized(s, None).or_else(|e| {
+            let s = s.trim();
+            if !s.contains(['e', 'E']) {
+                return Err(e);
+            }
+            s.parse::<f64>()
+                .ok()
+                .map(|f| f * 100.0)
+                .filter(|cents| (cents - cents.round()).abs() < 1e-6)
+                .and_then(cents_from_f64)
+                .map(Euro)
+                .ok_or(e)
+        })
     }
 }
 
@@ -55,7 +135,7 @@ impl Neg for Euro {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        Euro::default() - self
+        self.checked_neg().expect("Euro negation overflowed")
     }
 }
 
@@ -65,23 +145,35 @@ impl Sum for Euro {
     }
 }
 
+impl<'a> Sum<&'a Euro> for Euro {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
 impl From<(i32, i32)> for Euro {
     fn from((euros, cents): (i32, i32)) -> Self {
-        Euro::new(euros, cents).round()
+        Euro::new(euros, cents)
     }
 }
 
 impl From<i32> for Euro {
     fn from(value: i32) -> Self {
-        Euro::new(value, 0).round()
+        Euro::new(value, 0)
     }
 }
 
+/// Whole euros. Amounts that don't fit saturate, like `From<f64>`.
 macro_rules! from_integer_type {
     ($($t:ty),* $(,)?) => {
         $(impl From<$t> for Euro {
             fn from(value: $t) -> Self {
-                Euro::new(value as i32, 0).round()
+                let cents = i64::try_from(value).ok().and_then(|v| v.checked_mul(100));
+                Euro(cents.unwrap_or(if value < <$t>::default() {
+                    i64::MIN
+                } else {
+                    i64::MAX
+                }))
             }
         })*
     };
@@ -89,29 +181,72 @@ macro_rules! from_integer_type {
 
 from_integer_type!(i8, i16, i64, i128, isize, u8, u16, u32, u64, u128, usize);
 
-
 impl Debug for Euro {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let value = self.0 as f64;
-        f.debug_tuple("Euro").field(&value).finish()
+        f.debug_tuple("Euro").field(&self.xml_string()).finish()
     }
 }
 
 impl Display for Euro {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let value = if f.sign_minus() { self.0.abs() } else { self.0 };
-        write!(f, "€{:.2}", value)
+        let value = if f.sign_minus() { self.abs() } else { *self };
+        write!(f, "€{}", value.xml_string())
     }
 }
 
+/// Reads what the f64 based `Euro` read: numbers and decimal strings, with
+/// any number of decimals rounded to the cent, half a cent to even. The
+/// strict [`FromStr`] is for amounts people type in.
 impl<'de> Deserialize<'de> for Euro {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let r = s.parse::<f64>().map_err(serde::de::Error::custom)?;
-        Ok(Euro(r))
+        // formats like bincode can't tell what comes next, and only ever
+        // have the string `Serialize` writes
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(EuroVisitor)
+        } else {
+            deserializer.deserialize_str(EuroVisitor)
+        }
+    }
+}
+
+struct EuroVisitor;
+
+impl Visitor<'_> for EuroVisitor {
+    type Value = Euro;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an amount in euros")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Euro, E> {
+        Euro::parse_decimal(v)
+            .or_else(|| {
+                let euros = v.trim().parse::<f64>().ok()?;
+                cents_from_f64(euros * 100.0).map(Euro)
+            })
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Euro, E> {
+        cents_from_f64(v * 100.0)
+            .map(Euro)
+            .ok_or_else(|| E::invalid_value(Unexpected::Float(v), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Euro, E> {
+        Euro::from_cents(v)
+            .checked_mul(100)
+            .ok_or_else(|| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Euro, E> {
+        i64::try_from(v)
+            .ok()
+            .and_then(|v| Euro::from_cents(v).checked_mul(100))
+            .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(v), &self))
     }
 }
 
@@ -120,8 +255,7 @@ impl Serialize for Euro {
     where
         S: serde::Serializer,
     {
-        format!("{:0.02}", self.0)
-            .serialize(serializer)
+        self.xml_string().serialize(serializer)
     }
 }
 
@@ -129,7 +263,7 @@ impl Add for Euro {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Euro(self.0 + rhs.0).round()
+        self.checked_add(rhs).expect("Euro addition overflowed")
     }
 }
 
@@ -143,7 +277,7 @@ impl Sub for Euro {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Euro(self.0 - rhs.0).round()
+        self.checked_sub(rhs).expect("Euro subtraction overflowed")
     }
 }
 
@@ -151,7 +285,7 @@ impl Sub<f64> for Euro {
     type Output = Self;
 
     fn sub(self, rhs: f64) -> Self::Output {
-        Euro(self.0 - rhs).round()
+        self - Euro::from(rhs)
     }
 }
 
@@ -167,17 +301,21 @@ impl From<f32> for Euro {
     }
 }
 
+/// Rounds to the nearest cent and half a cent to even, like `Mul<f64>`. NaN
+/// becomes zero and infinities saturate.
 impl From<f64> for Euro {
     fn from(value: f64) -> Self {
-        Euro(value).round()
+        Euro((value * 100.0).round_ties_even() as i64)
     }
 }
 
+/// Rounds half a cent to even, see [`Euro::checked_mul_f64`] for a variant that
+/// doesn't saturate.
 impl Mul<f64> for Euro {
     type Output = Euro;
 
     fn mul(self, rhs: f64) -> Self::Output {
-        Euro(self.0 * rhs).round()
+        Euro((self.0 as f64 * rhs).round_ties_even() as i64)
     }
 }
 
@@ -185,13 +323,16 @@ impl Mul<usize> for Euro {
     type Output = Euro;
 
     fn mul(self, rhs: usize) -> Self::Output {
-        Euro(self.0 * rhs as f64).round()
+        i64::try_from(rhs)
+            .ok()
+            .and_then(|rhs| self.checked_mul(rhs))
+            .expect("Euro multiplication overflowed")
     }
 }
 
-impl Into<f64> for Euro {
-    fn into(self) -> f64 {
-        self.0
+impl From<Euro> for f64 {
+    fn from(value: Euro) -> Self {
+        value.0 as f64 / 100.0
     }
 }
 
@@ -229,4 +370,81 @@ mod tests {
 
         assert_eq!(a.xml_string(), "5821.04");
     }
+
+    #[test]
+    fn sum_is_exact() {
+        let total: Euro = std::iter::repeat_n(Euro::from(0.1), 100_000).sum();
+        assert_eq!(total, Euro::from(10_000));
+    }
+
+    #[test]
+    fn from_f64_rounds_like_mul() {
+        // exactly half a cent, so both have to pick the same side
+        assert_eq!(Euro::from(0.125), Euro::new(0, 12));
+        assert_eq!(Euro::from(-0.125), Euro::new(0, -12));
+        assert_eq!(Euro::from(0.375), Euro::new(0, 38));
+        assert_eq!(Euro::new(0, 25) * 0.5, Euro::from(0.125));
+        assert_eq!(Euro::new(0, 75) * 0.5, Euro::from(0.375));
+    }
+
+    #[test]
+    fn mul_rounds_half_to_even() {
+        assert_eq!(Euro::new(0, 5) * 0.5, Euro::new(0, 2));
+        assert_eq!(Euro::new(0, 7) * 0.5, Euro::new(0, 4));
+        assert_eq!(Euro::new(0, -5) * 0.5, Euro::new(0, -2));
+        assert_eq!(Euro::new(10, 0) * 3usize, Euro::new(30, 0));
+    }
+
+    #[test]
+    fn wide_integers_saturate() {
+        assert_eq!(Euro::from(12u64), Euro::new(12, 0));
+        assert_eq!(Euro::from(-12i128), Euro::new(-12, 0));
+        assert_eq!(Euro::from(i64::MAX), Euro::from_cents(i64::MAX));
+        assert_eq!(Euro::from(i64::MIN / 10), Euro::from_cents(i64::MIN));
+        assert_eq!(Euro::from(u128::MAX), Euro::from_cents(i64::MAX));
+        assert_eq!(Euro::from(usize::MAX), Euro::from_cents(i64::MAX));
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(
+            Euro::from_cents(i64::MAX).checked_add(Euro::new(0, 1)),
+            None
+        );
+        assert_eq!(Euro::from_cents(i64::MIN).checked_neg(), None);
+        assert_eq!(Euro::new(1, 0).checked_mul_f64(f64::NAN), None);
+        assert_eq!(Euro::from(f64::NAN), Euro::default());
+        assert_eq!(Euro::new(1, 0) * f64::NAN, Euro::default());
+    }
+
+    #[test]
+    fn string_formats_are_stable() {
+        assert_eq!(Euro::new(-3, -5).xml_string(), "-3.05");
+        assert_eq!(Euro::new(0, -5).to_string(), "€-0.05");
+        assert_eq!(format!("{:-}", Euro::new(-12, -50)), "€12.50");
+        assert_eq!("12.5".parse::<Euro>(), Ok(Euro::new(12, 50)));
+        assert!("-0.125".parse::<Euro>().is_err());
+        assert!("1.2345e1".parse::<Euro>().is_err());
+        assert_eq!("1e2".parse::<Euro>(), Ok(Euro::from(100)));
+        assert!("abc".parse::<Euro>().is_err());
+    }
+
+    #[test]
+    fn deserialize_reads_what_the_f64_version_did() {
+        use serde::{de::value::Error, de::IntoDeserializer, Deserialize};
+
+        let euro = |s: &str| Euro::deserialize(IntoDeserializer::<Error>::into_deserializer(s));
+        assert_eq!(euro("12.5"), Ok(Euro::new(12, 50)));
+        assert_eq!(euro("0.125"), Ok(Euro::new(0, 12)));
+        assert_eq!(euro("1.2e1"), Ok(Euro::new(12, 0)));
+        assert_eq!(euro(" -3 "), Ok(Euro::new(-3, 0)));
+        assert!(euro("1.234,50").is_err());
+        assert!(euro("NaN").is_err());
+
+        let number = |f: f64| Euro::deserialize(IntoDeserializer::<Error>::into_deserializer(f));
+        assert_eq!(number(7.005), Ok(Euro::new(7, 0)));
+        assert_eq!(number(-0.375), Ok(Euro::new(0, -38)));
+        let whole = Euro::deserialize(IntoDeserializer::<Error>::into_deserializer(25i64));
+        assert_eq!(whole, Ok(Euro::from(25)));
+    }
 }
@@ -0,0 +1,255 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::Euro;
+
+/// Which notation to use for amounts, both for reading and writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// `€1,234.56`
+    #[default]
+    En,
+    /// `€ 1.234,56`
+    Nl,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Nl];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::En => "English (€1,234.56)",
+            Locale::Nl => "Nederlands (€ 1.234,56)",
+        }
+    }
+
+    fn separators(&self) -> (char, char) {
+        match self {
+            Locale::En => (',', '.'),
+            Locale::Nl => ('.', ','),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEuroError {
+    input: String,
+}
+
+impl ParseEuroError {
+    fn new(input: &str) -> Self {
+        Self {
+            input: input.to_string(),
+        }
+    }
+}
+
+impl Display for ParseEuroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a valid amount", self.input)
+    }
+}
+
+impl std::error::Error for ParseEuroError {}
+
+/// Displays an amount in the notation of a [`Locale`], `{:-}` shows the absolute
+/// value just like [`Euro`]'s own `Display`.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalizedEuro {
+    euro: Euro,
+    locale: Locale,
+}
+
+impl Display for LocalizedEuro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (thousands, decimal) = self.locale.separators();
+        let cents = self.euro.cents();
+        let negative = cents < 0 && !f.sign_minus();
+        let abs = cents.unsigned_abs();
+
+        let digits = (abs / 100).to_string();
+        let mut whole = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                whole.push(thousands);
+            }
+            whole.push(c);
+        }
+        let sign = if negative { "-" } else { "" };
+
+        match self.locale {
+            Locale::En => write!(f, "{}€{}{}{:02}", sign, whole, decimal, abs % 100),
+            Locale::Nl => write!(f, "€ {}{}{}{:02}", sign, whole, decimal, abs % 100),
+        }
+    }
+}
+
+impl Euro {
+    pub fn localized(self, locale: Locale) -> LocalizedEuro {
+        LocalizedEuro { euro: self, locale }
+    }
+
+    /// Parses amounts the way people type them: with or without a euro sign,
+    /// Dutch or English separators, thousands separators and negative amounts
+    /// as `-12,50` or `(12,50)`.
+    ///
+    /// Without a locale the notation is guessed: if both `.` and `,` occur the
+    /// last one is the decimal separator, a separator that occurs more than once
+    /// separates thousands, and a single separator is the decimal one. A single
+    /// separator followed by three digits, like `1.234`, could be either and is
+    /// refused. More than two decimals are refused instead of rounded.
+    pub fn parse_localized(s: &str, locale: Option<Locale>) -> Result<Self, ParseEuroError> {
+        let err = || ParseEuroError::new(s);
+
+        let mut body = s.trim();
+        let parenthesised = body.starts_with('(') && body.ends_with(')');
+        if parenthesised {
+            body = &body[1..body.len() - 1];
+        }
+        let body: String = body
+            .replace('€', "")
+            .replace("EUR", "")
+            .replace("eur", "")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+
+        let (negative, body) = match body.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, body.strip_prefix('+').unwrap_or(&body)),
+        };
+        if negative && parenthesised {
+            return Err(err());
+        }
+        if body.is_empty()
+            || !body
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+        {
+            return Err(err());
+        }
+
+        let (thousands, decimal) = match locale {
+            Some(locale) => locale.separators(),
+            None if is_ambiguous(body) => return Err(err()),
+            None => guess_separators(body),
+        };
+
+        let (whole, frac) = match body.rsplit_once(decimal) {
+            Some((whole, frac)) => (whole, frac),
+            None => (body, ""),
+        };
+        if frac.contains([thousands, decimal]) || frac.len() > 2 {
+            return Err(err());
+        }
+        if whole.contains(decimal) {
+            return Err(err());
+        }
+        let whole = if whole.contains(thousands) {
+            let mut groups = whole.split(thousands);
+            let first = groups.next().unwrap_or_default();
+            if first.is_empty() || first.len() > 3 || groups.any(|g| g.len() != 3) {
+                return Err(err());
+            }
+            whole.replace(thousands, "")
+        } else {
+            whole.to_string()
+        };
+
+        let amount = Euro::parse_decimal(&format!("{}.{}", whole, frac)).ok_or_else(err)?;
+        if negative || parenthesised {
+            amount.checked_neg().ok_or_else(err)
+        } else {
+            Ok(amount)
+        }
+    }
+}
+
+/// One separator with three digits after it is a thousands separator in one
+/// notation and a decimal one in the other
+fn is_ambiguous(body: &str) -> bool {
+    let mut separators = body.match_indices(['.', ',']);
+    match (separators.next(), separators.next()) {
+        (Some((i, _)), None) => body.len() - i - 1 == 3,
+        _ => false,
+    }
+}
+
+fn guess_separators(body: &str) -> (char, char) {
+    let last_dot = body.rfind('.');
+    let last_comma = body.rfind(',');
+    match (last_dot, last_comma) {
+        (Some(dot), Some(comma)) if comma > dot => Locale::Nl.separators(),
+        (Some(_), Some(_)) => Locale::En.separators(),
+        (None, Some(_)) if body.matches(',').count() > 1 => Locale::En.separators(),
+        (None, Some(_)) => Locale::Nl.separators(),
+        (Some(_), None) if body.matches('.').count() > 1 => Locale::Nl.separators(),
+        _ => Locale::En.separators(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+    use crate::Euro;
+
+    #[test]
+    fn parses_common_notations() {
+        let cases = [
+            ("€ 1.234,56", Euro::new(1234, 56)),
+            ("€1,234.56", Euro::new(1234, 56)),
+            ("12,50", Euro::new(12, 50)),
+            ("12.5", Euro::new(12, 50)),
+            ("1.234.567", Euro::new(1234567, 0)),
+            ("(12,50)", Euro::new(-12, -50)),
+            ("-€ 3,05", Euro::new(-3, -5)),
+            ("€ -3,05", Euro::new(-3, -5)),
+            ("12,50 EUR", Euro::new(12, 50)),
+            ("1\u{a0}234,56", Euro::new(1234, 56)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Euro>(), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn explicit_locale_decides_ambiguous_input() {
+        assert_eq!(
+            Euro::parse_localized("1.234", Some(Locale::Nl)),
+            Ok(Euro::from(1234))
+        );
+        assert!(Euro::parse_localized("1.234", Some(Locale::En)).is_err());
+        assert_eq!(
+            Euro::parse_localized("1,234", Some(Locale::En)),
+            Ok(Euro::from(1234))
+        );
+        assert!(Euro::parse_localized("1,2345.6", None).is_err());
+        assert!(Euro::parse_localized("(-5)", None).is_err());
+        assert!(Euro::parse_localized("€", None).is_err());
+    }
+
+    #[test]
+    fn ambiguous_or_too_precise_input_is_refused() {
+        for input in ["1,234", "1.234", "12,345", "0.125", "12,505", "1.234,567"] {
+            assert!(input.parse::<Euro>().is_err(), "{}", input);
+        }
+        assert_eq!("1.234,5".parse::<Euro>(), Ok(Euro::new(1234, 50)));
+        assert_eq!("1,234.56".parse::<Euro>(), Ok(Euro::new(1234, 56)));
+        assert_eq!("1234".parse::<Euro>(), Ok(Euro::from(1234)));
+    }
+
+    #[test]
+    fn formats_per_locale() {
+        let e = Euro::new(-1234, -56);
+        assert_eq!(e.localized(Locale::Nl).to_string(), "€ -1.234,56");
+        assert_eq!(e.localized(Locale::En).to_string(), "-€1,234.56");
+        assert_eq!(format!("{:-}", e.localized(Locale::Nl)), "€ 1.234,56");
+        assert_eq!(Euro::new(0, 7).localized(Locale::En).to_string(), "€0.07");
+        assert_eq!(
+            Euro::from(1_000_000).localized(Locale::Nl).to_string(),
+            "€ 1.000.000,00"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// IBAN lengths per country, from the SWIFT IBAN registry.
+const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AD", 24),
+    ("AE", 23),
+    ("AL", 28),
+    ("AT", 20),
+    ("AZ", 28),
+    ("BA", 20),
+    ("BE", 16),
+    ("BG", 22),
+    ("BH", 22),
+    ("BI", 27),
+    ("BR", 29),
+    ("BY", 28),
+    ("CH", 21),
+    ("CR", 22),
+    ("CY", 28),
+    ("CZ", 24),
+    ("DE", 22),
+    ("DJ", 27),
+    ("DK", 18),
+    ("DO", 28),
+    ("EE", 20),
+    ("EG", 29),
+    ("ES", 24),
+    ("FI", 18),
+    ("FK", 18),
+    ("FO", 18),
+    ("FR", 27),
+    ("GB", 22),
+    ("GE", 22),
+    ("GI", 23),
+    ("GL", 18),
+    ("GR", 27),
+    ("GT", 28),
+    ("HN", 28),
+    ("HR", 21),
+    ("HU", 28),
+    ("IE", 22),
+    ("IL", 23),
+    ("IQ", 23),
+    ("IS", 26),
+    ("IT", 27),
+    ("JO", 30),
+    ("KW", 30),
+    ("KZ", 20),
+    ("LB", 28),
+    ("LC", 32),
+    ("LI", 21),
+    ("LT", 20),
+    ("LU", 20),
+    ("LV", 21),
+    ("LY", 25),
+    ("MC", 27),
+    ("MD", 24),
+    ("ME", 22),
+    ("MK", 19),
+    ("MN", 20),
+    ("MR", 27),
+    ("MT", 31),
+    ("MU", 30),
+    ("NI", 28),
+    ("NL", 18),
+    ("NO", 15),
+    ("OM", 23),
+    ("PK", 24),
+    ("PL", 28),
+    ("PS", 29),
+    ("PT", 25),
+    ("QA", 29),
+    ("RO", 24),
+    ("RS", 22),
+    ("RU", 33),
+    ("SA", 24),
+    ("SC", 31),
+    ("SD", 18),
+    ("SE", 24),
+    ("SI", 19),
+    ("SK", 24),
+    ("SM", 27),
+    ("SO", 23),
+    ("ST", 25),
+    ("SV", 28),
+    ("TL", 23),
+    ("TN", 24),
+    ("TR", 26),
+    ("UA", 29),
+    ("VA", 22),
+    ("VG", 24),
+    ("XK", 20),
+    ("YE", 30),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IbanError {
+    #[error("IBAN is empty")]
+    Empty,
+    #[error("IBAN contains invalid character '{0}'")]
+    InvalidCharacter(char),
+    #[error("IBAN has unknown country code '{0}'")]
+    UnknownCountry(String),
+    #[error("IBAN for {country} should be {expected} characters, not {actual}")]
+    WrongLength {
+        country: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("IBAN checksum is incorrect, probably a typo")]
+    Checksum,
+}
+
+/// A checked IBAN, stored in electronic format (uppercase, no spaces).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Iban(String);
+
+impl Iban {
+    pub fn new(s: &str) -> Result<Self, IbanError> {
+        let iban: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if iban.is_empty() {
+            return Err(IbanError::Empty);
+        }
+        if let Some(c) = iban.chars().find(|c| !c.is_ascii_alphanumeric()) {
+            return Err(IbanError::InvalidCharacter(c));
+        }
+        let country = iban.get(..2).unwrap_or(&iban);
+        let expected = IBAN_LENGTHS
+            .iter()
+            .find(|(c, _)| *c == country)
+            .map(|(_, l)| *l)
+            .ok_or_else(|| IbanError::UnknownCountry(country.to_string()))?;
+        if iban.len() != expected {
+            return Err(IbanError::WrongLength {
+                country: country.to_string(),
+                expected,
+                actual: iban.len(),
+            });
+        }
+        if !iban[2..4].bytes().all(|b| b.is_ascii_digit()) || mod97(&iban) != 1 {
+            return Err(IbanError::Checksum);
+        }
+        Ok(Self(iban))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn country(&self) -> &str {
+        &self.0[..2]
+    }
+
+    pub fn check_digits(&self) -> &str {
+        &self.0[2..4]
+    }
+
+    pub fn bban(&self) -> &str {
+        &self.0[4..]
+    }
+
+    /// The part of the BBAN that identifies the bank, for the countries where
+    /// that is known
+    pub fn bank_code(&self) -> Option<&str> {
+        crate::bic::bank_code(self)
+    }
+
+    /// Groups of four, the way IBANs are printed on bank cards
+    pub fn pretty(&self) -> String {
+        self.0
+            .as_bytes()
+            .chunks(4)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// ISO 7064 mod 97-10 over the IBAN with its first four characters moved to the end
+fn mod97(iban: &str) -> u32 {
+    let rearranged = iban[4..].bytes().chain(iban[..4].bytes());
+    rearranged.fold(0, |acc, b| match b {
+        b'0'..=b'9' => (acc * 10 + (b - b'0') as u32) % 97,
+        _ => (acc * 100 + (b - b'A' + 10) as u32) % 97,
+    })
+}
+
+impl FromStr for Iban {
+    type Err = IbanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Display for Iban {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Iban {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Serialize for Iban {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Iban {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Iban, IbanError};
+
+    #[test]
+    fn accepts_valid_ibans() {
+        let iban: Iban = " nl91 abna 0417 1643 00 ".parse().unwrap();
+        assert_eq!(iban.as_str(), "NL91ABNA0417164300");
+        assert_eq!(iban.pretty(), "NL91 ABNA 0417 1643 00");
+        assert_eq!(iban.country(), "NL");
+        assert_eq!(iban.bban(), "ABNA0417164300");
+        assert!("GB82WEST12345698765432".parse::<Iban>().is_ok());
+        assert!("DE89370400440532013000".parse::<Iban>().is_ok());
+    }
+
+    #[test]
+    fn rejects_typos() {
+        assert_eq!(
+            "NL91ABNA0417164301".parse::<Iban>(),
+            Err(IbanError::Checksum)
+        );
+        assert_eq!(
+            "NL91ABNA041716430".parse::<Iban>(),
+            Err(IbanError::WrongLength {
+                country: "NL".to_string(),
+                expected: 18,
+                actual: 17
+            })
+        );
+        assert_eq!(
+            "XX91ABNA0417164300".parse::<Iban>(),
+            Err(IbanError::UnknownCountry("XX".to_string()))
+        );
+        assert_eq!(
+            "NL91-ABNA0417164300".parse::<Iban>(),
+            Err(IbanError::InvalidCharacter('-'))
+        );
+        assert_eq!("".parse::<Iban>(), Err(IbanError::Empty));
+    }
+}
@@ -0,0 +1,124 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// A value that comes in as text, like an IBAN in Conscribo or the config.
+/// It is parsed when it is read, but one that doesn't parse is kept with its
+/// error instead of failing everything around it, so it can be shown next to
+/// whoever it belongs to and written back as it was.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Lenient<T: FromStr> {
+    /// Nothing was filled in
+    #[default]
+    Missing,
+    Valid(T),
+    Invalid {
+        raw: String,
+        error: T::Err,
+    },
+}
+
+impl<T: FromStr> Lenient<T> {
+    /// Keeps an invalid `s` as it is, so it can still be edited
+    pub fn new(s: &str) -> Self {
+        if s.trim().is_empty() {
+            return Self::Missing;
+        }
+        match s.trim().parse() {
+            Ok(v) => Self::Valid(v),
+            Err(error) => Self::Invalid {
+                raw: s.to_string(),
+                error,
+            },
+        }
+    }
+
+    pub fn valid(&self) -> Option<&T> {
+        match self {
+            Self::Valid(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// `None` when nothing was filled in
+    pub fn get(&self) -> Option<Result<&T, &T::Err>> {
+        match self {
+            Self::Missing => None,
+            Self::Valid(v) => Some(Ok(v)),
+            Self::Invalid { error, .. } => Some(Err(error)),
+        }
+    }
+
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Self::Missing)
+    }
+}
+
+impl<T: FromStr> From<&str> for Lenient<T> {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Writes the value as it was read, an invalid one included
+impl<T: FromStr + Display> Display for Lenient<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => Ok(()),
+            Self::Valid(v) => v.fmt(f),
+            Self::Invalid { raw, .. } => f.write_str(raw),
+        }
+    }
+}
+
+impl<T: FromStr + Serialize> Serialize for Lenient<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Missing => serializer.serialize_str(""),
+            Self::Valid(v) => v.serialize(serializer),
+            Self::Invalid { raw, .. } => serializer.serialize_str(raw),
+        }
+    }
+}
+
+impl<'de, T: FromStr> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = Option::<String>::deserialize(deserializer)?;
+        Ok(s.as_deref().map(Self::new).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lenient;
+    use crate::{Bic, Iban, IbanError};
+
+    #[test]
+    fn keeps_what_does_not_parse() {
+        let iban: Lenient<Iban> = " NL91 ABNA 0417 1643 00 ".into();
+        assert_eq!(iban.valid(), Some(&"NL91ABNA0417164300".parse().unwrap()));
+        let iban: Lenient<Iban> = "NL91ABNA0417164301".into();
+        assert_eq!(iban.get(), Some(Err(&IbanError::Checksum)));
+        assert_eq!(iban.to_string(), "NL91ABNA0417164301");
+        assert!(Lenient::<Iban>::from("  ").is_missing());
+    }
+
+    #[test]
+    fn reads_and_writes_strings() {
+        let bics: Vec<Lenient<Bic>> =
+            serde_json::from_str(r#"["ABNANL2A", "ABNANL2", "", null]"#).unwrap();
+        assert!(bics[0].valid().is_some());
+        assert!(matches!(bics[1], Lenient::Invalid { .. }));
+        assert!(bics[2].is_missing() && bics[3].is_missing());
+        assert_eq!(
+            serde_json::to_string(&bics).unwrap(),
+            r#"["ABNANL2A","ABNANL2","",""]"#
+        );
+    }
+}
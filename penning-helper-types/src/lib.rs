@@ -1,7 +1,16 @@
 mod euro;
-pub use euro::Euro;
+pub use euro::{Euro, Locale, LocalizedEuro, ParseEuroError};
 mod date;
 pub use date::Date;
 
 mod address;
-pub use address::Address;
\ No newline at end of file
+pub use address::Address;
+
+mod iban;
+pub use iban::{Iban, IbanError};
+mod creditor_reference;
+pub use creditor_reference::{CreditorReference, CreditorReferenceError};
+mod bic;
+pub use bic::{Bic, BicDirectory, BicDirectoryError, BicError};
+mod lenient;
+pub use lenient::Lenient;
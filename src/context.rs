@@ -0,0 +1,159 @@
+use std::{path::PathBuf, sync::Arc};
+
+use penning_helper_config::Config;
+use penning_helper_conscribo::{
+    accounts::{AccountRequest, AccountResponse},
+    add_transaction::AddTransaction,
+    blocking::ConscriboClient,
+    multirequest::MultiRequest,
+    session::Credentials,
+    transactions::UnifiedTransaction,
+};
+use penning_helper_invoices::Relations;
+use penning_helper_sepa::SEPAConfig;
+use penning_helper_types::BicDirectory;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("Could not read the config at {0}: {1}")]
+    ConfigFile(PathBuf, String),
+    #[error("The config is incomplete: {0}")]
+    Config(String),
+    #[error("Conscribo: {0}")]
+    Conscribo(String),
+    #[error("{0}")]
+    Sepa(String),
+    #[error("Could not read or write {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// The data dir the interface keeps its files in, next to `clientcache.bin`
+pub fn data_file(name: &str) -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or(PathBuf::from("."))
+        .join("penning-helper")
+        .join(name)
+}
+
+/// Reads the code of the authenticator app from stdin, so it also works over
+/// ssh or piped in from a script
+fn two_fa_code() -> Option<i32> {
+    eprint!("Conscribo two-factor code: ");
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).ok()?;
+    line.trim().parse().ok()
+}
+
+/// The config and a Conscribo session, shared by every subcommand
+pub struct Context {
+    pub cfg: Config,
+    client: Option<ConscriboClient>,
+}
+
+impl Context {
+    /// Reads `path`, or the config of the interface when there is none
+    pub fn load(path: Option<PathBuf>) -> Result<Self, CliError> {
+        let cfg = match path {
+            Some(path) => {
+                let toml = std::fs::read_to_string(&path)
+                    .map_err(|e| CliError::ConfigFile(path.clone(), e.to_string()))?;
+                Config::from_toml(&toml).map_err(|e| CliError::ConfigFile(path, e.to_string()))?
+            }
+            None => Config::load_from_file(),
+        };
+        Ok(Self { cfg, client: None })
+    }
+
+    /// Logs in to Conscribo the first time it is needed
+    pub fn conscribo(&mut self) -> Result<&ConscriboClient, CliError> {
+        if self.client.is_none() {
+            let cfg = self.cfg.conscribo();
+            if cfg.username.is_empty() || cfg.password.is_empty() || cfg.account_name.is_empty() {
+                return Err(CliError::Config(
+                    "the Conscribo account, username and password have to be set".to_string(),
+                ));
+            }
+            let mut client = ConscriboClient::new(cfg.account_name.clone())
+                .with_credentials(Credentials::new(cfg.username.clone(), cfg.password.clone()));
+            if !cfg.base_url.is_empty() {
+                client = client.with_base_url(&cfg.base_url);
+            }
+            self.client = Some(if cfg.two_fa {
+                client.with_two_fa_prompt(two_fa_code)
+            } else {
+                client
+            });
+        }
+        Ok(self.client.as_ref().unwrap())
+    }
+
+    pub fn sepa(&self) -> Result<SEPAConfig, CliError> {
+        let sepa = SEPAConfig::from_config(self.cfg.sepa())
+            .map_err(|e| CliError::Config(format!("SEPA settings are invalid: {}", e)))?;
+        match BicDirectory::bundled_with_overrides(&data_file("bic_directory.csv")) {
+            Ok(d) => Ok(sepa.with_bic_directory(Arc::new(d))),
+            Err(e) => {
+                eprintln!("Error loading BIC directory: {}", e);
+                Ok(sepa)
+            }
+        }
+    }
+
+    pub fn relations(&mut self) -> Result<Relations, CliError> {
+        let relations = self
+            .conscribo()?
+            .get_relations()
+            .map_err(|e| CliError::Conscribo(e.to_string()))?;
+        Ok(Relations::new(&[relations]))
+    }
+
+    pub fn accounts(&mut self) -> Result<AccountResponse, CliError> {
+        self.conscribo()?
+            .execute(AccountRequest::today())
+            .and_then(|r| r.into_result())
+            .map_err(|e| CliError::Conscribo(e.to_string()))
+    }
+
+    /// Every transaction on the debtor and creditor accounts, waiting for the
+    /// download to finish
+    pub fn transactions(&mut self) -> Result<Vec<UnifiedTransaction>, CliError> {
+        self.conscribo()?
+            .wait_for_transactions(|p| {
+                if p.count % 100 == 0 {
+                    eprintln!(
+                        "Got {} out of {} transactions, with {} from cache",
+                        p.count, p.total, p.from_cache
+                    );
+                }
+            })
+            .map_err(|e| CliError::Conscribo(e.to_string()))
+    }
+
+    /// Books `transactions` in one request, failing when Conscribo refused
+    /// any of them
+    pub fn add_transactions(
+        &mut self,
+        transactions: Vec<AddTransaction>,
+    ) -> Result<usize, CliError> {
+        let responses = self
+            .conscribo()?
+            .execute(MultiRequest::new().push_all(transactions.into_iter().enumerate().collect()))
+            .and_then(|res| res.responses_owned())
+            .map_err(|e| CliError::Conscribo(e.to_string()))?;
+        let refused = responses
+            .values()
+            .filter_map(|e| Some(format!("{}: {}", e.seq(), e.error()?.message())))
+            .collect::<Vec<_>>();
+        if !refused.is_empty() {
+            return Err(CliError::Conscribo(format!(
+                "{} of {} transactions were refused: {}",
+                refused.len(),
+                responses.len(),
+                refused.join(", ")
+            )));
+        }
+        Ok(responses.len())
+    }
+}
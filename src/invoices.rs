@@ -0,0 +1,84 @@
+use clap::{Subcommand, ValueEnum};
+use penning_helper_invoices::{invoices, Invoices};
+use penning_helper_sepa::Selection;
+use penning_helper_types::Euro;
+
+use crate::context::{CliError, Context};
+
+#[derive(Debug, Subcommand)]
+pub enum InvoicesCommand {
+    /// Prints the open balance of every relation that would be invoiced, what
+    /// would be collected or paid, and why a relation can't be in the SEPA file
+    Preview {
+        #[arg(long, value_enum, default_value_t)]
+        category: SelectionArg,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: InvoicesCommand) -> Result<(), CliError> {
+    match command {
+        InvoicesCommand::Preview { category } => {
+            let category = Selection::from(category);
+            let sepa = ctx.sepa().ok();
+            let invoices = open_invoices(ctx)?;
+            let mut total = Euro::default();
+            for i in invoices
+                .iter()
+                .filter(|i| category.filter(i, i.total_cost()))
+            {
+                let (amount, _) = i.collection(category, i.total_cost());
+                let problems = i.problems(sepa.as_ref());
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    i.code,
+                    i.name,
+                    i.total_cost(),
+                    amount,
+                    problems.join(", ")
+                );
+                total += i.total_cost();
+            }
+            eprintln!("Total open: {}", total);
+            Ok(())
+        }
+    }
+}
+
+/// Which relations to invoice, on the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SelectionArg {
+    /// Every open balance
+    #[default]
+    All,
+    /// Balances over 100 euros
+    OwesUsALot,
+    /// Balances under -10 euros, which we pay out
+    IsOwedByUs,
+    /// The yearly contribution, at most 50 euros
+    Contribution,
+    /// The yearly alumni contribution
+    AlumniContribution,
+}
+
+impl From<SelectionArg> for Selection {
+    fn from(value: SelectionArg) -> Self {
+        match value {
+            SelectionArg::All => Selection::All,
+            SelectionArg::OwesUsALot => Selection::OwesUsALot,
+            SelectionArg::IsOwedByUs => Selection::IsOwedByUs,
+            SelectionArg::Contribution => Selection::Contribution,
+            SelectionArg::AlumniContribution => Selection::AlumniContribution,
+        }
+    }
+}
+
+/// The open balances of the relations that get invoices, with a warning for
+/// transactions of codes that aren't a relation
+pub fn open_invoices(ctx: &mut Context) -> Result<Invoices, CliError> {
+    let relations = ctx.relations()?;
+    let invoices = invoices(&relations, ctx.transactions()?);
+    for code in invoices.unknown() {
+        eprintln!("No relation found for {}", code);
+    }
+    Ok(invoices)
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use clap::Subcommand;
+use penning_helper_invoices::Invoice;
+use penning_helper_mail::MailServer;
+use penning_helper_sepa::{RunStore, Selection};
+use penning_helper_types::{CreditorReference, Date, Locale};
+
+use crate::{
+    context::{data_file, CliError, Context},
+    invoices::{open_invoices, SelectionArg},
+};
+
+/// The mail server refuses more than this many emails in a row
+const BATCH: usize = 20;
+const BATCH_PAUSE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Subcommand)]
+pub enum MailCommand {
+    /// Emails every selected relation their invoice
+    Send {
+        #[arg(long, value_enum, default_value_t)]
+        category: SelectionArg,
+        /// Transactions before this date are summed up as one line, defaults
+        /// to today
+        #[arg(long)]
+        last_invoice_date: Option<NaiveDate>,
+        /// Only prints who would get which amount
+        #[arg(long)]
+        dry_run: bool,
+        /// Sends the first email to our reply-to address instead
+        #[arg(long)]
+        test: bool,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: MailCommand) -> Result<(), CliError> {
+    match command {
+        MailCommand::Send {
+            category,
+            last_invoice_date,
+            dry_run,
+            test,
+        } => {
+            let category = Selection::from(category);
+            let last_invoice_date = last_invoice_date.map(Date::from).unwrap_or_default();
+            let sepa = ctx.sepa()?;
+            let mail = if dry_run {
+                None
+            } else {
+                let server = MailServer::new(ctx.cfg.mail(), ctx.cfg.sepa())
+                    .map_err(|e| CliError::Config(e.to_string()))?;
+                Some(server.with_locale(ctx.cfg.locale()))
+            };
+            let mut runs = RunStore::open(&data_file("runs.jsonl"))
+                .map_err(|e| CliError::Sepa(e.to_string()))?;
+            let invoices = open_invoices(ctx)?;
+            let to_send = invoices
+                .iter()
+                .filter(|i| category.filter(i, i.total_cost()))
+                .take(if test { 1 } else { usize::MAX })
+                .collect::<Vec<_>>();
+
+            let today = Date::today();
+            let period = category.period(today);
+            let mail_cfg = ctx.cfg.mail();
+            let mut failed = 0;
+            for (n, i) in to_send.iter().enumerate() {
+                if i.email.is_empty() {
+                    eprintln!("No email for {}", i.name);
+                    continue;
+                }
+                let total = i.total_cost();
+                let Some(mail) = &mail else {
+                    println!("{}\t{}\t{}\t{}", i.code, i.name, i.email, total);
+                    continue;
+                };
+                if n > 0 && n % BATCH == 0 {
+                    eprintln!(
+                        "Waiting {} minutes for the mail server",
+                        BATCH_PAUSE.as_secs() / 60
+                    );
+                    std::thread::sleep(BATCH_PAUSE);
+                }
+                let reference =
+                    category.creditor_reference(ctx.cfg.sepa().creditor_references, &i.code, today);
+                let qr = i.qr_payload(Some(&sepa), reference.clone(), today);
+                let pdf = pdf(
+                    last_invoice_date,
+                    i,
+                    reference.as_ref(),
+                    qr.as_deref(),
+                    ctx.cfg.locale(),
+                )?;
+                let address = if test {
+                    mail_cfg.reply_to.address.as_str()
+                } else {
+                    i.email.as_str()
+                };
+                match mail.send_mail(
+                    &i.name,
+                    address,
+                    pdf,
+                    total,
+                    today,
+                    !i.has_bank_details(Some(&sepa)),
+                    reference.as_ref().map(|r| r.pretty()).as_deref(),
+                    qr.as_deref(),
+                    &mail_cfg.board_line,
+                    &mail_cfg.name,
+                ) {
+                    Ok(()) => {
+                        println!("{}\t{}\t{}\t{}", i.code, i.name, address, total);
+                        if !test {
+                            runs.mark_email_sent(&i.code, &period, today)
+                                .map_err(|e| CliError::Sepa(e.to_string()))?;
+                        }
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        eprintln!("Error sending mail to {}: {}", i.name, e);
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(CliError::Other(format!(
+                    "{} emails could not be sent",
+                    failed
+                )));
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn pdf(
+    last_invoice_date: Date,
+    i: &Invoice,
+    reference: Option<&CreditorReference>,
+    qr: Option<&str>,
+    locale: Locale,
+) -> Result<Vec<u8>, CliError> {
+    Ok(i.pdf(last_invoice_date, reference, qr, locale))
+}
+
+#[cfg(not(feature = "pdf"))]
+fn pdf(
+    _: Date,
+    _: &Invoice,
+    _: Option<&CreditorReference>,
+    _: Option<&str>,
+    _: Locale,
+) -> Result<Vec<u8>, CliError> {
+    Err(CliError::Other(
+        "Built without the pdf feature, only --dry-run works".to_string(),
+    ))
+}
@@ -1,16 +1,100 @@
-fn main() {
-    let current_version = env!("CARGO_PKG_VERSION");
-    let res =
-        reqwest::blocking::get("https://github.com/AEGEE-Delft/penning-helper/releases/latest");
-    match res {
-        Ok(res) => {
-            let url_version = res.url().path().split_once("tag/v").unwrap().1;
-            let uv_sv = semver::Version::parse(url_version).unwrap();
-            let pkg_sv = semver::Version::parse(&current_version).unwrap();
-            println!("Current {}, Latest {}, Up to date: {}", pkg_sv, uv_sv, pkg_sv >= uv_sv);
-        }
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+
+use context::{CliError, Context};
+use invoices::InvoicesCommand;
+use mail::MailCommand;
+use members::MembersCommand;
+use merch::MerchCommand;
+use sepa::SepaCommand;
+use turflist::TurflistCommand;
+
+mod context;
+mod invoices;
+mod mail;
+mod members;
+mod merch;
+mod sepa;
+mod turflist;
+
+/// The treasurer's workflows of the interface, without a display
+#[derive(Debug, Parser)]
+#[command(name = "penning-helper", version)]
+struct Cli {
+    /// The config file to use instead of the one the interface saves
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Relations in Conscribo
+    #[command(subcommand)]
+    Members(MembersCommand),
+    /// Turflists from the members portal and Loyverse
+    #[command(subcommand)]
+    Turflist(TurflistCommand),
+    /// Open balances
+    #[command(subcommand)]
+    Invoices(InvoicesCommand),
+    /// Direct debit and transfer files for the bank
+    #[command(subcommand)]
+    Sepa(SepaCommand),
+    /// Invoice emails
+    #[command(subcommand)]
+    Mail(MailCommand),
+    /// Merch sales
+    #[command(subcommand)]
+    Merch(MerchCommand),
+    /// Checks whether this is the latest release
+    Version,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
-            println!("Error: {}", e);
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    let ctx = || Context::load(cli.config.clone());
+    match cli.command {
+        Command::Members(c) => members::run(&mut ctx()?, c),
+        Command::Turflist(c) => turflist::run(&mut ctx()?, c),
+        Command::Invoices(c) => invoices::run(&mut ctx()?, c),
+        Command::Sepa(c) => sepa::run(&mut ctx()?, c),
+        Command::Mail(c) => mail::run(&mut ctx()?, c),
+        Command::Merch(c) => merch::run(&mut ctx()?, c),
+        Command::Version => check_version(),
+    }
+}
+
+fn check_version() -> Result<(), CliError> {
+    let current_version = env!("CARGO_PKG_VERSION");
+    let res =
+        reqwest::blocking::get("https://github.com/AEGEE-Delft/penning-helper/releases/latest")
+            .map_err(|e| CliError::Other(e.to_string()))?;
+    let url_version = res
+        .url()
+        .path()
+        .split_once("tag/v")
+        .map(|(_, v)| v)
+        .ok_or_else(|| CliError::Other(format!("No release version in {}", res.url())))?;
+    let uv_sv = semver::Version::parse(url_version).map_err(|e| CliError::Other(e.to_string()))?;
+    let pkg_sv = semver::Version::parse(current_version).unwrap();
+    println!(
+        "Current {}, Latest {}, Up to date: {}",
+        pkg_sv,
+        uv_sv,
+        pkg_sv >= uv_sv
+    );
+    Ok(())
+}
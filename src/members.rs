@@ -0,0 +1,40 @@
+use clap::Subcommand;
+
+use crate::context::{CliError, Context};
+
+#[derive(Debug, Subcommand)]
+pub enum MembersCommand {
+    /// Prints code, name, email and IBAN of every relation, tab separated
+    List {
+        /// Only relations whose name contains this, ignoring case
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: MembersCommand) -> Result<(), CliError> {
+    match command {
+        MembersCommand::List { filter } => {
+            let filter = filter.unwrap_or_default().to_lowercase();
+            let relations = ctx.relations()?;
+            for member in relations
+                .iter()
+                .filter(|m| m.display_name.to_lowercase().contains(&filter))
+            {
+                let iban = match &member.account {
+                    Some(a) => match a.iban.get() {
+                        Some(Ok(iban)) => iban.pretty(),
+                        None => String::new(),
+                        Some(Err(e)) => format!("{} ({})", a.iban, e),
+                    },
+                    None => String::new(),
+                };
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    member.code, member.display_name, member.email, iban
+                );
+            }
+            Ok(())
+        }
+    }
+}
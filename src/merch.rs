@@ -0,0 +1,91 @@
+use chrono::Local;
+use clap::Subcommand;
+use penning_helper_conscribo::add_transaction::AddTransaction;
+use penning_helper_types::Euro;
+
+use crate::context::{CliError, Context};
+
+#[derive(Debug, Subcommand)]
+pub enum MerchCommand {
+    /// Books the sale of merch to a relation
+    Add {
+        /// The relation code
+        #[arg(long)]
+        member: String,
+        /// The balance account the items are in
+        #[arg(long)]
+        account: String,
+        /// What the items are on the balance for, each
+        #[arg(long)]
+        cost: Euro,
+        /// What the relation pays, each
+        #[arg(long)]
+        price: Euro,
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+        /// Like T0011-00
+        #[arg(long)]
+        reference: String,
+        /// The account the profit goes to, defaults to the one in the config
+        #[arg(long)]
+        profit_account: Option<String>,
+        /// Shows the sale without booking it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: MerchCommand) -> Result<(), CliError> {
+    match command {
+        MerchCommand::Add {
+            member,
+            account,
+            cost,
+            price,
+            count,
+            reference,
+            profit_account,
+            dry_run,
+        } => {
+            let profit_account =
+                profit_account.unwrap_or_else(|| ctx.cfg.conscribo().merch_winst_rekening.clone());
+            if profit_account.is_empty() {
+                return Err(CliError::Config(
+                    "there is no merch profit account, give one with --profit-account".to_string(),
+                ));
+            }
+            let accounts = ctx.accounts()?;
+            let Some(stock) = accounts.iter().find(|a| a.account_nr == account) else {
+                return Err(CliError::Conscribo(format!(
+                    "There is no account {}",
+                    account
+                )));
+            };
+            // the name ends in the price, like "Shotglass (2.50)"
+            let description = match stock.account_name.rsplit_once('(') {
+                Some((name, _)) => name.trim().to_string(),
+                None => stock.account_name.clone(),
+            };
+            let count = f64::from(count);
+            let (cost, price) = (cost * count, price * count);
+            println!(
+                "{}\t{}\t{}\tprofit {}",
+                member,
+                description,
+                price,
+                price - cost
+            );
+            if dry_run {
+                return Ok(());
+            }
+            let t = AddTransaction::new()
+                .with_date(Local::now().date_naive())
+                .with_description(description)
+                .with_reference(reference)
+                .with_relation_nr(member)
+                .add_merch(account, profit_account, price, cost);
+            ctx.add_transactions(vec![t])?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use clap::Subcommand;
+use penning_helper_sepa::{
+    Direction, IdRegistry, MandateLedger, RunOptions, RunStore, SaveError, Selection,
+};
+use penning_helper_types::Date;
+
+use crate::{
+    context::{data_file, CliError, Context},
+    invoices::{open_invoices, SelectionArg},
+};
+
+#[derive(Debug, Subcommand)]
+pub enum SepaCommand {
+    /// Writes `<output>.invoice.xml` with the direct debits and
+    /// `<output>.transfer.xml` with the transfers
+    Generate {
+        #[arg(long, value_enum, default_value_t)]
+        category: SelectionArg,
+        #[arg(long)]
+        output: PathBuf,
+        /// Also collect relations that were already collected for this
+        /// period, like after a return
+        #[arg(long)]
+        collect_again: bool,
+    },
+    /// Records a new mandate for a relation, collected under from the next
+    /// run. Needed before collecting again after a final or one-off
+    /// collection.
+    Sign {
+        /// The relation code
+        code: String,
+        mandate_id: String,
+        #[arg(long)]
+        signed: NaiveDate,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: SepaCommand) -> Result<(), CliError> {
+    match command {
+        SepaCommand::Generate {
+            category,
+            output,
+            collect_again,
+        } => generate(ctx, category.into(), &output, collect_again),
+        SepaCommand::Sign {
+            code,
+            mandate_id,
+            signed,
+        } => sign(&code, &mandate_id, signed.into()),
+    }
+}
+
+fn sign(code: &str, mandate_id: &str, signed: Date) -> Result<(), CliError> {
+    let path = data_file("mandates.json");
+    let store_error = |e: penning_helper_sepa::StoreError| CliError::Sepa(e.to_string());
+    let mut ledger = MandateLedger::load(&path).map_err(store_error)?;
+    ledger
+        .sign(code, mandate_id, signed)
+        .map_err(|e| CliError::Sepa(e.to_string()))?;
+    ledger.save(&path).map_err(store_error)
+}
+
+fn generate(
+    ctx: &mut Context,
+    selection: Selection,
+    output: &Path,
+    collect_again: bool,
+) -> Result<(), CliError> {
+    let sepa = ctx.sepa()?;
+    let store_error = |e: penning_helper_sepa::StoreError| CliError::Sepa(e.to_string());
+    // the same files as the interface, so both see the same history
+    let mut ledger = MandateLedger::load(&data_file("mandates.json")).map_err(store_error)?;
+    let mut ids = IdRegistry::load(&data_file("issued_ids.json")).map_err(store_error)?;
+    let mut runs = RunStore::open(&data_file("runs.jsonl")).map_err(store_error)?;
+    let options = RunOptions {
+        selection,
+        creditor_references: ctx.cfg.sepa().creditor_references,
+        collect_again,
+        date: Date::today(),
+    };
+
+    let invoices = open_invoices(ctx)?;
+    let relations = invoices.iter().map(|i| (&i.relation, i.total_cost()));
+    let generated = sepa.generate(relations, options, &ledger, &ids, &runs);
+    let summary = match &generated {
+        Ok(g) => &g.summary,
+        Err(summary) => summary,
+    };
+    for (name, reason) in &summary.skipped {
+        eprintln!("Skipped {}: {}", name, reason);
+    }
+    for (name, run) in &summary.reissued {
+        eprintln!("{} was already invoiced in run {}", name, run);
+    }
+    for s in &summary.sanitised {
+        eprintln!("{}", s);
+    }
+    for d in &summary.diagnostics {
+        eprintln!("{}", d);
+    }
+    for d in &summary.double_collected {
+        eprintln!("{}", d);
+    }
+    let generated = match generated {
+        Ok(generated) => generated,
+        Err(summary) if summary.has_errors() => {
+            return Err(CliError::Sepa(
+                "Nothing was saved, the files would be refused by the bank".to_string(),
+            ))
+        }
+        Err(_) => {
+            return Err(CliError::Sepa(
+                "Nothing was saved, use --collect-again if this is on purpose".to_string(),
+            ))
+        }
+    };
+
+    let saved = generated.save(output, &mut runs, &mut ledger, &mut ids);
+    // whatever was written and recorded is on its way to the bank, so its
+    // mandates and ids are kept even when the rest of the run failed
+    ledger
+        .save(&data_file("mandates.json"))
+        .map_err(store_error)?;
+    ids.save(&data_file("issued_ids.json"))
+        .map_err(store_error)?;
+    let saved = saved.map_err(|e| match e {
+        SaveError::Io(path, e) => CliError::Io(path, e),
+        e => CliError::Sepa(e.to_string()),
+    })?;
+    for file in saved {
+        let what = match file.record.direction {
+            Direction::Collection => "direct debits",
+            Direction::Transfer => "transfers",
+        };
+        println!(
+            "{}\t{} {}\t{}",
+            file.path.display(),
+            file.record.payments.len(),
+            what,
+            file.record.total()
+        );
+    }
+    Ok(())
+}
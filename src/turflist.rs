@@ -0,0 +1,124 @@
+use std::{ffi::OsStr, path::PathBuf};
+
+use chrono::Local;
+use clap::Subcommand;
+use penning_helper_conscribo::add_transaction::AddTransaction;
+use penning_helper_turflists::turflist::TurfList;
+use penning_helper_types::Euro;
+
+use crate::context::{CliError, Context};
+
+#[derive(Debug, Subcommand)]
+pub enum TurflistCommand {
+    /// Books a turflist (csv or xlsx) on the relations in it
+    Import {
+        file: PathBuf,
+        /// The Conscribo account the turflist is booked against
+        #[arg(long)]
+        account: String,
+        /// Like T0011-00
+        #[arg(long)]
+        reference: String,
+        #[arg(long, default_value = "")]
+        description: String,
+        /// The price of one item, only for xlsx lists which count items
+        #[arg(long)]
+        price: Option<Euro>,
+        /// Shows what would be booked without booking it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+pub fn run(ctx: &mut Context, command: TurflistCommand) -> Result<(), CliError> {
+    match command {
+        TurflistCommand::Import {
+            file,
+            account,
+            reference,
+            description,
+            price,
+            dry_run,
+        } => {
+            let list = read(&file, price)?;
+            let relations = ctx.relations()?;
+            let mut matched = list.get_matches(&relations.names(), &relations.emails());
+            matched.remove_zero_cost();
+
+            let mut transactions = vec![];
+            let mut externals = 0;
+            for row in matched.iter() {
+                match row.idx() {
+                    Some(idx) => {
+                        let member = &relations[idx];
+                        println!(
+                            "{}\t{}\t{}\t{}",
+                            member.code, member.display_name, row.name, row.amount
+                        );
+                        let t = AddTransaction::new()
+                            .with_date(Local::now().date_naive())
+                            .with_description(description.clone())
+                            .with_reference(reference.clone())
+                            .with_relation_nr(member.code.clone());
+                        transactions.push(if row.amount > Euro::default() {
+                            t.add_debet(account.clone(), row.amount)
+                        } else {
+                            t.add_credit(account.clone(), row.amount)
+                        });
+                    }
+                    None => {
+                        externals += 1;
+                        println!(
+                            "-\t{}\t{}\t{}",
+                            if row.iban.is_missing() {
+                                "no IBAN".to_string()
+                            } else {
+                                row.iban.to_string()
+                            },
+                            row.name,
+                            row.amount
+                        );
+                    }
+                }
+            }
+            if externals > 0 {
+                eprintln!(
+                    "{} rows are not relations (externals), add these to Conscribo by hand",
+                    externals
+                );
+            }
+            if dry_run {
+                eprintln!("Would book {} transactions", transactions.len());
+                return Ok(());
+            }
+            let booked = ctx.add_transactions(transactions)?;
+            eprintln!("Booked {} transactions", booked);
+            Ok(())
+        }
+    }
+}
+
+fn read(file: &PathBuf, price: Option<Euro>) -> Result<TurfList, CliError> {
+    let ext = file.extension().and_then(OsStr::to_str).unwrap_or("");
+    let mut list = match ext {
+        "csv" => penning_helper_turflists::csv::read_csv(file)
+            .map_err(|e| CliError::Other(e.to_string()))?,
+        "xlsx" | "xls" => {
+            let Some(price) = price else {
+                return Err(CliError::Other(
+                    "xlsx turflists count items, give their price with --price".to_string(),
+                ));
+            };
+            penning_helper_turflists::xlsx::read_excel(file, price)
+                .map_err(|e| CliError::Other(e.to_string()))?
+        }
+        _ => {
+            return Err(CliError::Other(format!(
+                "{} is not a csv or xlsx file",
+                file.display()
+            )))
+        }
+    };
+    list.shrink();
+    Ok(list)
+}
@@ -0,0 +1,82 @@
+use std::{path::PathBuf, process::Command};
+
+use penning_helper_config::Config;
+
+fn cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_penning-helper"))
+}
+
+/// A config as the interface would save it before anything is filled in
+fn empty_config(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "penning-helper-cli-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&path, Config::default().to_toml().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn help_lists_the_workflows() {
+    let out = cli().arg("--help").output().unwrap();
+    assert!(out.status.success());
+    let help = String::from_utf8(out.stdout).unwrap();
+    for command in ["members", "turflist", "invoices", "sepa", "mail", "merch"] {
+        assert!(help.contains(command), "{} is missing from {}", command, help);
+    }
+}
+
+#[test]
+fn missing_arguments_are_usage_errors() {
+    let out = cli().args(["turflist", "import"]).output().unwrap();
+    assert_eq!(out.status.code(), Some(2));
+
+    let out = cli()
+        .args(["merch", "add", "--member", "1", "--cost", "abc"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn unreadable_config_is_an_error() {
+    let out = cli()
+        .args(["--config", "/nonexistent/config.toml", "members", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8(out.stderr).unwrap();
+    assert!(err.contains("Could not read the config"), "{}", err);
+}
+
+#[test]
+fn conscribo_credentials_are_required() {
+    let config = empty_config("members");
+    let out = cli()
+        .arg("--config")
+        .arg(&config)
+        .args(["members", "list"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&config).unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8(out.stderr).unwrap();
+    assert!(err.contains("have to be set"), "{}", err);
+}
+
+#[test]
+fn turflist_is_read_before_logging_in() {
+    let config = empty_config("turflist");
+    let out = cli()
+        .arg("--config")
+        .arg(&config)
+        .args(["turflist", "import", "list.txt"])
+        .args(["--account", "1001", "--reference", "T0011-00"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&config).unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8(out.stderr).unwrap();
+    assert!(err.contains("not a csv or xlsx file"), "{}", err);
+}
@@ -0,0 +1,211 @@
+//! The workflows against the mock Conscribo server, each in a directory of
+//! its own that also holds the data dir
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use penning_helper_config::Config;
+use penning_helper_conscribo_mock::{Fixtures, MockConscribo, Row};
+use penning_helper_types::Euro;
+
+struct Workflow {
+    dir: PathBuf,
+    mock: MockConscribo,
+}
+
+impl Workflow {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "penning-helper-workflow-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mock = MockConscribo::start(Fixtures::default());
+
+        let mut cfg = Config::default();
+        let conscribo = cfg.conscribo_mut();
+        conscribo.username = "penningmeester".to_string();
+        conscribo.password = "geheim".to_string();
+        conscribo.account_name = "aegee-delft".to_string();
+        conscribo.base_url = mock.url().to_string();
+        let sepa = cfg.sepa_mut();
+        sepa.company_name = "AEGEE-Delft".to_string();
+        sepa.company_iban = "NL44RABO0123456789".into();
+        sepa.company_bic = "RABONL2U".into();
+        sepa.company_id = "NL00ZZZ404840000000".to_string();
+        std::fs::write(dir.join("config.toml"), cfg.to_toml().unwrap()).unwrap();
+        Self { dir, mock }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Runs the command line with the data dir in here instead of the user's
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_penning-helper"))
+            .env("XDG_DATA_HOME", self.path("data"))
+            .env("HOME", &self.dir)
+            .arg("--config")
+            .arg(self.path("config.toml"))
+            .args(args)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Workflow {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).to_string()
+}
+
+fn arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn turflist_import_books_on_the_relations() {
+    let w = Workflow::new("turflist");
+    let list = w.path("turflist.csv");
+    std::fs::write(
+        &list,
+        "Name,Email,Total\n\
+         Jan Jansen,jan@example.com,12.50\n\
+         Erika Mustermann,erika@example.com,3.20\n",
+    )
+    .unwrap();
+    let out = w.run(&[
+        "turflist",
+        "import",
+        arg(&list),
+        "--account",
+        "8010",
+        "--reference",
+        "T0011-00",
+    ]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let booked = w.mock.booked();
+    assert_eq!(booked.len(), 2);
+    let jan = booked
+        .iter()
+        .find(|b| {
+            b.rows
+                .iter()
+                .any(|r| r.relation_nr.as_deref() == Some("101"))
+        })
+        .unwrap();
+    assert_eq!(
+        jan.rows,
+        vec![
+            Row::credit("8010", Euro::new(12, 50))
+                .with_relation("101")
+                .with_reference("T0011-00")
+                .with_description(""),
+            Row::debet("1001", Euro::new(12, 50))
+                .with_relation("101")
+                .with_reference("T0011-00")
+                .with_description(""),
+        ]
+    );
+}
+
+#[test]
+fn refused_bookings_are_errors() {
+    let w = Workflow::new("refused");
+    let list = w.path("turflist.csv");
+    std::fs::write(
+        &list,
+        "Name,Email,Total\nJan Jansen,jan@example.com,12.50\n",
+    )
+    .unwrap();
+    let out = w.run(&[
+        "turflist",
+        "import",
+        arg(&list),
+        "--account",
+        "9999",
+        "--reference",
+        "T0011-00",
+    ]);
+    assert_eq!(out.status.code(), Some(1));
+    let err = stderr(&out);
+    assert!(err.contains("1 of 1 transactions were refused"), "{}", err);
+    assert!(err.contains("Account 9999 does not exist"), "{}", err);
+    assert!(w.mock.booked().is_empty());
+}
+
+#[test]
+fn sepa_generate_collects_once() {
+    let w = Workflow::new("sepa");
+    let output = w.path("march");
+    let out = w.run(&["sepa", "generate", "--output", arg(&output)]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    // Jan owes 25 euros, Erika is owed 20
+    let debits = std::fs::read_to_string(w.path("march.invoice.xml")).unwrap();
+    assert!(debits.contains("NL91ABNA0417164300"), "{}", debits);
+    assert!(debits.contains("25.00"), "{}", debits);
+    assert!(!debits.contains("DE89370400440532013000"), "{}", debits);
+    let transfers = std::fs::read_to_string(w.path("march.transfer.xml")).unwrap();
+    assert!(
+        transfers.contains("DE89370400440532013000"),
+        "{}",
+        transfers
+    );
+    assert!(transfers.contains("20.00"), "{}", transfers);
+
+    let out = w.run(&["sepa", "generate", "--output", arg(&w.path("again"))]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(stderr(&out).contains("--collect-again"), "{}", stderr(&out));
+    assert!(!w.path("again.invoice.xml").exists());
+}
+
+#[test]
+fn merch_add_books_the_sale() {
+    let w = Workflow::new("merch");
+    let out = w.run(&[
+        "merch",
+        "add",
+        "--member",
+        "101",
+        "--account",
+        "3010",
+        "--cost",
+        "1.00",
+        "--price",
+        "2.50",
+        "--count",
+        "2",
+        "--reference",
+        "T0012-00",
+        "--profit-account",
+        "8400",
+    ]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let booked = w.mock.booked();
+    assert_eq!(booked.len(), 1);
+    let row = |r: Row| {
+        r.with_relation("101")
+            .with_reference("T0012-00")
+            .with_description("Shotglass")
+    };
+    assert_eq!(
+        booked[0].rows,
+        vec![
+            row(Row::credit("3010", Euro::new(2, 0))),
+            row(Row::credit("8400", Euro::new(3, 0))),
+            row(Row::debet("1001", Euro::new(5, 0))),
+        ]
+    );
+}